@@ -0,0 +1,437 @@
+use std::{collections::BTreeMap, ops::Range};
+
+use crate::{
+    data_handler::{from_json, from_yaml},
+    format::FileFormat,
+    md_handler::Markdown,
+    script::{ContainerKind, ParseOptions, Script, ScriptError, TextContainer, TextSpan},
+    tex_handler::Tex,
+};
+
+/// A range of byte offsets within the source which a node was parsed from.
+pub type SourceRange = Range<usize>;
+
+/// The line which divides one section of a script from the next.
+pub const SECTION_DIVIDER: &str = "--8<--";
+
+/** The (1-based) line of the text which the byte offset is on.
+
+# Examples
+
+```
+# use lilscript::ast::line_of;
+assert_eq!(line_of("one\ntwo\nthree", 0), 1);
+assert_eq!(line_of("one\ntwo\nthree", 5), 2);
+```
+*/
+pub fn line_of(text: &str, offset: usize) -> usize {
+    1 + text[..offset].matches('\n').count()
+}
+
+/// A value of the document tree, along with where in the source it was parsed from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Positioned<T> {
+    pub value: T,
+    pub range: SourceRange,
+}
+
+impl<T> Positioned<T> {
+    pub fn new(value: T, range: SourceRange) -> Self {
+        Self { value, range }
+    }
+}
+
+/**
+A parsed script, as a tree of document ⟶ sections ⟶ containers ⟶ spans, where each node knows
+where in the source it came from.
+
+Every parser produces a `Document` (see `Document::parse`), which is lowered into a `Script` (dropping
+the positions) for the renderers, so that neither side needs to know about the other. Tooling which
+needs to point back into the source (e.g., to report where a problem is) can use the `Document` itself.
+
+# Examples
+
+```
+# use lilscript::{script::{ParseOptions, Script}, tex_handler::Tex};
+let source = r"\renewcommand{\SceneName}{A Very Cool Script}
+\scriptAuthor{lilellia}
+\scriptSeries{}
+\scriptTags{}
+\scriptDate{}
+\summary{}
+\begin{document}
+\clearpage
+\stagedir{The door creaks open.}
+\plaintext{--8<--}
+\spoken{Hello.}
+\end{document}";
+
+let document = Tex::from(source).parse_document(&ParseOptions::default()).unwrap();
+assert_eq!(document.sections.len(), 2);
+
+let hello = source.find("Hello").unwrap();
+let container = document.container_at(hello).unwrap();
+assert_eq!(&source[container.range.clone()], r"\spoken{Hello.}");
+
+let script = Script::from(document);
+assert_eq!(script.paragraphs.len(), 3);
+```
+*/
+#[derive(Clone, Debug, PartialEq)]
+pub struct Document {
+    /// The script's metadata (title, author, characters, etc.). Its `paragraphs` are empty, as the
+    /// body is held by `sections` instead.
+    pub metadata: Script,
+
+    /// Where the metadata is: everything from the first metadata command to the start of the body.
+    pub header: SourceRange,
+
+    /// The body of the script, split at each divider line (see `Section`).
+    pub sections: Vec<Section>,
+
+    /// The lines which couldn't be parsed, and so were left out, with why.
+    pub skipped: Vec<Positioned<String>>,
+
+    /// The whole of the source.
+    pub range: SourceRange,
+}
+
+/// A run of containers within the body of a script. Every section but the first starts with the
+/// `\plaintext{--8<--}` divider which separates it from the one before, so no container is lost in
+/// splitting the body up.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Section {
+    pub containers: Vec<ContainerNode>,
+    pub range: SourceRange,
+}
+
+/// A container of the document tree: the positioned counterpart of `TextContainer`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContainerNode {
+    pub kind: ContainerKind,
+    pub speaker: Option<String>,
+    pub attributes: BTreeMap<String, String>,
+    pub spans: Vec<Positioned<TextSpan>>,
+    pub overlaps: Vec<ContainerNode>,
+    pub commentary: Vec<Positioned<String>>,
+    pub range: SourceRange,
+}
+
+impl ContainerNode {
+    /// Whether this container is the divider between two sections.
+    pub fn is_divider(&self) -> bool {
+        self.kind == ContainerKind::PlainText
+            && matches!(self.spans.as_slice(), [span] if span.value.contents == SECTION_DIVIDER)
+    }
+
+    /// A container (along with its overlapping cues, spans, and commentary) of a source which holds no finer
+    /// positions than the given range (e.g., a line of an imported script), which every node then covers.
+    pub fn spanning(container: &TextContainer, range: SourceRange) -> Self {
+        ContainerNode {
+            kind: container.kind.clone(),
+            speaker: container.speaker.clone(),
+            attributes: container.attributes.clone(),
+            spans: container
+                .spans
+                .iter()
+                .map(|span| Positioned::new(span.clone(), range.clone()))
+                .collect(),
+            overlaps: container
+                .overlaps
+                .iter()
+                .map(|cue| ContainerNode::spanning(cue, range.clone()))
+                .collect(),
+            commentary: container
+                .commentary
+                .iter()
+                .map(|text| Positioned::new(text.clone(), range.clone()))
+                .collect(),
+            range,
+        }
+    }
+}
+
+/// Add a container to the end of the body, starting a new section if it's a divider (or the first container).
+pub fn push_container(sections: &mut Vec<Section>, container: ContainerNode) {
+    match sections.last_mut() {
+        Some(section) if !container.is_divider() => {
+            section.range.end = container.range.end;
+            section.containers.push(container);
+        }
+        _ => sections.push(Section {
+            range: container.range.clone(),
+            containers: vec![container],
+        }),
+    }
+}
+
+impl From<&ContainerNode> for TextContainer {
+    fn from(node: &ContainerNode) -> Self {
+        let mut container = TextContainer::with_spans(
+            node.kind.clone(),
+            node.spans.iter().map(|span| span.value.clone()).collect(),
+        );
+        container.speaker = node.speaker.clone();
+        container.attributes = node.attributes.clone();
+        container.overlaps = node.overlaps.iter().map(TextContainer::from).collect();
+        container.commentary = node
+            .commentary
+            .iter()
+            .map(|text| text.value.clone())
+            .collect();
+        container
+    }
+}
+
+impl Document {
+    /**
+    Parse the text of a script, in the given format, into a `Document`. This is the one way in which every
+    format is read: each parser produces a `Document`, which is then lowered into a `Script` (see
+    `Script::parse_as`).
+
+    The positions are as precise as the format allows: .tex files position every span, Markdown files and
+    imported scripts position each paragraph (or line), and JSON and YAML (which have no lines of their own)
+    position everything at the whole of the source.
+
+    # Examples
+
+    ```
+    # use lilscript::{ast::Document, script::ParseOptions, FileFormat};
+    let md = "# A Very Cool Script\n\n> *[A door opens.]*\n\n--8<--\n\n**Hello.**";
+    let document = Document::parse(md, FileFormat::Markdown, &ParseOptions::default()).unwrap();
+
+    assert_eq!(document.metadata.title, "A Very Cool Script");
+    assert_eq!(document.sections.len(), 2);
+    assert_eq!(&md[document.header.clone()], "# A Very Cool Script\n\n");
+    let hello = document.container_at(md.find("Hello").unwrap()).unwrap();
+    assert_eq!(&md[hello.range.clone()], "**Hello.**");
+    ```
+    */
+    pub fn parse(
+        contents: &str,
+        format: FileFormat,
+        options: &ParseOptions,
+    ) -> Result<Self, ScriptError> {
+        match format {
+            FileFormat::Tex => Tex::from(contents)
+                .parse_document(options)
+                .map_err(ScriptError::Parse),
+            FileFormat::Markdown => Markdown::from(contents)
+                .parse_document(options)
+                .map_err(|e| {
+                    ScriptError::Parse(format!(
+                        "{} (on line {})",
+                        e.value,
+                        line_of(contents, e.range.start)
+                    ))
+                }),
+            FileFormat::Json => from_json(contents)
+                .map(|script| Self::spanning(script, 0..contents.len()))
+                .map_err(ScriptError::Parse),
+            FileFormat::Yaml => from_yaml(contents)
+                .map(|script| Self::spanning(script, 0..contents.len()))
+                .map_err(ScriptError::Parse),
+            format => Err(ScriptError::Unsupported(format)),
+        }
+    }
+
+    /// A document of a script whose source holds no finer positions than the given range (e.g., one read from
+    /// JSON), which every node then covers.
+    pub fn spanning(script: Script, range: SourceRange) -> Self {
+        let mut sections: Vec<Section> = Vec::new();
+        for container in &script.paragraphs {
+            push_container(
+                &mut sections,
+                ContainerNode::spanning(container, range.clone()),
+            );
+        }
+
+        Document {
+            metadata: Script {
+                paragraphs: Vec::new(),
+                ..script
+            },
+            header: range.clone(),
+            sections,
+            skipped: Vec::new(),
+            range,
+        }
+    }
+
+    /// Iterate over the top-level containers of every section, in order.
+    pub fn containers(&self) -> impl Iterator<Item = &ContainerNode> {
+        self.sections
+            .iter()
+            .flat_map(|section| section.containers.iter())
+    }
+
+    /// Find the innermost container (i.e., an overlapping cue rather than the line it overlaps)
+    /// at the given byte offset of the source, if any.
+    pub fn container_at(&self, offset: usize) -> Option<&ContainerNode> {
+        let mut container = self
+            .containers()
+            .find(|container| container.range.contains(&offset))?;
+
+        while let Some(cue) = container
+            .overlaps
+            .iter()
+            .find(|cue| cue.range.contains(&offset))
+        {
+            container = cue;
+        }
+
+        Some(container)
+    }
+}
+
+impl From<Document> for Script {
+    fn from(document: Document) -> Self {
+        let paragraphs = document.containers().map(TextContainer::from).collect();
+        Script {
+            paragraphs,
+            ..document.metadata
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SOURCE: &str = r"\renewcommand{\SceneName}{A Very Cool Script}
+\scriptAuthor{lilellia}
+\scriptSeries{}
+\scriptTags{}
+\scriptDate{}
+\summary{}
+\begin{document}
+\character{Speaker}{a kind witch}
+\clearpage
+\spoken{Oh! You're here\textellipsis{} \direct{softly} I wasn't expecting you.}
+
+\overlap{\sfx{rain} \sfx{thunder}}{\spoken[Witch]{Listen to \ul{that} rain.}}
+\plaintext{--8<--}
+\listener{Hi, \phonetic{Siobhan}{shi-VAWN}.}
+\end{document}";
+
+    fn parse() -> Document {
+        Tex::from(SOURCE)
+            .parse_document(&ParseOptions::default())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_lowers_to_parsed_script() {
+        let script = Tex::from(SOURCE)
+            .parse_script(&ParseOptions::default())
+            .unwrap();
+        assert_eq!(Script::from(parse()), script);
+    }
+
+    #[test]
+    fn test_section_ranges() {
+        let document = parse();
+        let ranges: Vec<&str> = document
+            .sections
+            .iter()
+            .map(|section| &SOURCE[section.range.clone()])
+            .collect();
+
+        assert!(ranges[0].starts_with(r"\spoken{Oh!"));
+        assert!(ranges[0].ends_with(r"rain.}}"));
+        assert_eq!(
+            ranges[1],
+            "\\plaintext{--8<--}\n\\listener{Hi, \\phonetic{Siobhan}{shi-VAWN}.}"
+        );
+        assert!(document.sections[1].containers[0].is_divider());
+        assert_eq!(
+            &SOURCE[document.header.clone()],
+            &SOURCE[..SOURCE.find(r"\spoken").unwrap() - 1]
+        );
+    }
+
+    #[test]
+    fn test_span_ranges() {
+        let document = parse();
+        let spans = |index: usize| -> Vec<&str> {
+            let container = document.containers().nth(index).unwrap();
+            container
+                .spans
+                .iter()
+                .map(|span| &SOURCE[span.range.clone()])
+                .collect()
+        };
+
+        assert_eq!(
+            spans(0),
+            vec![
+                r"Oh! You're here\textellipsis{} ",
+                r"\direct{softly}",
+                " I wasn't expecting you."
+            ]
+        );
+        assert_eq!(spans(1), vec!["Listen to ", r"\ul{that}", " rain."]);
+        assert_eq!(spans(3), vec!["Hi, ", r"\phonetic{Siobhan}{shi-VAWN}", "."]);
+    }
+
+    #[test]
+    fn test_container_at_finds_overlapping_cue() {
+        let document = parse();
+
+        let thunder = SOURCE.find("thunder").unwrap();
+        let cue = document.container_at(thunder).unwrap();
+        assert_eq!(&SOURCE[cue.range.clone()], r"\sfx{thunder}");
+
+        // a line with overlapping cues covers them as well
+        let listen = SOURCE.find("Listen").unwrap();
+        let line = document.container_at(listen).unwrap();
+        assert!(&SOURCE[line.range.clone()].starts_with(r"\overlap{"));
+        assert_eq!(line.speaker.as_deref(), Some("Witch"));
+
+        assert!(document.container_at(0).is_none());
+    }
+
+    #[test]
+    fn test_markdown_positions() {
+        let md = "# A Very Cool Script\n\n\
+            > *[sfx: rain (over the following line)]*\n\n\
+            **Listen to that rain.**\n\n\
+            > **Commentary:** Written on a rainy day.\n\n\
+            --8<--\n\n\
+            > *« Hi. »*";
+        let document = Document::parse(md, FileFormat::Markdown, &ParseOptions::default()).unwrap();
+
+        assert_eq!(document.sections.len(), 2);
+        let line = &document.sections[0].containers[0];
+        assert!(md[line.range.clone()].starts_with("> *[sfx: rain"));
+        assert!(md[line.range.clone()].ends_with("**Listen to that rain.**\n"));
+        assert_eq!(
+            &md[line.commentary[0].range.clone()],
+            "> **Commentary:** Written on a rainy day.\n"
+        );
+        assert!(&md[document.sections[0].range.clone()].ends_with("rainy day.\n"));
+        assert!(document.sections[1].containers[0].is_divider());
+
+        let hi = document.container_at(md.find("Hi.").unwrap()).unwrap();
+        assert_eq!(hi.kind, ContainerKind::ListenerDialogue);
+        assert_eq!(&md[hi.spans[0].range.clone()], "> *« Hi. »*");
+    }
+
+    #[test]
+    fn test_json_spans_the_whole_source() {
+        let json = r#"{
+            "title": "A Very Cool Script",
+            "paragraphs": [{ "kind": "sfx", "spans": [{ "kind": "normal", "contents": "rain" }] }]
+        }"#;
+        let document = Document::parse(json, FileFormat::Json, &ParseOptions::default()).unwrap();
+
+        assert_eq!(document.metadata.title, "A Very Cool Script");
+        assert!(document.metadata.paragraphs.is_empty());
+        let container = document.containers().next().unwrap();
+        assert_eq!(container.range, 0..json.len());
+        assert_eq!(
+            Script::from(document),
+            Script::parse_as(json, FileFormat::Json, &ParseOptions::default()).unwrap()
+        );
+    }
+}
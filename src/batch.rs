@@ -0,0 +1,310 @@
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+use crate::script::{write_atomically, CountingRules, Script};
+
+/** Find every file within the given directory (recursively) which has one of the given extensions.
+
+# Arguments
+
+* `dir` - the directory to search
+* `extensions` - the extensions (without the leading `.`) of the files to find
+
+# Return
+
+* `Ok(Vec<PathBuf>)` - the paths of the files found, in sorted order;
+* `Err(_)` if the directory could not be read
+*/
+pub fn discover(dir: &Path, extensions: &[&str]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut found: Vec<PathBuf> = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            found.append(&mut discover(&path, extensions)?);
+        } else if path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| extensions.contains(&ext))
+        {
+            found.push(path);
+        }
+    }
+
+    found.sort();
+    Ok(found)
+}
+
+/// The destination of a batch conversion: either a directory, or a zip archive.
+/// In both cases, the directory structure of the inputs is mirrored.
+pub enum BatchOutput {
+    /// Write each converted file below this directory
+    Directory(PathBuf),
+
+    /// Write each converted file into this zip archive
+    Zip(Box<ZipWriter<File>>),
+}
+
+impl BatchOutput {
+    /** Prepare the output at the given path: a `.zip` path creates an archive, and anything else a directory.
+
+    # Arguments
+
+    * `path` - the path of the directory/archive to create
+    */
+    pub fn create(path: &Path) -> Result<Self, Box<dyn Error>> {
+        if path.extension().is_some_and(|ext| ext == "zip") {
+            Ok(Self::Zip(Box::new(ZipWriter::new(File::create(path)?))))
+        } else {
+            fs::create_dir_all(path)?;
+            Ok(Self::Directory(path.to_path_buf()))
+        }
+    }
+
+    /** Write a converted file to the output.
+
+    # Arguments
+
+    * `relative` - the path of the file, relative to the root of the output
+    * `contents` - the contents of the file
+    */
+    pub fn write(&mut self, relative: &Path, contents: &str) -> Result<(), Box<dyn Error>> {
+        match self {
+            Self::Directory(root) => {
+                let path = root.join(relative);
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                write_atomically(&path, contents)?;
+            }
+            Self::Zip(zip) => {
+                // zip entries always use forward slashes, whatever the platform
+                let name = relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+
+                zip.start_file(name, SimpleFileOptions::default())?;
+                zip.write_all(contents.as_bytes())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Finish writing the output (for an archive, this writes its central directory).
+    pub fn finish(self) -> Result<(), Box<dyn Error>> {
+        if let Self::Zip(zip) = self {
+            zip.finish()?;
+        }
+
+        Ok(())
+    }
+}
+
+/** Build a Markdown index of the converted scripts of a series: a table of each script's part, title (linking
+to its converted file), tags, and spoken word count, in the given order. It's followed by where each character
+appears across the series (see `Script::appearances`), if any lines name their speaker.
+
+# Arguments
+
+* `entries` - each converted script, with its path relative to the root of the output
+* `rules` - the rules by which to count words
+
+# Return
+
+The Markdown of the index, headed by the title of the series if every script shares one.
+*/
+pub fn series_index(entries: &[(PathBuf, Script)], rules: &CountingRules) -> String {
+    let escape = |cell: &str| cell.replace('|', "\\|");
+
+    let series = entries
+        .first()
+        .and_then(|(_, script)| script.series.title.clone());
+    let heading = match series {
+        Some(series)
+            if entries
+                .iter()
+                .all(|(_, s)| s.series.title.as_ref() == Some(&series)) =>
+        {
+            series
+        }
+        _ => String::from("Index"),
+    };
+
+    let mut index = format!("# {}\n\n", heading);
+    index.push_str("| Part | Title | Tags | Spoken words |\n");
+    index.push_str("| ---- | ----- | ---- | -----------: |\n");
+
+    for (path, script) in entries {
+        // links always use forward slashes, whatever the platform
+        let link = path
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let part = script
+            .series
+            .part
+            .as_ref()
+            .map(|part| part.to_string())
+            .unwrap_or_default();
+
+        index.push_str(&format!(
+            "| {} | [{}](<{}>) | {} | {} |\n",
+            escape(&part),
+            escape(&script.title),
+            link,
+            escape(&script.tags.join(", ")),
+            script.wordcount_with(rules).spoken()
+        ));
+    }
+
+    // each character, in the order they first appear, with their appearances in each script
+    let mut characters: Vec<(String, Vec<String>)> = Vec::new();
+    for (_, script) in entries {
+        for (speaker, appearances) in script.appearances() {
+            let appearances: Vec<String> = appearances.iter().map(|a| a.to_string()).collect();
+            let appearances = format!("{} ({})", script.title, appearances.join("; "));
+            match characters.iter_mut().find(|(name, _)| *name == speaker) {
+                Some((_, scripts)) => scripts.push(appearances),
+                None => characters.push((speaker, vec![appearances])),
+            }
+        }
+    }
+
+    if !characters.is_empty() {
+        index.push_str("\n## Appearances\n\n");
+        for (speaker, scripts) in characters {
+            index.push_str(&format!("- **{}**: {}\n", speaker, scripts.join("; ")));
+        }
+    }
+
+    index
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::script::{ContainerKind, TextContainer, TextSpan};
+    use std::io::Read;
+    use zip::ZipArchive;
+
+    /// Create an empty scratch directory for a test.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("lilscript-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_discover() {
+        let dir = scratch_dir("discover");
+        fs::create_dir_all(dir.join("arc2")).unwrap();
+        for file in ["part02.tex", "part01.tex", "notes.txt", "arc2/part03.tex"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+
+        let found = discover(&dir, &["tex"]).unwrap();
+        let found: Vec<&Path> = found
+            .iter()
+            .map(|p| p.strip_prefix(&dir).unwrap())
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                Path::new("arc2/part03.tex"),
+                Path::new("part01.tex"),
+                Path::new("part02.tex")
+            ]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_zip_output() {
+        let dir = scratch_dir("zip");
+        let archive = dir.join("series.zip");
+
+        let mut output = BatchOutput::create(&archive).unwrap();
+        output.write(Path::new("part01.md"), "one").unwrap();
+        output.write(Path::new("arc2/part02.md"), "two").unwrap();
+        output.finish().unwrap();
+
+        let mut zip = ZipArchive::new(File::open(&archive).unwrap()).unwrap();
+        let mut contents = String::new();
+        zip.by_name("arc2/part02.md")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(zip.len(), 2);
+        assert_eq!(contents, "two");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_series_index() {
+        let mut first = Script::new("lilellia", "First Night");
+        first.series = "Moonrise (Part 1)".into();
+        first.tags = vec!["F4A".into(), "comfort".into()];
+        let mut second = Script::new("lilellia", "Second | Night");
+        second.series = "Moonrise (Part 2)".into();
+
+        let entries = vec![
+            (PathBuf::from("part01.md"), first),
+            (PathBuf::from("arc2/part02.md"), second),
+        ];
+        let index = series_index(&entries, &CountingRules::default());
+
+        assert!(index.starts_with("# Moonrise\n"));
+        assert!(index.contains("| Part 1 | [First Night](<part01.md>) | F4A, comfort | 0 |\n"));
+        assert!(index.contains("| Part 2 | [Second \\| Night](<arc2/part02.md>) |  | 0 |\n"));
+        assert!(!index.contains("## Appearances"));
+
+        let mut other = entries[0].1.clone();
+        other.series = "Sunset (Part 1)".into();
+        let index = series_index(
+            &[entries[0].clone(), (PathBuf::from("sunset.md"), other)],
+            &CountingRules::default(),
+        );
+        assert!(index.starts_with("# Index\n"));
+    }
+
+    #[test]
+    fn test_series_index_appearances() {
+        let line = |speaker: &str| {
+            TextContainer::new(ContainerKind::Spoken)
+                .push(TextSpan::normal("Hello."))
+                .spoken_by(speaker)
+        };
+
+        let mut first = Script::new("lilellia", "First Night");
+        first.paragraphs = vec![line("Alice"), line("Alice"), line("Bob")];
+        let mut second = Script::new("lilellia", "Second Night");
+        second.paragraphs = vec![line("Bob"), line("Carol"), line("Bob")];
+
+        let entries = vec![
+            (PathBuf::from("part01.md"), first),
+            (PathBuf::from("part02.md"), second),
+        ];
+        let index = series_index(&entries, &CountingRules::default());
+
+        assert!(index.ends_with(
+            "## Appearances\n\n\
+             - **Alice**: First Night (section 1, lines 001–002)\n\
+             - **Bob**: First Night (section 1, line 003); \
+             Second Night (section 1, line 001; section 1, line 003)\n\
+             - **Carol**: Second Night (section 1, line 002)\n"
+        ));
+    }
+}
@@ -0,0 +1,421 @@
+use glob::Pattern;
+use std::{
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
+    process,
+};
+
+use crate::{
+    ast::line_of,
+    format::FileFormat,
+    md_handler::Markdown,
+    script::{decode_text, CountingRules, ParseOptions, ScriptError},
+    stats::ScriptStats,
+    tex_handler::{lenient_lines, Tex},
+};
+
+/// How serious a finding is: only errors make a check fail.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Something wrong with a script, and where it is.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Finding {
+    pub path: PathBuf,
+
+    /// The (1-based) line of the script which the finding is about.
+    pub line: usize,
+
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    /**
+    Written as `path:line: severity: message`, as compilers do, so that editors can jump to it.
+
+    ```
+    # use lilscript::check::{Finding, Severity};
+    # use std::path::PathBuf;
+    let finding = Finding {
+        path: PathBuf::from("script.tex"),
+        line: 12,
+        severity: Severity::Error,
+        message: "Invalid tex line".to_owned(),
+    };
+    assert_eq!(finding.to_string(), "script.tex:12: error: Invalid tex line");
+    ```
+    */
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}: {}",
+            self.path.display(),
+            self.line,
+            self.severity,
+            self.message
+        )
+    }
+}
+
+/// What a script is checked against, beyond whether it parses.
+#[derive(Clone, Debug)]
+pub struct CheckOptions {
+    /// The target number of spoken words, overriding any `\scriptTarget` in the script.
+    pub target: Option<usize>,
+
+    /// How far (as a percentage) the spoken word count may be from the target.
+    pub tolerance: f64,
+
+    /// Which words count towards the word count.
+    pub counting: CountingRules,
+}
+
+/**
+Check a script, returning everything wrong with it.
+
+Anything which stops the script from being read in full (a line which can't be parsed, or a header
+which can't be found) or which fails validation (a spoken word count outside the tolerance of its target)
+is an error; anything which is only accepted because the parser is lenient (e.g., an unknown command,
+or a date which can't be read) is a warning.
+
+Markdown scripts are parsed in strict mode, and anything which stops that is an error. Scripts in any other
+format aren't checked (which is only a warning).
+
+# Arguments
+
+* `path` - the path of the script, for reporting (and for telling its format)
+* `contents` - the text of the script
+* `check` - what to check the script against
+* `options` - how to parse the script
+*/
+pub fn check(
+    path: &Path,
+    contents: &str,
+    check: &CheckOptions,
+    options: &ParseOptions,
+) -> Vec<Finding> {
+    let finding = |line: usize, severity: Severity, message: String| Finding {
+        path: path.to_owned(),
+        line,
+        severity,
+        message,
+    };
+
+    let format = FileFormat::detect(path, contents);
+    let options = ParseOptions {
+        strict: false,
+        quiet: true,
+        ..options.clone()
+    };
+
+    match format {
+        Some(FileFormat::Tex) => (),
+        Some(FileFormat::Markdown) => return check_markdown(path, contents, check, &options),
+        Some(_) => {
+            let e = "only .tex and .md scripts can be linted";
+            return vec![finding(1, Severity::Warning, format!("not checked: {}", e))];
+        }
+        None => {
+            let e = ScriptError::UnknownFormat;
+            return vec![finding(1, Severity::Warning, format!("not checked: {}", e))];
+        }
+    }
+    let document = match Tex::from(contents).parse_document(&options) {
+        Ok(document) => document,
+        Err(e) => return vec![finding(1, Severity::Error, e)],
+    };
+
+    let mut findings: Vec<Finding> = Vec::new();
+    let header_line = line_of(contents, document.header.start);
+
+    // the header on its own, so that its problems aren't mixed up with those of the body
+    if document.header.end > 0 {
+        let strict = ParseOptions {
+            strict: true,
+            ..options.clone()
+        };
+        let header = &contents[..document.header.end];
+        if let Err(e) = Tex::from(header).parse_document(&strict) {
+            findings.push(finding(header_line, Severity::Warning, e));
+        }
+    }
+
+    for problem in lenient_lines(contents, &document, &options) {
+        let line = line_of(contents, problem.range.start);
+        findings.push(finding(line, Severity::Warning, problem.value));
+    }
+
+    for skipped in &document.skipped {
+        let line = line_of(contents, skipped.range.start);
+        findings.push(finding(line, Severity::Error, skipped.value.clone()));
+    }
+
+    let target_line = contents
+        .find(r"\scriptTarget")
+        .map(|offset| line_of(contents, offset))
+        .unwrap_or(header_line);
+    let mut stats = ScriptStats::counted(&document.into(), &check.counting);
+    stats.target = check.target.or(stats.target);
+    if let Err(e) = stats.check_target(check.tolerance) {
+        findings.push(finding(target_line, Severity::Error, e));
+    }
+
+    findings.sort_by_key(|finding| finding.line);
+    findings
+}
+
+/// Check a Markdown script: as it has no header, and nothing is only accepted because the parser is lenient,
+/// anything which wouldn't be accepted in strict mode is an error.
+fn check_markdown(
+    path: &Path,
+    contents: &str,
+    check: &CheckOptions,
+    options: &ParseOptions,
+) -> Vec<Finding> {
+    let finding = |line: usize, message: String| Finding {
+        path: path.to_owned(),
+        line,
+        severity: Severity::Error,
+        message,
+    };
+
+    let strict = ParseOptions {
+        strict: true,
+        ..options.clone()
+    };
+    let script = match Markdown::from(contents).parse_positioned(&strict) {
+        Ok(script) => script,
+        Err(e) => return vec![finding(line_of(contents, e.range.start), e.value)],
+    };
+
+    let mut stats = ScriptStats::counted(&script, &check.counting);
+    stats.target = check.target;
+    match stats.check_target(check.tolerance) {
+        Ok(()) => Vec::new(),
+        Err(e) => vec![finding(1, e)],
+    }
+}
+
+/// The names (without their extension, and in any case) of Markdown files which are never scripts, and so
+/// are never checked by `staged_scripts`.
+pub const IGNORED_NAMES: [&str; 2] = ["README", "CHANGELOG"];
+
+/// The staged files which `staged_scripts` found: the scripts to check, and those it was told to leave out.
+#[derive(Clone, Debug, Default)]
+pub struct Staged {
+    /// The path (relative to the current directory) and staged contents of each script.
+    pub scripts: Vec<(PathBuf, String)>,
+
+    /// The path (relative to the current directory) of each .tex/.md file which was left out.
+    pub ignored: Vec<PathBuf>,
+}
+
+/**
+List the scripts which are staged in the git repository containing the current directory: each added,
+copied, modified, or renamed .tex or .md file, along with its staged contents. Files named in
+`IGNORED_NAMES` (e.g., a README) and files whose path within the repository matches any of the `ignore`
+patterns are left out, and listed as such.
+
+# Return
+
+* `Ok(Staged)` - the scripts to check, and the files which were left out
+* `Err(_)` if git couldn't be run, or the current directory isn't within a repository
+*/
+pub fn staged_scripts(ignore: &[Pattern]) -> Result<Staged, Box<dyn Error>> {
+    let root = PathBuf::from(git(&["rev-parse", "--show-toplevel"])?.trim_end());
+    let cwd = std::env::current_dir()?;
+
+    let names = git(&[
+        "diff",
+        "--cached",
+        "--name-only",
+        "--diff-filter=ACMR",
+        "-z",
+    ])?;
+
+    let mut staged = Staged::default();
+    for name in names.split('\0').filter(|name| !name.is_empty()) {
+        let format = FileFormat::from_path(Path::new(name));
+        if !matches!(format, Ok(FileFormat::Tex | FileFormat::Markdown)) {
+            continue;
+        }
+
+        let path = root.join(name);
+        let path = path.strip_prefix(&cwd).map(Path::to_owned).unwrap_or(path);
+        if is_ignored(Path::new(name), format == Ok(FileFormat::Markdown), ignore) {
+            staged.ignored.push(path);
+            continue;
+        }
+
+        // check what is about to be committed, rather than what happens to be in the working tree
+        let contents = decode_text(&git_bytes(&["show", &format!(":{}", name)])?)?;
+        staged.scripts.push((path, contents));
+    }
+
+    Ok(staged)
+}
+
+/// Whether the file at the given path (within the repository) should be left out of the staged scripts.
+fn is_ignored(path: &Path, is_markdown: bool, ignore: &[Pattern]) -> bool {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_uppercase())
+        .unwrap_or_default();
+
+    (is_markdown && IGNORED_NAMES.contains(&stem.as_str()))
+        || ignore.iter().any(|pattern| pattern.matches_path(path))
+}
+
+/// Run git with the given arguments, returning what it printed.
+fn git(args: &[&str]) -> Result<String, Box<dyn Error>> {
+    Ok(String::from_utf8(git_bytes(args)?)?)
+}
+
+/// Run git with the given arguments, returning what it printed as raw bytes.
+fn git_bytes(args: &[&str]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let output = process::Command::new("git").args(args).output()?;
+    if !output.status.success() {
+        Err(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))?;
+    }
+
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SOURCE: &str = r"\documentclass{article}
+\renewcommand{\SceneName}{A Very Cool Script}
+\scriptAuthor{lilellia}
+\scriptSeries{Moonrise, part two}
+\scriptTags{}
+\scriptDate{}
+\scriptTarget{10}
+\summary{}
+\begin{document}
+\clearpage
+\spoken{Hello there.}
+\whisper{Psst.}
+\spoken{unbalanced
+\end{document}";
+
+    #[test]
+    fn test_check_findings() {
+        let check_options = CheckOptions {
+            target: None,
+            tolerance: 10.,
+            counting: CountingRules::default(),
+        };
+        let findings = check(
+            Path::new("script.tex"),
+            SOURCE,
+            &check_options,
+            &ParseOptions::default(),
+        );
+
+        let found: Vec<(usize, Severity)> = findings
+            .iter()
+            .map(|finding| (finding.line, finding.severity))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (2, Severity::Warning),
+                (7, Severity::Error),
+                (12, Severity::Warning),
+                (13, Severity::Error)
+            ]
+        );
+        assert!(findings[0].message.contains("Moonrise, part two"));
+    }
+
+    #[test]
+    fn test_check_markdown() {
+        let check_options = CheckOptions {
+            target: Some(2),
+            tolerance: 10.,
+            counting: CountingRules::default(),
+        };
+        let check_md = |contents: &str| {
+            check(
+                Path::new("script.md"),
+                contents,
+                &check_options,
+                &ParseOptions::default(),
+            )
+        };
+
+        assert_eq!(check_md("## Characters\n\n**Hello there.**"), vec![]);
+
+        let findings = check_md(
+            "## Characters\n\n- **Witch** ∼ a kind witch\n\n- Someone\n\n**Hello there.**",
+        );
+        assert_eq!(findings.len(), 1);
+        assert_eq!(
+            (findings[0].line, findings[0].severity),
+            (5, Severity::Error)
+        );
+        assert!(findings[0].message.contains("Someone"));
+
+        let findings = check_md("**Hello there.**\n\n> *[sfx: rain (over the following line)]*");
+        assert_eq!(
+            (findings[0].line, findings[0].severity),
+            (3, Severity::Error)
+        );
+
+        // the word count is checked too
+        let findings = check_md("**Hello there, again.**");
+        assert_eq!(
+            (findings[0].line, findings[0].severity),
+            (1, Severity::Error)
+        );
+    }
+
+    #[test]
+    fn test_check_unsupported_format() {
+        let check_options = CheckOptions {
+            target: None,
+            tolerance: 10.,
+            counting: CountingRules::default(),
+        };
+        let findings = check(
+            Path::new("script.html"),
+            "<p>Hello.</p>",
+            &check_options,
+            &ParseOptions::default(),
+        );
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_ignored_files() {
+        let ignore = vec![Pattern::new("drafts/*").unwrap()];
+
+        assert!(is_ignored(Path::new("README.md"), true, &ignore));
+        assert!(is_ignored(Path::new("docs/changelog.md"), true, &ignore));
+        assert!(is_ignored(Path::new("drafts/moonrise.tex"), false, &ignore));
+        assert!(!is_ignored(Path::new("moonrise.md"), true, &ignore));
+        assert!(!is_ignored(Path::new("series/README-notes.md"), true, &[]));
+    }
+}
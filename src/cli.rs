@@ -0,0 +1,1558 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_verbosity_flag::{InfoLevel, Verbosity};
+use glob::Pattern;
+use paris::{info, warn};
+use rayon::prelude::*;
+use regex::Regex;
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    batch::{self, BatchOutput},
+    check::{self, CheckOptions, Severity},
+    emoji,
+    format::{render, DirectionPlacement, FileFormat, OutputProfile, RenderOptions},
+    html_handler::{is_css_colour, HtmlOptions},
+    import, index,
+    md_handler::{MarkdownOptions, MarkdownStyle},
+    numbered_path, report,
+    script::{
+        decode_text, write_atomically, ContainerKind, CountingRules, DateOrder, ParseOptions,
+        QuoteStyle, ReplaceScope, Script, ScriptError, TextContainer, TextSpan,
+    },
+    stats::{self, ScriptStats},
+    subtitle::{SubtitleBudget, SubtitleOptions},
+    tex_handler::{self, Tex, TexCommands, TexOptions},
+    transform::{self, Transform},
+    tui,
+};
+
+/// The extensions of the files which are read as scripts by default, when a whole directory is read.
+const READABLE_EXTENSIONS: &str = "tex,md,json,yaml,yml";
+
+/// For command-line parsing.
+#[derive(Parser)]
+#[command(author, version, about, long_about=None)]
+pub struct ArgumentParser {
+    #[command(subcommand)]
+    pub command: Command,
+
+    #[arg(
+        long,
+        global = true,
+        help = "treat anything which would normally produce a warning (e.g., an unknown command) as an error"
+    )]
+    pub strict: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "normalize the script's tags: lowercased, trimmed, deduplicated, and with any --tag-alias applied"
+    )]
+    pub normalize_tags: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "ALIAS=TAG",
+        value_parser = parse_tag_alias,
+        help = "when normalizing tags, replace ALIAS with TAG (may be given more than once)"
+    )]
+    pub tag_alias: Vec<(String, String)>,
+
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t,
+        help = "how to read numeric dates such as 03/04/2024"
+    )]
+    pub date_order: DateOrder,
+
+    #[arg(
+        long,
+        global = true,
+        help = "convert emoji in .md input back to :shortcode:s (the reverse of --emoji)"
+    )]
+    pub shortcodes: bool,
+
+    #[command(flatten)]
+    pub verbose: Verbosity<InfoLevel>,
+}
+
+/// The subcommands understood by the command-line interface.
+#[derive(Subcommand)]
+pub enum Command {
+    /// Convert a script from one format to another
+    Convert(ConvertArgs),
+
+    /// Print a fingerprint of each script's content, for detecting duplicates
+    Hash(HashArgs),
+
+    /// Normalize the tags of each .tex script in place
+    FixTags(FixTagsArgs),
+
+    /// Print the statistics of each script, including its progress towards a target word count
+    Stats(StatsArgs),
+
+    /// Check that each script parses, and that it is within tolerance of its target word count
+    Validate(ValidateArgs),
+
+    /// Print a readable plain-text rendering of a script, for quick inspection in the terminal
+    Preview(PreviewArgs),
+
+    /// Find and replace text throughout each .tex script in place, without touching its markup
+    Replace(ReplaceArgs),
+
+    /// Rewrite each .tex script in place in its canonical form
+    Fmt(FmtArgs),
+
+    /// Concatenate several scripts (in any of the input formats) into a single output file
+    Cat(CatArgs),
+
+    /// Inspect a .tex script interactively in the terminal, re-parsing it whenever it changes
+    Tui(TuiArgs),
+
+    /// Lint each script, printing each problem as path:line (e.g., from a git pre-commit hook)
+    Check(CheckArgs),
+
+    /// Import a Markdown/plain-text script written in other authors' conventions, reporting how confident the import is
+    Import(ImportArgs),
+
+    /// Summarise every script in a directory (totals by year and series, tags, speech density) as a Markdown/HTML report
+    Report(ReportArgs),
+
+    /// Build a searchable index of the metadata of every script in a directory, or search one (`index query`)
+    Index(IndexArgs),
+}
+
+/// Arguments for the `convert` subcommand.
+#[derive(Args)]
+pub struct ConvertArgs {
+    #[arg(
+        short,
+        long,
+        help = "the input file to operate on (or - for stdin, or a directory to convert every script in it)"
+    )]
+    pub infile: PathBuf,
+
+    #[arg(
+        short,
+        long,
+        help = "the file to output the results to (for a directory of inputs: a directory or .zip archive)"
+    )]
+    pub outfile: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "the format to convert to, when converting a directory"
+    )]
+    pub to: Option<FileFormat>,
+
+    #[arg(
+        long = "ext",
+        value_name = "EXT",
+        value_delimiter = ',',
+        default_value = READABLE_EXTENSIONS,
+        help = "when converting a directory, the comma-separated extensions of the files to convert"
+    )]
+    pub extensions: Vec<String>,
+
+    #[arg(
+        long,
+        help = "when converting a directory, also write an index.md listing every script's title, part, tags, and word count"
+    )]
+    pub series_index: bool,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_parser = parse_colour,
+        help = "comma-separated CSS colours to assign to each speaker in HTML output"
+    )]
+    pub palette: Vec<String>,
+
+    #[arg(
+        long,
+        help = "convert :shortcode:s (and \\emoji{...} commands, or custom commands such as \\sparkles) to emoji in the output"
+    )]
+    pub emoji: bool,
+
+    #[arg(
+        long,
+        help = "apply the built-in transforms: uppercase sfx, sentence-case tone cues, and ALL CAPS ⟶ emphasis"
+    )]
+    pub normalize: bool,
+
+    #[arg(
+        long,
+        help = "reword tone cues consistently, using the built-in synonyms (e.g., softly ⟶ quietly) and any --cue-synonym"
+    )]
+    pub normalize_cues: bool,
+
+    #[arg(
+        long,
+        value_name = "FROM=TO",
+        value_parser = parse_cue_synonym,
+        help = "when normalizing cues, replace FROM with TO (may be given more than once)"
+    )]
+    pub cue_synonym: Vec<(String, String)>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        help = "the style of output to produce"
+    )]
+    pub profile: OutputProfile,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        help = "where tone cues are placed, relative to the sentence they modify"
+    )]
+    pub directions: DirectionPlacement,
+
+    #[arg(
+        long,
+        help = "prefix each line of .md/.html output with its line ID (e.g., [012]), as in --profile checklist"
+    )]
+    pub number_lines: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "how to write double quotation marks in .md/.html/.ssml output (.tex output always uses TeX quotes)"
+    )]
+    pub quotes: Option<QuoteStyle>,
+
+    #[arg(
+        long,
+        help = "include the director's commentary (\\commentary{...}) in .md/.html output, for an annotated edition"
+    )]
+    pub commentary: bool,
+
+    #[arg(
+        long,
+        help = "leave the summary (and any --spoiler-tag) out of the output, keeping the body intact"
+    )]
+    pub no_spoilers: bool,
+
+    #[arg(
+        long,
+        value_name = "TAG",
+        requires = "no_spoilers",
+        help = "with --no-spoilers, a tag which gives the twist away (may be given more than once)"
+    )]
+    pub spoiler_tag: Vec<String>,
+
+    #[arg(
+        long,
+        help = "replace each listener line with a neutral [listener responds] stage direction"
+    )]
+    pub hide_listener: bool,
+
+    #[command(flatten)]
+    pub counting: CountingRules,
+
+    #[arg(
+        long,
+        value_name = "STYLE",
+        help = "a .toml file of the conventions to write .md output with (the wrappers of each kind of line and span, etc.)"
+    )]
+    pub style: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        help = "a file to take the preamble of .tex output from, with {{title}}, {{author}}, etc. filled in"
+    )]
+    pub tex_preamble: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        help = "a file to take the header (the metadata commands, up to \\clearpage) of .tex output from"
+    )]
+    pub tex_header: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "KIND=COMMAND",
+        value_parser = parse_tex_command,
+        help = "in .tex output, write lines or spans of KIND (e.g., emphasis) with \\COMMAND (may be given more than once)"
+    )]
+    pub tex_command: Vec<(String, String)>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 42,
+        help = "in .srt/.vtt output, the most characters on each line of a cue"
+    )]
+    pub subtitle_chars: usize,
+
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 2,
+        help = "in .srt/.vtt output, the most lines in each cue"
+    )]
+    pub subtitle_lines: usize,
+
+    #[arg(
+        long,
+        value_name = "WPM",
+        default_value_t = stats::WORDS_PER_MINUTE,
+        value_parser = parse_words_per_minute,
+        help = "in .srt/.vtt output, the speaking rate (in spoken words per minute) from which each cue is timed"
+    )]
+    pub wpm: f64,
+}
+
+/// How the `stats` subcommand prints the statistics of each script.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum StatsFormat {
+    /// A human-readable summary of each script
+    #[default]
+    Text,
+
+    /// A JSON array with one object per script (see `stats::write_json`), for other tools
+    Json,
+}
+
+/// Arguments for the `hash` subcommand.
+#[derive(Args)]
+pub struct HashArgs {
+    #[arg(required = true, help = "the input files to fingerprint")]
+    pub infiles: Vec<PathBuf>,
+}
+
+/// Arguments for the `fix-tags` subcommand.
+#[derive(Args)]
+pub struct FixTagsArgs {
+    #[arg(
+        required = true,
+        help = "the .tex files whose tags should be normalized"
+    )]
+    pub infiles: Vec<PathBuf>,
+}
+
+/// Arguments for the `stats` subcommand.
+#[derive(Args)]
+pub struct StatsArgs {
+    #[arg(
+        required_unless_present = "batch",
+        help = "the input files to summarise"
+    )]
+    pub infiles: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "summarise every script within this directory (recursively) as well"
+    )]
+    pub batch: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub discover: DiscoverArgs,
+
+    #[arg(
+        long,
+        value_name = "REPORT",
+        help = "write the statistics to this .csv file (one row per script) instead of printing them"
+    )]
+    pub csv: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "the target number of spoken words (overriding any \\scriptTarget in the scripts)"
+    )]
+    pub target: Option<usize>,
+
+    #[arg(
+        long,
+        help = "also list every distinct sound effect cue, with how often and where it occurs"
+    )]
+    pub sfx: bool,
+
+    #[arg(
+        long,
+        value_name = "WPM",
+        default_value_t = stats::WORDS_PER_MINUTE,
+        value_parser = parse_words_per_minute,
+        help = "the speaking rate (in spoken words per minute) at which to estimate each script's runtime"
+    )]
+    pub wpm: f64,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        conflicts_with = "csv",
+        help = "how to print the statistics"
+    )]
+    pub format: StatsFormat,
+
+    #[command(flatten)]
+    pub counting: CountingRules,
+}
+
+/// Arguments for the `validate` subcommand.
+#[derive(Args)]
+pub struct ValidateArgs {
+    #[arg(required = true, help = "the input files to validate")]
+    pub infiles: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        help = "the target number of spoken words (overriding any \\scriptTarget in the scripts)"
+    )]
+    pub target: Option<usize>,
+
+    #[arg(
+        long,
+        default_value_t = 10.,
+        help = "how far (as a percentage) the spoken word count may be from the target"
+    )]
+    pub tolerance: f64,
+
+    #[command(flatten)]
+    pub counting: CountingRules,
+}
+
+/// Arguments for the `preview` subcommand.
+#[derive(Args)]
+pub struct PreviewArgs {
+    #[arg(help = "the input file to preview (or - for stdin)")]
+    pub infile: PathBuf,
+
+    #[arg(
+        long,
+        default_value_t = 100,
+        help = "the width to wrap the preview to (0 to not wrap at all)"
+    )]
+    pub width: usize,
+}
+
+/// Arguments for the `replace` subcommand.
+#[derive(Args)]
+pub struct ReplaceArgs {
+    #[arg(help = "the text to search for")]
+    pub pattern: String,
+
+    #[arg(help = "the text to replace it with")]
+    pub replacement: String,
+
+    #[arg(required = true, help = "the .tex files to search")]
+    pub infiles: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        help = "which text of the scripts to search"
+    )]
+    pub scope: ReplaceScope,
+
+    #[arg(
+        long,
+        help = "treat the pattern as a regular expression (and allow $1, etc. in the replacement)"
+    )]
+    pub regex: bool,
+}
+
+/// Arguments for the `fmt` subcommand.
+#[derive(Args)]
+pub struct FmtArgs {
+    #[arg(required = true, help = "the .tex files to format")]
+    pub infiles: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        help = "only check that the files are in canonical form, without rewriting them"
+    )]
+    pub check: bool,
+}
+
+/// Arguments for the `cat` subcommand.
+#[derive(Args)]
+pub struct CatArgs {
+    #[arg(
+        required = true,
+        help = "the input files to concatenate, in order (or - for stdin)"
+    )]
+    pub infiles: Vec<PathBuf>,
+
+    #[arg(short, long, help = "the file to output the combined script to")]
+    pub outfile: PathBuf,
+
+    #[arg(
+        long,
+        help = "a line of plain text (e.g., --8<--) to put between each of the scripts"
+    )]
+    pub separator: Option<String>,
+}
+
+/// Arguments for the `tui` subcommand.
+#[derive(Args)]
+pub struct TuiArgs {
+    #[arg(help = "the .tex file to inspect")]
+    pub infile: PathBuf,
+}
+
+/// Arguments for the `check` subcommand.
+#[derive(Args)]
+pub struct CheckArgs {
+    #[arg(
+        required_unless_present = "staged",
+        help = "the .tex / .md files to check"
+    )]
+    pub infiles: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        conflicts_with = "infiles",
+        help = "check the scripts staged in the current git repository, as they are staged"
+    )]
+    pub staged: bool,
+
+    #[arg(
+        long,
+        value_name = "GLOB",
+        requires = "staged",
+        help = "with --staged, leave out the files whose path within the repository matches GLOB (may be given more than once)"
+    )]
+    pub ignore: Vec<Pattern>,
+
+    #[arg(
+        long,
+        help = "the target number of spoken words (overriding any \\scriptTarget in the scripts)"
+    )]
+    pub target: Option<usize>,
+
+    #[arg(
+        long,
+        default_value_t = 10.,
+        help = "how far (as a percentage) the spoken word count may be from the target"
+    )]
+    pub tolerance: f64,
+
+    #[command(flatten)]
+    pub counting: CountingRules,
+}
+
+/// Arguments for the `import` subcommand.
+#[derive(Args)]
+pub struct ImportArgs {
+    #[arg(help = "the Markdown/plain-text file to import (or - for stdin)")]
+    pub infile: PathBuf,
+
+    #[arg(short, long, help = "the file to output the imported script to")]
+    pub outfile: PathBuf,
+
+    #[arg(long, help = "the author of the script, if it doesn't say")]
+    pub author: Option<String>,
+
+    #[arg(
+        long,
+        help = "the title of the script, if it doesn't say (by default, the name of the file)"
+    )]
+    pub title: Option<String>,
+}
+
+/// Which files within a directory are read as scripts, for the subcommands which read a whole archive.
+#[derive(Args, Clone, Debug)]
+pub struct DiscoverArgs {
+    #[arg(
+        long = "ext",
+        value_name = "EXT",
+        value_delimiter = ',',
+        default_value = READABLE_EXTENSIONS,
+        help = "the comma-separated extensions of the files within the directory to read as scripts"
+    )]
+    pub extensions: Vec<String>,
+}
+
+impl DiscoverArgs {
+    /// Find every file within the given directory (recursively) with one of the extensions.
+    fn discover(&self, dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let extensions: Vec<&str> = self.extensions.iter().map(|ext| ext.as_str()).collect();
+        batch::discover(dir, &extensions)
+    }
+}
+
+/// Arguments for the `report` subcommand.
+#[derive(Args)]
+pub struct ReportArgs {
+    #[arg(help = "the directory of scripts to summarise")]
+    pub dir: PathBuf,
+
+    #[command(flatten)]
+    pub discover: DiscoverArgs,
+
+    #[arg(
+        short,
+        long,
+        help = "the .md/.html file to write the report to (by default, Markdown is printed to stdout)"
+    )]
+    pub outfile: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub counting: CountingRules,
+}
+
+/// Arguments for the `index` subcommand.
+#[derive(Args)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct IndexArgs {
+    #[command(subcommand)]
+    pub command: Option<IndexCommand>,
+
+    #[arg(required = true, help = "the directory of scripts to index")]
+    pub dir: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub discover: DiscoverArgs,
+
+    #[arg(
+        short,
+        long,
+        default_value = "index.json",
+        help = "the .json file to write the index to (only JSON is supported, not SQLite)"
+    )]
+    pub outfile: PathBuf,
+}
+
+/// The subcommands of the `index` subcommand.
+#[derive(Subcommand)]
+pub enum IndexCommand {
+    /// Print every script in an index which matches all of the given criteria
+    Query(QueryArgs),
+}
+
+/// Arguments for the `index query` subcommand.
+#[derive(Args)]
+pub struct QueryArgs {
+    #[arg(
+        short,
+        long,
+        default_value = "index.json",
+        help = "the index to search"
+    )]
+    pub index: PathBuf,
+
+    #[arg(
+        long = "tag",
+        help = "a tag which the script must have (may be repeated)"
+    )]
+    pub tags: Vec<String>,
+
+    #[arg(long, help = "the series the script belongs to")]
+    pub series: Option<String>,
+
+    #[arg(long, help = "the author of the script")]
+    pub author: Option<String>,
+
+    #[arg(long, help = "the name of a character in the script")]
+    pub character: Option<String>,
+
+    #[arg(long, help = "text which appears in the title of the script")]
+    pub title: Option<String>,
+
+    #[arg(long, help = "the fewest spoken words the script may have")]
+    pub min_words: Option<usize>,
+
+    #[arg(long, help = "the most spoken words the script may have")]
+    pub max_words: Option<usize>,
+}
+
+/// Parse a `--tag-alias` of the form `ALIAS=TAG`.
+fn parse_tag_alias(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((alias, tag)) if !alias.trim().is_empty() && !tag.trim().is_empty() => {
+            Ok((alias.to_owned(), tag.to_owned()))
+        }
+        _ => Err(format!("Invalid tag alias {:?}: expected ALIAS=TAG", s)),
+    }
+}
+
+/// Parse a `--wpm` speaking rate, which must be positive.
+fn parse_words_per_minute(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(wpm) if wpm > 0.0 && wpm.is_finite() => Ok(wpm),
+        _ => Err(format!(
+            "Invalid speaking rate {:?}: expected a positive number of words per minute",
+            s
+        )),
+    }
+}
+
+/// Parse a `--palette` colour, which must be a CSS colour value (see `is_css_colour`).
+fn parse_colour(s: &str) -> Result<String, String> {
+    if is_css_colour(s) {
+        Ok(s.trim().to_owned())
+    } else {
+        Err(format!(
+            "Invalid colour {:?}: expected a CSS colour, e.g. #0072B2 or teal",
+            s
+        ))
+    }
+}
+
+/// Parse a `--cue-synonym` of the form `FROM=TO`.
+fn parse_cue_synonym(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((from, to)) if !from.trim().is_empty() && !to.trim().is_empty() => {
+            Ok((from.to_owned(), to.to_owned()))
+        }
+        _ => Err(format!("Invalid cue synonym {:?}: expected FROM=TO", s)),
+    }
+}
+
+/// Parse a `--tex-command` of the form `KIND=COMMAND`, checking it against the kinds which can be set.
+fn parse_tex_command(s: &str) -> Result<(String, String), String> {
+    let (kind, command) = s.split_once('=').ok_or(format!(
+        "Invalid TeX command {:?}: expected KIND=COMMAND",
+        s
+    ))?;
+
+    TexCommands::default().set(kind, command)?;
+    Ok((kind.to_owned(), command.to_owned()))
+}
+
+impl ArgumentParser {
+    /// Set the log level based on the verbosity passed in.
+    pub fn set_log_level(&self) {
+        env_logger::Builder::new()
+            .filter_level(self.verbose.log_level_filter())
+            .init();
+    }
+}
+
+/// Read and decode the text stored at the given path (or from stdin, if the path is `-`).
+fn read_text(path: &Path, options: &ParseOptions) -> Result<String, String> {
+    let bytes = if path == Path::new("-") {
+        if !options.quiet {
+            info!("Reading from: stdin");
+        }
+        let mut bytes: Vec<u8> = Vec::new();
+        io::stdin()
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("Could not read from stdin: {}", e))?;
+        bytes
+    } else {
+        if !options.quiet {
+            info!("Reading from: {:?}", path);
+        }
+        fs::read(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?
+    };
+
+    decode_text(&bytes).map_err(|e| e.to_string())
+}
+
+/// Read and parse the script stored at the given path (or from stdin, if the path is `-`).
+fn read_script(path: &Path, options: &ParseOptions) -> Result<Script, Box<dyn Error>> {
+    let contents = read_text(path, options)?;
+    let format = FileFormat::detect(path, &contents).ok_or(ScriptError::UnknownFormat)?;
+    Ok(Script::parse_as(&contents, format, options)?)
+}
+
+/// Read and parse every script stored at the given path (or from stdin, if the path is `-`):
+/// a .tex file may hold several (see `Tex::split`), while any other holds just the one.
+fn read_scripts(path: &Path, options: &ParseOptions) -> Result<Vec<Script>, Box<dyn Error>> {
+    let contents = read_text(path, options)?;
+    let format = FileFormat::detect(path, &contents).ok_or(ScriptError::UnknownFormat)?;
+
+    match format {
+        FileFormat::Tex => Ok(Tex::from(contents.as_str())
+            .parse_scripts(options)
+            .map_err(ScriptError::Parse)?),
+        format => Ok(vec![Script::parse_as(&contents, format, options)?]),
+    }
+}
+
+pub fn run(args: ArgumentParser) -> Result<(), Box<dyn Error>> {
+    let options = ParseOptions {
+        strict: args.strict,
+        normalize_tags: args.normalize_tags,
+        tag_aliases: args.tag_alias.into_iter().collect(),
+        date_order: args.date_order,
+        shortcodes: args.shortcodes,
+        quiet: false,
+    };
+
+    match args.command {
+        Command::Convert(args) => convert(args, &options),
+        Command::Hash(args) => hash(args, &options),
+        Command::FixTags(args) => fix_tags(args, &options),
+        Command::Stats(args) => stats(args, &options),
+        Command::Validate(args) => validate(args, &options),
+        Command::Preview(args) => preview(args, &options),
+        Command::Replace(args) => replace(args, &options),
+        Command::Fmt(args) => fmt(args, &options),
+        Command::Cat(args) => cat(args, &options),
+        Command::Tui(args) => tui::run(&args.infile, &options),
+        Command::Check(args) => check(args, &options),
+        Command::Import(args) => import(args, &options),
+        Command::Report(args) => report(args, &options),
+        Command::Index(args) => index(args, &options),
+    }
+}
+
+/// Run the `convert` subcommand.
+fn convert(args: ConvertArgs, options: &ParseOptions) -> Result<(), Box<dyn Error>> {
+    if args.infile.is_dir() {
+        return convert_batch(&args, options);
+    }
+
+    let scripts = prepare_scripts(&args.infile, &args, options)?;
+
+    // Write the desired file(s)
+    if let Some(outfile) = &args.outfile {
+        let render_options = RenderOptions::from_args(&args, options)?;
+        let format = FileFormat::from_path(outfile)?;
+
+        for (i, script) in scripts.iter().enumerate() {
+            let path = match scripts.len() {
+                1 => outfile.clone(),
+                _ => numbered_path(outfile, i, script),
+            };
+            script.save(&path, format, &render_options)?;
+        }
+
+        if scripts.len() > 1 {
+            info!("Wrote {} scripts alongside {:?}", scripts.len(), outfile);
+        }
+    }
+
+    Ok(())
+}
+
+/// A script converted as part of a batch, with the path (relative to the output) to write it to.
+struct Converted {
+    path: PathBuf,
+    script: Script,
+    contents: String,
+}
+
+/// Convert every script within a single file of a batch.
+fn convert_one(
+    infile: &Path,
+    out_format: FileFormat,
+    args: &ConvertArgs,
+    options: &ParseOptions,
+    render_options: &RenderOptions,
+) -> Result<Vec<Converted>, Box<dyn Error>> {
+    let scripts = prepare_scripts(infile, args, options)?;
+    let relative = infile
+        .strip_prefix(&args.infile)?
+        .with_extension(out_format.extension());
+
+    let mut converted = Vec::with_capacity(scripts.len());
+    for (i, script) in scripts.iter().enumerate() {
+        let contents = render(script, out_format, render_options)?;
+        let path = match scripts.len() {
+            1 => relative.clone(),
+            _ => numbered_path(&relative, i, script),
+        };
+        converted.push(Converted {
+            path,
+            script: script.clone(),
+            contents,
+        });
+    }
+
+    Ok(converted)
+}
+
+/// Convert every script within the `--infile` directory, writing them (with the same directory
+/// structure) into the `--outfile` directory or .zip archive.
+fn convert_batch(args: &ConvertArgs, options: &ParseOptions) -> Result<(), Box<dyn Error>> {
+    let outfile = args.outfile.as_ref().ok_or(
+        "An --outfile (a directory or .zip archive) is required when converting a directory",
+    )?;
+    let out_format = args
+        .to
+        .ok_or("A --to format is required when converting a directory")?;
+
+    let extensions: Vec<&str> = args.extensions.iter().map(|ext| ext.as_str()).collect();
+    let infiles = batch::discover(&args.infile, &extensions)?;
+    let render_options = RenderOptions::from_args(args, options)?;
+
+    // each file is converted independently (in parallel), so that one failure doesn't stop the rest
+    let results: Vec<Result<Vec<Converted>, String>> = infiles
+        .par_iter()
+        .map(|infile| {
+            convert_one(infile, out_format, args, options, &render_options)
+                .map_err(|e| e.to_string())
+        })
+        .collect();
+
+    let mut output = BatchOutput::create(outfile)?;
+    let mut converted: Vec<(PathBuf, Script)> = Vec::new();
+    let mut failures: Vec<(&PathBuf, String)> = Vec::new();
+    for (infile, result) in infiles.iter().zip(results) {
+        match result {
+            Ok(scripts) => {
+                for Converted {
+                    path,
+                    script,
+                    contents,
+                } in scripts
+                {
+                    output.write(&path, &contents)?;
+                    converted.push((path, script));
+                }
+            }
+            Err(e) => failures.push((infile, e)),
+        }
+    }
+
+    if args.series_index {
+        let index = batch::series_index(&converted, &args.counting);
+        output.write(Path::new("index.md"), &index)?;
+    }
+
+    output.finish()?;
+    info!(
+        "Converted {} scripts from {} files into {:?}",
+        converted.len(),
+        infiles.len() - failures.len(),
+        outfile
+    );
+
+    report_failures(&failures, infiles.len())
+}
+
+/// Warn about each file of a batch which couldn't be read (or converted), after the rest have been dealt with,
+/// failing if there were any.
+fn report_failures(failures: &[(&PathBuf, String)], total: usize) -> Result<(), Box<dyn Error>> {
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    for (infile, e) in failures {
+        warn!("{}: {}", infile.display(), e);
+    }
+    Err(format!("{} of {} files failed", failures.len(), total).into())
+}
+
+/// Read the script(s) to be converted, reporting their word counts and applying any conversions requested.
+/// A .tex file may hold several scripts (see `Tex::split`), and each is converted separately.
+fn prepare_scripts(
+    infile: &Path,
+    args: &ConvertArgs,
+    options: &ParseOptions,
+) -> Result<Vec<Script>, Box<dyn Error>> {
+    let mut scripts = read_scripts(infile, options)?;
+    let count = scripts.len();
+
+    let mut cue_synonyms: BTreeMap<String, String> = transform::CUE_SYNONYMS
+        .iter()
+        .map(|&(from, to)| (from.to_owned(), to.to_owned()))
+        .collect();
+    cue_synonyms.extend(args.cue_synonym.iter().cloned());
+
+    for script in scripts.iter_mut() {
+        if count > 1 {
+            info!("<bold>{}</>", script.title);
+        }
+        info!(
+            "<on-cyan><black>Word count: {}</>",
+            script.wordcount_with(&args.counting)
+        );
+
+        let by_language = script.wordcount_by_language();
+        if by_language.len() > 1 {
+            for (lang, wordcount) in &by_language {
+                let lang = lang.as_deref().unwrap_or("(default)");
+                info!("  {}: {}", lang, wordcount);
+            }
+        }
+
+        if args.emoji {
+            emoji::emojify(script);
+        }
+
+        if args.no_spoilers {
+            transform::strip_spoilers(script, &args.spoiler_tag);
+        }
+
+        if args.hide_listener {
+            transform::hide_listener_lines(script);
+        }
+
+        if args.normalize_cues {
+            transform::normalize_cues(script, &cue_synonyms);
+        }
+
+        if args.normalize {
+            for transform in Transform::ALL {
+                transform.apply(script);
+            }
+        }
+    }
+
+    Ok(scripts)
+}
+
+impl RenderOptions {
+    /// Collect the rendering options from the arguments of the `convert` subcommand.
+    fn from_args(args: &ConvertArgs, options: &ParseOptions) -> Result<Self, Box<dyn Error>> {
+        let mut markdown = MarkdownOptions {
+            number_lines: args.number_lines,
+            ..Default::default()
+        };
+        if let Some(style) = &args.style {
+            markdown.style = MarkdownStyle::from_toml(&decode_text(&fs::read(style)?)?)?;
+        }
+
+        let mut html = HtmlOptions {
+            number_lines: args.number_lines,
+            ..Default::default()
+        };
+        if !args.palette.is_empty() {
+            html.palette = args.palette.clone();
+        }
+
+        let mut tex = TexOptions::default();
+        if let Some(template) = &args.tex_preamble {
+            tex.preamble = read_template(template, options)?;
+        }
+        if let Some(template) = &args.tex_header {
+            tex.header = read_template(template, options)?;
+        }
+        for (kind, command) in &args.tex_command {
+            tex.commands.set(kind, command)?;
+        }
+
+        let subtitles = SubtitleOptions {
+            budget: SubtitleBudget {
+                max_chars_per_line: args.subtitle_chars,
+                max_lines_per_cue: args.subtitle_lines,
+            },
+            words_per_minute: args.wpm,
+        };
+
+        Ok(Self {
+            profile: args.profile,
+            directions: args.directions,
+            quotes: args.quotes,
+            commentary: args.commentary,
+            markdown,
+            html,
+            tex,
+            subtitles,
+            strict: options.strict,
+        })
+    }
+}
+
+/// Read a .tex preamble or header template, warning about (or, in strict mode, failing on) any placeholder
+/// which it can't be filled in with.
+fn read_template(path: &Path, options: &ParseOptions) -> Result<String, Box<dyn Error>> {
+    let template = decode_text(&fs::read(path)?)?;
+    for placeholder in tex_handler::unknown_placeholders(&template) {
+        options.warn_or_fail(format!(
+            "Unknown placeholder in TeX template {}: {}",
+            path.display(),
+            placeholder
+        ))?;
+    }
+    Ok(template)
+}
+
+/// Run the `hash` subcommand, printing one `fingerprint  path` line per input.
+fn hash(args: HashArgs, options: &ParseOptions) -> Result<(), Box<dyn Error>> {
+    for infile in &args.infiles {
+        let script = read_script(infile, options)?;
+        println!("{}  {}", script.fingerprint(), infile.display());
+    }
+
+    Ok(())
+}
+
+/// Run the `fix-tags` subcommand, rewriting the `\scriptTags{...}` of each file with its normalized tags.
+fn fix_tags(args: FixTagsArgs, options: &ParseOptions) -> Result<(), Box<dyn Error>> {
+    // parse the tags as written, so that we can tell whether they need fixing
+    let options = ParseOptions {
+        normalize_tags: false,
+        ..options.clone()
+    };
+
+    for infile in &args.infiles {
+        let contents = decode_text(&fs::read(infile)?)?;
+        let mut script = Tex::from(contents.as_str()).parse_script(&options)?;
+
+        let before = script.tags.clone();
+        script.normalize_tags(&options.tag_aliases);
+
+        if script.tags == before {
+            info!("{}: tags are already normalized", infile.display());
+            continue;
+        }
+
+        let fixed = tex_handler::replace_tags(&contents, &script.tags)
+            .ok_or(format!("{}: could not find \\scriptTags", infile.display()))?;
+        write_atomically(infile, &fixed)?;
+
+        info!(
+            "{}: {} ⟶ {}",
+            infile.display(),
+            tex_handler::tags_to_tex(&before),
+            tex_handler::tags_to_tex(&script.tags)
+        );
+    }
+
+    Ok(())
+}
+
+/// Run the `stats` subcommand.
+fn stats(args: StatsArgs, options: &ParseOptions) -> Result<(), Box<dyn Error>> {
+    let mut infiles = args.infiles;
+    if let Some(dir) = &args.batch {
+        infiles.extend(args.discover.discover(dir)?);
+    }
+
+    // JSON is meant to be piped into other tools, so nothing else may be printed alongside it
+    let options = ParseOptions {
+        quiet: options.quiet || args.format == StatsFormat::Json,
+        ..options.clone()
+    };
+
+    let mut rows: Vec<(PathBuf, Script, ScriptStats)> = Vec::with_capacity(infiles.len());
+    for infile in infiles {
+        let script = read_script(&infile, &options)?;
+
+        let mut stats = ScriptStats::counted(&script, &args.counting);
+        stats.target = args.target.or(stats.target);
+        stats.words_per_minute = Some(args.wpm);
+
+        let text = args.csv.is_none() && args.format == StatsFormat::Text;
+        if text {
+            let mut metadata: Vec<String> = Vec::new();
+            if let Some(series) = &script.series.title {
+                match &script.series.part {
+                    Some(part) => metadata.push(format!("Series: {}, {}", series, part)),
+                    None => metadata.push(format!("Series: {}", series)),
+                }
+            }
+            if !script.tags.is_empty() {
+                metadata.push(format!("Tags: {}", script.tags.join(", ")));
+            }
+
+            println!("{} ({})", script.title, infile.display());
+            if !metadata.is_empty() {
+                println!("{}", metadata.join(" · "));
+            }
+            println!("{}\n", stats);
+        }
+        if args.sfx && args.format == StatsFormat::Text {
+            let inventory = stats::sfx_inventory(&script);
+            if args.csv.is_some() {
+                println!("{} ({})", script.title, infile.display());
+            }
+            println!("Sound effects ({} distinct):", inventory.len());
+            for cue in inventory {
+                println!("  {}", cue);
+            }
+            println!();
+        }
+        rows.push((infile, script, stats));
+    }
+
+    if args.format == StatsFormat::Json {
+        let mut contents: Vec<u8> = Vec::new();
+        stats::write_json(
+            rows.iter()
+                .map(|(infile, script, stats)| (infile.as_path(), script, stats)),
+            &mut contents,
+        )?;
+        println!("{}", String::from_utf8(contents)?);
+    }
+
+    if let Some(report) = &args.csv {
+        let mut contents: Vec<u8> = Vec::new();
+        stats::write_csv(
+            rows.iter()
+                .map(|(infile, script, stats)| (infile.as_path(), script, stats)),
+            &mut contents,
+        )?;
+        write_atomically(report, &String::from_utf8(contents)?)?;
+        info!(
+            "Wrote the statistics of {} scripts to {:?}",
+            rows.len(),
+            report
+        );
+    }
+
+    Ok(())
+}
+
+/// Run the `validate` subcommand, failing if any of the scripts has a problem.
+fn validate(args: ValidateArgs, options: &ParseOptions) -> Result<(), Box<dyn Error>> {
+    let mut failures = 0;
+
+    for infile in &args.infiles {
+        let script = match read_script(infile, options) {
+            Ok(script) => script,
+            Err(e) => {
+                warn!("{}: {}", infile.display(), e);
+                failures += 1;
+                continue;
+            }
+        };
+
+        let mut stats = ScriptStats::counted(&script, &args.counting);
+        stats.target = args.target.or(stats.target);
+
+        match stats.check_target(args.tolerance) {
+            Ok(()) => info!("{}: OK", infile.display()),
+            Err(e) => {
+                warn!("{}: {}", infile.display(), e);
+                failures += 1;
+            }
+        }
+    }
+
+    if failures > 0 {
+        Err(format!(
+            "{} of {} scripts failed validation",
+            failures,
+            args.infiles.len()
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Run the `preview` subcommand.
+fn preview(args: PreviewArgs, options: &ParseOptions) -> Result<(), Box<dyn Error>> {
+    let script = read_script(&args.infile, options)?;
+
+    match args.width {
+        0 => println!("{}", script),
+        width => println!("{:width$}", script, width = width),
+    }
+
+    Ok(())
+}
+
+/// Run the `replace` subcommand, rewriting each file which has any matches.
+fn replace(args: ReplaceArgs, options: &ParseOptions) -> Result<(), Box<dyn Error>> {
+    let (pattern, replacement) = if args.regex {
+        (Regex::new(&args.pattern)?, args.replacement)
+    } else {
+        (
+            Regex::new(&regex::escape(&args.pattern))?,
+            args.replacement.replace('$', "$$"),
+        )
+    };
+
+    for infile in &args.infiles {
+        let contents = decode_text(&fs::read(infile)?)?;
+        let (replaced, count) =
+            tex_handler::replace_text(&contents, &pattern, &replacement, args.scope);
+
+        if count == 0 {
+            info!("{}: no matches", infile.display());
+            continue;
+        }
+
+        // make sure that the replacement hasn't broken the script before overwriting it
+        Tex::from(replaced.as_str())
+            .parse_script(options)
+            .map_err(|e| {
+                format!(
+                    "{}: not replacing, as the result would not parse: {}",
+                    infile.display(),
+                    e
+                )
+            })?;
+        write_atomically(infile, &replaced)?;
+
+        info!("{}: replaced {} matches", infile.display(), count);
+    }
+
+    Ok(())
+}
+
+/// Run the `fmt` subcommand, rewriting each file which isn't already in canonical form.
+fn fmt(args: FmtArgs, options: &ParseOptions) -> Result<(), Box<dyn Error>> {
+    // anything which can't be parsed would be lost on rewriting, so refuse to go on instead
+    let options = ParseOptions {
+        strict: true,
+        ..options.clone()
+    };
+
+    let mut unformatted = 0;
+    for infile in &args.infiles {
+        let contents = decode_text(&fs::read(infile)?)?;
+
+        // likewise, refuse to rewrite a file if doing so would drop anything
+        let unkept = tex_handler::unkept(&contents);
+        if !unkept.is_empty() {
+            Err(format!(
+                "{}: formatting would lose {}",
+                infile.display(),
+                unkept.join(", ")
+            ))?;
+        }
+
+        let mut script = Tex::from(contents.as_str())
+            .parse_script(&options)
+            .map_err(|e| format!("{}: {}", infile.display(), e))?;
+        script.canonicalize();
+
+        // keep the file's own preamble
+        let mut formatted = tex_handler::preamble(&contents)
+            .unwrap_or(tex_handler::DEFAULT_PREAMBLE)
+            .to_owned();
+        tex_handler::write_document(&script, &TexOptions::default(), &mut formatted)?;
+
+        if formatted == contents {
+            info!("{}: already in canonical form", infile.display());
+        } else if args.check {
+            warn!("{}: not in canonical form", infile.display());
+            unformatted += 1;
+        } else {
+            write_atomically(infile, &formatted)?;
+            info!("{}: formatted", infile.display());
+        }
+    }
+
+    if unformatted > 0 {
+        Err(format!(
+            "{} of {} scripts are not in canonical form",
+            unformatted,
+            args.infiles.len()
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Run the `cat` subcommand, combining the scripts into the first (whose metadata is kept).
+fn cat(args: CatArgs, options: &ParseOptions) -> Result<(), Box<dyn Error>> {
+    let mut infiles = args.infiles.iter();
+    let first = infiles
+        .next()
+        .ok_or("At least one input file is required")?;
+    let mut script = read_script(first, options)?;
+
+    for infile in infiles {
+        if let Some(separator) = &args.separator {
+            script.paragraphs.push(
+                TextContainer::new(ContainerKind::PlainText).push(TextSpan::normal(separator)),
+            );
+        }
+        script.append(read_script(infile, options)?);
+    }
+
+    info!("<on-cyan><black>Word count: {}</>", script.wordcount());
+
+    let render_options = RenderOptions {
+        strict: options.strict,
+        ..Default::default()
+    };
+    script.save(
+        &args.outfile,
+        FileFormat::from_path(&args.outfile)?,
+        &render_options,
+    )?;
+    info!(
+        "Combined {} scripts into {:?}",
+        args.infiles.len(),
+        args.outfile
+    );
+
+    Ok(())
+}
+
+/// Run the `check` subcommand, printing every finding and failing if any of them is an error.
+fn check(args: CheckArgs, options: &ParseOptions) -> Result<(), Box<dyn Error>> {
+    let scripts = if args.staged {
+        let staged = check::staged_scripts(&args.ignore)?;
+        for path in &staged.ignored {
+            info!("Skipping {} (ignored)", path.display());
+        }
+        staged.scripts
+    } else {
+        args.infiles
+            .into_iter()
+            .map(|infile| {
+                let contents = decode_text(&fs::read(&infile)?)?;
+                Ok((infile, contents))
+            })
+            .collect::<Result<Vec<_>, Box<dyn Error>>>()?
+    };
+
+    let check_options = CheckOptions {
+        target: args.target,
+        tolerance: args.tolerance,
+        counting: args.counting,
+    };
+
+    let mut errors = 0;
+    for (path, contents) in &scripts {
+        for finding in check::check(path, contents, &check_options, options) {
+            if finding.severity == Severity::Error {
+                errors += 1;
+            }
+            println!("{}", finding);
+        }
+    }
+
+    if errors > 0 {
+        Err(format!(
+            "{} errors found in {} scripts",
+            errors,
+            scripts.len()
+        ))?;
+    }
+
+    info!("Checked {} scripts", scripts.len());
+    Ok(())
+}
+
+/// Run the `import` subcommand, printing how confident the import is.
+fn import(args: ImportArgs, options: &ParseOptions) -> Result<(), Box<dyn Error>> {
+    let contents = read_text(&args.infile, options)?;
+    let (mut script, report) = import::import(&contents);
+    if script.title.is_empty() {
+        script.title = args.title.unwrap_or_else(|| {
+            args.infile
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        });
+    }
+    if script.author.is_empty() {
+        script.author = args.author.unwrap_or_default();
+    }
+
+    println!("{}", report);
+    if !report.assumed.is_empty() {
+        warn!(
+            "{} lines followed no recognised convention, so were assumed to be spoken",
+            report.assumed.len()
+        );
+    }
+
+    let render_options = RenderOptions {
+        strict: options.strict,
+        ..Default::default()
+    };
+    script.save(
+        &args.outfile,
+        FileFormat::from_path(&args.outfile)?,
+        &render_options,
+    )?;
+    info!("<on-cyan><black>Word count: {}</>", script.wordcount());
+
+    Ok(())
+}
+
+/// Run the `report` subcommand.
+fn report(args: ReportArgs, options: &ParseOptions) -> Result<(), Box<dyn Error>> {
+    let mut report = report::Report {
+        counting: args.counting,
+        ..Default::default()
+    };
+    let infiles = args.discover.discover(&args.dir)?;
+    let mut failures: Vec<(&PathBuf, String)> = Vec::new();
+    for infile in &infiles {
+        match read_scripts(infile, options) {
+            Ok(scripts) => scripts.iter().for_each(|script| report.add(script)),
+            Err(e) => failures.push((infile, e.to_string())),
+        }
+    }
+
+    let Some(outfile) = &args.outfile else {
+        println!("{}", report.to_markdown());
+        return report_failures(&failures, infiles.len());
+    };
+
+    let contents = match FileFormat::from_path(outfile)? {
+        FileFormat::Markdown => report.to_markdown(),
+        FileFormat::Html => report.to_html(),
+        _ => return Err(ScriptError::UnknownFormat.into()),
+    };
+    write_atomically(outfile, &contents)?;
+    info!(
+        "Wrote the report of {} scripts to {:?}",
+        report.overall.scripts, outfile
+    );
+
+    report_failures(&failures, infiles.len())
+}
+
+/// Run the `index` subcommand, either building an index of a directory or searching one.
+fn index(args: IndexArgs, options: &ParseOptions) -> Result<(), Box<dyn Error>> {
+    if let Some(IndexCommand::Query(args)) = args.command {
+        let index = index::Index::from_json(&decode_text(&fs::read(&args.index)?)?)?;
+        let query = index::Query {
+            tags: args.tags,
+            series: args.series,
+            author: args.author,
+            character: args.character,
+            title: args.title,
+            min_words: args.min_words,
+            max_words: args.max_words,
+        };
+
+        let mut matches = 0;
+        for entry in index.search(&query) {
+            matches += 1;
+            let series = match (&entry.series, &entry.part) {
+                (Some(series), Some(part)) => format!(" [{} ({})]", series, part),
+                (Some(series), None) => format!(" [{}]", series),
+                _ => String::new(),
+            };
+            println!(
+                "{}{} — {} spoken words ({})",
+                entry.title, series, entry.spoken_words, entry.path
+            );
+        }
+        info!("{} of {} scripts matched", matches, index.scripts.len());
+
+        return Ok(());
+    }
+
+    // clap requires the directory whenever there is no subcommand
+    let dir = args.dir.ok_or("No directory to index")?;
+
+    // an earlier index written into the directory is .json too, but isn't a script
+    let outfile = fs::canonicalize(&args.outfile).ok();
+    let infiles: Vec<PathBuf> = args
+        .discover
+        .discover(&dir)?
+        .into_iter()
+        .filter(|infile| outfile.is_none() || fs::canonicalize(infile).ok() != outfile)
+        .collect();
+    let mut index = index::Index::default();
+    let mut failures: Vec<(&PathBuf, String)> = Vec::new();
+    for infile in &infiles {
+        match read_scripts(infile, options) {
+            Ok(scripts) => scripts.iter().for_each(|script| index.add(infile, script)),
+            Err(e) => failures.push((infile, e.to_string())),
+        }
+    }
+
+    write_atomically(&args.outfile, &index.to_json()?)?;
+    info!(
+        "Indexed {} scripts into {:?}",
+        index.scripts.len(),
+        args.outfile
+    );
+
+    report_failures(&failures, infiles.len())
+}
@@ -0,0 +1,85 @@
+use crate::script::Script;
+
+pub trait ToJson {
+    /// Convert the object to (pretty-printed) JSON, for use by other tools.
+    fn to_json(&self) -> String;
+}
+
+pub trait ToYaml {
+    /// Convert the object to YAML, for use by other tools.
+    fn to_yaml(&self) -> String;
+}
+
+impl ToJson for Script {
+    /**
+    Convert the script to JSON, with every field of the script (unlike Markdown, nothing is lost).
+
+    # Examples
+
+    ```
+    # use lilscript::{data_handler::{from_json, ToJson}, script::{ContainerKind, Script, TextContainer, TextSpan}};
+    let mut script = Script::new("lilellia", "A Very Cool Script");
+    script.paragraphs.push(
+        TextContainer::new(ContainerKind::Spoken)
+            .push(TextSpan::normal("Hello."))
+            .spoken_by("Alice"),
+    );
+
+    let json = script.to_json();
+    assert!(json.contains(r#""kind": "spoken""#));
+    assert!(json.contains(r#""speaker": "Alice""#));
+    assert_eq!(from_json(&json).unwrap(), script);
+    ```
+    */
+    fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("a script can always be written as JSON")
+    }
+}
+
+impl ToYaml for Script {
+    /**
+    Convert the script to YAML, with the same structure as `ToJson::to_json`.
+
+    # Examples
+
+    ```
+    # use lilscript::{data_handler::{from_yaml, ToYaml}, script::Script};
+    let mut script = Script::new("lilellia", "A Very Cool Script");
+    script.tags = vec!["F4A".into()];
+
+    let yaml = script.to_yaml();
+    assert!(yaml.starts_with("author: lilellia\ntitle: A Very Cool Script\n"));
+    assert_eq!(from_yaml(&yaml).unwrap(), script);
+    ```
+    */
+    fn to_yaml(&self) -> String {
+        serde_yaml::to_string(self).expect("a script can always be written as YAML")
+    }
+}
+
+/**
+Read a script back in from JSON, as written by `ToJson::to_json`. Any field (other than the kind and spans
+of each container) may be left out, and is then empty.
+
+# Examples
+
+```
+# use lilscript::{data_handler::from_json, script::ContainerKind};
+let script = from_json(r#"{
+    "title": "A Very Cool Script",
+    "paragraphs": [{ "kind": "sfx", "spans": [{ "kind": "normal", "contents": "rain" }] }]
+}"#).unwrap();
+assert_eq!(script.title, "A Very Cool Script");
+assert_eq!(script.paragraphs[0].kind, ContainerKind::Sfx);
+
+assert!(from_json("{ \"title\": 4 }").is_err());
+```
+*/
+pub fn from_json(json: &str) -> Result<Script, String> {
+    serde_json::from_str(json).map_err(|e| format!("Invalid JSON script: {}", e))
+}
+
+/// Read a script back in from YAML, as written by `ToYaml::to_yaml` (see `from_json`).
+pub fn from_yaml(yaml: &str) -> Result<Script, String> {
+    serde_yaml::from_str(yaml).map_err(|e| format!("Invalid YAML script: {}", e))
+}
@@ -0,0 +1,117 @@
+use crate::script::Script;
+use regex::{Captures, Regex};
+use std::sync::LazyLock;
+
+/// The shortcodes (without the surrounding colons) understood by the conversion, with their emoji.
+const SHORTCODES: &[(&str, &str)] = &[
+    ("angry", "😠"),
+    ("blush", "😊"),
+    ("broken_heart", "💔"),
+    ("cat", "🐱"),
+    ("coffee", "☕"),
+    ("cold_sweat", "😰"),
+    ("cry", "😢"),
+    ("crescent_moon", "🌙"),
+    ("fire", "🔥"),
+    ("flushed", "😳"),
+    ("grin", "😁"),
+    ("headphones", "🎧"),
+    ("heart", "❤️"),
+    ("heart_eyes", "😍"),
+    ("hugs", "🤗"),
+    ("joy", "😂"),
+    ("kiss", "😘"),
+    ("microphone", "🎤"),
+    ("musical_note", "🎵"),
+    ("pensive", "😔"),
+    ("pleading_face", "🥺"),
+    ("rain_cloud", "🌧️"),
+    ("relieved", "😌"),
+    ("rose", "🌹"),
+    ("sleeping", "😴"),
+    ("smile", "😄"),
+    ("smirk", "😏"),
+    ("sob", "😭"),
+    ("sparkles", "✨"),
+    ("sparkling_heart", "💖"),
+    ("star", "⭐"),
+    ("sunflower", "🌻"),
+    ("sweat_smile", "😅"),
+    ("thinking", "🤔"),
+    ("two_hearts", "💕"),
+    ("wave", "👋"),
+    ("wink", "😉"),
+    ("yawning_face", "🥱"),
+    ("zzz", "💤"),
+];
+
+/// Look up the emoji for the given shortcode (without the surrounding colons).
+///
+/// # Examples
+/// ```
+/// # use lilscript::emoji::emoji_for;
+/// assert_eq!(emoji_for("sparkles"), Some("✨"));
+/// assert_eq!(emoji_for("not_an_emoji"), None);
+/// ```
+pub fn emoji_for(shortcode: &str) -> Option<&'static str> {
+    SHORTCODES
+        .iter()
+        .find(|(code, _)| *code == shortcode)
+        .map(|(_, emoji)| *emoji)
+}
+
+/// A `:shortcode:`, or a custom TeX emote command named after one (e.g., `\sparkles` or `\sparkles{}`),
+/// which `Tex::unescaped` leaves in the text as it is.
+static SHORTCODE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r":([a-z0-9_+-]+):|\\([a-z_]+)(?:\{\})?").unwrap());
+
+/// Replace every known `:shortcode:` (or custom TeX emote command of the same name) in the text with its emoji.
+/// Unknown shortcodes (and things which merely look like them, such as times) are left alone.
+///
+/// # Examples
+/// ```
+/// # use lilscript::emoji::shortcodes_to_emoji;
+/// let s = "Good night :sparkles: see you at 10:30:00 :unknown:";
+/// assert_eq!(shortcodes_to_emoji(s), "Good night ✨ see you at 10:30:00 :unknown:");
+/// assert_eq!(shortcodes_to_emoji(r"Sleep well\zzz{} \sighs"), r"Sleep well💤 \sighs");
+/// ```
+pub fn shortcodes_to_emoji(text: &str) -> String {
+    SHORTCODE
+        .replace_all(text, |caps: &Captures| {
+            let shortcode = caps.get(1).or(caps.get(2)).unwrap().as_str();
+            emoji_for(shortcode).unwrap_or(&caps[0]).to_owned()
+        })
+        .into_owned()
+}
+
+/// Replace every known emoji in the text with its `:shortcode:`; the reverse of `shortcodes_to_emoji`.
+/// This is applied to Markdown input when `ParseOptions::shortcodes` is set.
+///
+/// # Examples
+/// ```
+/// # use lilscript::emoji::emoji_to_shortcodes;
+/// assert_eq!(emoji_to_shortcodes("Good night ✨"), "Good night :sparkles:");
+/// ```
+pub fn emoji_to_shortcodes(text: &str) -> String {
+    // longer emoji first, so that e.g. a heart with a variation selector is not split apart
+    let mut shortcodes: Vec<&(&str, &str)> = SHORTCODES.iter().collect();
+    shortcodes.sort_by_key(|(_, emoji)| std::cmp::Reverse(emoji.len()));
+
+    shortcodes
+        .into_iter()
+        .fold(text.to_owned(), |text, (code, emoji)| {
+            text.replace(emoji, &format!(":{}:", code))
+        })
+}
+
+/// Replace the shortcodes throughout the text of the script (and its metadata) with emoji.
+pub fn emojify(script: &mut Script) {
+    script.title = shortcodes_to_emoji(&script.title);
+    script.summary = shortcodes_to_emoji(&script.summary);
+
+    for character in script.characters.iter_mut() {
+        character.description = shortcodes_to_emoji(&character.description);
+    }
+
+    script.map_spans(|span| span.contents = shortcodes_to_emoji(&span.contents));
+}
@@ -0,0 +1,359 @@
+use regex::Regex;
+use std::path::Path;
+
+use crate::{
+    data_handler::{ToJson, ToYaml},
+    html_handler::{teleprompter, HtmlOptions, ToHtml},
+    md_handler::{ambiguous_emphasis, checklist, outline, to_markdown_with, MarkdownOptions},
+    script::{QuoteStyle, Script},
+    ssml_handler::ToSsml,
+    subtitle::{to_srt, to_vtt, SubtitleOptions},
+    tex_handler::{TexOptions, ToTex},
+    transform,
+};
+
+/// A representation of the file formats that this library can process.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum FileFormat {
+    /// Represents a LaTeX (.tex) file
+    Tex,
+
+    /// Represents a Markdown (.md) file
+    #[cfg_attr(feature = "cli", value(alias = "md"))]
+    Markdown,
+
+    /// Represents an SSML (.ssml) file (export only)
+    Ssml,
+
+    /// Represents an HTML (.html) file (export only)
+    Html,
+
+    /// Represents a JSON (.json) file, holding the whole parsed script
+    Json,
+
+    /// Represents a YAML (.yaml) file, holding the whole parsed script
+    #[cfg_attr(feature = "cli", value(alias = "yml"))]
+    Yaml,
+
+    /// Represents a SubRip (.srt) subtitle file of the spoken lines (export only)
+    Srt,
+
+    /// Represents a WebVTT (.vtt) subtitle file of the spoken lines (export only)
+    Vtt,
+}
+
+impl FileFormat {
+    /** Determine the file format from a given path.
+
+    # Arguments
+
+    * `p` - a reference to a path object
+
+    # Return
+
+    * `Ok(ext: FileFormat)` if the extension could be determined;
+    * `Err(_)` otherwise
+
+    # Examples:
+
+    * With a valid extension:
+    ```
+    # use lilscript::FileFormat;
+    # use std::path::PathBuf;
+    let p = PathBuf::from(r"/home/user/Documents/f.tex");
+    let file_format = FileFormat::from_path(&p).unwrap();
+    assert_eq!(file_format, FileFormat::Tex);
+    ```
+
+    * With an invalid extension:
+    ```
+    # use lilscript::FileFormat;
+    # use std::path::PathBuf;
+    let p = PathBuf::from(r"/home/user/Documents/g.csv");
+    let file_format = FileFormat::from_path(&p);
+    assert!(file_format.is_err());
+    ```
+    */
+    pub fn from_path(p: &Path) -> Result<Self, String> {
+        match p.extension() {
+            Some(ext) => match ext.to_str() {
+                Some("tex") => Ok(Self::Tex),
+                Some("md") => Ok(Self::Markdown),
+                Some("ssml") => Ok(Self::Ssml),
+                Some("html" | "htm") => Ok(Self::Html),
+                Some("json") => Ok(Self::Json),
+                Some("yaml" | "yml") => Ok(Self::Yaml),
+                Some("srt") => Ok(Self::Srt),
+                Some("vtt") => Ok(Self::Vtt),
+                _ => Err(
+                    "Invalid file extension: should be .tex / .md / .ssml / .html / .json / .yaml / .srt / .vtt"
+                        .to_owned(),
+                ),
+            },
+            None => Err("Invalid file extension: could not be determined".to_owned()),
+        }
+    }
+
+    /** The usual extension (without the leading `.`) for files of this format.
+
+    # Examples
+
+    ```
+    # use lilscript::FileFormat;
+    assert_eq!(FileFormat::Markdown.extension(), "md");
+    ```
+    */
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Tex => "tex",
+            Self::Markdown => "md",
+            Self::Ssml => "ssml",
+            Self::Html => "html",
+            Self::Json => "json",
+            Self::Yaml => "yaml",
+            Self::Srt => "srt",
+            Self::Vtt => "vtt",
+        }
+    }
+
+    /** Determine the format of a file which is to be read: from its extension where possible, and from its
+    contents (see `FileFormat::sniff`) otherwise.
+
+    # Examples
+
+    ```
+    # use lilscript::FileFormat;
+    # use std::path::Path;
+    let tex = "\\documentclass{article}\n\\renewcommand{\\SceneName}{Title}";
+    assert_eq!(FileFormat::detect(Path::new("script.md"), tex), Some(FileFormat::Markdown));
+    assert_eq!(FileFormat::detect(Path::new("script.txt"), tex), Some(FileFormat::Tex));
+    assert_eq!(FileFormat::detect(Path::new("notes.txt"), "just some notes"), None);
+    ```
+    */
+    pub fn detect(path: &Path, contents: &str) -> Option<Self> {
+        Self::from_path(path).ok().or_else(|| Self::sniff(contents))
+    }
+
+    /** Determine the file format from the contents of a file, for when the path is no help
+    (e.g., a file piped in via stdin, or one with a missing or unusual extension).
+
+    # Arguments
+
+    * `contents` - the text of the file
+
+    # Return
+
+    * `Some(FileFormat)` if the contents look like one of the input formats;
+    * `None` otherwise
+
+    # Examples
+
+    ```
+    # use lilscript::FileFormat;
+    let tex = "\\documentclass{article}\n\\renewcommand{\\SceneName}{Title}";
+    assert_eq!(FileFormat::sniff(tex), Some(FileFormat::Tex));
+
+    let md = "## Characters\n\n- **Speaker** ∼ a kind witch";
+    assert_eq!(FileFormat::sniff(md), Some(FileFormat::Markdown));
+    assert_eq!(FileFormat::sniff("# Title\n\n**Hello.**"), Some(FileFormat::Markdown));
+    assert_eq!(FileFormat::sniff("> *[A door opens.]*\n\n**Hello.**"), Some(FileFormat::Markdown));
+
+    assert_eq!(FileFormat::sniff("{\"author\": \"lilellia\"}"), Some(FileFormat::Json));
+    assert_eq!(FileFormat::sniff("author: lilellia\ntitle: Title"), Some(FileFormat::Yaml));
+    assert_eq!(FileFormat::sniff("name,age\nlilellia,7"), None);
+    ```
+    */
+    pub fn sniff(contents: &str) -> Option<Self> {
+        let contents = contents.trim_start_matches('\u{feff}').trim_start();
+
+        if contents.starts_with(r"\documentclass")
+            || contents.contains(r"\renewcommand{\SceneName}")
+            || contents.contains(r"\begin{document}")
+        {
+            return Some(Self::Tex);
+        }
+
+        // as written by `data_handler`, whose output always starts with the author
+        if contents.starts_with('{') {
+            return Some(Self::Json);
+        }
+        if contents.starts_with("author:") {
+            return Some(Self::Yaml);
+        }
+
+        // a heading (the `# Title`, the `## Characters`, or that of an outline or checklist), or the
+        // conventions of the body as read by `Markdown::parse_script`: spoken lines in bold (possibly
+        // after a line ID or a speaker), quoted stage directions and listener lines, and the divider
+        let body = Regex::new(
+            r"^(?:> )?(?:\[\d{3,}\] )?(?:\*\*\S.*?\*\*|\*\(.+?\)\*|\*\[.*\]\*|\*«.*»\*)",
+        )
+        .unwrap();
+        if contents.starts_with("# ")
+            || contents.starts_with("## ")
+            || contents
+                .lines()
+                .map(str::trim)
+                .any(|line| line == "--8<--" || body.is_match(line))
+        {
+            return Some(Self::Markdown);
+        }
+
+        None
+    }
+}
+
+/// The different styles of output which can be produced from a script.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum OutputProfile {
+    /// The full script, following the conventions of the output format
+    #[default]
+    Standard,
+
+    /// A self-contained HTML page of the spoken lines, for reading from while recording
+    Teleprompter,
+
+    /// A Markdown skeleton of the script, for reviewing its pacing
+    Outline,
+
+    /// A Markdown checklist with one item per line, for tracking takes while recording
+    Checklist,
+}
+
+/// Where tone cues (inline directions) are placed in the output, relative to the sentence they modify.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum DirectionPlacement {
+    /// Before the sentence, as they are written in the script
+    #[default]
+    Before,
+
+    /// After the sentence, as a trailing parenthetical
+    After,
+}
+
+/// Options controlling how a script is rendered to an output format.
+#[derive(Clone, Debug, Default)]
+pub struct RenderOptions {
+    /// The style of output to produce.
+    pub profile: OutputProfile,
+
+    /// Where tone cues are placed, relative to the sentence they modify.
+    pub directions: DirectionPlacement,
+
+    /// How double quotation marks are written (except in .tex output), or `None` to keep them as they are.
+    pub quotes: Option<QuoteStyle>,
+
+    /// Whether the director's commentary is kept (it always is in .tex, .json, and .yaml output).
+    pub commentary: bool,
+
+    /// The options for Markdown output.
+    pub markdown: MarkdownOptions,
+
+    /// The options for HTML output.
+    pub html: HtmlOptions,
+
+    /// The options for .tex output.
+    pub tex: TexOptions,
+
+    /// The options for .srt and .vtt output.
+    pub subtitles: SubtitleOptions,
+
+    /// Whether anything ambiguous in the output should be an error, rather than just a warning.
+    pub strict: bool,
+}
+
+/**
+Render the script in the given format, according to the requested output profile.
+
+# Return
+
+* `Ok(String)` - the rendered script;
+* `Err(_)` if the profile can't be written in that format (or, in strict mode, the output would be ambiguous)
+
+# Examples
+
+```
+# use lilscript::{render, script::Script, FileFormat, OutputProfile, RenderOptions};
+let script = Script::new("lilellia", "A Very Cool Script");
+assert!(render(&script, FileFormat::Ssml, &RenderOptions::default()).is_ok());
+
+let options = RenderOptions { profile: OutputProfile::Teleprompter, ..Default::default() };
+assert!(render(&script, FileFormat::Markdown, &options).is_err());
+```
+*/
+pub fn render(
+    script: &Script,
+    out_format: FileFormat,
+    options: &RenderOptions,
+) -> Result<String, String> {
+    // the Markdown renderer can only warn about these, so strict mode must catch them up front
+    if options.strict && out_format == FileFormat::Markdown {
+        if let Some(problem) = ambiguous_emphasis(script).into_iter().next() {
+            return Err(problem);
+        }
+    }
+
+    // .tex output always has TeX quotes (see `tex_handler::escape`), and the data and subtitle exports keep
+    // the quotes as they were written; .tex (like the JSON and YAML interchange formats) always keeps any commentary
+    let quotes = options.quotes.filter(|_| {
+        matches!(
+            out_format,
+            FileFormat::Markdown | FileFormat::Html | FileFormat::Ssml
+        )
+    });
+    let strip_commentary = !options.commentary
+        && !matches!(
+            out_format,
+            FileFormat::Tex | FileFormat::Json | FileFormat::Yaml
+        )
+        && script
+            .paragraphs
+            .iter()
+            .flat_map(|c| c.with_overlaps())
+            .any(|c| !c.commentary.is_empty());
+
+    let rewritten;
+    let script = match (options.directions, quotes, strip_commentary) {
+        (DirectionPlacement::Before, None, false) => script,
+        (directions, quotes, strip_commentary) => {
+            let mut script = script.clone();
+            if directions == DirectionPlacement::After {
+                transform::trailing_directions(&mut script);
+            }
+            if let Some(style) = quotes {
+                script.map_spans(|span| span.contents = style.convert(&span.contents));
+            }
+            if strip_commentary {
+                script.map_containers(|container| container.commentary.clear());
+            }
+            rewritten = script;
+            &rewritten
+        }
+    };
+
+    let contents = match (options.profile, out_format) {
+        (OutputProfile::Standard, FileFormat::Tex) => script.to_tex_with(&options.tex),
+        (OutputProfile::Standard, FileFormat::Markdown) => {
+            to_markdown_with(script, &options.markdown)
+        }
+        (OutputProfile::Standard, FileFormat::Ssml) => script.to_ssml(),
+        (OutputProfile::Standard, FileFormat::Html) => script.to_html_with(&options.html),
+        (OutputProfile::Standard, FileFormat::Json) => script.to_json(),
+        (OutputProfile::Standard, FileFormat::Yaml) => script.to_yaml(),
+        (OutputProfile::Standard, FileFormat::Srt) => to_srt(script, &options.subtitles),
+        (OutputProfile::Standard, FileFormat::Vtt) => to_vtt(script, &options.subtitles),
+        (OutputProfile::Teleprompter, FileFormat::Html) => teleprompter(script, &options.html),
+        (OutputProfile::Outline, FileFormat::Markdown) => outline(script),
+        (OutputProfile::Checklist, FileFormat::Markdown) => checklist(script),
+        (profile, format) => {
+            return Err(format!(
+                "The {:?} profile cannot be written to a {:?} file",
+                profile, format
+            ))
+        }
+    };
+
+    Ok(contents)
+}
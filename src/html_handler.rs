@@ -0,0 +1,537 @@
+use crate::md_handler::line_id;
+use crate::script::{ContainerKind, Script, SpanKind, TextContainer, TextSpan};
+use crate::ssml_handler::{escape_xml, escape_xml_attribute};
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// Options controlling how scripts are rendered to HTML.
+#[derive(Debug, Clone)]
+pub struct HtmlOptions {
+    /// The colours (CSS colour values; see `is_css_colour`) assigned to each speaker, in order of first appearance.
+    /// If there are more speakers than colours, the palette is reused from the start.
+    pub palette: Vec<String>,
+
+    /// Whether each line is prefixed with its line ID (e.g., `[012]`), matching the Markdown checklist.
+    pub number_lines: bool,
+}
+
+impl Default for HtmlOptions {
+    fn default() -> Self {
+        // the Okabe–Ito palette, which remains distinguishable under colour-blindness
+        let palette = [
+            "#0072B2", "#D55E00", "#009E73", "#CC79A7", "#E69F00", "#56B4E9",
+        ];
+        Self {
+            palette: palette.iter().map(|c| c.to_string()).collect(),
+            number_lines: false,
+        }
+    }
+}
+
+pub trait ToHtml {
+    /// Convert the object to an HTML format, using the default options.
+    fn to_html(&self) -> String {
+        self.to_html_with(&HtmlOptions::default())
+    }
+
+    /// Convert the object to an HTML format, using the given options.
+    fn to_html_with(&self, options: &HtmlOptions) -> String;
+}
+
+impl ToHtml for TextSpan {
+    /// Convert the TextSpan to HTML
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use lilscript::{script::TextSpan, html_handler::ToHtml};
+    /// let span = TextSpan::normal("Fish & chips");
+    /// assert_eq!(span.to_html(), "Fish &amp; chips");
+    /// ```
+    /// ```
+    /// # use lilscript::{script::TextSpan, html_handler::ToHtml};
+    /// let span = TextSpan::inline("softly");
+    /// assert_eq!(span.to_html(), r#"<span class="direction">(softly)</span>"#);
+    /// ```
+    /// ```
+    /// # use lilscript::{script::TextSpan, html_handler::ToHtml};
+    /// let span = TextSpan::phonetic("Siobhan", "shi-VAWN");
+    /// assert_eq!(span.to_html(), "<ruby>Siobhan<rp>(</rp><rt>shi-VAWN</rt><rp>)</rp></ruby>");
+    /// ```
+    /// ```
+    /// # use lilscript::{script::TextSpan, html_handler::ToHtml};
+    /// let span = TextSpan::emphasis("vraiment").with_lang("fr");
+    /// assert_eq!(span.to_html(), r#"<em lang="fr">vraiment</em>"#);
+    /// ```
+    fn to_html_with(&self, _options: &HtmlOptions) -> String {
+        let s = escape_xml(&self.contents);
+        let lang = match &self.lang {
+            Some(lang) => format!(r#" lang="{}""#, escape_xml_attribute(lang)),
+            None => String::new(),
+        };
+
+        match &self.kind {
+            SpanKind::Normal if lang.is_empty() => s,
+            SpanKind::Normal => format!("<span{}>{}</span>", lang, s),
+            SpanKind::Emphasis => format!("<em{}>{}</em>", lang, s),
+            SpanKind::InlineDirection => {
+                format!(r#"<span class="direction"{}>({})</span>"#, lang, s)
+            }
+            SpanKind::Phonetic(pronunciation) => format!(
+                "<ruby{}>{}<rp>(</rp><rt>{}</rt><rp>)</rp></ruby>",
+                lang,
+                s,
+                escape_xml(pronunciation)
+            ),
+        }
+    }
+}
+
+impl ToHtml for TextContainer {
+    /// Convert the TextContainer to HTML, as a paragraph classed according to its kind
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use lilscript::{script::{ContainerKind, TextSpan, TextContainer}, html_handler::ToHtml};
+    /// let container = TextContainer::new(ContainerKind::Spoken)
+    ///     .push(TextSpan::inline("quietly"))
+    ///     .push(TextSpan::normal("Hello."));
+    /// let expected = r#"<p class="spoken"><span class="direction">(quietly)</span> Hello.</p>"#;
+    /// assert_eq!(container.to_html(), expected);
+    /// ```
+    ///
+    /// ```
+    /// # use lilscript::{script::{ContainerKind, TextSpan, TextContainer}, html_handler::ToHtml};
+    /// let container = TextContainer::new(ContainerKind::Spoken)
+    ///     .push(TextSpan::normal("Hello."))
+    ///     .spoken_by("Alice");
+    /// let expected = r#"<p class="spoken" data-speaker="Alice"><span class="speaker">Alice:</span> Hello.</p>"#;
+    /// assert_eq!(container.to_html(), expected);
+    /// ```
+    ///
+    /// ```
+    /// # use lilscript::{script::{ContainerKind, TextSpan, TextContainer}, html_handler::ToHtml};
+    /// let container = TextContainer::new(ContainerKind::Spoken)
+    ///     .push(TextSpan::normal("Listen."))
+    ///     .overlapping(TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal("rain")));
+    /// let expected = r#"<div class="overlap"><p class="sfx">rain</p><p class="spoken">Listen.</p></div>"#;
+    /// assert_eq!(container.to_html(), expected);
+    /// ```
+    ///
+    /// ```
+    /// # use lilscript::{script::{ContainerKind, TextSpan, TextContainer}, html_handler::ToHtml};
+    /// let cue = TextContainer::new(ContainerKind::Sfx)
+    ///     .push(TextSpan::normal("rain"))
+    ///     .with_attribute("category", "weather")
+    ///     .with_attribute("intensity", "soft");
+    /// let expected = r#"<p class="sfx" data-category="weather" data-intensity="soft">rain</p>"#;
+    /// assert_eq!(cue.to_html(), expected);
+    /// ```
+    ///
+    /// ```
+    /// # use lilscript::{script::{ContainerKind, TextSpan, TextContainer}, html_handler::ToHtml};
+    /// let container = TextContainer::new(ContainerKind::Spoken)
+    ///     .push(TextSpan::normal("Welcome home."))
+    ///     .with_commentary("Written last.");
+    /// let expected = r#"<p class="spoken">Welcome home.</p><aside class="commentary">Written last.</aside>"#;
+    /// assert_eq!(container.to_html(), expected);
+    /// ```
+    ///
+    /// ```
+    /// # use lilscript::{script::TextContainer, html_handler::ToHtml};
+    /// let marker = TextContainer::marker("take 2");
+    /// assert_eq!(marker.to_html(), r#"<a id="take-2" class="marker"></a>"#);
+    /// ```
+    fn to_html_with(&self, options: &HtmlOptions) -> String {
+        container_html(self, options, None)
+    }
+}
+
+/// Convert the container to HTML (see `TextContainer::to_html_with`), optionally numbered with its line ID.
+fn container_html(container: &TextContainer, options: &HtmlOptions, line: Option<&str>) -> String {
+    if let Some(id) = container.marker_id() {
+        return format!(
+            r#"<a id="{}" class="marker"></a>"#,
+            escape_xml_attribute(&id)
+        );
+    }
+
+    let mut text = container
+        .spans
+        .iter()
+        .map(|span| span.to_html_with(options))
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    // speakers are coloured via the stylesheet, keyed on the data-speaker attribute
+    let mut attributes = format!(r#"class="{}""#, css_class(&container.kind));
+    for (key, value) in &container.attributes {
+        let key: String = key
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect();
+        attributes = format!(
+            r#"{} data-{}="{}""#,
+            attributes,
+            key,
+            escape_xml_attribute(value)
+        );
+    }
+    if let Some(speaker) = &container.speaker {
+        let speaker = escape_xml_attribute(speaker);
+        attributes = format!(r#"{} data-speaker="{}""#, attributes, speaker);
+        text = format!(r#"<span class="speaker">{}:</span> {}"#, speaker, text);
+    }
+    if let Some(line) = line {
+        text = format!(r#"<span class="line-id">[{}]</span> {}"#, line, text);
+    }
+
+    let mut html = format!("<p {}>{}</p>", attributes, text);
+    if !container.overlaps.is_empty() {
+        // group concurrent cues together with the line they run underneath
+        let cues = container
+            .overlaps
+            .iter()
+            .map(|cue| cue.to_html_with(options))
+            .collect::<Vec<String>>()
+            .join("");
+        html = format!(r#"<div class="overlap">{}{}</div>"#, cues, html);
+    }
+
+    for text in &container.commentary {
+        html.push_str(&format!(
+            r#"<aside class="commentary">{}</aside>"#,
+            escape_xml(text)
+        ));
+    }
+
+    html
+}
+
+impl ToHtml for Script {
+    fn to_html_with(&self, options: &HtmlOptions) -> String {
+        let title = escape_xml(&self.title);
+        let mut lines: Vec<String> = vec![
+            String::from("<!DOCTYPE html>"),
+            String::from(r#"<html lang="en">"#),
+            String::from("<head>"),
+            String::from(r#"<meta charset="utf-8">"#),
+            format!("<title>{}</title>", title),
+            format!("<style>{}</style>", STYLESHEET),
+        ];
+
+        let speaker_styles = speaker_styles(self, options);
+        if !speaker_styles.is_empty() {
+            lines.push(format!("<style>{}</style>", speaker_styles));
+        }
+
+        lines.append(&mut vec![
+            String::from("</head>"),
+            String::from("<body>"),
+            format!("<h1>{}</h1>", title),
+            format!(r#"<p class="author">by {}</p>"#, escape_xml(&self.author)),
+        ]);
+
+        if !self.characters.is_empty() {
+            lines.push(String::from("<h2>Characters</h2>"));
+            lines.push(String::from("<ul>"));
+            for character in &self.characters {
+                let details = match character.details().as_str() {
+                    "" => String::new(),
+                    details => format!(r#" <span class="details">{}</span>"#, escape_xml(details)),
+                };
+                lines.push(format!(
+                    r#"<li data-speaker="{}"><strong>{}</strong> ∼ {}{}</li>"#,
+                    escape_xml_attribute(&character.name),
+                    escape_xml(&character.name),
+                    escape_xml(&character.description),
+                    details
+                ));
+            }
+            lines.push(String::from("</ul>"));
+        }
+
+        lines.push(String::from("<hr>"));
+        for (i, container) in self.paragraphs.iter().enumerate() {
+            let line = options.number_lines.then(|| line_id(i));
+            lines.push(container_html(container, options, line.as_deref()));
+        }
+
+        lines.push(String::from("</body>"));
+        lines.push(String::from("</html>"));
+        lines.join("\n")
+    }
+}
+
+/// A CSS colour value: a hex colour, a named colour, or an `rgb()`/`hsl()` (etc.) function of numbers.
+static CSS_COLOUR: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(?:#(?:[0-9A-Fa-f]{3,4}|[0-9A-Fa-f]{6}|[0-9A-Fa-f]{8})|[A-Za-z]+|(?:rgba?|hsla?|hwb|lab|lch|oklab|oklch)\([0-9A-Za-z.,%/+\- ]*\))$",
+    )
+    .unwrap()
+});
+
+/**
+Whether the given text is a CSS colour value which can be written into a stylesheet as it is.
+
+# Examples
+
+```
+# use lilscript::html_handler::is_css_colour;
+assert!(is_css_colour("#0072B2"));
+assert!(is_css_colour("rebeccapurple"));
+assert!(is_css_colour("rgb(0 114 178 / 50%)"));
+assert!(!is_css_colour("red; } body { display: none"));
+assert!(!is_css_colour("</style><script>"));
+```
+*/
+pub fn is_css_colour(text: &str) -> bool {
+    CSS_COLOUR.is_match(text.trim())
+}
+
+/// Escape text for use within a double-quoted CSS string: anything which could end the string
+/// (or the `<style>` element around it) is written as a hex escape, e.g. `<` as `\3c `.
+fn escape_css_string(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_alphanumeric() || (c.is_ascii_punctuation() && !"\"\\<>&".contains(c)) || c == ' ' {
+            escaped.push(c);
+        } else {
+            escaped.push_str(&format!("\\{:x} ", c as u32));
+        }
+    }
+    escaped
+}
+
+/**
+Build the CSS rules which give each speaker in the script their own colour.
+
+Colours are assigned from the palette in order of each speaker's first appearance,
+so the same script always produces the same colours. Palette entries which aren't
+CSS colours (see `is_css_colour`) are skipped.
+
+# Examples
+
+```
+# use lilscript::html_handler::{speaker_styles, HtmlOptions};
+# use lilscript::script::{ContainerKind, Script, TextContainer, TextSpan};
+let mut script = Script::new("lilellia", "A Very Cool Script");
+for speaker in ["Alice", "Bob", "Alice"] {
+    script.paragraphs.push(
+        TextContainer::new(ContainerKind::Spoken)
+            .push(TextSpan::normal("Hello."))
+            .spoken_by(speaker),
+    );
+}
+
+let options = HtmlOptions { palette: vec!["red".to_string(), "blue".to_string()], ..Default::default() };
+let expected = r#"[data-speaker="Alice"] { color: red; } [data-speaker="Bob"] { color: blue; }"#;
+assert_eq!(speaker_styles(&script, &options), expected);
+
+// neither a speaker's name nor a palette entry can end the <style> element
+let mut script = Script::new("lilellia", "A Very Cool Script");
+script.paragraphs.push(
+    TextContainer::new(ContainerKind::Spoken)
+        .push(TextSpan::normal("Hello."))
+        .spoken_by(r#"</style><b>"Eve"</b>"#),
+);
+let options = HtmlOptions {
+    palette: vec!["red</style>".to_string(), "#0072B2".to_string()],
+    ..Default::default()
+};
+let expected = r#"[data-speaker="\3c /style\3e \3c b\3e \22 Eve\22 \3c /b\3e "] { color: #0072B2; }"#;
+assert_eq!(speaker_styles(&script, &options), expected);
+```
+*/
+pub fn speaker_styles(script: &Script, options: &HtmlOptions) -> String {
+    let palette: Vec<&String> = options
+        .palette
+        .iter()
+        .filter(|colour| is_css_colour(colour))
+        .collect();
+    if palette.is_empty() {
+        return String::new();
+    }
+
+    script
+        .speakers()
+        .iter()
+        .zip(palette.iter().cycle())
+        .map(|(speaker, colour)| {
+            format!(
+                r#"[data-speaker="{}"] {{ color: {}; }}"#,
+                escape_css_string(speaker),
+                colour.trim()
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/**
+Render the script as a teleprompter: a single self-contained HTML page for performers to read from.
+
+Only the spoken lines are shown, in large high-contrast text, with any tone cues (and cues which
+run concurrently with a line) dimmed. The page scrolls itself, driven by the keyboard:
+
+* `space` - start/stop scrolling
+* `↑` / `↓` - scroll slower/faster
+* `+` / `-` - make the text larger/smaller
+* `Home` - jump back to the start
+
+# Examples
+
+```
+# use lilscript::html_handler::{teleprompter, HtmlOptions};
+# use lilscript::script::{ContainerKind, Script, TextContainer, TextSpan};
+let mut script = Script::new("lilellia", "A Very Cool Script");
+script.paragraphs.push(TextContainer::new(ContainerKind::StageDir).push(TextSpan::normal("A door opens.")));
+script.paragraphs.push(
+    TextContainer::new(ContainerKind::Spoken)
+        .push(TextSpan::inline("softly"))
+        .push(TextSpan::normal("Hello.")),
+);
+
+let html = teleprompter(&script, &HtmlOptions::default());
+assert!(html.contains(r#"<p class="line"><span class="cue">(softly)</span> Hello.</p>"#));
+assert!(!html.contains("A door opens."));
+```
+*/
+pub fn teleprompter(script: &Script, options: &HtmlOptions) -> String {
+    let title = escape_xml(&script.title);
+    let mut lines: Vec<String> = vec![
+        String::from("<!DOCTYPE html>"),
+        String::from(r#"<html lang="en">"#),
+        String::from("<head>"),
+        String::from(r#"<meta charset="utf-8">"#),
+        format!("<title>{} (teleprompter)</title>", title),
+        format!("<style>{}</style>", TELEPROMPTER_STYLESHEET),
+        format!("<style>{}</style>", speaker_styles(script, options)),
+        String::from("</head>"),
+        String::from("<body>"),
+        format!("<h1>{}</h1>", title),
+    ];
+
+    for container in &script.paragraphs {
+        // markers are kept (invisibly) so that the page can still be jumped to them
+        if let Some(id) = container.marker_id() {
+            lines.push(format!(r#"<a id="{}"></a>"#, escape_xml_attribute(&id)));
+        }
+        if container.kind != ContainerKind::Spoken {
+            continue;
+        }
+
+        let mut text: Vec<String> = container
+            .overlaps
+            .iter()
+            .map(|cue| {
+                format!(
+                    r#"<span class="cue">[{}]</span>"#,
+                    escape_xml(&cue.plain_text())
+                )
+            })
+            .collect();
+
+        if let Some(speaker) = &container.speaker {
+            text.push(format!(
+                r#"<span class="cue">{}:</span>"#,
+                escape_xml(speaker)
+            ));
+        }
+
+        for span in &container.spans {
+            text.push(match span.kind {
+                SpanKind::InlineDirection => {
+                    format!(
+                        r#"<span class="cue">({})</span>"#,
+                        escape_xml(&span.contents)
+                    )
+                }
+                _ => span.to_html_with(options),
+            });
+        }
+
+        let speaker = match &container.speaker {
+            Some(speaker) => format!(r#" data-speaker="{}""#, escape_xml_attribute(speaker)),
+            None => String::new(),
+        };
+        lines.push(format!(
+            r#"<p class="line"{}>{}</p>"#,
+            speaker,
+            text.join(" ")
+        ));
+    }
+
+    lines.push(format!("<script>{}</script>", TELEPROMPTER_SCRIPT));
+    lines.push(String::from("</body>"));
+    lines.push(String::from("</html>"));
+    lines.join("\n")
+}
+
+/// The CSS class used for a container of the given kind.
+fn css_class(kind: &ContainerKind) -> &'static str {
+    match kind {
+        ContainerKind::Spoken => "spoken",
+        ContainerKind::StageDir => "stagedir",
+        ContainerKind::Sfx => "sfx",
+        ContainerKind::ListenerDialogue => "listener",
+        ContainerKind::PlainText => "plaintext",
+        ContainerKind::Marker => "marker",
+    }
+}
+
+/// The styling embedded into exported documents, mirroring the Markdown conventions.
+const STYLESHEET: &str = "\
+body { max-width: 40em; margin: auto; font-family: sans-serif; line-height: 1.5; } \
+.spoken { font-weight: bold; } \
+.direction { font-weight: normal; font-style: italic; } \
+.stagedir, .sfx, .listener { font-style: italic; margin-left: 2em; } \
+.stagedir::before { content: '['; } \
+.stagedir::after { content: ']'; } \
+.sfx::before { content: '[sfx: '; } \
+.sfx::after { content: ']'; } \
+.listener::before { content: '« '; } \
+.listener::after { content: ' »'; } \
+.overlap { border-left: 3px solid #999; padding-left: 1em; } \
+.overlap > p { margin: 0.25em 0; } \
+.speaker { font-variant: small-caps; } \
+.details { color: #666; font-size: 0.9em; } \
+.commentary { margin: 0.5em 0 1em 2em; padding-left: 1em; border-left: 3px solid #c9a; color: #555; } \
+.commentary::before { content: 'Commentary: '; font-weight: bold; } \
+.line-id { font-weight: normal; font-style: normal; color: #999; font-family: monospace; }";
+
+/// The styling for the teleprompter: large, high-contrast text on a dark background.
+const TELEPROMPTER_STYLESHEET: &str = "\
+body { background: #000; color: #fff; font-family: sans-serif; font-size: 48px; \
+line-height: 1.6; max-width: 30em; margin: auto; padding: 50vh 1em; } \
+h1 { font-size: 1em; color: #888; } \
+.line { margin: 0 0 1.5em 0; } \
+.cue { color: #666; font-style: italic; font-size: 0.75em; } \
+em { text-decoration: underline; font-style: normal; } \
+rt { color: #888; }";
+
+/// The keyboard-driven auto-scrolling for the teleprompter.
+const TELEPROMPTER_SCRIPT: &str = "
+let speed = 40, scrolling = false, last = null, offset = window.scrollY;
+function step(now) {
+  if (!scrolling) { last = null; return; }
+  if (last !== null) { offset += speed * (now - last) / 1000; window.scrollTo(0, offset); }
+  last = now;
+  requestAnimationFrame(step);
+}
+document.addEventListener('keydown', (e) => {
+  const size = parseFloat(getComputedStyle(document.body).fontSize);
+  switch (e.key) {
+    case ' ': scrolling = !scrolling; offset = window.scrollY; requestAnimationFrame(step); break;
+    case 'ArrowUp': speed = Math.max(5, speed - 5); break;
+    case 'ArrowDown': speed += 5; break;
+    case '+': case '=': document.body.style.fontSize = (size + 4) + 'px'; break;
+    case '-': document.body.style.fontSize = Math.max(12, size - 4) + 'px'; break;
+    case 'Home': offset = 0; window.scrollTo(0, 0); break;
+    default: return;
+  }
+  e.preventDefault();
+});
+";
@@ -0,0 +1,415 @@
+use regex::Regex;
+use std::{collections::BTreeMap, fmt};
+
+use crate::{
+    ast::{push_container, ContainerNode, Document, Section, SECTION_DIVIDER},
+    script::{ContainerKind, Script, TextContainer, TextSpan},
+};
+
+/// How sure the importer is about a script: how many lines it recognised, by which convention,
+/// and which lines it could only guess at.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImportReport {
+    /// The number of lines recognised by each convention.
+    pub recognised: BTreeMap<&'static str, usize>,
+
+    /// The (1-based) line number and text of each line which didn't follow any convention,
+    /// and so was assumed to be spoken.
+    pub assumed: Vec<(usize, String)>,
+}
+
+impl ImportReport {
+    /** The proportion of lines which were recognised, rather than assumed to be spoken.
+
+    # Examples
+
+    ```
+    # use lilscript::import::import;
+    let (_, report) = import("[sfx: rain]\nHello there.");
+    assert_eq!(report.confidence(), 0.5);
+    ```
+    */
+    pub fn confidence(&self) -> f64 {
+        let recognised: usize = self.recognised.values().sum();
+        match recognised + self.assumed.len() {
+            0 => 1.,
+            total => recognised as f64 / total as f64,
+        }
+    }
+
+    /// Count a line as recognised by the given convention.
+    fn recognise(&mut self, convention: &'static str) {
+        *self.recognised.entry(convention).or_default() += 1;
+    }
+}
+
+impl fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let recognised: usize = self.recognised.values().sum();
+        write!(
+            f,
+            "Recognised {} of {} lines ({:.1}% confidence)",
+            recognised,
+            recognised + self.assumed.len(),
+            100. * self.confidence()
+        )?;
+
+        for (convention, count) in &self.recognised {
+            write!(f, "\n  {}: {}", convention, count)?;
+        }
+
+        if !self.assumed.is_empty() {
+            write!(f, "\nAssumed to be spoken:")?;
+            for (line, text) in &self.assumed {
+                write!(f, "\n  line {}: {}", line, text)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The regular expressions recognising each convention, compiled once per import.
+struct Conventions {
+    heading: Regex,
+    tagged_title: Regex,
+    author: Regex,
+    divider: Regex,
+    sfx: Regex,
+    direction: Regex,
+    speaker: Regex,
+    bold: Regex,
+    inline: Regex,
+    escape: Regex,
+}
+
+impl Conventions {
+    fn new() -> Self {
+        Self {
+            heading: Regex::new(r"^#+\s+(.+)$").unwrap(),
+            tagged_title: Regex::new(r"^(\[[^\[\]]{1,40}\]\s*)+[^\[\]]+(\s*\[[^\[\]]{1,40}\])*$")
+                .unwrap(),
+            author: Regex::new(r"(?i)^(?:written by|script by|author:|by)\s+(.+)$").unwrap(),
+            divider: Regex::new(r"^(?:-{3,}|\*{3,}|_{3,}|(?:\* ){2,}\*|--8<--)$").unwrap(),
+            sfx: Regex::new(
+                r"(?i)^[\*_]*[\[(]\s*(?:sfx|sound effects?|sound|music)\s*[:\-–]\s*(.+?)\s*[\])][\*_]*$",
+            )
+            .unwrap(),
+            direction: Regex::new(r"^(?:\*[\[(](.+)[\])]\*|\[(.+)\]|\((.+)\)|\*([^*].*)\*|_(.+)_)$")
+                .unwrap(),
+            speaker: Regex::new(
+                r"^(?:\*\*)?([A-Z][\w'.-]*(?: [A-Z][\w'.-]*){0,2})(?:\*\*)?\s*:(?:\*\*)?\s+(.+)$",
+            )
+            .unwrap(),
+            bold: Regex::new(r"^\*\*(.+)\*\*$").unwrap(),
+            inline: Regex::new(
+                r"\*?[\[(]([^\[\]()]+)[\])]\*?|\*\*([^*]+)\*\*|\*([^*]+)\*|\b_([^_]+)_\b",
+            )
+            .unwrap(),
+            escape: Regex::new(r"\\([\\*_\[\]()#>`])").unwrap(),
+        }
+    }
+}
+
+/**
+Import a script written by someone else, as Markdown or plain text, on a best-effort basis.
+
+Each line is matched against the conventions commonly used by other authors:
+
+* a `# Heading`, or a line of `[tags]` and the title (e.g., `[F4A] [Comfort] Title [Sleep aid]`),
+  before the body starts ⟶ the title (and tags), and `Written by ...` ⟶ the author
+* `[sfx: ...]` (or `(sound: ...)`, `[music: ...]`, etc.) ⟶ a sound effect
+* a line wholly within `[...]`, `(...)`, `*...*`, or `_..._` ⟶ a stage direction
+* `Name: ...` ⟶ a line spoken by `Name` (or by the listener, if `Name` is `Listener`)
+* `**...**` ⟶ a spoken line
+* `---`, `***`, or `--8<--` ⟶ a divider between sections
+
+Within a spoken line, `(...)` and `[...]` become inline directions, `*...*` and `_..._` emphasis,
+and `**...**` is left as plain text. Anything else is assumed to be spoken, and listed in the report.
+
+# Return
+
+* `(Script, ImportReport)` - the imported script, and how confident the importer is about it
+
+# Examples
+
+```
+# use lilscript::{import::import, script::ContainerKind};
+let text = "[F4A] [Comfort] A Rainy Evening
+Written by lilellia
+
+[sfx: rain on a window]
+[She sits down beside you.]
+Witch: Listen to *that* rain. (softly) Lovely.
+Listener: It is.
+Mm.";
+
+let (script, report) = import(text);
+assert_eq!(script.title, "A Rainy Evening");
+assert_eq!(script.author, "lilellia");
+assert_eq!(script.tags, vec!["F4A", "Comfort"]);
+
+let kinds: Vec<ContainerKind> = script.paragraphs.iter().map(|c| c.kind.clone()).collect();
+assert_eq!(kinds, vec![
+    ContainerKind::Sfx,
+    ContainerKind::StageDir,
+    ContainerKind::Spoken,
+    ContainerKind::ListenerDialogue,
+    ContainerKind::Spoken,
+]);
+assert_eq!(script.paragraphs[2].speaker.as_deref(), Some("Witch"));
+
+assert_eq!(report.assumed, vec![(8, "Mm.".to_owned())]);
+assert_eq!(report.confidence(), 6. / 7.);
+```
+*/
+pub fn import(text: &str) -> (Script, ImportReport) {
+    let (document, report) = import_document(text);
+    (document.into(), report)
+}
+
+/**
+Import a script as in `import`, into a `Document` whose every line is positioned at the line of the source
+it came from. The header is made up of the lines before the first line of the body.
+
+# Examples
+
+```
+# use lilscript::import::import_document;
+let text = "# A Rainy Evening\n\n[sfx: rain]\nHello there.";
+let (document, _) = import_document(text);
+
+assert_eq!(&text[document.header.clone()], "# A Rainy Evening\n\n");
+let line = document.containers().nth(1).unwrap();
+assert_eq!(&text[line.range.clone()], "Hello there.");
+```
+*/
+pub fn import_document(text: &str) -> (Document, ImportReport) {
+    let conventions = Conventions::new();
+    let mut script = Script::default();
+    let mut sections: Vec<Section> = Vec::new();
+    let mut header_start: Option<usize> = None;
+    let mut report = ImportReport::default();
+
+    let mut offset = 0;
+    for (index, line) in text.split_inclusive('\n').enumerate() {
+        let range = offset..offset + line.len();
+        offset = range.end;
+
+        let mut line = line.trim().trim_start_matches('\u{feff}');
+        while let Some(quoted) = line.strip_prefix('>') {
+            line = quoted.trim_start();
+        }
+        if line.is_empty() {
+            continue;
+        }
+        let (original, line) = (line, protect(&conventions, line));
+        let line = line.as_str();
+
+        let in_header = sections.is_empty();
+        if in_header {
+            header_start.get_or_insert(range.start);
+            if let Some(caps) = conventions.heading.captures(line) {
+                if script.title.is_empty() {
+                    script.title = clean(&caps[1]);
+                }
+                report.recognise("title");
+                continue;
+            }
+
+            if script.title.is_empty() && conventions.tagged_title.is_match(line) {
+                let (title, tags) = tagged_title(line);
+                script.title = clean(&title);
+                script.tags = tags.iter().map(|tag| clean(tag)).collect();
+                report.recognise("title and tags");
+                continue;
+            }
+
+            if let Some(caps) = conventions.author.captures(line) {
+                script.author = clean(&caps[1]);
+                report.recognise("author");
+                continue;
+            }
+        }
+
+        let container = if conventions.divider.is_match(line) {
+            report.recognise("divider");
+            TextContainer::new(ContainerKind::PlainText).push(TextSpan::normal(SECTION_DIVIDER))
+        } else if let Some(caps) = conventions.sfx.captures(line) {
+            report.recognise("sound effect");
+            TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal(&clean(&caps[1])))
+        } else if let Some(caps) = conventions.direction.captures(line) {
+            let direction = caps.iter().skip(1).flatten().next().unwrap().as_str();
+            report.recognise("stage direction");
+            TextContainer::new(ContainerKind::StageDir).push(TextSpan::normal(&clean(direction)))
+        } else if let Some(caps) = conventions.speaker.captures(line) {
+            report.recognise("speaker prefix");
+            let speech = speech(&conventions, &caps[2]);
+            match &caps[1] {
+                "Listener" => TextContainer::with_spans(ContainerKind::ListenerDialogue, speech),
+                speaker => {
+                    TextContainer::with_spans(ContainerKind::Spoken, speech).spoken_by(speaker)
+                }
+            }
+        } else if let Some(caps) = conventions.bold.captures(line) {
+            report.recognise("bold speech");
+            TextContainer::with_spans(ContainerKind::Spoken, speech(&conventions, &caps[1]))
+        } else {
+            report.assumed.push((index + 1, original.to_owned()));
+            TextContainer::with_spans(ContainerKind::Spoken, speech(&conventions, line))
+        };
+
+        push_container(&mut sections, ContainerNode::spanning(&container, range));
+    }
+
+    let header = match (header_start, sections.first()) {
+        (Some(start), Some(section)) => start..section.range.start,
+        (Some(start), None) => start..text.len(),
+        (None, _) => 0..0,
+    };
+    let document = Document {
+        metadata: script,
+        header,
+        sections,
+        skipped: Vec::new(),
+        range: 0..text.len(),
+    };
+
+    (document, report)
+}
+
+/// Split a line such as `[F4A] [Comfort] Title [Sleep aid]` into its title and tags.
+fn tagged_title(line: &str) -> (String, Vec<String>) {
+    let mut title = String::new();
+    let mut tags: Vec<String> = Vec::new();
+
+    let mut rest = line;
+    while let Some(open) = rest.find('[') {
+        title.push_str(&rest[..open]);
+        let close = open + rest[open..].find(']').unwrap_or(rest.len() - open);
+        tags.push(rest[open + 1..close].trim().to_owned());
+        rest = rest.get(close + 1..).unwrap_or("");
+    }
+    title.push_str(rest);
+
+    (title.trim().to_owned(), tags)
+}
+
+/// Split a spoken line into spans: `(...)`/`[...]` are inline directions, `*...*`/`_..._` are emphasis.
+/// The whitespace of each span is collapsed (as in `md_handler`), and any span left blank is dropped.
+fn speech(conventions: &Conventions, line: &str) -> Vec<TextSpan> {
+    let mut spans: Vec<TextSpan> = Vec::new();
+    let mut push = |make: fn(&str) -> TextSpan, text: &str| {
+        let text = clean(text)
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" ");
+        if !text.is_empty() {
+            spans.push(make(&text));
+        }
+    };
+    let mut last = 0;
+
+    for caps in conventions.inline.captures_iter(line) {
+        let whole = caps.get(0).unwrap();
+        push(TextSpan::normal, &line[last..whole.start()]);
+        last = whole.end();
+
+        if let Some(direction) = caps.get(1) {
+            push(TextSpan::inline, direction.as_str());
+        } else if let Some(bold) = caps.get(2) {
+            push(TextSpan::normal, bold.as_str());
+        } else if let Some(emphasis) = caps.get(3).or(caps.get(4)) {
+            push(TextSpan::emphasis, emphasis.as_str());
+        }
+    }
+    push(TextSpan::normal, &line[last..]);
+
+    spans
+}
+
+/// The offset into the Private Use Area at which escaped punctuation is hidden (see `protect`).
+const PROTECTED: u32 = 0xE000;
+
+/// Hide each Markdown-escaped character of the line (e.g., `\*`) from the conventions, by moving it
+/// into the Private Use Area, so that it is only ever taken as text.
+fn protect(conventions: &Conventions, line: &str) -> String {
+    conventions
+        .escape
+        .replace_all(line, |caps: &regex::Captures| {
+            let c = caps[1].chars().next().unwrap();
+            char::from_u32(PROTECTED + c as u32).unwrap().to_string()
+        })
+        .into_owned()
+}
+
+/// Bring back any escaped characters hidden by `protect`, as the characters themselves.
+fn clean(text: &str) -> String {
+    text.chars()
+        .map(|c| match c as u32 {
+            n if (PROTECTED..PROTECTED + 0x80).contains(&n) => {
+                char::from_u32(n - PROTECTED).unwrap()
+            }
+            _ => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::script::SpanKind;
+
+    #[test]
+    fn test_speech_spans() {
+        let (script, _) =
+            import("**Oh! You're here\\*.** [softly] I _really_ wasn't expecting you.");
+        let spans: Vec<(SpanKind, &str)> = script.paragraphs[0]
+            .spans
+            .iter()
+            .map(|span| (span.kind.clone(), span.contents.as_str()))
+            .collect();
+
+        assert_eq!(
+            spans,
+            vec![
+                (SpanKind::Normal, "Oh! You're here*."),
+                (SpanKind::InlineDirection, "softly"),
+                (SpanKind::Normal, "I"),
+                (SpanKind::Emphasis, "really"),
+                (SpanKind::Normal, "wasn't expecting you."),
+            ]
+        );
+
+        let (script, _) = import("Hello   there. ( ) Come\tin.");
+        assert_eq!(
+            script.paragraphs[0].spans,
+            vec![
+                TextSpan::normal("Hello there."),
+                TextSpan::normal("Come in.")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_own_markdown_is_recognised() {
+        let text = "> *[sfx: rain]*\n\n> *[A door opens.]*\n\n--8<--\n\n**Hello.**";
+        let (script, report) = import(text);
+
+        assert_eq!(report.confidence(), 1.);
+        assert_eq!(script.paragraphs[0].kind, ContainerKind::Sfx);
+        assert_eq!(script.paragraphs[1].kind, ContainerKind::StageDir);
+        assert!(script.paragraphs[2].is_divider());
+        assert_eq!(script.paragraphs[3].plain_text(), "Hello.");
+    }
+
+    #[test]
+    fn test_stage_direction_is_not_a_title_once_the_body_starts() {
+        let (script, report) = import("# A Rainy Evening\n\nHello.\n[She leaves.]");
+
+        assert_eq!(script.title, "A Rainy Evening");
+        assert!(script.tags.is_empty());
+        assert_eq!(script.paragraphs[1].kind, ContainerKind::StageDir);
+        assert_eq!(report.assumed, vec![(3, "Hello.".to_owned())]);
+    }
+}
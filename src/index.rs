@@ -0,0 +1,202 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::script::{normalize_tag, Script};
+
+/// The metadata of a single script within an index.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// The file the script was read from (which may hold several scripts).
+    pub path: String,
+    pub title: String,
+    pub author: String,
+    pub series: Option<String>,
+    pub part: Option<String>,
+    pub tags: Vec<String>,
+
+    /// The date of the script, as `YYYY-MM-DD`.
+    pub date: Option<String>,
+    pub characters: Vec<String>,
+    pub target: Option<usize>,
+    pub spoken_words: usize,
+    pub unspoken_words: usize,
+    pub total_words: usize,
+
+    /// The content fingerprint of the script, as from `Script::fingerprint`.
+    pub fingerprint: String,
+}
+
+impl IndexEntry {
+    /// Gather the metadata of a script read from the given path.
+    pub fn new(path: &Path, script: &Script) -> Self {
+        let wordcount = script.wordcount();
+
+        Self {
+            path: path.display().to_string(),
+            title: script.title.clone(),
+            author: script.author.clone(),
+            series: script.series.title.clone(),
+            part: script.series.part.as_ref().map(|part| part.to_string()),
+            tags: script.tags.clone(),
+            date: script.date.map(|date| date.format("%Y-%m-%d").to_string()),
+            characters: script
+                .characters
+                .iter()
+                .map(|character| character.name.clone())
+                .collect(),
+            target: script.target,
+            spoken_words: wordcount.spoken(),
+            unspoken_words: wordcount.unspoken(),
+            total_words: wordcount.total(),
+            fingerprint: script.fingerprint(),
+        }
+    }
+}
+
+/// A searchable index of the metadata of a whole archive of scripts.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Index {
+    pub scripts: Vec<IndexEntry>,
+}
+
+impl Index {
+    /// Add a script read from the given path to the index.
+    pub fn add(&mut self, path: &Path, script: &Script) {
+        self.scripts.push(IndexEntry::new(path, script));
+    }
+
+    /// Write the index as (pretty-printed) JSON.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Read an index back in from JSON, as written by `Index::to_json`.
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    /// Return every script in the index which matches the query, in index order.
+    pub fn search<'a>(&'a self, query: &'a Query) -> impl Iterator<Item = &'a IndexEntry> {
+        self.scripts.iter().filter(|entry| query.matches(entry))
+    }
+}
+
+/// A search of an index, in which every given criterion must match.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Query {
+    /// Tags which the script must all have (compared after normalizing them).
+    pub tags: Vec<String>,
+
+    /// The series the script belongs to (case-insensitively).
+    pub series: Option<String>,
+
+    /// The author of the script (case-insensitively).
+    pub author: Option<String>,
+
+    /// The name of a character who appears in the script (case-insensitively).
+    pub character: Option<String>,
+
+    /// Text which appears in the title of the script (case-insensitively).
+    pub title: Option<String>,
+
+    /// The fewest spoken words the script may have.
+    pub min_words: Option<usize>,
+
+    /// The most spoken words the script may have.
+    pub max_words: Option<usize>,
+}
+
+impl Query {
+    /**
+    Determine whether the given script matches the query.
+
+    # Examples
+
+    ```
+    # use lilscript::index::{IndexEntry, Query};
+    let entry = IndexEntry {
+        title: "First Night".into(),
+        series: Some("Moonrise".into()),
+        tags: vec!["F4A".into(), "comfort".into()],
+        spoken_words: 2000,
+        ..Default::default()
+    };
+
+    let query = Query { tags: vec!["f4a".into()], series: Some("moonrise".into()), ..Default::default() };
+    assert!(query.matches(&entry));
+
+    let query = Query { tags: vec!["f4a".into(), "yandere".into()], ..Default::default() };
+    assert!(!query.matches(&entry));
+
+    let query = Query { title: Some("night".into()), max_words: Some(1500), ..Default::default() };
+    assert!(!query.matches(&entry));
+    ```
+    */
+    pub fn matches(&self, entry: &IndexEntry) -> bool {
+        let equals = |expected: &Option<String>, actual: Option<&str>| match expected {
+            Some(expected) => {
+                actual.is_some_and(|actual| actual.to_lowercase() == expected.to_lowercase())
+            }
+            None => true,
+        };
+
+        let tags: Vec<String> = entry.tags.iter().map(|tag| normalize_tag(tag)).collect();
+        let has_tags = self
+            .tags
+            .iter()
+            .all(|tag| tags.contains(&normalize_tag(tag)));
+
+        let has_character = match &self.character {
+            Some(name) => entry
+                .characters
+                .iter()
+                .any(|character| character.to_lowercase() == name.to_lowercase()),
+            None => true,
+        };
+
+        let has_title = self
+            .title
+            .as_ref()
+            .is_none_or(|title| entry.title.to_lowercase().contains(&title.to_lowercase()));
+
+        has_tags
+            && has_character
+            && has_title
+            && equals(&self.series, entry.series.as_deref())
+            && equals(&self.author, Some(&entry.author))
+            && self.min_words.is_none_or(|min| entry.spoken_words >= min)
+            && self.max_words.is_none_or(|max| entry.spoken_words <= max)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::script::{ContainerKind, TextContainer, TextSpan};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_json_round_trip() {
+        let mut script = Script::new("lilellia", "First Night");
+        script.series = "Moonrise (Part 1)".into();
+        script.tags = vec!["f4a".into()];
+        script.date = NaiveDate::from_ymd_opt(2024, 3, 3);
+        script
+            .paragraphs
+            .push(TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Hello there.")));
+
+        let mut index = Index::default();
+        index.add(Path::new("moonrise.tex"), &script);
+
+        let entry = &index.scripts[0];
+        assert_eq!(entry.series.as_deref(), Some("Moonrise"));
+        assert_eq!(entry.part.as_deref(), Some("Part 1"));
+        assert_eq!(entry.date.as_deref(), Some("2024-03-03"));
+        assert_eq!(entry.spoken_words, 2);
+        assert_eq!(entry.fingerprint, script.fingerprint());
+
+        let json = index.to_json().unwrap();
+        assert_eq!(Index::from_json(&json).unwrap(), index);
+    }
+}
@@ -1,132 +1,97 @@
-use clap::Parser;
-use clap_verbosity_flag::{InfoLevel, Verbosity};
-use env_logger;
-use paris::info;
-use std::{error::Error, fs, path::PathBuf};
+use std::path::{Path, PathBuf};
 
-use crate::{md_handler::ToMarkdown, script::Script, tex_handler::Tex};
+use crate::script::Script;
 
 /// A module which handles the creation of `Script` objects and their components.
 pub mod script;
 
+/// A module which handles the file formats which scripts are read from and written to, and rendering into them
+pub mod format;
+pub use format::{render, DirectionPlacement, FileFormat, OutputProfile, RenderOptions};
+
+/// A module which handles the document tree which parsers produce, with the position of each node in the source
+pub mod ast;
+
 /// A module which handles `Script` ⟷ TeX format inter-conversions
 pub mod tex_handler;
 
 /// A module which handles `Script` ⟷ Markdown format inter-conversions
 pub mod md_handler;
-// use crate::md_handler::ToMarkdown;
 
-/// For command-line parsing.
-#[derive(Parser)]
-#[command(author, version, about, long_about=None)]
-pub struct ArgumentParser {
-    #[arg(short, long, help = "the input file to operate on")]
-    pub infile: PathBuf,
+/// A module which handles `Script` ⟷ JSON/YAML inter-conversions, for piping scripts into other tools
+pub mod data_handler;
 
-    #[arg(short, long, help = "the file to output the results to")]
-    pub outfile: Option<PathBuf>,
+/// A module which handles `Script` ⟶ SSML exports, for text-to-speech engines
+pub mod ssml_handler;
 
-    #[command(flatten)]
-    pub verbose: Verbosity<InfoLevel>,
-}
+/// A module which handles `Script` ⟶ HTML exports
+pub mod html_handler;
 
-impl ArgumentParser {
-    /// Set the log level based on the verbosity passed in.
-    pub fn set_log_level(&self) {
-        env_logger::Builder::new()
-            .filter_level(self.verbose.log_level_filter())
-            .init();
-    }
-}
+/// A module which handles conversion between `:shortcode:`s and emoji
+pub mod emoji;
 
-/// A representation of the file formats that this library can process.
-#[derive(Debug, PartialEq, Eq, Hash)]
-pub enum FileFormat {
-    /// Represents a LaTeX (.tex) file
-    Tex,
+/// A module which handles converting whole directories of scripts at once
+pub mod batch;
 
-    /// Represents a Markdown (.md) file
-    Markdown,
-}
+/// A module which handles summarising the statistics of scripts
+pub mod stats;
 
-impl FileFormat {
-    /** Determine the file format from a given path.
-
-    # Arguments
-
-    * `p` - a reference to a path object
-
-    # Return
-
-    * `Ok(ext: FileFormat)` if the extension could be determined;
-    * `Err(_)` otherwise
-
-    # Examples:
-
-    * With a valid extension:
-    ```
-    # use lilscript::FileFormat;
-    # use std::path::PathBuf;
-    let p = PathBuf::from(r"/home/user/Documents/f.tex");
-    let file_format = FileFormat::from_path(&p).unwrap();
-    assert_eq!(file_format, FileFormat::Tex);
-    ```
-
-    * With an invalid extension:
-    ```
-    # use lilscript::FileFormat;
-    # use std::path::PathBuf;
-    let p = PathBuf::from(r"/home/user/Documents/g.csv");
-    let file_format = FileFormat::from_path(&p);
-    assert!(file_format.is_err());
-    ```
-    */
-    pub fn from_path(p: &PathBuf) -> Result<Self, String> {
-        match p.extension() {
-            Some(ext) => match ext.to_str() {
-                Some("tex") => Ok(Self::Tex),
-                Some("md") => Ok(Self::Markdown),
-                _ => Err("Invalid file extension: should be .tex / .md".to_owned()),
-            },
-            None => Err("Invalid file extension: could not be determined".to_owned()),
-        }
-    }
-}
+/// A module which handles walking over the document tree of a `Script`
+pub mod visitor;
+
+/// A module which handles the built-in in-place transforms of a `Script`
+pub mod transform;
+
+/// A module which handles the interactive terminal inspector for scripts
+#[cfg(feature = "cli")]
+pub mod tui;
+
+/// A module which handles linting scripts for the `check` subcommand
+pub mod check;
+
+/// A module which handles splitting spoken lines into subtitle cues which fit within a line-length budget
+pub mod subtitle;
+
+/// A module which handles best-effort importing of scripts written in other authors' conventions
+pub mod import;
+
+/// A module which handles summarising a whole archive of scripts for the `report` subcommand
+pub mod report;
+
+/// A module which handles the searchable metadata index of an archive of scripts
+pub mod index;
+
+/// A module which handles the command-line interface: argument parsing, and running each subcommand
+#[cfg(feature = "cli")]
+pub mod cli;
+#[cfg(feature = "cli")]
+pub use cli::{run, ArgumentParser};
+
+/** The path to write the given script to, when it is one of several from the same input file:
+the (zero-based) `index` and slug of the script are added to the file name, so that
+`series.md` becomes `series-01-first-night.md`, etc.
+
+# Examples
 
-// pub fn export_script(script: &Script, file_format: &FileFormat) -> String {
-//     match file_format {
-//         FileFormat::Tex => String::new(),
-//         FileFormat::Markdown => script.to_markdown()
-//     }
-// }
-
-pub fn run(args: ArgumentParser) -> Result<(), Box<dyn Error>> {
-    let in_extension = FileFormat::from_path(&args.infile)?;
-
-    info!("Reading from: {:?}", args.infile);
-    let fcontents = fs::read_to_string(&args.infile)?;
-
-    let script = match in_extension {
-        FileFormat::Tex => {
-            let tex = Tex::from(fcontents.as_str());
-            Script::try_from(&tex)
-        }
-        _ => {
-            Err("Only .tex input files are currently supported".to_string())?
-        },
-    }?;
-
-    info!("<on-cyan><black>Word count: {}</>", script.wordcount());
-
-    // Write the desired file
-    if args.outfile.is_some() {
-        let outfile = args.outfile.unwrap();
-        let contents = match FileFormat::from_path(&outfile)? {
-            FileFormat::Tex => Err("Only .md output files are currently supported".to_string())?,
-            FileFormat::Markdown => script.to_markdown()
-        };
-        fs::write(outfile, &contents)?;
+```
+# use lilscript::{numbered_path, script::Script};
+# use std::path::Path;
+let script = Script::new("lilellia", "First Night");
+assert_eq!(
+    numbered_path(Path::new("out/series.md"), 0, &script),
+    Path::new("out/series-01-first-night.md")
+);
+```
+*/
+pub fn numbered_path(path: &Path, index: usize, script: &Script) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut name = format!("{}-{:02}-{}", stem, index + 1, script.slug());
+    if let Some(extension) = path.extension() {
+        name = format!("{}.{}", name, extension.to_string_lossy());
     }
 
-    Ok(())
+    path.with_file_name(name)
 }
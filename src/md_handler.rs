@@ -1,11 +1,32 @@
-use crate::script::{ContainerKind, Script, SpanKind, TextContainer, TextSpan};
+use crate::{
+    ast::{line_of, push_container, ContainerNode, Document, Positioned, Section, SECTION_DIVIDER},
+    emoji::emoji_to_shortcodes,
+    script::{Character, ContainerKind, ParseOptions, Script, SpanKind, TextContainer, TextSpan},
+    ssml_handler::escape_xml_attribute,
+    tex_handler::Tex,
+    visitor::{ContainerContext, ScriptVisitor},
+};
 use paris::{error, warn};
-use regex::Regex;
-use std::{collections::HashMap, fmt::Write};
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Write},
+    sync::LazyLock,
+};
 
 pub trait ToMarkdown {
+    /// Write the object, in a Markdown format, into the given buffer.
+    fn write_markdown(&self, w: &mut impl fmt::Write) -> fmt::Result;
+
     /// Convert the object to a Markdown format.
-    fn to_markdown(&self) -> String;
+    fn to_markdown(&self) -> String {
+        let mut buf = String::new();
+        self.write_markdown(&mut buf).unwrap_or_else(|_| {
+            error!("<red>Failed writing Markdown to buffer</>");
+        });
+        buf
+    }
 }
 
 impl ToMarkdown for TextSpan {
@@ -28,13 +49,24 @@ impl ToMarkdown for TextSpan {
     /// let span = TextSpan::inline("an inline");
     /// assert_eq!(span.to_markdown(), "*(an inline)*");
     /// ```
-    fn to_markdown(&self) -> String {
-        let s = &self.contents;
-        match self.kind {
-            SpanKind::Normal => s.to_owned(),
-            SpanKind::Emphasis => format!("/{}/", s),
-            SpanKind::InlineDirection => format!("*({})*", s),
-        }
+    /// ```
+    /// # use lilscript::{script::TextSpan, md_handler::ToMarkdown};
+    /// let span = TextSpan::phonetic("pronunciation", "pro-nun-see-AY-shun");
+    /// assert_eq!(span.to_markdown(), "pronunciation (pro-nun-see-AY-shun)");
+    /// ```
+    fn write_markdown(&self, w: &mut impl fmt::Write) -> fmt::Result {
+        write_span(self, &MarkdownStyle::default(), w)
+    }
+}
+
+/// Write a single span as Markdown, in the given style.
+fn write_span(span: &TextSpan, style: &MarkdownStyle, w: &mut impl fmt::Write) -> fmt::Result {
+    let s = Tex::substituted(&span.contents, &style.substitutions);
+    match &span.kind {
+        SpanKind::Normal => w.write_str(&s),
+        SpanKind::Emphasis => write!(w, "{}{}{}", style.emphasis.0, s, style.emphasis.1),
+        SpanKind::InlineDirection => write!(w, "{}{}{}", style.tone_cue.0, s, style.tone_cue.1),
+        SpanKind::Phonetic(pronunciation) => write!(w, "{} ({})", s, pronunciation),
     }
 }
 
@@ -51,7 +83,7 @@ impl ToMarkdown for TextContainer {
     ///     TextSpan::inline("loudly"),
     ///     TextSpan::emphasis("EMPHASIS")
     /// ];
-    /// let container = TextContainer { kind, spans };
+    /// let container = TextContainer::with_spans(kind, spans);
     /// let expected = "some text *(loudly)* /EMPHASIS/";
     /// assert_eq!(container.to_markdown(), expected);
     /// ```
@@ -64,7 +96,7 @@ impl ToMarkdown for TextContainer {
     ///     TextSpan::inline("loudly"),
     ///     TextSpan::emphasis("EMPHASIS")
     /// ];
-    /// let container = TextContainer { kind, spans };
+    /// let container = TextContainer::with_spans(kind, spans);
     ///
     /// // notice that the asterisks are suppressed around the inline
     /// let expected = "> *[some text (loudly) /EMPHASIS/]*";
@@ -79,7 +111,7 @@ impl ToMarkdown for TextContainer {
     ///     TextSpan::inline("loudly"),
     ///     TextSpan::emphasis("EMPHASIS")
     /// ];
-    /// let container = TextContainer { kind, spans };
+    /// let container = TextContainer::with_spans(kind, spans);
     ///
     /// // notice that the asterisks are suppressed around the inline
     /// let expected = "> *[sfx: some text (loudly) /EMPHASIS/]*";
@@ -94,7 +126,7 @@ impl ToMarkdown for TextContainer {
     ///     TextSpan::inline("loudly"),
     ///     TextSpan::emphasis("EMPHASIS")
     /// ];
-    /// let container = TextContainer { kind, spans };
+    /// let container = TextContainer::with_spans(kind, spans);
     ///
     /// // notice that the asterisks are suppressed around the inline
     /// let expected = "> *« some text (loudly) /EMPHASIS/ »*";
@@ -111,125 +143,1117 @@ impl ToMarkdown for TextContainer {
     ///     TextSpan::emphasis("EMPHASIS"),
     ///     TextSpan::normal("...hm?")
     /// ];
-    /// let container = TextContainer { kind, spans };
+    /// let container = TextContainer::with_spans(kind, spans);
     ///
     /// // notice that the asterisks are suppressed around the inline
     /// let expected = "*(quietly, slowly)* **some text** *(loudly)* **/EMPHASIS/** **...hm?**";
     /// assert_eq!(container.to_markdown(), expected);
     /// ```
-    fn to_markdown(&self) -> String {
-        // TODO: combine adjacent like-blocks after alterations (the spoken emphasis in example)
-        let mut buf = String::new();
+    ///
+    /// ```
+    /// # use lilscript::{script::{ContainerKind, TextSpan, TextContainer}, md_handler::ToMarkdown};
+    /// let rain = TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal("rain"));
+    /// let container = TextContainer::new(ContainerKind::Spoken)
+    ///     .push(TextSpan::normal("Listen to that."))
+    ///     .overlapping(rain);
+    ///
+    /// let expected = "> *[sfx: rain (over the following line)]*\n\n**Listen to that.**";
+    /// assert_eq!(container.to_markdown(), expected);
+    /// ```
+    ///
+    /// ```
+    /// # use lilscript::{script::{ContainerKind, TextSpan, TextContainer}, md_handler::ToMarkdown};
+    /// let container = TextContainer::new(ContainerKind::Spoken)
+    ///     .push(TextSpan::normal("Welcome home."))
+    ///     .with_commentary("The line the whole series was built around.");
+    ///
+    /// let expected = "**Welcome home.**\n\n> **Commentary:** The line the whole series was built around.";
+    /// assert_eq!(container.to_markdown(), expected);
+    /// ```
+    fn write_markdown(&self, w: &mut impl fmt::Write) -> fmt::Result {
+        write_line(self, None, &MarkdownStyle::default(), w)
+    }
+}
+
+/// Write a container as Markdown, preceded by any overlapping cues, and optionally numbered with its line ID.
+fn write_line(
+    container: &TextContainer,
+    line: Option<&str>,
+    style: &MarkdownStyle,
+    w: &mut impl fmt::Write,
+) -> fmt::Result {
+    // any concurrent cues come just before the line, noting that they run underneath it
+    let over = TextSpan::inline("over the following line");
+    for cue in &container.overlaps {
+        write_container(cue, Some(&over), None, style, w)?;
+        w.write_str("\n\n")?;
+    }
+
+    write_container(container, None, line, style, w)?;
+
+    for text in &container.commentary {
+        write!(w, "\n\n> **Commentary:** {}", text)?;
+    }
+
+    Ok(())
+}
+
+/// Write a single container (without its overlapping cues) as Markdown, with an optional
+/// extra span appended to its contents, and optionally numbered with its line ID.
+fn write_container(
+    container: &TextContainer,
+    extra: Option<&TextSpan>,
+    line: Option<&str>,
+    style: &MarkdownStyle,
+    w: &mut impl fmt::Write,
+) -> fmt::Result {
+    // TODO: combine adjacent like-blocks after alterations (the spoken emphasis in example)
+
+    // a marker has no text to show, so it's kept as an anchor which can be linked to
+    if let Some(id) = container.marker_id() {
+        return write!(w, r#"<a id="{}"></a>"#, escape_xml_attribute(&id));
+    }
+
+    // handle the global formatting
+    let (prefix, suffix) = match container.kind {
+        ContainerKind::PlainText | ContainerKind::Marker => &style.plain_text,
+        ContainerKind::Spoken => &style.spoken,
+        ContainerKind::StageDir => &style.stage_direction,
+        ContainerKind::Sfx => &style.sfx,
+        ContainerKind::ListenerDialogue => &style.listener,
+    };
+
+    // the line ID goes first, but within the quote of a quoted container
+    match (line, prefix.strip_prefix("> ")) {
+        (Some(line), Some(prefix)) => write!(w, "> [{}] {}", line, prefix)?,
+        (Some(line), None) => write!(w, "[{}] {}", line, prefix)?,
+        (None, _) => w.write_str(prefix)?,
+    }
+
+    if container.is_divider() {
+        w.write_str(&style.divider)?;
+        return w.write_str(suffix);
+    }
+
+    // name the speaker of a line, where there's more than one character who could say it
+    if let (ContainerKind::Spoken, Some(speaker)) = (&container.kind, &container.speaker) {
+        write!(w, "{}{}{} ", style.speaker.0, speaker, style.speaker.1)?;
+    }
+
+    // remove extraneous spaces as we go
+    let mut body = CollapseWhitespace::new(w);
+    for (i, span) in container.spans.iter().chain(extra).enumerate() {
+        // handle the different contexts
+        match (&container.kind, &span.kind) {
+            // asterisks on an inline should be suppressed:
+            // > *[this is text (and this could be an inline)]*
+            (
+                ContainerKind::StageDir | ContainerKind::Sfx | ContainerKind::ListenerDialogue,
+                SpanKind::InlineDirection,
+            ) => write!(
+                body,
+                " {}{}{} ",
+                style.nested_tone_cue.0,
+                Tex::substituted(&span.contents, &style.substitutions),
+                style.nested_tone_cue.1
+            )?,
+
+            // spoken dialogue (which is wrapped in Normal) should be bold
+            (ContainerKind::Spoken, SpanKind::Normal | SpanKind::Phonetic(_)) => {
+                write!(body, " {}", style.spoken_text.0)?;
+                write_span(span, style, &mut body)?;
+                write!(body, "{} ", style.spoken_text.1)?;
+            }
+            (ContainerKind::Spoken, SpanKind::Emphasis) => {
+                if i < container.spans.len() && is_ambiguous_emphasis(container, i) {
+                    warn!(
+                        "<yellow>The emphasised span <bold>{}</bold> occurs within the scope of a \
+                    spoken line and has been rendered as spoken. However, it MAY occur \
+                    within an inline direction, etc., but we do not know. \
+                    Context: \"{}\"</>",
+                        span.to_markdown(),
+                        container.plain_text()
+                    );
+                }
+                write!(body, " {}", style.spoken_text.0)?;
+                write_span(span, style, &mut body)?;
+                write!(body, "{} ", style.spoken_text.1)?;
+            }
+
+            // This one's nice and easy ^_^
+            _ => {
+                body.write_char(' ')?;
+                write_span(span, style, &mut body)?;
+                body.write_char(' ')?;
+            }
+        }
+    }
+
+    w.write_str(suffix)
+}
+
+/// A writer which collapses each run of (ASCII) whitespace to a single space, and trims both ends.
+/// Non-breaking spaces are deliberately kept.
+struct CollapseWhitespace<'w, W: fmt::Write> {
+    /// The writer to pass the collapsed text on to.
+    inner: &'w mut W,
+
+    /// Whether anything has been written yet (so leading whitespace can be dropped).
+    started: bool,
+
+    /// Whether whitespace has been seen since the last text (only written once more text follows).
+    pending_space: bool,
+}
+
+impl<'w, W: fmt::Write> CollapseWhitespace<'w, W> {
+    fn new(inner: &'w mut W) -> Self {
+        Self {
+            inner,
+            started: false,
+            pending_space: false,
+        }
+    }
+}
+
+impl<W: fmt::Write> fmt::Write for CollapseWhitespace<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for (i, piece) in s.split(|c: char| c.is_ascii_whitespace()).enumerate() {
+            if i > 0 {
+                self.pending_space = true;
+            }
+            if piece.is_empty() {
+                continue;
+            }
+
+            if self.pending_space && self.started {
+                self.inner.write_char(' ')?;
+            }
+            self.pending_space = false;
+            self.started = true;
+            self.inner.write_str(piece)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Options controlling how scripts are rendered to Markdown.
+#[derive(Clone, Debug, Default)]
+pub struct MarkdownOptions {
+    /// Whether each line is prefixed with its line ID (e.g., `[012]`), matching `checklist`.
+    pub number_lines: bool,
+
+    /// The conventions the Markdown is written with.
+    pub style: MarkdownStyle,
+}
+
+/**
+The conventions with which a script is written as Markdown, for posting to platforms which expect different
+ones. Each wrapper is the text written before and after what it wraps. Only the default style can be read
+back in (see `Markdown::parse_script`).
+
+A style can be loaded from TOML (see `MarkdownStyle::from_toml`), in which any field left out keeps its default.
+
+# Examples
+
+```
+# use lilscript::md_handler::{to_markdown_with, MarkdownOptions, MarkdownStyle};
+# use lilscript::script::{ContainerKind, Script, TextContainer, TextSpan};
+let mut script = Script::new("lilellia", "A Very Cool Script");
+script.paragraphs.push(TextContainer::new(ContainerKind::StageDir).push(TextSpan::normal("A door opens.")));
+script.paragraphs.push(
+    TextContainer::new(ContainerKind::Spoken)
+        .push(TextSpan::normal("Hello"))
+        .push(TextSpan::emphasis("there"))
+        .push(TextSpan::normal(r"\sigh")),
+);
+
+let style = MarkdownStyle::from_toml(r#"
+    stage_direction = ["_[", "]_"]
+    spoken_text = ["", ""]
+    emphasis = ["*", "*"]
+    formatting_guide = false
+    characters = false
+
+    [substitutions]
+    sigh = "*sighs*"
+"#).unwrap();
+
+let options = MarkdownOptions { style, ..Default::default() };
+assert_eq!(to_markdown_with(&script, &options), "_[A door opens.]_\n\nHello *there* *sighs*");
+```
+*/
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MarkdownStyle {
+    /// The wrapper of each spoken line (whose spans are then wrapped in `spoken_text`).
+    pub spoken: (String, String),
+
+    /// The wrapper of each stage direction.
+    pub stage_direction: (String, String),
+
+    /// The wrapper of each sound effect.
+    pub sfx: (String, String),
+
+    /// The wrapper of each line of listener dialogue.
+    pub listener: (String, String),
+
+    /// The wrapper of each line of untagged text.
+    pub plain_text: (String, String),
+
+    /// The wrapper of each spoken span within a spoken line.
+    pub spoken_text: (String, String),
+
+    /// The wrapper of each emphasised span.
+    pub emphasis: (String, String),
+
+    /// The wrapper of each tone cue (inline direction).
+    pub tone_cue: (String, String),
+
+    /// The wrapper of each tone cue within a stage direction, sound effect, or listener line, which are
+    /// already set apart.
+    pub nested_tone_cue: (String, String),
+
+    /// The wrapper of the speaker's name, written before each line which has one.
+    pub speaker: (String, String),
+
+    /// What's written in place of each `--8<--` divider between sections.
+    pub divider: String,
+
+    /// Whether the formatting guide (an example of each convention) is written after the characters.
+    pub formatting_guide: bool,
+
+    /// Whether the list of characters is written before the script.
+    pub characters: bool,
+
+    /// Text to write in place of custom TeX commands left in the script (see `Tex::substituted`), keyed by
+    /// the name of the command, without its backslash.
+    pub substitutions: BTreeMap<String, String>,
+}
+
+impl Default for MarkdownStyle {
+    fn default() -> Self {
+        let wrapper = |before: &str, after: &str| (before.to_owned(), after.to_owned());
+        Self {
+            spoken: wrapper("", ""),
+            stage_direction: wrapper("> *[", "]*"),
+            sfx: wrapper("> *[sfx: ", "]*"),
+            listener: wrapper("> *«\u{a0}", "\u{a0}»*"),
+            plain_text: wrapper("", ""),
+            spoken_text: wrapper("**", "**"),
+            emphasis: wrapper("/", "/"),
+            tone_cue: wrapper("*(", ")*"),
+            nested_tone_cue: wrapper("(", ")"),
+            speaker: wrapper("**", ":**"),
+            divider: SECTION_DIVIDER.to_owned(),
+            formatting_guide: true,
+            characters: true,
+            substitutions: BTreeMap::new(),
+        }
+    }
+}
+
+impl MarkdownStyle {
+    /**
+    Read a style from TOML, with each wrapper given as a `["before", "after"]` pair.
+
+    # Examples
+
+    ```
+    # use lilscript::md_handler::MarkdownStyle;
+    let style = MarkdownStyle::from_toml("divider = \"* * *\"").unwrap();
+    assert_eq!(style.divider, "* * *");
+    assert_eq!(style.emphasis, MarkdownStyle::default().emphasis);
+
+    assert!(MarkdownStyle::from_toml("stage_directions = [\"(\", \")\"]").is_err());
+    ```
+    */
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| format!("Invalid Markdown style: {}", e))
+    }
+}
+
+impl ToMarkdown for Script {
+    fn write_markdown(&self, w: &mut impl fmt::Write) -> fmt::Result {
+        write_script(self, &MarkdownOptions::default(), w)
+    }
+}
+
+/**
+Convert the script to Markdown, using the given options.
+
+# Examples
+
+```
+# use lilscript::md_handler::{to_markdown_with, MarkdownOptions};
+# use lilscript::script::{ContainerKind, Script, TextContainer, TextSpan};
+let mut script = Script::new("lilellia", "A Very Cool Script");
+script.paragraphs.push(TextContainer::new(ContainerKind::StageDir).push(TextSpan::normal("A door opens.")));
+script.paragraphs.push(TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Hello.")));
+
+let options = MarkdownOptions { number_lines: true, ..Default::default() };
+let markdown = to_markdown_with(&script, &options);
+assert!(markdown.ends_with("> [001] *[A door opens.]*\n\n[002] **Hello.**"));
+```
+*/
+pub fn to_markdown_with(script: &Script, options: &MarkdownOptions) -> String {
+    let mut buf = String::new();
+    write_script(script, options, &mut buf).unwrap_or_else(|_| {
+        error!("<red>Failed writing Markdown to buffer</>");
+    });
+    buf
+}
+
+/// Write the script, in a Markdown format, into the given buffer.
+fn write_script(
+    script: &Script,
+    options: &MarkdownOptions,
+    w: &mut impl fmt::Write,
+) -> fmt::Result {
+    // NOTE: This does not include any script info header information
+    let style = &options.style;
+
+    // each part is separated from the one before it (with nothing before the first)
+    let mut separator = "";
+
+    // Character info
+    if style.characters {
+        w.write_str("## Characters")?;
+        for character in &script.characters {
+            write!(
+                w,
+                "\n\n- **{}** ∼ {}",
+                character.name, character.description
+            )?;
+            let details = character.details();
+            if !details.is_empty() {
+                write!(w, " *({})*", details)?;
+            }
+        }
+        separator = "\n\n";
+    }
+
+    // Formatting guide
+    if style.formatting_guide {
+        write!(w, "{}## Formatting guide", separator)?;
+        separator = "\n\n";
+    }
+    let guide = [
+        TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("spoken text")),
+        TextContainer::new(ContainerKind::Spoken).push(TextSpan::emphasis("emphasis")),
+        TextContainer::new(ContainerKind::Spoken).push(TextSpan::inline("tone cue, suggested")),
+        TextContainer::new(ContainerKind::StageDir)
+            .push(TextSpan::normal("stage direction and/or sfx")),
+        TextContainer::new(ContainerKind::ListenerDialogue).push(TextSpan::normal(
+            "example listener dialogue, not intended to be voiced",
+        )),
+        TextContainer::new(ContainerKind::PlainText).push(TextSpan::normal(SECTION_DIVIDER)),
+    ];
+
+    for container in guide.iter().filter(|_| style.formatting_guide) {
+        w.write_str("\n\n")?;
+        write_line(container, None, style, w)?;
+    }
+
+    for (i, container) in script.paragraphs.iter().enumerate() {
+        w.write_str(separator)?;
+        separator = "\n\n";
+        let line = options.number_lines.then(|| line_id(i));
+        write_line(container, line.as_deref(), style, w)?;
+    }
+
+    Ok(())
+}
+
+/// A thin wrapper around a String, used to represent a .md formatted string (as written by `ToMarkdown`).
+/// Also includes a few convenience methods for parsing.
+pub struct Markdown {
+    pub text: String,
+}
+
+impl From<&str> for Markdown {
+    fn from(value: &str) -> Self {
+        Self {
+            text: value.to_string(),
+        }
+    }
+}
+
+impl From<String> for Markdown {
+    fn from(value: String) -> Self {
+        Self { text: value }
+    }
+}
+
+impl TryFrom<&Markdown> for Script {
+    type Error = String;
+
+    /// Attempt to create a Script from the given .md file.
+    fn try_from(value: &Markdown) -> Result<Self, Self::Error> {
+        value.parse_script(&ParseOptions::default())
+    }
+}
+
+/// A paragraph of the body of a Markdown script.
+enum BodyParagraph {
+    /// A line of the script
+    Container(TextContainer),
 
-        for span in &self.spans {
-            // handle the different contexts
-            let text = match self.kind {
-                // This one's nice and easy ^_^
-                ContainerKind::PlainText => span.to_markdown(),
-
-                ContainerKind::StageDir | ContainerKind::Sfx | ContainerKind::ListenerDialogue => {
-                    match span.kind {
-                        // asterisks on an inline should be suppressed:
-                        // > *[this is text (and this could be an inline)]*
-                        SpanKind::InlineDirection => {
-                            span.to_markdown().trim_matches('*').to_string()
+    /// A cue which runs underneath the line after it
+    Overlap(TextContainer),
+
+    /// The director's commentary on the line before it
+    Commentary(String),
+}
+
+/// The part of a Markdown script being read.
+enum Part {
+    Body,
+    Characters,
+    Guide,
+}
+
+impl Markdown {
+    /** Parse the whole .md file into a Script.
+
+    This reads back the conventions which `ToMarkdown` writes: the character list, quoted stage directions,
+    sound effects (including those running over the following line), and listener dialogue, bold spoken
+    text with italic tone cues, `/emphasis/`, markers, commentary, and line IDs (which are dropped).
+    The formatting guide is skipped. An optional `# Title` heading gives the title, but the rest of the
+    metadata (author, series, tags, etc.) isn't written to Markdown, and so is left empty; phonetic hints
+    are read back as normal text.
+
+    # Arguments
+
+    * `options` - controls whether questionable input is warned about or rejected
+
+    # Return
+
+    * `Ok(Script)` if (in strict mode) the whole file is conformant;
+    * `Err(_)` otherwise
+
+    # Examples
+
+    ```
+    # use lilscript::{md_handler::Markdown, script::{ContainerKind, ParseOptions, TextSpan}};
+    let md = "## Characters\n\n- **Speaker** ∼ a kind witch *(she/her · age 30s)*\n\n\
+        > *[sfx: rain (over the following line)]*\n\n\
+        *(softly)* **Oh! You're here.**\n\n\
+        > **Commentary:** The line the whole series was built around.\n\n\
+        > [003] *[The door /slams/ shut.]*";
+    let script = Markdown::from(md).parse_script(&ParseOptions::default()).unwrap();
+
+    assert_eq!(script.characters[0].name, "Speaker");
+    assert_eq!(script.characters[0].age.as_deref(), Some("30s"));
+
+    let line = &script.paragraphs[0];
+    assert_eq!(line.kind, ContainerKind::Spoken);
+    assert_eq!(line.spans, vec![TextSpan::inline("softly"), TextSpan::normal("Oh! You're here.")]);
+    assert_eq!(line.overlaps[0].kind, ContainerKind::Sfx);
+    assert_eq!(line.commentary, vec!["The line the whole series was built around."]);
+
+    let direction = &script.paragraphs[1];
+    assert_eq!(direction.kind, ContainerKind::StageDir);
+    assert_eq!(
+        direction.spans,
+        vec![TextSpan::normal("The door"), TextSpan::emphasis("slams"), TextSpan::normal("shut.")]
+    );
+    ```
+    */
+    pub fn parse_script(&self, options: &ParseOptions) -> Result<Script, String> {
+        self.parse_positioned(options).map_err(|e| {
+            format!(
+                "{} (on line {})",
+                e.value,
+                line_of(&self.text, e.range.start)
+            )
+        })
+    }
+
+    /** The same as `Markdown::parse_script`, except that any error is positioned at the paragraph of the source
+    (as a byte range within it) which it's about.
+
+    # Examples
+
+    ```
+    # use lilscript::{ast::line_of, md_handler::Markdown, script::ParseOptions};
+    let md = "## Characters\n\n- **Speaker** ∼ a kind witch\n\n- Someone else\n\n**Hello.**";
+    let strict = ParseOptions { strict: true, ..Default::default() };
+
+    let e = Markdown::from(md).parse_positioned(&strict).unwrap_err();
+    assert_eq!(line_of(md, e.range.start), 5);
+    assert_eq!(&md[e.range], "- Someone else\n");
+    assert!(Markdown::from(md).parse_script(&strict).unwrap_err().ends_with("(on line 5)"));
+    ```
+    */
+    pub fn parse_positioned(&self, options: &ParseOptions) -> Result<Script, Positioned<String>> {
+        self.parse_document(options).map(Script::from)
+    }
+
+    /** Parse the .md file into a `Document`: the script, along with the paragraph of the source which each
+    line came from. As the lines of a paragraph are joined up, each of its spans is positioned at the whole
+    paragraph. The header is the title, character list, and formatting guide before the first line.
+
+    # Return
+
+    * `Ok(Document)` - the parsed document, whose ranges are byte offsets into `self.text`
+    * `Err(_)` - if (in strict mode) anything couldn't be parsed, positioned as in `Markdown::parse_positioned`
+
+    # Examples
+
+    ```
+    # use lilscript::{md_handler::Markdown, script::ParseOptions};
+    let md = "## Characters\n\n- **Speaker** ∼ a kind witch\n\n\
+        > *[sfx: rain (over the following line)]*\n\n**Listen.**";
+    let document = Markdown::from(md).parse_document(&ParseOptions::default()).unwrap();
+
+    assert_eq!(&md[document.header.clone()], "## Characters\n\n- **Speaker** ∼ a kind witch\n\n");
+    let line = document.containers().next().unwrap();
+    assert!(md[line.range.clone()].starts_with("> *[sfx: rain"));
+    assert_eq!(&md[line.overlaps[0].range.clone()], "> *[sfx: rain (over the following line)]*\n");
+    ```
+    */
+    pub fn parse_document(&self, options: &ParseOptions) -> Result<Document, Positioned<String>> {
+        let mut metadata = Script::default();
+        let mut sections: Vec<Section> = Vec::new();
+        let mut skipped: Vec<Positioned<String>> = Vec::new();
+        let mut part = Part::Body;
+        let mut overlaps: Vec<ContainerNode> = Vec::new();
+        let mut header_start: Option<usize> = None;
+        let mut body_start: Option<usize> = None;
+
+        let text = self.text.trim_start_matches('\u{feff}');
+        let bom = self.text.len() - text.len();
+        for Positioned {
+            value: paragraph,
+            range,
+        } in paragraphs(text)
+        {
+            let range = bom + range.start..bom + range.end;
+            let paragraph = match options.shortcodes {
+                true => emoji_to_shortcodes(&paragraph),
+                false => paragraph,
+            };
+            let at = |e: String| Positioned {
+                value: e,
+                range: range.clone(),
+            };
+
+            // anything before the first line of the body is part of the header
+            let is_metadata = paragraph.starts_with("# ")
+                || matches!(paragraph.as_str(), "## Characters" | "## Formatting guide")
+                || match part {
+                    Part::Guide => true,
+                    Part::Characters => paragraph.starts_with("- "),
+                    Part::Body => false,
+                };
+            match (is_metadata, body_start) {
+                (true, None) => {
+                    header_start.get_or_insert(range.start);
+                }
+                (false, None) => body_start = Some(range.start),
+                _ => (),
+            }
+
+            if let Some(title) = paragraph.strip_prefix("# ") {
+                if metadata.title.is_empty() {
+                    metadata.title = title.trim().to_owned();
+                }
+                continue;
+            }
+
+            match paragraph.as_str() {
+                "## Characters" => {
+                    part = Part::Characters;
+                    continue;
+                }
+                "## Formatting guide" => {
+                    part = Part::Guide;
+                    continue;
+                }
+                _ => (),
+            }
+
+            match part {
+                // the guide is only examples, ending with the divider
+                Part::Guide => {
+                    if paragraph == SECTION_DIVIDER {
+                        part = Part::Body;
+                    }
+                    continue;
+                }
+                Part::Characters if paragraph.starts_with("- ") => {
+                    match parse_character(&paragraph) {
+                        Ok(character) => metadata.characters.push(character),
+                        Err(e) => {
+                            options.warn_or_fail(e.clone()).map_err(at)?;
+                            skipped.push(at(e));
                         }
-                        _ => span.to_markdown(),
                     }
+                    continue;
                 }
+                Part::Characters => part = Part::Body,
+                Part::Body => (),
+            }
 
-                ContainerKind::Spoken => match span.kind {
-                    // spoken dialogue (which is wrapped in Normal) should be bold
-                    SpanKind::Normal => format!("**{}**", span.to_markdown()),
-                    SpanKind::Emphasis => {
-                        let md = span.to_markdown();
-                        let context = self.plain_text();
-
-                        warn!(
-                            "<yellow>The emphasised span <bold>{}</bold> occurs within the scope of a \
-                            spoken line and has been rendered as spoken. However, it MAY occur \
-                            within an inline direction, etc., but we do not know. \
-                            Context: \"{}\"</>",
-                            md, context
-                        );
-                        format!("**{}**", md)
+            match parse_paragraph(&paragraph) {
+                BodyParagraph::Overlap(cue) => {
+                    overlaps.push(ContainerNode::spanning(&cue, range.clone()));
+                }
+                BodyParagraph::Container(container) => {
+                    let mut node = ContainerNode::spanning(&container, range.clone());
+                    if let Some(first) = overlaps.first() {
+                        node.range.start = first.range.start;
                     }
-                    _ => span.to_markdown(),
-                },
-            };
+                    node.overlaps = std::mem::take(&mut overlaps);
+                    push_container(&mut sections, node);
+                }
+                BodyParagraph::Commentary(text) => {
+                    match sections.last_mut() {
+                        Some(section) => {
+                            section.range.end = range.end;
+                            // a section is never empty, as it starts at the container which began it
+                            let container = section.containers.last_mut().unwrap();
+                            container.commentary.push(at(text));
+                        }
+                        None => options
+                            .warn_or_fail(format!(
+                                "The commentary \"{}\" has no line before it to annotate",
+                                text
+                            ))
+                            .map_err(at)?,
+                    }
+                }
+            }
+        }
+
+        // cues at the very end have no line to run over, so they're kept as lines of their own
+        if let Some(first) = overlaps.first() {
+            let range = first.range.start..overlaps.last().unwrap().range.end;
+            options
+                .warn_or_fail(format!(
+                    "{} cue(s) at the end of the script have no following line to run over",
+                    overlaps.len()
+                ))
+                .map_err(|e| Positioned { value: e, range })?;
+            for cue in overlaps {
+                push_container(&mut sections, cue);
+            }
+        }
+
+        let header = match header_start {
+            Some(start) => start..body_start.unwrap_or(self.text.len()),
+            None => 0..0,
+        };
 
-            write!(buf, " {} ", text).unwrap_or_else(|_| {
-                error!("<red>Failed writing to buffer: {}</>", span.contents);
-                ()
+        Ok(Document {
+            metadata,
+            header,
+            sections,
+            skipped,
+            range: 0..self.text.len(),
+        })
+    }
+}
+
+/// Split the text into its paragraphs (separated by blank lines), with the lines of each joined by a space,
+/// each positioned at its lines within the text.
+fn paragraphs(text: &str) -> Vec<Positioned<String>> {
+    let mut paragraphs: Vec<Positioned<String>> = Vec::new();
+    let mut lines: Vec<&str> = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+
+    for line in text.split_inclusive('\n').chain([""]) {
+        let trimmed = line.trim();
+        if !trimmed.is_empty() {
+            if lines.is_empty() {
+                start = offset;
+            }
+            lines.push(trimmed);
+        } else if !lines.is_empty() {
+            paragraphs.push(Positioned {
+                value: lines.join(" "),
+                range: start..offset,
             });
+            lines.clear();
         }
+        offset += line.len();
+    }
+
+    paragraphs
+}
+
+/// An entry of the character list.
+static CHARACTER_ENTRY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^- \*\*(?P<name>.+?)\*\*\s*∼\s*(?P<desc>.*?)(?:\s*\*\((?P<details>[^()]*)\)\*)?$")
+        .unwrap()
+});
+
+/// Parse an entry of the character list: `- **Name** ∼ description *(details)*`.
+fn parse_character(entry: &str) -> Result<Character, String> {
+    let captures = CHARACTER_ENTRY
+        .captures(entry)
+        .ok_or_else(|| format!("Could not parse character: \"{}\"", entry))?;
 
-        // remove extraneous spaces
-        let re = Regex::new(r"[[:space:]]+").unwrap();
-        buf = re.replace_all(&buf, " ").trim().to_string();
+    let mut character = Character::new(&captures["name"], &captures["desc"]);
 
-        // handle the global formatting
-        match self.kind {
-            ContainerKind::PlainText | ContainerKind::Spoken => buf,
-            ContainerKind::StageDir => format!("> *[{}]*", buf),
-            ContainerKind::Sfx => format!("> *[sfx: {}]*", buf),
-            ContainerKind::ListenerDialogue => format!("> *« {} »*", buf),
+    // the reverse of `Character::details`
+    let details = captures.name("details").map_or("", |m| m.as_str());
+    for detail in details.split(" · ").filter(|d| !d.trim().is_empty()) {
+        if let Some(age) = detail.strip_prefix("age ") {
+            character.set("age", age)?;
+        } else if let Some(voice) = detail.strip_prefix("voice: ") {
+            character.set("voice", voice)?;
+        } else if let Some(tags) = detail.strip_prefix("tags: ") {
+            character.set("tags", tags)?;
+        } else {
+            character.set("pronouns", detail)?;
         }
     }
+
+    Ok(character)
 }
 
-impl ToMarkdown for Script {
-    fn to_markdown(&self) -> String {
-        const DIVIDER: &str = "--8<--";
+/// The line ID (from --number-lines) at the start of a paragraph, after any quotation mark.
+static LINE_ID: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(> )?\[\d{3,}\] ").unwrap());
 
-        let mut lines: Vec<String> = Vec::new();
+/// The anchor which a marker is written as.
+static MARKER_ANCHOR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"^<a id="(.*)"></a>$"#).unwrap());
 
-        // NOTE: This does not include any script info header information
+/// A line naming its speaker.
+static SPEAKER_LINE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\*\*(?P<speaker>[^*:]+):\*\* (?P<line>.*\S.*)$").unwrap());
 
-        // Character info
-        lines.push(String::from("## Characters"));
-        for character in &self.characters {
-            lines.push(format!(
-                "- **{}** ∼ {}",
-                character.name, character.description
-            ))
+/// Parse a paragraph of the body into a line, an overlapping cue, or a piece of commentary.
+fn parse_paragraph(paragraph: &str) -> BodyParagraph {
+    // the line ID (from --number-lines) is dropped, as it's only derived from the line's position
+    let paragraph = LINE_ID.replace(paragraph, "$1");
+
+    if let Some(captures) = MARKER_ANCHOR.captures(&paragraph) {
+        return BodyParagraph::Container(TextContainer::marker(&captures[1]));
+    }
+
+    let Some(quoted) = paragraph.strip_prefix("> ") else {
+        // a line naming its speaker, as in `**Alice:** **Hello.**`
+        if let Some(captures) = SPEAKER_LINE.captures(&paragraph) {
+            let container =
+                TextContainer::with_spans(ContainerKind::Spoken, parse_spans(&captures["line"]));
+            return BodyParagraph::Container(container.spoken_by(captures["speaker"].trim()));
         }
 
-        // Formatting guide
-        lines.append(&mut vec![
-            String::from("## Formatting guide"),
-            TextContainer::new(ContainerKind::Spoken)
-                .push(TextSpan::normal("spoken text"))
-                .to_markdown(),
-            TextContainer::new(ContainerKind::Spoken)
-                .push(TextSpan::emphasis("emphasis"))
-                .to_markdown(),
-            TextContainer::new(ContainerKind::Spoken)
-                .push(TextSpan::inline("tone cue, suggested"))
-                .to_markdown(),
-            TextContainer::new(ContainerKind::StageDir)
-                .push(TextSpan::normal("stage direction and/or sfx"))
-                .to_markdown(),
-            TextContainer::new(ContainerKind::ListenerDialogue)
-                .push(TextSpan::normal(
-                    "example listener dialogue, not intended to be voiced",
-                ))
-                .to_markdown(),
-            TextContainer::new(ContainerKind::PlainText)
-                .push(TextSpan::normal(DIVIDER))
-                .to_markdown(),
-        ]);
-
-        for container in &self.paragraphs {
-            lines.push(container.to_markdown());
+        let kind = match paragraph.contains("**") || paragraph.starts_with("*(") {
+            true => ContainerKind::Spoken,
+            false => ContainerKind::PlainText,
+        };
+        return BodyParagraph::Container(TextContainer::with_spans(kind, parse_spans(&paragraph)));
+    };
+
+    if let Some(text) = quoted.strip_prefix("**Commentary:**") {
+        return BodyParagraph::Commentary(text.trim().to_owned());
+    }
+
+    let (kind, text) = if let Some(text) = strip_affixes(quoted, "*[sfx:", "]*") {
+        (ContainerKind::Sfx, text)
+    } else if let Some(text) = strip_affixes(quoted, "*[", "]*") {
+        (ContainerKind::StageDir, text)
+    } else if let Some(text) = strip_affixes(quoted, "*«", "»*") {
+        (ContainerKind::ListenerDialogue, text)
+    } else {
+        // some other quotation, which is best kept as it is
+        return BodyParagraph::Container(TextContainer::with_spans(
+            ContainerKind::PlainText,
+            parse_spans(quoted),
+        ));
+    };
+
+    let text = text.trim_matches(|c: char| c.is_whitespace());
+    if let Some(cue) = text.strip_suffix("(over the following line)") {
+        return BodyParagraph::Overlap(TextContainer::with_spans(kind, parse_quoted_spans(cue)));
+    }
+    BodyParagraph::Container(TextContainer::with_spans(kind, parse_quoted_spans(text)))
+}
+
+/// Strip the given prefix and suffix from the text, if it has both.
+fn strip_affixes<'a>(text: &'a str, prefix: &str, suffix: &str) -> Option<&'a str> {
+    text.strip_prefix(prefix)?.strip_suffix(suffix)
+}
+
+/// The spans of an unquoted line which aren't normal text.
+static SPANS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\*\*(?P<bold>.+?)\*\*|\*\((?P<cue>.+?)\)\*|/(?P<em>[^/\s](?:[^/]*[^/\s])?)/")
+        .unwrap()
+});
+
+/// Parse the spans of an unquoted line: `**spoken**` (or `**/emphasised/**`) text, `*(tone cues)*`,
+/// and `/emphasis/`, with anything else as normal text.
+fn parse_spans(text: &str) -> Vec<TextSpan> {
+    split_spans(text, &SPANS, |captures| {
+        if let Some(bold) = captures.name("bold") {
+            let bold = bold.as_str().trim();
+            return Some(
+                match bold.strip_prefix('/').and_then(|b| b.strip_suffix('/')) {
+                    Some(emphasis) if !emphasis.is_empty() => TextSpan::emphasis(emphasis),
+                    _ => TextSpan::normal(bold),
+                },
+            );
+        }
+        if let Some(cue) = captures.name("cue") {
+            return Some(TextSpan::inline(cue.as_str().trim()));
         }
+        emphasis(text, captures)
+    })
+}
+
+/// The spans of a quoted line which aren't normal text.
+static QUOTED_SPANS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\((?P<cue>[^()]+)\)|/(?P<em>[^/\s](?:[^/]*[^/\s])?)/").unwrap());
 
-        lines.join("\n\n")
+/// Parse the spans within a quoted line, whose tone cues are written as just `(tone cue)`.
+fn parse_quoted_spans(text: &str) -> Vec<TextSpan> {
+    split_spans(text, &QUOTED_SPANS, |captures| match captures.name("cue") {
+        Some(cue) => Some(TextSpan::inline(cue.as_str().trim())),
+        None => emphasis(text, captures),
+    })
+}
+
+/// The emphasised span matched by the `em` group, so long as it stands as a word of its own
+/// (so that, e.g., "either/or/neither" isn't taken as emphasis).
+fn emphasis(text: &str, captures: &Captures) -> Option<TextSpan> {
+    let whole = captures.get(0)?;
+    let before = text[..whole.start()].chars().next_back();
+    let after = text[whole.end()..].chars().next();
+
+    let is_boundary = |c: Option<char>| c.is_none_or(char::is_whitespace);
+    if !(is_boundary(before) && is_boundary(after)) {
+        return None;
     }
+    Some(TextSpan::emphasis(captures.name("em")?.as_str()))
+}
+
+/// Split the text into spans at each match of the pattern, as made by `span` (or kept as normal text
+/// if it gives `None`), with the text between the matches as normal spans.
+fn split_spans(
+    text: &str,
+    re: &Regex,
+    span: impl Fn(&Captures) -> Option<TextSpan>,
+) -> Vec<TextSpan> {
+    let mut spans: Vec<TextSpan> = Vec::new();
+    let mut normal = String::new();
+
+    let flush = |normal: &mut String, spans: &mut Vec<TextSpan>| {
+        let text = normal.split_whitespace().collect::<Vec<&str>>().join(" ");
+        if !text.is_empty() {
+            spans.push(TextSpan::normal(&text));
+        }
+        normal.clear();
+    };
+
+    let mut start = 0;
+    for captures in re.captures_iter(text) {
+        let whole = captures.get(0).unwrap();
+        normal.push_str(&text[start..whole.start()]);
+        start = whole.end();
+
+        match span(&captures) {
+            Some(span) => {
+                flush(&mut normal, &mut spans);
+                spans.push(span);
+            }
+            None => normal.push_str(whole.as_str()),
+        }
+    }
+    normal.push_str(&text[start..]);
+    flush(&mut normal, &mut spans);
+
+    spans
+}
+
+/**
+Render just the structural skeleton of the script, for quickly reviewing its pacing.
+
+The title is kept as a heading, stage directions and sound effects are kept in full, and each
+spoken line is cut down to its first few words, along with its word count.
+
+# Examples
+
+```
+# use lilscript::md_handler::outline;
+# use lilscript::script::{ContainerKind, Script, TextContainer, TextSpan};
+let mut script = Script::new("lilellia", "A Very Cool Script");
+script.paragraphs.push(TextContainer::new(ContainerKind::StageDir).push(TextSpan::normal("A door opens.")));
+script.paragraphs.push(
+    TextContainer::new(ContainerKind::Spoken)
+        .push(TextSpan::inline("softly"))
+        .push(TextSpan::normal("Oh! You're here already? I wasn't expecting you.")),
+);
+
+let expected = "# A Very Cool Script\n\n> *[A door opens.]*\n\n- **Oh! You're here already? I…** *(8 words)*";
+assert_eq!(outline(&script), expected);
+```
+*/
+pub fn outline(script: &Script) -> String {
+    const PREVIEW_WORDS: usize = 5;
+
+    let mut lines: Vec<String> = vec![format!("# {}", script.title)];
+
+    for container in &script.paragraphs {
+        let cues = container
+            .overlaps
+            .iter()
+            .filter(|cue| matches!(cue.kind, ContainerKind::StageDir | ContainerKind::Sfx));
+        for cue in cues {
+            lines.push(cue.to_markdown());
+        }
+
+        match container.kind {
+            ContainerKind::StageDir | ContainerKind::Sfx | ContainerKind::Marker => {
+                // the overlapping cues have already been written out
+                let mut cue = container.clone();
+                cue.overlaps.clear();
+                lines.push(cue.to_markdown());
+            }
+            ContainerKind::Spoken => lines.push(format!(
+                "- **{}** *({} words)*",
+                container.first_words(PREVIEW_WORDS),
+                container.wordcount().spoken()
+            )),
+            _ => (),
+        }
+    }
+
+    lines.join("\n\n")
+}
+
+/**
+Render the script as a checklist for tracking takes while recording, with one checkbox per container.
+
+Each item carries the container's line ID, its kind (and speaker, if any), the first few words,
+and its word count (only the spoken words, for spoken lines).
+
+# Examples
+
+```
+# use lilscript::md_handler::checklist;
+# use lilscript::script::{ContainerKind, Script, TextContainer, TextSpan};
+let mut script = Script::new("lilellia", "A Very Cool Script");
+script.paragraphs.push(TextContainer::new(ContainerKind::StageDir).push(TextSpan::normal("A door opens.")));
+script.paragraphs.push(
+    TextContainer::new(ContainerKind::Spoken)
+        .push(TextSpan::inline("softly"))
+        .push(TextSpan::normal("Oh! You're here already? I wasn't expecting you.")),
+);
+
+let expected = "# A Very Cool Script\n\n\
+    - [ ] 001 Stage direction: \"A door opens.\" (3 words)\n\
+    - [ ] 002 Spoken: \"Oh! You're here already? I…\" (8 words)";
+assert_eq!(checklist(&script), expected);
+```
+*/
+pub fn checklist(script: &Script) -> String {
+    const PREVIEW_WORDS: usize = 5;
+
+    let items = script
+        .paragraphs
+        .iter()
+        .enumerate()
+        .map(|(i, container)| {
+            let wordcount = container.wordcount();
+            let words = match container.kind {
+                ContainerKind::Spoken => wordcount.spoken(),
+                _ => wordcount.total(),
+            };
+
+            let kind = match &container.speaker {
+                Some(speaker) => format!("{} ({})", container.kind, speaker),
+                None => container.kind.to_string(),
+            };
+
+            format!(
+                "- [ ] {} {}: \"{}\" ({} words)",
+                line_id(i),
+                kind,
+                container.first_words(PREVIEW_WORDS),
+                words
+            )
+        })
+        .collect::<Vec<String>>();
+
+    format!("# {}\n\n{}", script.title, items.join("\n"))
+}
+
+/// Return the ID used to refer to the container at the given (zero-based) index of a script's paragraphs.
+///
+/// # Examples
+/// ```
+/// # use lilscript::md_handler::line_id;
+/// assert_eq!(line_id(13), "014");
+/// assert_eq!(line_id(1234), "1235");
+/// ```
+pub fn line_id(index: usize) -> String {
+    format!("{:03}", index + 1)
+}
+
+/// Whether the span at the given index of the container is emphasis within a spoken line which is right next to
+/// an inline direction. It is rendered as spoken, but may have belonged to the direction instead.
+fn is_ambiguous_emphasis(container: &TextContainer, index: usize) -> bool {
+    let is_direction = |i: Option<usize>| {
+        i.and_then(|i| container.spans.get(i))
+            .is_some_and(|span| span.kind == SpanKind::InlineDirection)
+    };
+
+    container.kind == ContainerKind::Spoken
+        && container.spans[index].kind == SpanKind::Emphasis
+        && (is_direction(index.checked_sub(1)) || is_direction(Some(index + 1)))
+}
+
+/// Describe each emphasised span within a spoken line whose Markdown rendering is ambiguous: that is, which is
+/// right next to an inline direction (it is rendered as spoken, but may have belonged to the direction).
+///
+/// # Examples
+/// ```
+/// # use lilscript::{script::{ContainerKind, Script, TextSpan, TextContainer}, md_handler::ambiguous_emphasis};
+/// let mut script = Script::default();
+/// script.paragraphs.push(
+///     TextContainer::new(ContainerKind::Spoken)
+///         .push(TextSpan::normal("I"))
+///         .push(TextSpan::emphasis("really"))
+///         .push(TextSpan::normal("mean it."))
+///         .push(TextSpan::inline("softly"))
+///         .push(TextSpan::emphasis("truly")),
+/// );
+///
+/// let problems = ambiguous_emphasis(&script);
+/// assert_eq!(problems.len(), 1);
+/// assert!(problems[0].contains("truly"));
+/// ```
+pub fn ambiguous_emphasis(script: &Script) -> Vec<String> {
+    struct AmbiguousEmphasis(Vec<String>);
+
+    impl ScriptVisitor for AmbiguousEmphasis {
+        fn visit_container(&mut self, container: &TextContainer, _: ContainerContext) {
+            for (i, span) in container.spans.iter().enumerate() {
+                if is_ambiguous_emphasis(container, i) {
+                    self.0.push(format!(
+                        "The emphasised span \"{}\" occurs within the scope of a spoken line, next to \
+                        an inline direction, so it cannot be told apart from spoken text in Markdown. \
+                        Context: \"{}\"",
+                        span.contents,
+                        container.plain_text()
+                    ));
+                }
+            }
+        }
+    }
+
+    let mut visitor = AmbiguousEmphasis(Vec::new());
+    visitor.visit_script(script);
+    visitor.0
 }
 
 /// Convert the given input to small capital letters
-/// 
+///
 /// # Examples
 /// ```
 /// # use lilscript::md_handler::small_caps;
@@ -0,0 +1,308 @@
+use std::collections::BTreeMap;
+
+use chrono::Datelike;
+use num_format::{Locale, ToFormattedString};
+
+use crate::{
+    script::{normalize_tag, CountingRules, Script, WordCount},
+    ssml_handler::escape_xml,
+};
+
+/// The running totals of a group of scripts (e.g., all those of a year).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Totals {
+    /// The number of scripts in the group.
+    pub scripts: usize,
+
+    /// The combined word count of the scripts.
+    pub wordcount: WordCount,
+
+    /// The sum of the speech densities of the scripts which have any words (for averaging).
+    density_sum: f64,
+
+    /// The number of scripts which have any words.
+    densities: usize,
+}
+
+impl Totals {
+    /// Add a script with the given word count to the group.
+    pub fn add(&mut self, wordcount: WordCount) {
+        self.scripts += 1;
+        self.wordcount += wordcount;
+
+        let density = wordcount.speech_density();
+        if !density.is_nan() {
+            self.density_sum += density;
+            self.densities += 1;
+        }
+    }
+
+    /// The mean number of spoken words per script, if there are any scripts.
+    pub fn average_spoken(&self) -> Option<f64> {
+        (self.scripts > 0).then(|| self.wordcount.spoken() as f64 / self.scripts as f64)
+    }
+
+    /// The mean speech density of the scripts (each script counting equally), if any of them have words.
+    pub fn average_density(&self) -> Option<f64> {
+        (self.densities > 0).then(|| self.density_sum / self.densities as f64)
+    }
+}
+
+/// An author's summary of a whole archive of scripts.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    /// The rules by which each script's words are counted.
+    pub counting: CountingRules,
+
+    /// The totals across every script.
+    pub overall: Totals,
+
+    /// The totals of each year, with `None` collecting the undated scripts.
+    pub by_year: BTreeMap<Option<i32>, Totals>,
+
+    /// The totals of each series, with `None` collecting the standalone scripts.
+    pub by_series: BTreeMap<Option<String>, Totals>,
+
+    /// The number of scripts with each (normalized) tag.
+    pub tags: BTreeMap<String, usize>,
+}
+
+impl<'a> FromIterator<&'a Script> for Report {
+    fn from_iter<T: IntoIterator<Item = &'a Script>>(scripts: T) -> Self {
+        let mut report = Self::default();
+        for script in scripts {
+            report.add(script);
+        }
+        report
+    }
+}
+
+impl Report {
+    /// Add a script to the report.
+    pub fn add(&mut self, script: &Script) {
+        let wordcount = script.wordcount_with(&self.counting);
+
+        self.overall.add(wordcount);
+        self.by_year
+            .entry(script.date.map(|date| date.year()))
+            .or_default()
+            .add(wordcount);
+        self.by_series
+            .entry(script.series.title.clone())
+            .or_default()
+            .add(wordcount);
+
+        let mut tags: Vec<String> = script.tags.iter().map(|tag| normalize_tag(tag)).collect();
+        tags.sort();
+        tags.dedup();
+        for tag in tags.into_iter().filter(|tag| !tag.is_empty()) {
+            *self.tags.entry(tag).or_default() += 1;
+        }
+    }
+
+    /**
+    Render the report as Markdown: the overall totals, then a table each of the years (with how the
+    average length of a script changed from the year before), the series, and the tags (most used first).
+
+    # Examples
+
+    ```
+    # use lilscript::{report::Report, script::{ContainerKind, Script, TextContainer, TextSpan}};
+    # use chrono::NaiveDate;
+    let script = |year: i32, words: &str| {
+        let mut script = Script::new("lilellia", "A Very Cool Script");
+        script.date = NaiveDate::from_ymd_opt(year, 3, 3);
+        script.tags = vec!["F4A".into()];
+        script.paragraphs.push(TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal(words)));
+        script
+    };
+    let scripts = [script(2023, "One two."), script(2024, "One two three four."), script(2024, "One two.")];
+
+    let report: Report = scripts.iter().collect();
+    let markdown = report.to_markdown();
+    assert!(markdown.contains("| 2023 | 1 | 2 | 2 | 2 | — | 100.00% |"));
+    assert!(markdown.contains("| 2024 | 2 | 6 | 6 | 3 | +50.0% | 100.00% |"));
+    assert!(markdown.contains("| f4a | 3 |"));
+    ```
+    */
+    pub fn to_markdown(&self) -> String {
+        let mut sections: Vec<String> = vec![String::from("# Portfolio report"), self.summary()];
+        for (heading, table) in self.tables() {
+            sections.push(format!("## {}", heading));
+            sections.push(table.to_markdown());
+        }
+        sections.join("\n\n")
+    }
+
+    /// Render the report as a self-contained HTML page, with the same contents as `Report::to_markdown`.
+    pub fn to_html(&self) -> String {
+        let mut lines: Vec<String> = vec![
+            String::from("<!DOCTYPE html>"),
+            String::from(r#"<html lang="en">"#),
+            String::from("<head>"),
+            String::from(r#"<meta charset="utf-8">"#),
+            String::from("<title>Portfolio report</title>"),
+            format!("<style>{}</style>", STYLESHEET),
+            String::from("</head>"),
+            String::from("<body>"),
+            String::from("<h1>Portfolio report</h1>"),
+            format!("<p>{}</p>", escape_xml(&self.summary())),
+        ];
+        for (heading, table) in self.tables() {
+            lines.push(format!("<h2>{}</h2>", heading));
+            lines.push(table.to_html());
+        }
+        lines.push(String::from("</body>"));
+        lines.push(String::from("</html>"));
+        lines.join("\n")
+    }
+
+    /// The overall totals, as a single line.
+    fn summary(&self) -> String {
+        let locale = Locale::en;
+        format!(
+            "{} scripts · {} spoken words · {} total words · average speech density {}",
+            self.overall.scripts.to_formatted_string(&locale),
+            self.overall.wordcount.spoken().to_formatted_string(&locale),
+            self.overall.wordcount.total().to_formatted_string(&locale),
+            percentage(self.overall.average_density())
+        )
+    }
+
+    /// The tables of the report, each with its heading.
+    fn tables(&self) -> Vec<(&'static str, Table)> {
+        let locale = Locale::en;
+        let count = |n: usize| n.to_formatted_string(&locale);
+        let average = |n: Option<f64>| match n {
+            Some(n) => count(n.round() as usize),
+            None => String::from("—"),
+        };
+
+        let mut years = Table::new(&[
+            "Year",
+            "Scripts",
+            "Spoken words",
+            "Total words",
+            "Average spoken words",
+            "Change",
+            "Average density",
+        ]);
+        let mut previous: Option<f64> = None;
+        for (year, totals) in &self.by_year {
+            let spoken = totals.average_spoken();
+            // the trend is only meaningful between consecutive dated years
+            let change = match (year, previous, spoken) {
+                (Some(_), Some(previous), Some(spoken)) if previous > 0. => {
+                    format!("{:+.1}%", 100. * (spoken / previous - 1.))
+                }
+                _ => String::from("—"),
+            };
+            if year.is_some() {
+                previous = spoken;
+            }
+
+            years.rows.push(vec![
+                year.map_or(String::from("Undated"), |year| year.to_string()),
+                count(totals.scripts),
+                count(totals.wordcount.spoken()),
+                count(totals.wordcount.total()),
+                average(spoken),
+                change,
+                percentage(totals.average_density()),
+            ]);
+        }
+
+        let mut series = Table::new(&[
+            "Series",
+            "Scripts",
+            "Spoken words",
+            "Total words",
+            "Average density",
+        ]);
+        for (title, totals) in &self.by_series {
+            series.rows.push(vec![
+                title.clone().unwrap_or(String::from("(standalone)")),
+                count(totals.scripts),
+                count(totals.wordcount.spoken()),
+                count(totals.wordcount.total()),
+                percentage(totals.average_density()),
+            ]);
+        }
+
+        let mut tags = Table::new(&["Tag", "Scripts"]);
+        let mut frequencies: Vec<(&String, &usize)> = self.tags.iter().collect();
+        frequencies.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (tag, n) in frequencies {
+            tags.rows.push(vec![tag.clone(), count(*n)]);
+        }
+
+        vec![("By year", years), ("By series", series), ("Tags", tags)]
+    }
+}
+
+/// Write a proportion as a percentage, or a dash if there isn't one.
+fn percentage(proportion: Option<f64>) -> String {
+    match proportion {
+        Some(p) => format!("{:.2}%", 100. * p),
+        None => String::from("—"),
+    }
+}
+
+/// A table of the report, which can be written as either Markdown or HTML.
+struct Table {
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    fn new(headers: &[&'static str]) -> Self {
+        Self {
+            headers: headers.to_vec(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Write the table in Markdown, with every column but the first aligned right.
+    fn to_markdown(&self) -> String {
+        let alignment: Vec<&str> = (0..self.headers.len())
+            .map(|i| if i == 0 { "---" } else { "---:" })
+            .collect();
+
+        let mut lines = vec![
+            format!("| {} |", self.headers.join(" | ")),
+            format!("|{}|", alignment.join("|")),
+        ];
+        for row in &self.rows {
+            // a pipe within a cell would start a new column
+            let cells: Vec<String> = row.iter().map(|cell| cell.replace('|', r"\|")).collect();
+            lines.push(format!("| {} |", cells.join(" | ")));
+        }
+        lines.join("\n")
+    }
+
+    fn to_html(&self) -> String {
+        let headers: String = self
+            .headers
+            .iter()
+            .map(|header| format!("<th>{}</th>", header))
+            .collect();
+
+        let mut lines = vec![String::from("<table>"), format!("<tr>{}</tr>", headers)];
+        for row in &self.rows {
+            let cells: String = row
+                .iter()
+                .map(|cell| format!("<td>{}</td>", escape_xml(cell)))
+                .collect();
+            lines.push(format!("<tr>{}</tr>", cells));
+        }
+        lines.push(String::from("</table>"));
+        lines.join("\n")
+    }
+}
+
+/// The styling embedded into HTML reports.
+const STYLESHEET: &str = "\
+body { max-width: 50em; margin: auto; font-family: sans-serif; line-height: 1.5; } \
+table { border-collapse: collapse; margin-bottom: 2em; } \
+th, td { padding: 0.25em 0.75em; border-bottom: 1px solid #ccc; } \
+td:not(:first-child), th:not(:first-child) { text-align: right; }";
@@ -1,13 +1,29 @@
-use chrono::NaiveDate;
+use crate::{
+    ast::{Document, SECTION_DIVIDER},
+    format::{render, FileFormat, RenderOptions},
+    md_handler::line_id,
+    visitor::{ContainerContext, ScriptVisitor},
+};
+use chrono::{Local, NaiveDate};
 use num_format::{Locale, ToFormattedString};
+use paris::warn;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
+    error::Error,
     fmt::{self, Display},
-    ops::Add,
+    fs,
+    io::{self, Write},
+    ops::{Add, AddAssign, RangeInclusive},
+    path::Path,
+    str::FromStr,
+    sync::LazyLock,
 };
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
 
 /// A representation of a word count for a script
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct WordCount {
     /// The number of spoken words.
     spoken: usize,
@@ -64,6 +80,16 @@ impl WordCount {
         }
     }
 
+    /// Return the number of spoken words.
+    pub fn spoken(&self) -> usize {
+        self.spoken
+    }
+
+    /// Return the number of unspoken words.
+    pub fn unspoken(&self) -> usize {
+        self.unspoken
+    }
+
     /**
     Return the total number of words.
 
@@ -91,7 +117,7 @@ impl WordCount {
     /// # Notes
     ///
     /// The result will be NaN if there are no words counted.
-    fn speech_density(&self) -> f64 {
+    pub fn speech_density(&self) -> f64 {
         (self.spoken as f64) / (self.total() as f64)
     }
 }
@@ -107,7 +133,14 @@ impl Add for WordCount {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+impl AddAssign for WordCount {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SpanKind {
     /// just some normal text
     Normal,
@@ -117,38 +150,97 @@ pub enum SpanKind {
 
     /// inline direction
     InlineDirection,
+
+    /// a word with a pronunciation hint, e.g. "Nguyen" pronounced "nwin"
+    Phonetic(String),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct TextSpan {
     /// The kind of span this represents.
     pub kind: SpanKind,
 
     /// The text within the span.
     pub contents: String,
+
+    /// The language of the text (as a BCP 47 tag, e.g. "fr"), if it differs from the script's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+}
+
+impl Display for TextSpan {
+    /// Write the span as plain text, marking anything which isn't simply spoken.
+    ///
+    /// ```
+    /// # use lilscript::script::TextSpan;
+    /// assert_eq!(TextSpan::normal("Hello").to_string(), "Hello");
+    /// assert_eq!(TextSpan::emphasis("really").to_string(), "/really/");
+    /// assert_eq!(TextSpan::inline("softly").to_string(), "(softly)");
+    /// assert_eq!(TextSpan::phonetic("Siobhan", "shi-VAWN").to_string(), "Siobhan [shi-VAWN]");
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            SpanKind::Normal => write!(f, "{}", self.contents),
+            SpanKind::Emphasis => write!(f, "/{}/", self.contents),
+            SpanKind::InlineDirection => write!(f, "({})", self.contents),
+            SpanKind::Phonetic(pronunciation) => write!(f, "{} [{}]", self.contents, pronunciation),
+        }
+    }
 }
 
+/// A word, as counted by `TextSpan::num_words`.
+static WORD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[A-Za-zÀ-ÖØ-öø-ÿ'~-]+").unwrap());
+
 impl TextSpan {
     pub fn new(kind: SpanKind, contents: &str) -> Self {
         Self {
             kind,
             contents: contents.to_string(),
+            lang: None,
         }
     }
 
     /// Construct a new span with kind Normal
     pub fn normal(contents: &str) -> Self {
-        Self::new(SpanKind::Normal, &contents)
+        Self::new(SpanKind::Normal, contents)
     }
 
     /// Construct a new span with kind Emphasis
     pub fn emphasis(contents: &str) -> Self {
-        Self::new(SpanKind::Emphasis, &contents)
+        Self::new(SpanKind::Emphasis, contents)
     }
 
     /// Construct a new span with kind InlineDirection
     pub fn inline(contents: &str) -> Self {
-        Self::new(SpanKind::InlineDirection, &contents)
+        Self::new(SpanKind::InlineDirection, contents)
+    }
+
+    /// Construct a new span with kind Phonetic, containing the word and a hint for its pronunciation
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use lilscript::script::{SpanKind, TextSpan};
+    /// let span = TextSpan::phonetic("Nguyen", "nwin");
+    /// assert_eq!(span.contents, "Nguyen");
+    /// assert_eq!(span.kind, SpanKind::Phonetic("nwin".to_string()));
+    /// ```
+    pub fn phonetic(word: &str, pronunciation: &str) -> Self {
+        Self::new(SpanKind::Phonetic(pronunciation.to_owned()), word)
+    }
+
+    /// Mark the span as being in the given language and return it back
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use lilscript::script::TextSpan;
+    /// let span = TextSpan::normal("Bonjour").with_lang("fr");
+    /// assert_eq!(span.lang, Some("fr".to_string()));
+    /// ```
+    pub fn with_lang(mut self, lang: &str) -> Self {
+        self.lang = Some(lang.to_owned());
+        self
     }
 
     /// Convert this TextSpan to a different variant
@@ -156,6 +248,7 @@ impl TextSpan {
         Self {
             kind: variant,
             contents: self.contents.clone(),
+            lang: self.lang.clone(),
         }
     }
 
@@ -188,8 +281,7 @@ impl TextSpan {
     /// assert_eq!(span.num_words(), 0);
     /// ```
     pub fn num_words(&self) -> usize {
-        let re = Regex::new(r"[A-Za-zÀ-ÖØ-öø-ÿ'~-]+").unwrap();
-        re.find_iter(&self.contents).count()
+        WORD.find_iter(&self.contents).count()
     }
 
     /// Determine whether this span counts as spoken within the context of the given parent container.
@@ -208,15 +300,85 @@ impl TextSpan {
             return false;
         }
 
-        match self.kind {
-            SpanKind::InlineDirection => false,
-            _ => true,
+        !matches!(self.kind, SpanKind::InlineDirection)
+    }
+
+    /// Return the word count of the span within the context of the given parent container.
+    pub fn wordcount(&self, context: ContainerKind) -> WordCount {
+        self.wordcount_with(context, &CountingRules::default())
+    }
+
+    /// Return the word count of the span within the context of the given parent container,
+    /// according to the given rules.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use lilscript::script::{ContainerKind, CountingRules, TextSpan, WordCount};
+    /// let cue = TextSpan::inline("softly");
+    /// assert_eq!(cue.wordcount(ContainerKind::Spoken), WordCount::new(0, 1));
+    ///
+    /// let rules = CountingRules { count_cues_as_spoken: true, ..Default::default() };
+    /// assert_eq!(cue.wordcount_with(ContainerKind::Spoken, &rules), WordCount::new(1, 0));
+    /// ```
+    pub fn wordcount_with(&self, context: ContainerKind, rules: &CountingRules) -> WordCount {
+        let excluded = match context {
+            // the name of a marker isn't part of the script's text
+            ContainerKind::Marker => true,
+            ContainerKind::ListenerDialogue => rules.exclude_listener,
+            ContainerKind::PlainText => rules.exclude_notes,
+            _ => false,
+        };
+        if excluded {
+            return WordCount::zero();
+        }
+
+        let words = self.num_words();
+        let cue_as_spoken = rules.count_cues_as_spoken
+            && context == ContainerKind::Spoken
+            && self.kind == SpanKind::InlineDirection;
+        if self.is_spoken(context) || cue_as_spoken {
+            WordCount::only_spoken(words)
+        } else {
+            WordCount::only_unspoken(words)
         }
     }
 }
 
+/// Which words count towards a script's word count (and which of them are spoken), since different
+/// platforms' "word count" fields expect different definitions. By default, tone cues are unspoken,
+/// and listener dialogue and notes are counted as unspoken.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::Args))]
+pub struct CountingRules {
+    /// Whether tone cues (inline directions) within spoken lines count as spoken words.
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, help = "count tone cues within spoken lines as spoken words")
+    )]
+    pub count_cues_as_spoken: bool,
+
+    /// Whether listener dialogue is left out of the word count entirely.
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, help = "leave listener dialogue out of the word count")
+    )]
+    pub exclude_listener: bool,
+
+    /// Whether notes (untagged text, e.g. an author's note) are left out of the word count entirely.
+    #[cfg_attr(
+        feature = "cli",
+        arg(
+            long,
+            help = "leave notes (untagged text, e.g. an author's note) out of the word count"
+        )
+    )]
+    pub exclude_notes: bool,
+}
+
 /// A representation of the type of text container.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ContainerKind {
     /// a container for spoken text
     Spoken,
@@ -232,25 +394,90 @@ pub enum ContainerKind {
 
     /// a container for untagged text
     PlainText,
+
+    /// a named point in the script (e.g., for referring to while recording or editing), with no text of its own
+    Marker,
+}
+
+impl Display for ContainerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            Self::Spoken => "Spoken",
+            Self::StageDir => "Stage direction",
+            Self::Sfx => "Sfx",
+            Self::ListenerDialogue => "Listener",
+            Self::PlainText => "Text",
+            Self::Marker => "Marker",
+        };
+        write!(f, "{}", label)
+    }
 }
 
 /// A representation of a container of text.
 /// Used for a "line" of a script.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct TextContainer {
     /// the type of container this is
     pub kind: ContainerKind,
 
     /// a vector over the text spans
     pub spans: Vec<TextSpan>,
+
+    /// any cues (e.g., sfx or music) which run concurrently with this container
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub overlaps: Vec<TextContainer>,
+
+    /// the character speaking the line, for scripts with more than one voice
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+
+    /// machine-readable metadata about the container, e.g. the category and intensity of a sound effect
+    /// from `\sfx[category=rain, intensity=soft]{...}`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attributes: BTreeMap<String, String>,
+
+    /// the director's commentary on the container (from `\commentary{...}` lines following it), which
+    /// isn't part of the script itself, and is only rendered on request
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub commentary: Vec<String>,
+}
+
+impl Display for TextContainer {
+    /// Write the spans of the container (but not any overlapping cues) as plain text.
+    ///
+    /// ```
+    /// # use lilscript::script::{ContainerKind, TextContainer, TextSpan};
+    /// let container = TextContainer::new(ContainerKind::Spoken)
+    ///     .push(TextSpan::inline("softly"))
+    ///     .push(TextSpan::normal("Hello."));
+    /// assert_eq!(container.to_string(), "(softly) Hello.");
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, span) in self.spans.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", span)?;
+        }
+        Ok(())
+    }
 }
 
 impl TextContainer {
     /// Create a new, text container of the given type.
     pub fn new(kind: ContainerKind) -> Self {
+        Self::with_spans(kind, vec![])
+    }
+
+    /// Create a new text container of the given type, holding the given spans.
+    pub fn with_spans(kind: ContainerKind, spans: Vec<TextSpan>) -> Self {
         Self {
             kind,
-            spans: vec![],
+            spans,
+            overlaps: vec![],
+            speaker: None,
+            attributes: BTreeMap::new(),
+            commentary: vec![],
         }
     }
 
@@ -260,15 +487,120 @@ impl TextContainer {
         self
     }
 
+    /// add the given commentary to the container and return the container back
+    pub fn with_commentary(mut self, text: &str) -> Self {
+        self.commentary.push(text.to_owned());
+        self
+    }
+
+    /// attribute the container to the given speaker and return the container back
+    pub fn spoken_by(mut self, speaker: &str) -> Self {
+        self.speaker = Some(speaker.to_owned());
+        self
+    }
+
+    /// set the given attribute on the container and return the container back
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use lilscript::script::{ContainerKind, TextContainer, TextSpan};
+    /// let cue = TextContainer::new(ContainerKind::Sfx)
+    ///     .push(TextSpan::normal("rain on a window"))
+    ///     .with_attribute("category", "rain");
+    /// assert_eq!(cue.attributes["category"], "rain");
+    /// ```
+    pub fn with_attribute(mut self, key: &str, value: &str) -> Self {
+        self.attributes.insert(key.to_owned(), value.to_owned());
+        self
+    }
+
+    /// add a cue which runs concurrently with this container and return the container back
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use lilscript::script::{TextContainer, ContainerKind, TextSpan};
+    /// let container = TextContainer::new(ContainerKind::Spoken)
+    ///     .push(TextSpan::normal("Listen to that rain."))
+    ///     .overlapping(TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal("rain")));
+    /// assert_eq!(container.overlaps.len(), 1);
+    /// ```
+    pub fn overlapping(mut self, cue: TextContainer) -> Self {
+        self.overlaps.push(cue);
+        self
+    }
+
+    /// Return this container along with every container overlapping it, with the overlapping cues first.
+    pub fn with_overlaps(&self) -> Vec<&TextContainer> {
+        let mut containers: Vec<&TextContainer> = self
+            .overlaps
+            .iter()
+            .flat_map(|cue| cue.with_overlaps())
+            .collect();
+        containers.push(self);
+        containers
+    }
+
     /// Return the number of spans in the container.
     pub fn len(&self) -> usize {
         self.spans.len()
     }
 
+    /// Return whether the container has no spans.
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /** Create a marker with the given name: a fixed point in the script, such as the start of a take.
+
+    # Examples
+
+    ```
+    # use lilscript::script::{ContainerKind, TextContainer};
+    let marker = TextContainer::marker("take 2");
+    assert_eq!(marker.kind, ContainerKind::Marker);
+    assert_eq!(marker.plain_text(), "take 2");
+    assert_eq!(marker.wordcount().total(), 0);
+    ```
+    */
+    pub fn marker(name: &str) -> Self {
+        Self::new(ContainerKind::Marker).push(TextSpan::normal(name))
+    }
+
+    /** An identifier for the marker, usable as an HTML anchor: its name, with whitespace replaced by `-`.
+
+    # Return
+
+    * `Some(String)` - the identifier, if this container is a marker
+    * `None` otherwise
+
+    # Examples
+
+    ```
+    # use lilscript::script::TextContainer;
+    assert_eq!(TextContainer::marker(" take  2 ").marker_id().unwrap(), "take-2");
+    ```
+    */
+    pub fn marker_id(&self) -> Option<String> {
+        if self.kind != ContainerKind::Marker {
+            return None;
+        }
+
+        let name = self.plain_text();
+        Some(name.split_whitespace().collect::<Vec<&str>>().join("-"))
+    }
+
+    /// Whether this container is the `--8<--` divider between two sections of a script.
+    pub fn is_divider(&self) -> bool {
+        self.kind == ContainerKind::PlainText
+            && matches!(self.spans.as_slice(), [span] if span.contents == SECTION_DIVIDER)
+    }
+
     /// Return the contents of the container without regard for formatting/context.
-    /// 
+    ///
     /// # Examples:
-    /// 
+    ///
     /// ```
     /// # use lilscript::script::{TextContainer, ContainerKind, TextSpan};
     /// let mut container = TextContainer::new(ContainerKind::Spoken);
@@ -278,148 +610,873 @@ impl TextContainer {
     /// assert_eq!(container.plain_text(), "some text a cue more text");
     /// ```
     pub fn plain_text(&self) -> String {
-        (&self.spans)
-            .into_iter()
-            .map(|s| s.contents.clone())
-            .collect::<Vec<String>>()
-            .join(" ")
+        let len = self.spans.iter().map(|s| s.contents.len() + 1).sum();
+        let mut text = String::with_capacity(len);
+
+        for (i, span) in self.spans.iter().enumerate() {
+            if i > 0 {
+                text.push(' ');
+            }
+            text.push_str(&span.contents);
+        }
+
+        text
     }
 
-    pub fn wordcount(&self) -> WordCount {
-        self.spans
+    /// Return the first few words of the container, skipping any inline directions.
+    /// An ellipsis is added if any words were left out.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// # use lilscript::script::{TextContainer, ContainerKind, TextSpan};
+    /// let container = TextContainer::new(ContainerKind::Spoken)
+    ///     .push(TextSpan::inline("softly"))
+    ///     .push(TextSpan::normal("Oh! You're  here already?"));
+    /// assert_eq!(container.first_words(2), "Oh! You're…");
+    /// assert_eq!(container.first_words(10), "Oh! You're here already?");
+    /// ```
+    pub fn first_words(&self, n: usize) -> String {
+        let mut words = self
+            .spans
             .iter()
-            .map(|span| {
-                let words = span.num_words();
-                if span.is_spoken(self.kind.clone()) {
-                    WordCount::only_spoken(words)
-                } else {
-                    WordCount::only_unspoken(words)
-                }
+            .filter(|span| span.kind != SpanKind::InlineDirection)
+            .flat_map(|span| span.contents.split_whitespace());
+
+        let preview = words.by_ref().take(n).collect::<Vec<&str>>().join(" ");
+        match words.next() {
+            Some(_) => format!("{}…", preview),
+            None => preview,
+        }
+    }
+
+    /// Return the word count of the container, including any overlapping cues.
+    pub fn wordcount(&self) -> WordCount {
+        self.wordcount_with(&CountingRules::default())
+    }
+
+    /// Return the word count of the container, including any overlapping cues, according to the given rules.
+    pub fn wordcount_with(&self, rules: &CountingRules) -> WordCount {
+        self.with_overlaps()
+            .into_iter()
+            .flat_map(|container| {
+                container
+                    .spans
+                    .iter()
+                    .map(|span| span.wordcount_with(container.kind.clone(), rules))
             })
             // add these wordcounts together
             .fold(WordCount::zero(), |acc, w| acc + w)
     }
 }
 
-#[derive(Debug, Default, PartialEq)]
+/// The position of a script within its series.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PartIndex {
+    /// A numbered part, e.g., "Part 4"
+    Number(usize),
+
+    /// A numbered part written in Roman numerals, e.g., "Part IV"
+    Roman(usize),
+
+    /// A named installment, e.g., "Finale" or "Prologue"
+    Named(String),
+}
+
+impl From<usize> for PartIndex {
+    fn from(value: usize) -> Self {
+        Self::Number(value)
+    }
+}
+
+impl fmt::Display for PartIndex {
+    /**
+    ```
+    # use lilscript::script::PartIndex;
+    assert_eq!(PartIndex::Number(4).to_string(), "Part 4");
+    assert_eq!(PartIndex::Roman(4).to_string(), "Part IV");
+    assert_eq!(PartIndex::Named("Finale".into()).to_string(), "Finale");
+    ```
+    */
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Number(n) => write!(f, "Part {}", n),
+            Self::Roman(n) => write!(f, "Part {}", to_roman(*n)),
+            Self::Named(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl PartIndex {
+    /** Parse a part index, as it appears within the parentheses of a series entry.
+
+    # Examples
+
+    ```
+    # use lilscript::script::PartIndex;
+    assert_eq!(PartIndex::parse("Part 12"), PartIndex::Number(12));
+    assert_eq!(PartIndex::parse("Part XII"), PartIndex::Roman(12));
+    assert_eq!(PartIndex::parse("Prologue"), PartIndex::Named("Prologue".into()));
+    ```
+    */
+    pub fn parse(value: &str) -> Self {
+        let value = value.trim();
+        let index = value.strip_prefix("Part ").map(str::trim);
+
+        if let Some(n) = index.and_then(|i| i.parse::<usize>().ok()) {
+            Self::Number(n)
+        } else if let Some(n) = index.and_then(from_roman) {
+            Self::Roman(n)
+        } else {
+            Self::Named(value.to_owned())
+        }
+    }
+
+    /// The number of the part, if it has one (named installments do not).
+    pub fn number(&self) -> Option<usize> {
+        match self {
+            Self::Number(n) | Self::Roman(n) => Some(*n),
+            Self::Named(_) => None,
+        }
+    }
+}
+
+/// The Roman numerals, largest first, including the subtractive pairs.
+const ROMAN_NUMERALS: [(usize, &str); 13] = [
+    (1000, "M"),
+    (900, "CM"),
+    (500, "D"),
+    (400, "CD"),
+    (100, "C"),
+    (90, "XC"),
+    (50, "L"),
+    (40, "XL"),
+    (10, "X"),
+    (9, "IX"),
+    (5, "V"),
+    (4, "IV"),
+    (1, "I"),
+];
+
+/// Write the given number in (uppercase) Roman numerals.
+///
+/// # Examples
+/// ```
+/// # use lilscript::script::to_roman;
+/// assert_eq!(to_roman(1994), "MCMXCIV");
+/// ```
+pub fn to_roman(mut n: usize) -> String {
+    let mut numeral = String::new();
+    for (value, symbol) in ROMAN_NUMERALS {
+        while n >= value {
+            numeral.push_str(symbol);
+            n -= value;
+        }
+    }
+    numeral
+}
+
+/// Read a number written in (uppercase) Roman numerals, which must be in their usual form.
+///
+/// # Examples
+/// ```
+/// # use lilscript::script::from_roman;
+/// assert_eq!(from_roman("MCMXCIV"), Some(1994));
+/// assert_eq!(from_roman("IIII"), None);
+/// assert_eq!(from_roman("Finale"), None);
+/// ```
+pub fn from_roman(numeral: &str) -> Option<usize> {
+    let mut rest = numeral;
+    let mut n = 0;
+    for (value, symbol) in ROMAN_NUMERALS {
+        while let Some(r) = rest.strip_prefix(symbol) {
+            n += value;
+            rest = r;
+        }
+    }
+
+    // reject anything left over, as well as non-canonical forms such as "IIII"
+    (rest.is_empty() && n > 0 && to_roman(n) == numeral).then_some(n)
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 /// A representation of the series a script belongs to, including its part index.
 pub struct SeriesEntry {
     /// The title of the series.
     pub title: Option<String>,
     /// The part index for the script.
-    pub part: Option<usize>,
+    pub part: Option<PartIndex>,
 }
 
 impl From<&str> for SeriesEntry {
+    /// Parse the series entry, falling back to an empty entry if it is not of the expected form.
     fn from(value: &str) -> Self {
-        match value {
-            "" | "—" | "\\textemdash" => Self {
-                title: None,
-                part: None,
-            },
-            value => {
-                let re = Regex::new(r"^(.*?) \(Part (\d+)\)$").unwrap();
-                let captures = re.captures(&value);
-
-                if captures.is_none() {
-                    return Self {
-                        title: None,
-                        part: None,
-                    };
-                }
-
-                let captures = captures.unwrap();
-                let title = captures.get(1).unwrap().as_str().to_owned();
-                let part = captures.get(2).unwrap().as_str();
-                let part: usize = part.parse().unwrap_or(0);
-
-                Self {
-                    title: Some(title),
-                    part: Some(part),
-                }
-            }
-        }
+        Self::parse(value).unwrap_or_default()
     }
 }
 
 impl fmt::Display for SeriesEntry {
     /**
     ```
-    # use lilscript::script::SeriesEntry;
+    # use lilscript::script::{PartIndex, SeriesEntry};
     let s = SeriesEntry::new("A Very Cool Series", 7);
     assert_eq!(format!("{}", s), "A Very Cool Series (Part 7)");
+
+    let s = SeriesEntry::new("A Very Cool Series", PartIndex::Named("Finale".into()));
+    assert_eq!(format!("{}", s), "A Very Cool Series (Finale)");
     ```
     */
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if let (Some(title), Some(part)) = (self.title.clone(), self.part) {
-            write!(f, "{} (Part {})", title, part)
+        if let (Some(title), Some(part)) = (&self.title, &self.part) {
+            write!(f, "{} ({})", title, part)
         } else {
             write!(f, "")
         }
     }
 }
 
+/// A series and the part of it which a script is, as in `Title (Part N)`.
+static SERIES_ENTRY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(.*?) \(([^()]+)\)$").unwrap());
+
 impl SeriesEntry {
     /// Construct a SeriesEntry with the given title and part index.
-    pub fn new(title: &str, part: usize) -> Self {
+    pub fn new(title: &str, part: impl Into<PartIndex>) -> Self {
         Self {
             title: Some(title.to_owned()),
-            part: Some(part),
+            part: Some(part.into()),
         }
     }
-}
 
-#[derive(Debug)]
-pub struct Character {
-    /// The name/header information regarding the character
-    pub name: String,
+    /** Parse a series entry of the form `Title (Part N)`, where the part may also be given in
+    Roman numerals (`Title (Part IV)`) or be a named installment (`Title (Finale)`).
 
-    /// The description of the character
-    pub description: String,
-}
+    # Arguments
 
-impl Display for Character {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{} => {}", self.name, self.description)
-    }
-}
+    * `value` - the series string, as it appears in the script's header
 
-impl Character {
-    /// Create a new character with the given fields.
-    pub fn new(name: &str, description: &str) -> Self {
-        Self {
-            name: name.to_owned(),
-            description: description.to_owned(),
+    # Return
+
+    * `Ok(SeriesEntry)` if the string is empty/an em dash (no series), or of the expected form;
+    * `Err(_)` otherwise
+
+    # Examples
+
+    ```
+    # use lilscript::script::{PartIndex, SeriesEntry};
+    assert_eq!(SeriesEntry::parse("Nightfall (Part 2)"), Ok(SeriesEntry::new("Nightfall", 2)));
+    assert_eq!(SeriesEntry::parse("Nightfall (Part II)"), Ok(SeriesEntry::new("Nightfall", PartIndex::Roman(2))));
+    assert_eq!(SeriesEntry::parse("—"), Ok(SeriesEntry::default()));
+    assert!(SeriesEntry::parse("Nightfall, part two").is_err());
+    ```
+    */
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim() {
+            "" | "—" | "\\textemdash" => Ok(Self::default()),
+            value => {
+                let captures = SERIES_ENTRY.captures(value).ok_or(format!(
+                    "Could not parse series \"{}\": expected \"Title (Part N)\" or \"Title (Name)\"",
+                    value
+                ))?;
+
+                let title = captures.get(1).unwrap().as_str().to_owned();
+                let part = PartIndex::parse(captures.get(2).unwrap().as_str());
+
+                Ok(Self {
+                    title: Some(title),
+                    part: Some(part),
+                })
+            }
         }
     }
 }
 
-#[derive(Debug, Default)]
-/// A representation of a script.
-pub struct Script {
-    /// The name of the author. Even with multiple authors, it is only one string.
-    pub author: String,
+/// How to read an ambiguous numeric date such as `03/04/2024`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum DateOrder {
+    /// `03/04/2024` is the 3rd of April
+    #[default]
+    DayFirst,
 
-    /// The title of the script.
-    pub title: String,
+    /// `03/04/2024` is the 4th of March
+    MonthFirst,
+}
 
-    /// The series (if any) that the script belongs to, as well as its part index.
-    pub series: SeriesEntry,
+/// The suffix of an ordinal day, as in `3rd March`.
+static ORDINAL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d)(?:st|nd|rd|th)\b").unwrap());
+
+/// The date formats understood by `parse_date`, described for error messages.
+const DATE_FORMATS: &str = "\"2024-03-03\", \"2024/03/03\", \"03 Mar 2024\", \"3 March 2024\", \
+    \"3rd March 2024\", \"March 3, 2024\", \"03/03/2024\" (see --date-order), or \\today";
+
+/** Parse a date in any of several common formats.
+
+# Arguments
+
+* `value` - the date, as written in the script's header
+* `order` - how to read a numeric date with slashes, where the day and month could be swapped
+
+# Return
+
+* `Ok(NaiveDate)` if the date is in one of the accepted formats;
+* `Err(_)` listing the accepted formats otherwise
+
+# Examples
+
+```
+# use lilscript::script::{parse_date, DateOrder};
+# use chrono::NaiveDate;
+let expected = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+assert_eq!(parse_date("2024-03-04", DateOrder::DayFirst), Ok(expected));
+assert_eq!(parse_date("04 Mar 2024", DateOrder::DayFirst), Ok(expected));
+assert_eq!(parse_date("4 March 2024", DateOrder::DayFirst), Ok(expected));
+assert_eq!(parse_date("March 4, 2024", DateOrder::DayFirst), Ok(expected));
+assert_eq!(parse_date("4th March 2024", DateOrder::DayFirst), Ok(expected));
+assert_eq!(parse_date("March 4th, 2024", DateOrder::DayFirst), Ok(expected));
+assert_eq!(parse_date("2024/03/04", DateOrder::MonthFirst), Ok(expected));
+assert_eq!(parse_date("04/03/2024", DateOrder::DayFirst), Ok(expected));
+assert_eq!(parse_date("03/04/2024", DateOrder::MonthFirst), Ok(expected));
+assert!(parse_date("the fourth of March", DateOrder::DayFirst).is_err());
+```
+*/
+pub fn parse_date(value: &str, order: DateOrder) -> Result<NaiveDate, String> {
+    let value = value.trim();
+    if value == r"\today" {
+        return Ok(Local::now().date_naive());
+    }
 
-    /// Any tags attributed to the script. Note that they do not include any brackets.
-    pub tags: Vec<String>,
+    // ordinal days (e.g., "3rd March") are read as plain numbers
+    let plain = ORDINAL.replace_all(value, "$1");
+
+    let numeric = match order {
+        DateOrder::DayFirst => "%d/%m/%Y",
+        DateOrder::MonthFirst => "%m/%d/%Y",
+    };
+
+    [
+        "%Y-%m-%d",
+        "%Y/%m/%d",
+        "%d %b %Y",
+        "%d %B %Y",
+        "%B %d, %Y",
+        "%b %d, %Y",
+        numeric,
+    ]
+    .iter()
+    .find_map(|format| NaiveDate::parse_from_str(&plain, format).ok())
+    .ok_or(format!(
+        "Could not parse date \"{}\": expected one of {}",
+        value, DATE_FORMATS
+    ))
+}
 
-    /// The date of the script.
-    pub date: Option<NaiveDate>,
+/// Options which control how strictly a script is parsed.
+#[derive(Clone, Debug, Default)]
+pub struct ParseOptions {
+    /// Whether conditions which would otherwise only produce a warning should be treated as errors.
+    pub strict: bool,
 
-    /// The summary of the script.
-    pub summary: String,
+    /// Whether the script's tags should be normalized (see `Script::normalize_tags`).
+    pub normalize_tags: bool,
 
-    /// Information about the characters
-    pub characters: Vec<Character>,
+    /// The aliases to apply when normalizing tags.
+    pub tag_aliases: BTreeMap<String, String>,
 
-    /// The actual text of the script.
-    pub paragraphs: Vec<TextContainer>,
+    /// How to read ambiguous numeric dates.
+    pub date_order: DateOrder,
+
+    /// Whether emoji in Markdown input are converted back to `:shortcode:`s (see `emoji::emoji_to_shortcodes`),
+    /// so that they survive export to .tex.
+    pub shortcodes: bool,
+
+    /// Whether warnings (and progress messages) should be left unprinted (e.g., while something else is drawing
+    /// to the terminal, or the output is meant to be piped).
+    pub quiet: bool,
+}
+
+impl ParseOptions {
+    /** Report a problem with the source: as a warning normally, or as an error in strict mode.
+
+    # Examples
+
+    ```
+    # use lilscript::script::ParseOptions;
+    let lenient = ParseOptions::default();
+    assert!(lenient.warn_or_fail("Unknown command".to_owned()).is_ok());
+
+    let strict = ParseOptions { strict: true, ..Default::default() };
+    assert_eq!(strict.warn_or_fail("Unknown command".to_owned()), Err("Unknown command".to_owned()));
+    ```
+    */
+    pub fn warn_or_fail(&self, message: String) -> Result<(), String> {
+        if self.strict {
+            return Err(message);
+        }
+
+        self.warn(&message);
+        Ok(())
+    }
+
+    /// Print a warning about the source, unless `quiet` is set.
+    pub fn warn(&self, message: &str) {
+        if !self.quiet {
+            warn!("{}", message);
+        }
+    }
+}
+
+/// Normalize a single tag: trimmed, lowercased, and with any internal whitespace collapsed to a single space.
+///
+/// # Examples
+/// ```
+/// # use lilscript::script::normalize_tag;
+/// assert_eq!(normalize_tag("  Sleep   Aid "), "sleep aid");
+/// ```
+pub fn normalize_tag(tag: &str) -> String {
+    tag.split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/**
+Turn text into a slug which is safe to use in file names and URLs: accents are stripped (along with
+a few other letters being transliterated, e.g. `ß` ⟶ `ss`), everything is lowercased, and each run of
+anything other than ASCII letters and digits becomes a single dash. Apostrophes are dropped, so that
+contractions stay as one word, and anything which can't be transliterated (e.g., CJK) is left out.
+If nothing is left at all, the slug is `untitled`.
+
+# Examples
+
+```
+# use lilscript::script::slugify;
+assert_eq!(slugify("A Very Cool Script"), "a-very-cool-script");
+assert_eq!(slugify("Crème Brûlée & Straße (Part 2)"), "creme-brulee-strasse-part-2");
+assert_eq!(slugify("  Don't Go… "), "dont-go");
+assert_eq!(slugify("ねぇ"), "untitled");
+```
+*/
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+
+    for c in text.nfkd() {
+        let transliterated = match c {
+            'ß' => "ss",
+            'æ' | 'Æ' => "ae",
+            'œ' | 'Œ' => "oe",
+            'ø' | 'Ø' => "o",
+            'ł' | 'Ł' => "l",
+            'đ' | 'Đ' | 'ð' | 'Ð' => "d",
+            'þ' | 'Þ' => "th",
+            '\'' | '’' => continue,
+            c if c.is_ascii_alphanumeric() => {
+                slug.push(c.to_ascii_lowercase());
+                continue;
+            }
+            c if is_combining_mark(c) => continue,
+            _ => "-",
+        };
+
+        if transliterated == "-" {
+            if !slug.is_empty() && !slug.ends_with('-') {
+                slug.push('-');
+            }
+        } else {
+            slug.push_str(transliterated);
+        }
+    }
+
+    match slug.trim_end_matches('-') {
+        "" => "untitled".to_owned(),
+        slug => slug.to_owned(),
+    }
+}
+
+/// Which text of a script a find-and-replace applies to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ReplaceScope {
+    /// only the words which are spoken aloud
+    Spoken,
+
+    /// only stage directions, sound effects, and inline directions
+    Directions,
+
+    /// every span, along with speaker attributions and the character list
+    #[default]
+    All,
+}
+
+impl ReplaceScope {
+    /**
+    Return whether the given span (in a container of the given kind) is within the scope.
+
+    # Examples
+
+    ```
+    # use lilscript::script::{ContainerKind, ReplaceScope, TextSpan};
+    let direction = TextSpan::inline("softly");
+    assert!(ReplaceScope::Directions.includes(&direction, ContainerKind::Spoken));
+    assert!(!ReplaceScope::Spoken.includes(&direction, ContainerKind::Spoken));
+    assert!(ReplaceScope::Directions.includes(&TextSpan::normal("rain"), ContainerKind::Sfx));
+    ```
+    */
+    pub fn includes(&self, span: &TextSpan, context: ContainerKind) -> bool {
+        match self {
+            Self::Spoken => span.is_spoken(context),
+            Self::Directions => {
+                matches!(context, ContainerKind::StageDir | ContainerKind::Sfx)
+                    || span.kind == SpanKind::InlineDirection
+            }
+            Self::All => true,
+        }
+    }
+}
+
+/// Return whether the span is normal text without a language of its own.
+fn is_plain(span: &TextSpan) -> bool {
+    span.kind == SpanKind::Normal && span.lang.is_none()
+}
+
+/** Normalize a piece of text: NFC normalization, with runs of whitespace collapsed to a single space and
+the ends trimmed.
+
+Non-breaking spaces are deliberate (e.g., before French punctuation), so they are kept as they are.
+
+# Examples
+
+```
+# use lilscript::script::normalize_text;
+assert_eq!(normalize_text("  Cafe\u{301}\t au\n  lait "), "Caf\u{e9} au lait");
+assert_eq!(normalize_text("Bonjour\u{a0}!"), "Bonjour\u{a0}!");
+```
+*/
+pub fn normalize_text(text: &str) -> String {
+    let collapsible = |c: char| c.is_whitespace() && !matches!(c, '\u{a0}' | '\u{202f}');
+
+    text.nfc()
+        .collect::<String>()
+        .split(collapsible)
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// How double quotation marks are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum QuoteStyle {
+    /// TeX quotes: ``like this''
+    Tex,
+
+    /// Straight quotes: "like this"
+    #[default]
+    Straight,
+
+    /// Curly (typographic) quotes: “like this”
+    Curly,
+}
+
+/// A double quotation mark, in any of the styles.
+static QUOTE: LazyLock<Regex> = LazyLock::new(|| Regex::new("``|''|\u{201c}|\u{201d}|\"").unwrap());
+
+impl QuoteStyle {
+    /**
+    Rewrite every double quotation mark in the text (in any of the styles) in this style.
+
+    A straight quote is taken as opening if it starts the text or follows whitespace or an opening
+    bracket/dash, and as closing otherwise.
+
+    # Examples
+
+    ```
+    # use lilscript::script::QuoteStyle;
+    assert_eq!(QuoteStyle::Curly.convert(r#"She said "hi" and ``bye''."#), "She said “hi” and “bye”.");
+    assert_eq!(QuoteStyle::Tex.convert("“Hi,” she said."), "``Hi,'' she said.");
+    assert_eq!(QuoteStyle::Straight.convert("``Hi,'' “you”"), r#""Hi," "you""#);
+    ```
+    */
+    pub fn convert(&self, text: &str) -> String {
+        let (open, close) = match self {
+            Self::Tex => ("``", "''"),
+            Self::Straight => ("\"", "\""),
+            Self::Curly => ("\u{201c}", "\u{201d}"),
+        };
+
+        QUOTE
+            .replace_all(text, |caps: &regex::Captures| {
+                let m = caps.get(0).unwrap();
+                let opening = match m.as_str() {
+                    "``" | "\u{201c}" => true,
+                    "''" | "\u{201d}" => false,
+                    _ => text[..m.start()]
+                        .chars()
+                        .next_back()
+                        .is_none_or(|c| c.is_whitespace() || "([{\u{2014}\u{2013}-".contains(c)),
+                };
+                if opening {
+                    open
+                } else {
+                    close
+                }
+            })
+            .into_owned()
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Character {
+    /// The name/header information regarding the character
+    pub name: String,
+
+    /// The description of the character
+    pub description: String,
+
+    /// Notes on how the character should sound, for casting (e.g., "warm, low, unhurried")
+    pub voice: Option<String>,
+
+    /// The age (or range of ages) of the character, as written (e.g., "20s" or "25-35")
+    pub age: Option<String>,
+
+    /// The pronouns of the character (e.g., "she/her")
+    pub pronouns: Option<String>,
+
+    /// Any tags attributed to the character (e.g., "lead")
+    pub tags: Vec<String>,
+}
+
+impl Display for Character {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} => {}", self.name, self.description)?;
+
+        let details = self.details();
+        if !details.is_empty() {
+            write!(f, " ({})", details)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Character {
+    /// The keys of the optional fields, as they are written in `key=value` form (see `Character::set`).
+    pub const KEYS: [&'static str; 4] = ["voice", "age", "pronouns", "tags"];
+
+    /// Create a new character with the given fields.
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            description: description.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    /**
+    Set one of the optional fields from its `key=value` form, where the `tags` are separated by commas.
+    An empty value clears the field.
+
+    # Return
+
+    * `Ok(())` if the field was set;
+    * `Err(_)` if the key isn't one of `Character::KEYS`
+
+    # Examples
+
+    ```
+    # use lilscript::script::Character;
+    let mut character = Character::new("Speaker", "a kind witch");
+    character.set("pronouns", "she/her").unwrap();
+    character.set("tags", "lead, witch").unwrap();
+    assert_eq!(character.pronouns.as_deref(), Some("she/her"));
+    assert_eq!(character.tags, vec!["lead", "witch"]);
+
+    assert!(character.set("height", "tall").is_err());
+    ```
+    */
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        let text = (!value.is_empty()).then(|| value.to_owned());
+
+        match key.trim() {
+            "voice" => self.voice = text,
+            "age" => self.age = text,
+            "pronouns" => self.pronouns = text,
+            "tags" => {
+                self.tags = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|tag| !tag.is_empty())
+                    .map(str::to_owned)
+                    .collect()
+            }
+            key => {
+                return Err(format!(
+                    "Unknown character field \"{}\": expected one of {}",
+                    key,
+                    Self::KEYS.join(", ")
+                ))
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+    The optional fields which are set, as `(key, value)` pairs in the order of `Character::KEYS`,
+    with the `tags` joined by commas.
+
+    # Examples
+
+    ```
+    # use lilscript::script::Character;
+    let mut character = Character::new("Speaker", "a kind witch");
+    character.age = Some("30s".to_owned());
+    character.tags = vec!["lead".to_owned(), "witch".to_owned()];
+    assert_eq!(character.fields(), vec![("age", "30s".to_owned()), ("tags", "lead, witch".to_owned())]);
+    ```
+    */
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let tags = (!self.tags.is_empty()).then(|| self.tags.join(", "));
+
+        Self::KEYS
+            .into_iter()
+            .zip([&self.voice, &self.age, &self.pronouns, &tags])
+            .filter_map(|(key, value)| value.clone().map(|value| (key, value)))
+            .collect()
+    }
+
+    /**
+    The optional fields which are set, as a line of text for character tables.
+
+    # Examples
+
+    ```
+    # use lilscript::script::Character;
+    let mut character = Character::new("Speaker", "a kind witch");
+    assert_eq!(character.details(), "");
+
+    character.voice = Some("warm, low".to_owned());
+    character.pronouns = Some("she/her".to_owned());
+    assert_eq!(character.details(), "she/her · voice: warm, low");
+    ```
+    */
+    pub fn details(&self) -> String {
+        let mut details: Vec<String> = Vec::new();
+
+        if let Some(pronouns) = &self.pronouns {
+            details.push(pronouns.clone());
+        }
+        if let Some(age) = &self.age {
+            details.push(format!("age {}", age));
+        }
+        if let Some(voice) = &self.voice {
+            details.push(format!("voice: {}", voice));
+        }
+        if !self.tags.is_empty() {
+            details.push(format!("tags: {}", self.tags.join(", ")));
+        }
+
+        details.join(" · ")
+    }
+}
+
+/// A run of lines within one section of a script in which a character speaks, without any other
+/// character speaking in between (see `Script::appearances`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Appearance {
+    /// The (1-based) section of the script, where sections are separated by `--8<--` dividers.
+    pub section: usize,
+
+    /// The (zero-based) indices of the first and last paragraphs of the run.
+    pub paragraphs: RangeInclusive<usize>,
+
+    /// The number of lines which the character speaks within the run.
+    pub lines: usize,
+}
+
+impl Display for Appearance {
+    /**
+    Written with the same line numbers as the recording checklist.
+
+    ```
+    # use lilscript::script::Appearance;
+    let appearance = Appearance { section: 2, paragraphs: 13..=20, lines: 3 };
+    assert_eq!(appearance.to_string(), "section 2, lines 014–021");
+
+    let appearance = Appearance { section: 1, paragraphs: 4..=4, lines: 1 };
+    assert_eq!(appearance.to_string(), "section 1, line 005");
+    ```
+    */
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (first, last) = (*self.paragraphs.start(), *self.paragraphs.end());
+        match first == last {
+            true => write!(f, "section {}, line {}", self.section, line_id(first)),
+            false => write!(
+                f,
+                "section {}, lines {}–{}",
+                self.section,
+                line_id(first),
+                line_id(last)
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+/// A representation of a script.
+pub struct Script {
+    /// The name of the author. Even with multiple authors, it is only one string.
+    pub author: String,
+
+    /// The title of the script.
+    pub title: String,
+
+    /// The series (if any) that the script belongs to, as well as its part index.
+    pub series: SeriesEntry,
+
+    /// Any tags attributed to the script. Note that they do not include any brackets.
+    pub tags: Vec<String>,
+
+    /// The date of the script.
+    pub date: Option<NaiveDate>,
+
+    /// The summary of the script.
+    pub summary: String,
+
+    /// The number of spoken words the script is aiming for, if any.
+    pub target: Option<usize>,
+
+    /// Information about the characters
+    pub characters: Vec<Character>,
+
+    /// The actual text of the script.
+    pub paragraphs: Vec<TextContainer>,
+}
+
+/// A short teaser for a script (see `Script::excerpt`), as plain text without any markup.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Excerpt {
+    /// The summary of the script.
+    pub summary: String,
+
+    /// The first spoken words of the script, ending with an ellipsis if any were left out.
+    pub text: String,
+}
+
+impl Display for Excerpt {
+    /// Write the summary and then the text, separated by a blank line (leaving out whichever is empty).
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.summary.is_empty(), self.text.is_empty()) {
+            (false, false) => write!(f, "{}\n\n{}", self.summary, self.text),
+            (false, true) => write!(f, "{}", self.summary),
+            _ => write!(f, "{}", self.text),
+        }
+    }
 }
 
 impl Script {
@@ -448,56 +1505,1790 @@ impl Script {
         }
     }
 
+    /**
+    A slug of the script's title (see `slugify`), for naming its output files and linking to it.
+
+    # Example
+
+    ```
+    # use lilscript::script::Script;
+    let script = Script::new("lilellia", "Moonrise: A Witch's Welcome");
+    assert_eq!(script.slug(), "moonrise-a-witchs-welcome");
+    ```
+    */
+    pub fn slug(&self) -> String {
+        slugify(&self.title)
+    }
+
     /// Return the word count for the entire script.
     pub fn wordcount(&self) -> WordCount {
+        self.wordcount_with(&CountingRules::default())
+    }
+
+    /**
+    Return the word count for the entire script, according to the given rules.
+
+    # Examples
+
+    ```
+    # use lilscript::script::{ContainerKind, CountingRules, Script, TextContainer, TextSpan, WordCount};
+    let mut script = Script::new("lilellia", "A Very Cool Script");
+    script.paragraphs = vec![
+        TextContainer::new(ContainerKind::PlainText).push(TextSpan::normal("Thanks for reading!")),
+        TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Hi.")),
+        TextContainer::new(ContainerKind::ListenerDialogue).push(TextSpan::normal("Hello?")),
+    ];
+    assert_eq!(script.wordcount(), WordCount::new(1, 4));
+
+    let rules = CountingRules { exclude_listener: true, exclude_notes: true, ..Default::default() };
+    assert_eq!(script.wordcount_with(&rules), WordCount::new(1, 0));
+    ```
+    */
+    pub fn wordcount_with(&self, rules: &CountingRules) -> WordCount {
         self.paragraphs
             .iter()
-            .map(|container| container.wordcount())
+            .map(|container| container.wordcount_with(rules))
             .fold(WordCount::zero(), |acc, w| acc + w)
     }
-}
 
-impl Display for Script {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "Title: {}", self.title)?;
-        writeln!(f, "Author: {}", self.author)?;
-        writeln!(f, "Series: {}", self.series)?;
+    /**
+    Normalize the script's tags: each is trimmed and lowercased (see `normalize_tag`), mapped through the
+    given aliases, and then any duplicates are removed (keeping the first occurrence).
 
-        let tags = self
-            .tags
+    # Arguments
+
+    * `aliases` - a map from a tag to the tag which should be used instead;
+      the keys are compared after normalization, so `"F4A"` and `"f4a"` are the same alias
+
+    # Examples
+
+    ```
+    # use lilscript::script::Script;
+    # use std::collections::BTreeMap;
+    let mut script = Script::new("lilellia", "A Very Cool Script");
+    script.tags = vec!["F4A".into(), " Comfort ".into(), "f4a".into(), "sleep aid".into()];
+
+    let aliases = BTreeMap::from([("Sleep Aid".to_owned(), "sleep".to_owned())]);
+    script.normalize_tags(&aliases);
+    assert_eq!(script.tags, vec!["f4a", "comfort", "sleep"]);
+    ```
+    */
+    pub fn normalize_tags(&mut self, aliases: &BTreeMap<String, String>) {
+        let aliases: BTreeMap<String, String> = aliases
             .iter()
-            .map(|tag| format!("[{}]", tag))
-            .collect::<Vec<String>>()
-            .join(" ");
-        writeln!(f, "Tags: {}", tags)?;
+            .map(|(alias, tag)| (normalize_tag(alias), normalize_tag(tag)))
+            .collect();
 
-        writeln!(f, "Date: {:?}", self.date)?;
-        writeln!(f, "Summary: {}", self.summary)?;
+        let mut tags: Vec<String> = Vec::new();
+        for tag in &self.tags {
+            let tag = normalize_tag(tag);
+            let tag = aliases.get(&tag).cloned().unwrap_or(tag);
 
-        for character in &self.characters {
-            writeln!(f, "Character: {}", character)?;
+            if !tag.is_empty() && !tags.contains(&tag) {
+                tags.push(tag);
+            }
         }
 
-        writeln!(f, "Words: {}", self.wordcount())?;
-        writeln!(f, "")?;
+        self.tags = tags;
+    }
 
-        for container in &self.paragraphs {
-            for (i, span) in container.spans.iter().enumerate() {
-                let prefix = if i == 0 {
-                    format!("{:?}", container.kind)
-                } else {
-                    String::from("_")
-                };
+    /**
+    Return the distinct speakers attributed to lines of the script, in order of first appearance.
+
+    # Examples
+
+    ```
+    # use lilscript::script::{ContainerKind, Script, TextContainer, TextSpan};
+    let mut script = Script::new("lilellia", "A Very Cool Script");
+    for speaker in ["Alice", "Bob", "Alice"] {
+        script.paragraphs.push(
+            TextContainer::new(ContainerKind::Spoken)
+                .push(TextSpan::normal("Hello."))
+                .spoken_by(speaker),
+        );
+    }
 
-                writeln!(f, "{}::{:?}", prefix, span)?;
+    assert_eq!(script.speakers(), vec!["Alice", "Bob"]);
+    ```
+    */
+    pub fn speakers(&self) -> Vec<&str> {
+        let mut speakers: Vec<&str> = Vec::new();
+        for container in self.paragraphs.iter().flat_map(|c| c.with_overlaps()) {
+            if let Some(speaker) = container.speaker.as_deref() {
+                if !speakers.contains(&speaker) {
+                    speakers.push(speaker);
+                }
             }
         }
-
-        Ok(())
+        speakers
     }
-}
+
+    /**
+    Count the words of each speaker's lines (according to the given rules), in order of first appearance.
+    Overlapping cues are counted for their own speaker, or for that of the line they accompany if they have
+    none, so every speaker listed by `Script::speakers` is counted; unattributed lines are skipped.
+
+    # Examples
+
+    ```
+    # use lilscript::script::{ContainerKind, CountingRules, Script, TextContainer, TextSpan, WordCount};
+    let line = |speaker: &str, text: &str| {
+        TextContainer::new(ContainerKind::Spoken)
+            .push(TextSpan::normal(text))
+            .spoken_by(speaker)
+    };
+
+    let mut script = Script::new("lilellia", "A Very Cool Script");
+    script.paragraphs = vec![
+        line("Alice", "Hello there."),
+        line("Bob", "Hi."),
+        TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Nobody says this.")),
+        line("Alice", "How are you?"),
+    ];
+
+    let words = script.wordcount_by_speaker(&CountingRules::default());
+    assert_eq!(words, vec![("Alice".into(), WordCount::new(5, 0)), ("Bob".into(), WordCount::new(1, 0))]);
+
+    // a speaker who only talks over someone else's line is counted too
+    script.paragraphs[1].overlaps.push(line("Carol", "Hey!"));
+    let words = script.wordcount_by_speaker(&CountingRules::default());
+    assert_eq!(words.iter().map(|(name, _)| name.as_str()).collect::<Vec<&str>>(), script.speakers());
+    assert_eq!(words[1], ("Carol".into(), WordCount::new(1, 0)));
+    assert_eq!(words[2], ("Bob".into(), WordCount::new(1, 0)));
+    ```
+    */
+    pub fn wordcount_by_speaker(&self, rules: &CountingRules) -> Vec<(String, WordCount)> {
+        let mut counts: Vec<(String, WordCount)> = Vec::new();
+        for paragraph in &self.paragraphs {
+            for container in paragraph.with_overlaps() {
+                let Some(speaker) = container
+                    .speaker
+                    .as_deref()
+                    .or(paragraph.speaker.as_deref())
+                else {
+                    continue;
+                };
+
+                let wordcount = container
+                    .spans
+                    .iter()
+                    .map(|span| span.wordcount_with(container.kind.clone(), rules))
+                    .fold(WordCount::zero(), |acc, w| acc + w);
+                match counts.iter_mut().find(|(name, _)| name == speaker) {
+                    Some((_, total)) => *total += wordcount,
+                    None => counts.push((speaker.to_owned(), wordcount)),
+                }
+            }
+        }
+        counts
+    }
+
+    /**
+    Count the words of each section of the script (according to the given rules), where the sections are
+    split at `--8<--` dividers.
+
+    # Examples
+
+    ```
+    # use lilscript::script::{ContainerKind, CountingRules, Script, TextContainer, TextSpan, WordCount};
+    let mut script = Script::new("lilellia", "A Very Cool Script");
+    script.paragraphs = vec![
+        TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Hello there.")),
+        TextContainer::new(ContainerKind::PlainText).push(TextSpan::normal("--8<--")),
+        TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal("rain")),
+        TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Listen.")),
+    ];
+
+    let sections = script.wordcount_by_section(&CountingRules::default());
+    assert_eq!(sections, vec![WordCount::new(2, 0), WordCount::new(1, 1)]);
+    ```
+    */
+    pub fn wordcount_by_section(&self, rules: &CountingRules) -> Vec<WordCount> {
+        let mut sections = vec![WordCount::zero()];
+        for container in &self.paragraphs {
+            match container.is_divider() {
+                true => sections.push(WordCount::zero()),
+                false => *sections.last_mut().unwrap() += container.wordcount_with(rules),
+            }
+        }
+        sections
+    }
+
+    /**
+    Return where each speaker attributed to lines of the script (in order of first appearance) speaks:
+    the runs of lines in which they speak, each within a single section, and ending whenever another
+    speaker takes over. Lines without a speaker (including stage directions, etc.) don't end a run,
+    and aren't attributed to anyone.
+
+    # Examples
+
+    ```
+    # use lilscript::script::{ContainerKind, Script, TextContainer, TextSpan};
+    let line = |speaker: &str| {
+        TextContainer::new(ContainerKind::Spoken)
+            .push(TextSpan::normal("Hello."))
+            .spoken_by(speaker)
+    };
+
+    let mut script = Script::new("lilellia", "A Very Cool Script");
+    script.paragraphs = vec![
+        line("Alice"),
+        TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal("door")),
+        line("Alice"),
+        line("Bob"),
+        line("Alice"),
+        TextContainer::new(ContainerKind::PlainText).push(TextSpan::normal("--8<--")),
+        line("Alice"),
+    ];
+
+    let appearances = script.appearances();
+    let alice: Vec<String> = appearances[0].1.iter().map(|a| a.to_string()).collect();
+    assert_eq!(appearances[0].0, "Alice");
+    assert_eq!(alice, vec!["section 1, lines 001–003", "section 1, line 005", "section 2, line 007"]);
+    assert_eq!(appearances[1].0, "Bob");
+    assert_eq!(appearances[1].1.len(), 1);
+    ```
+    */
+    pub fn appearances(&self) -> Vec<(String, Vec<Appearance>)> {
+        let mut appearances: Vec<(String, Vec<Appearance>)> = Vec::new();
+        let mut section = 1;
+
+        // the speakers of the last line which had any
+        let mut previous: Vec<&str> = Vec::new();
+
+        for (index, paragraph) in self.paragraphs.iter().enumerate() {
+            if paragraph.is_divider() {
+                section += 1;
+                previous.clear();
+                continue;
+            }
+
+            let mut speakers: Vec<&str> = Vec::new();
+            for container in paragraph.with_overlaps() {
+                match container.speaker.as_deref() {
+                    Some(speaker) if !speakers.contains(&speaker) => speakers.push(speaker),
+                    _ => {}
+                }
+            }
+            if speakers.is_empty() {
+                continue;
+            }
+
+            for speaker in &speakers {
+                let runs = match appearances.iter_mut().find(|(name, _)| name == speaker) {
+                    Some((_, runs)) => runs,
+                    None => {
+                        appearances.push((speaker.to_string(), Vec::new()));
+                        &mut appearances.last_mut().unwrap().1
+                    }
+                };
+
+                match runs.last_mut() {
+                    Some(run) if previous.contains(speaker) => {
+                        run.paragraphs = *run.paragraphs.start()..=index;
+                        run.lines += 1;
+                    }
+                    _ => runs.push(Appearance {
+                        section,
+                        paragraphs: index..=index,
+                        lines: 1,
+                    }),
+                }
+            }
+
+            previous = speakers;
+        }
+
+        appearances
+    }
+
+    /**
+    Return the word count for the entire script, broken down by language.
+
+    # Return
+
+    * `BTreeMap<Option<String>, WordCount>` - the word count for each language tag found on the
+      spans, where `None` collects every span without an explicit language
+
+    # Examples
+
+    ```
+    # use lilscript::script::{ContainerKind, Script, TextContainer, TextSpan, WordCount};
+    let mut script = Script::new("lilellia", "A Very Cool Script");
+    script.paragraphs.push(
+        TextContainer::new(ContainerKind::Spoken)
+            .push(TextSpan::normal("Good morning, or as they say,"))
+            .push(TextSpan::normal("bonjour à tous").with_lang("fr")),
+    );
+
+    let counts = script.wordcount_by_language();
+    assert_eq!(counts[&None], WordCount::new(6, 0));
+    assert_eq!(counts[&Some("fr".to_string())], WordCount::new(3, 0));
+    ```
+    */
+    pub fn wordcount_by_language(&self) -> BTreeMap<Option<String>, WordCount> {
+        struct ByLanguage(BTreeMap<Option<String>, WordCount>);
+
+        impl ScriptVisitor for ByLanguage {
+            fn visit_span(
+                &mut self,
+                span: &TextSpan,
+                container: &TextContainer,
+                _: ContainerContext,
+            ) {
+                let wordcount = span.wordcount(container.kind.clone());
+                *self.0.entry(span.lang.clone()).or_insert(WordCount::zero()) += wordcount;
+            }
+        }
+
+        let mut counts = ByLanguage(BTreeMap::new());
+        counts.visit_script(self);
+        counts.0
+    }
+
+    /**
+    Normalize all of the text of the script in place (see `normalize_text`), so that scripts which differ
+    only invisibly compare equal. Any spans left empty are removed.
+
+    # Examples
+
+    ```
+    # use lilscript::script::{ContainerKind, Script, TextContainer, TextSpan};
+    let mut a = Script::new("lilellia", "Caf\u{e9}");
+    a.paragraphs.push(TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Hello  there.")));
+
+    let mut b = Script::new("lilellia", "Cafe\u{301}");
+    b.paragraphs.push(
+        TextContainer::new(ContainerKind::Spoken)
+            .push(TextSpan::normal(" Hello\nthere. "))
+            .push(TextSpan::inline(" ")),
+    );
+
+    assert_ne!(a, b);
+    a.normalize();
+    b.normalize();
+    assert_eq!(a, b);
+    ```
+    */
+    pub fn normalize(&mut self) {
+        self.author = normalize_text(&self.author);
+        self.title = normalize_text(&self.title);
+        self.summary = normalize_text(&self.summary);
+        self.series.title = self.series.title.as_deref().map(normalize_text);
+
+        for tag in self.tags.iter_mut() {
+            *tag = normalize_text(tag);
+        }
+
+        for character in self.characters.iter_mut() {
+            character.name = normalize_text(&character.name);
+            character.description = normalize_text(&character.description);
+            for field in [
+                &mut character.voice,
+                &mut character.age,
+                &mut character.pronouns,
+            ] {
+                *field = field.as_deref().map(normalize_text);
+            }
+            for tag in character.tags.iter_mut() {
+                *tag = normalize_text(tag);
+            }
+        }
+
+        self.map_containers(|container| {
+            container.speaker = container.speaker.as_deref().map(normalize_text);
+
+            for text in container.commentary.iter_mut() {
+                *text = normalize_text(text);
+            }
+            container.commentary.retain(|text| !text.is_empty());
+
+            for span in container.spans.iter_mut() {
+                span.contents = normalize_text(&span.contents);
+                if let SpanKind::Phonetic(pronunciation) = &mut span.kind {
+                    *pronunciation = normalize_text(pronunciation);
+                }
+            }
+        });
+
+        self.retain_spans(|span| !span.contents.is_empty());
+    }
+
+    /**
+    Bring the script into its canonical form in place: the form which survives being rendered and then
+    parsed again unchanged. That is, for any format which can be both rendered and parsed,
+    `parse(render(script))` is the same as the canonical form of `script`. (Markdown holds no header, and writes
+    phonetic hints as plain text, so for Markdown this only holds of the characters and the rest of the body.)
+
+    Specifically, the script is normalized (see `Script::normalize`), and then:
+
+    * only normal spans keep their language (as that is all TeX can express);
+    * adjacent normal spans without a language are merged into one;
+    * containers left without any spans or cues are removed, as are empty speakers and tags;
+    * a series missing either its title or its part is removed, and the part is written in its canonical form.
+
+    # Examples
+
+    ```
+    # use lilscript::script::{ContainerKind, Script, TextContainer, TextSpan};
+    let mut script = Script::new("lilellia", "A Very Cool Script");
+    script.paragraphs.push(
+        TextContainer::new(ContainerKind::Spoken)
+            .push(TextSpan::normal("Hello "))
+            .push(TextSpan::normal("there."))
+            .push(TextSpan::emphasis("really").with_lang("en")),
+    );
+    script.paragraphs.push(TextContainer::new(ContainerKind::StageDir));
+
+    script.canonicalize();
+    assert_eq!(script.paragraphs.len(), 1);
+    assert_eq!(
+        script.paragraphs[0].spans,
+        vec![TextSpan::normal("Hello there."), TextSpan::emphasis("really")]
+    );
+    ```
+    */
+    pub fn canonicalize(&mut self) {
+        self.normalize();
+
+        self.tags.retain(|tag| !tag.is_empty());
+        self.series = SeriesEntry::parse(&self.series.to_string()).unwrap_or_default();
+
+        self.map_containers(|container| {
+            container.speaker = container.speaker.take().filter(|s| !s.is_empty());
+
+            let mut spans: Vec<TextSpan> = Vec::with_capacity(container.spans.len());
+            for mut span in container.spans.drain(..) {
+                if span.kind != SpanKind::Normal {
+                    span.lang = None;
+                }
+
+                match spans.last_mut() {
+                    Some(last) if is_plain(last) && is_plain(&span) => {
+                        last.contents.push(' ');
+                        last.contents.push_str(&span.contents);
+                    }
+                    _ => spans.push(span),
+                }
+            }
+
+            container.spans = spans;
+        });
+
+        self.retain_containers(|c| !c.spans.is_empty() || !c.overlaps.is_empty());
+    }
+
+    /**
+    Apply the given function to every container of the script in place, including any overlapping cues.
+
+    Cues are visited before the line they overlap, in the same order as `TextContainer::with_overlaps`.
+
+    # Examples
+
+    ```
+    # use lilscript::script::{ContainerKind, Script, TextContainer, TextSpan};
+    let mut script = Script::new("lilellia", "A Very Cool Script");
+    script.paragraphs.push(
+        TextContainer::new(ContainerKind::Spoken)
+            .push(TextSpan::normal("Hello."))
+            .overlapping(TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal("rain"))),
+    );
+
+    script.map_containers(|container| container.speaker = Some("Alice".to_owned()));
+    assert_eq!(script.paragraphs[0].speaker.as_deref(), Some("Alice"));
+    assert_eq!(script.paragraphs[0].overlaps[0].speaker.as_deref(), Some("Alice"));
+    ```
+    */
+    pub fn map_containers(&mut self, mut f: impl FnMut(&mut TextContainer)) {
+        fn map(container: &mut TextContainer, f: &mut impl FnMut(&mut TextContainer)) {
+            for cue in container.overlaps.iter_mut() {
+                map(cue, f);
+            }
+            f(container);
+        }
+
+        for container in self.paragraphs.iter_mut() {
+            map(container, &mut f);
+        }
+    }
+
+    /**
+    Apply the given function to every span of the script in place, including those of overlapping cues.
+
+    # Examples
+
+    ```
+    # use lilscript::script::{ContainerKind, Script, TextContainer, TextSpan};
+    let mut script = Script::new("lilellia", "A Very Cool Script");
+    script.paragraphs.push(TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal("rain")));
+
+    script.map_spans(|span| span.contents = span.contents.to_uppercase());
+    assert_eq!(script.paragraphs[0].plain_text(), "RAIN");
+    ```
+    */
+    pub fn map_spans(&mut self, mut f: impl FnMut(&mut TextSpan)) {
+        self.map_containers(|container| container.spans.iter_mut().for_each(&mut f));
+    }
+
+    /**
+    Keep only the containers of the script for which the given predicate returns `true`.
+
+    Overlapping cues are filtered too (before the line they overlap), and removing a line also removes
+    every cue overlapping it.
+
+    # Examples
+
+    ```
+    # use lilscript::script::{ContainerKind, Script, TextContainer, TextSpan};
+    let mut script = Script::new("lilellia", "A Very Cool Script");
+    script.paragraphs.push(
+        TextContainer::new(ContainerKind::Spoken)
+            .push(TextSpan::normal("Hello."))
+            .overlapping(TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal("rain"))),
+    );
+    script.paragraphs.push(TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal("thunder")));
+
+    script.retain_containers(|container| container.kind != ContainerKind::Sfx);
+    assert_eq!(script.paragraphs.len(), 1);
+    assert!(script.paragraphs[0].overlaps.is_empty());
+    ```
+    */
+    pub fn retain_containers(&mut self, mut f: impl FnMut(&TextContainer) -> bool) {
+        fn retain(containers: &mut Vec<TextContainer>, f: &mut impl FnMut(&TextContainer) -> bool) {
+            containers.retain_mut(|container| {
+                retain(&mut container.overlaps, f);
+                f(container)
+            });
+        }
+
+        retain(&mut self.paragraphs, &mut f);
+    }
+
+    /**
+    Keep only the spans of the script for which the given predicate returns `true`, including those of
+    overlapping cues.
+
+    Containers left without any spans are kept; follow up with `retain_containers` to drop them.
+
+    # Examples
+
+    ```
+    # use lilscript::script::{ContainerKind, Script, SpanKind, TextContainer, TextSpan};
+    let mut script = Script::new("lilellia", "A Very Cool Script");
+    script.paragraphs.push(
+        TextContainer::new(ContainerKind::Spoken)
+            .push(TextSpan::inline("softly"))
+            .push(TextSpan::normal("Hello.")),
+    );
+
+    script.retain_spans(|span| span.kind != SpanKind::InlineDirection);
+    assert_eq!(script.paragraphs[0].plain_text(), "Hello.");
+    ```
+    */
+    pub fn retain_spans(&mut self, mut f: impl FnMut(&TextSpan) -> bool) {
+        self.map_containers(|container| container.spans.retain(&mut f));
+    }
+
+    /**
+    Replace every match of the pattern within the text of the script, limited to the given scope.
+
+    Only the text itself is affected, never the structure around it: a match cannot span two spans,
+    and the kinds of containers and spans are left alone.
+
+    # Arguments
+
+    * `pattern` - the regular expression to search for
+    * `replacement` - the text to replace each match with, which may refer to capture groups (e.g., `$1`)
+    * `scope` - which text to search
+
+    # Return
+
+    * `usize` - the number of replacements made
+
+    # Examples
+
+    ```
+    # use lilscript::script::{ContainerKind, ReplaceScope, Script, TextContainer, TextSpan};
+    # use regex::Regex;
+    let mut script = Script::new("lilellia", "A Very Cool Script");
+    script.paragraphs.push(
+        TextContainer::new(ContainerKind::Spoken)
+            .push(TextSpan::inline("to Mira"))
+            .push(TextSpan::normal("Mira, come here."))
+            .spoken_by("Mira"),
+    );
+
+    let pattern = Regex::new(r"\bMira\b").unwrap();
+    assert_eq!(script.replace(&pattern, "Nell", ReplaceScope::Spoken), 1);
+    assert_eq!(script.paragraphs[0].to_string(), "(to Mira) Nell, come here.");
+
+    assert_eq!(script.replace(&pattern, "Nell", ReplaceScope::All), 2);
+    assert_eq!(script.paragraphs[0].speaker.as_deref(), Some("Nell"));
+    ```
+    */
+    pub fn replace(&mut self, pattern: &Regex, replacement: &str, scope: ReplaceScope) -> usize {
+        let mut count = 0;
+        let mut replace = |text: &mut String| {
+            let matches = pattern.find_iter(text).count();
+            if matches > 0 {
+                *text = pattern.replace_all(text, replacement).into_owned();
+                count += matches;
+            }
+        };
+
+        self.map_containers(|container| {
+            for span in container.spans.iter_mut() {
+                if scope.includes(span, container.kind.clone()) {
+                    replace(&mut span.contents);
+                }
+            }
+
+            if scope == ReplaceScope::All {
+                if let Some(speaker) = container.speaker.as_mut() {
+                    replace(speaker);
+                }
+            }
+        });
+
+        if scope == ReplaceScope::All {
+            for character in self.characters.iter_mut() {
+                replace(&mut character.name);
+                replace(&mut character.description);
+            }
+        }
+
+        count
+    }
+
+    /**
+    Append the body of another script to the end of this one, e.g. to stitch a prologue and outro onto the main script.
+
+    The metadata (title, author, etc.) of this script is kept. The other script's characters and tags are
+    added to this one's, skipping any which it already has.
+
+    # Examples
+
+    ```
+    # use lilscript::script::{Character, ContainerKind, Script, TextContainer, TextSpan};
+    let mut script = Script::new("lilellia", "A Very Cool Script");
+    script.characters.push(Character::new("Speaker", "a kind witch"));
+    script.paragraphs.push(TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Hello.")));
+
+    let mut outro = Script::new("lilellia", "Outro");
+    outro.characters.push(Character::new("Speaker", "a kind witch"));
+    outro.characters.push(Character::new("Listener", "a weary traveller"));
+    outro.paragraphs.push(TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Goodbye.")));
+
+    script.append(outro);
+    assert_eq!(script.title, "A Very Cool Script");
+    assert_eq!(script.characters.len(), 2);
+    assert_eq!(script.paragraphs.len(), 2);
+    ```
+    */
+    pub fn append(&mut self, other: Script) {
+        for character in other.characters {
+            if !self.characters.iter().any(|c| c.name == character.name) {
+                self.characters.push(character);
+            }
+        }
+
+        for tag in other.tags {
+            if !self.tags.contains(&tag) {
+                self.tags.push(tag);
+            }
+        }
+
+        self.paragraphs.extend(other.paragraphs);
+    }
+
+    /**
+    Compute a stable fingerprint of the script's content.
+
+    Only what every format keeps of the body is considered: the kind and speaker of each line (and which
+    cues overlap it), and the words within it, as text, emphasis, or tone cues. Differences in whitespace,
+    in Unicode normalization, in how the text is split into spans, or in how the source file was laid out
+    do not affect the result, nor does anything which some formats leave out (the header, the languages of
+    spans, attributes, and commentary). A pronunciation hint is taken as text following its word, as it is
+    written in Markdown. So the same script re-exported (in any format) or re-uploaded will produce the
+    same fingerprint.
+
+    # Return
+
+    * `String` - a 16-character hexadecimal digest of the normalised content
+
+    # Examples
+
+    ```
+    # use lilscript::script::{ContainerKind, Script, TextContainer, TextSpan};
+    let mut a = Script::new("lilellia", "A Very Cool Script");
+    a.paragraphs.push(TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Hello  there.")));
+
+    let mut b = Script::new("lilellia", "A Very Cool Script");
+    b.paragraphs.push(
+        TextContainer::new(ContainerKind::Spoken)
+            .push(TextSpan::normal(" Hello\n"))
+            .push(TextSpan::normal("there. ").with_lang("en")),
+    );
+
+    assert_eq!(a.fingerprint(), b.fingerprint());
+    assert_eq!(a.fingerprint().len(), 16);
+    ```
+    */
+    pub fn fingerprint(&self) -> String {
+        let mut hasher = Fnv1a::new();
+
+        for paragraph in &self.paragraphs {
+            for container in paragraph.with_overlaps() {
+                let spans: Vec<(&str, String)> = match container.marker_id() {
+                    Some(id) => vec![("text", id)],
+                    None => container
+                        .spans
+                        .iter()
+                        .map(|span| match &span.kind {
+                            SpanKind::Normal => ("text", span.contents.clone()),
+                            SpanKind::Phonetic(pronunciation) => {
+                                ("text", format!("{} ({})", span.contents, pronunciation))
+                            }
+                            SpanKind::Emphasis => ("emphasis", span.contents.clone()),
+                            SpanKind::InlineDirection => ("cue", span.contents.clone()),
+                        })
+                        .map(|(tag, text)| (tag, normalize_text(&text)))
+                        .filter(|(_, text)| !text.is_empty())
+                        .collect(),
+                };
+                if spans.is_empty() {
+                    // blank lines carry no content
+                    continue;
+                }
+
+                if !std::ptr::eq(container, paragraph) {
+                    // distinguish concurrent cues from ones which simply precede the line
+                    hasher.write(b"\x1d");
+                }
+
+                let kind = match container.kind {
+                    ContainerKind::Spoken => "spoken",
+                    ContainerKind::StageDir => "stagedir",
+                    ContainerKind::Sfx => "sfx",
+                    ContainerKind::ListenerDialogue => "listener",
+                    ContainerKind::PlainText => "plaintext",
+                    ContainerKind::Marker => "marker",
+                };
+                hasher.write(kind.as_bytes());
+                if let Some(speaker) = &container.speaker {
+                    hasher.write(b"\x1f");
+                    hasher.write(normalize_text(speaker).as_bytes());
+                }
+
+                // consecutive spans of the same kind are hashed as one, however they were split up
+                let mut previous: Option<&str> = None;
+                for (tag, text) in spans {
+                    if previous != Some(tag) {
+                        hasher.write(b"\x1f");
+                        hasher.write(tag.as_bytes());
+                        previous = Some(tag);
+                    }
+                    hasher.write(b" ");
+                    hasher.write(text.as_bytes());
+                }
+                hasher.write(b"\x1e");
+            }
+        }
+
+        format!("{:016x}", hasher.finish())
+    }
+
+    /**
+    Return a teaser for the script: its summary, along with the first `n_words` spoken words as plain text.
+    Only what is actually spoken is included (so no stage directions, sound effects, inline directions, etc.),
+    with the whitespace collapsed and the lines joined up, and an ellipsis is added if any words were left out.
+
+    # Examples
+
+    ```
+    # use lilscript::script::{ContainerKind, Script, TextContainer, TextSpan};
+    let mut script = Script::new("lilellia", "A Very Cool Script");
+    script.summary = "A witch finds a visitor on her doorstep.".to_owned();
+    script.paragraphs.push(TextContainer::new(ContainerKind::StageDir).push(TextSpan::normal("A door opens.")));
+    script.paragraphs.push(
+        TextContainer::new(ContainerKind::Spoken)
+            .push(TextSpan::normal("Oh! You're "))
+            .push(TextSpan::emphasis("here"))
+            .push(TextSpan::normal("? "))
+            .push(TextSpan::inline("softly"))
+            .push(TextSpan::normal(" Come in."))
+    );
+    script.paragraphs.push(TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Sit  down.")));
+
+    let excerpt = script.excerpt(5);
+    assert_eq!(excerpt.summary, "A witch finds a visitor on her doorstep.");
+    assert_eq!(excerpt.text, "Oh! You're here? Come in.…");
+    assert_eq!(script.excerpt(100).text, "Oh! You're here? Come in. Sit down.");
+    ```
+    */
+    pub fn excerpt(&self, n_words: usize) -> Excerpt {
+        let lines: Vec<String> = self
+            .paragraphs
+            .iter()
+            .filter(|container| container.kind == ContainerKind::Spoken)
+            .map(|container| {
+                // the parser trims each span, so the spans are joined with spaces (except before
+                // punctuation, which stays attached to the word before it)
+                let mut line = String::new();
+                for span in &container.spans {
+                    if !span.is_spoken(ContainerKind::Spoken) {
+                        line.push(' ');
+                        continue;
+                    }
+
+                    let closing = span
+                        .contents
+                        .starts_with(|c: char| ".,;:!?)]}…".contains(c));
+                    if !closing {
+                        line.push(' ');
+                    }
+                    line.push_str(&span.contents);
+                }
+                line
+            })
+            .collect();
+
+        let mut words = lines.iter().flat_map(|line| line.split_whitespace());
+        let text = words
+            .by_ref()
+            .take(n_words)
+            .collect::<Vec<&str>>()
+            .join(" ");
+
+        Excerpt {
+            summary: self.summary.trim().to_owned(),
+            text: match words.next() {
+                Some(_) => format!("{}…", text),
+                None => text,
+            },
+        }
+    }
+}
+
+/// A minimal 64-bit FNV-1a hasher.
+/// Unlike `std::collections::hash_map::DefaultHasher`, its output is guaranteed not to change
+/// between Rust releases, which matters for fingerprints that get stored.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    fn new() -> Self {
+        Self(Self::OFFSET_BASIS)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// The ways in which reading a script can fail.
+#[derive(Debug)]
+pub enum ScriptError {
+    /// The file could not be read or written
+    Io(io::Error),
+
+    /// The file is not valid text (UTF-8, or UTF-16 with a byte-order mark)
+    Encoding(String),
+
+    /// The format of the script could be determined from neither its extension nor its contents
+    UnknownFormat,
+
+    /// The script is in a format which cannot (yet) be read
+    Unsupported(FileFormat),
+
+    /// The script is in a known format, but could not be parsed
+    Parse(String),
+
+    /// The script could not be rendered in the requested format
+    Render(String),
+}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "I/O error: {}", e),
+            Self::Encoding(e) => write!(f, "Could not decode the script: {}", e),
+            Self::UnknownFormat => write!(
+                f,
+                "Could not determine the format of the script from its extension or its contents"
+            ),
+            Self::Unsupported(format) => {
+                write!(f, "Reading {:?} scripts is not currently supported", format)
+            }
+            Self::Parse(e) | Self::Render(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for ScriptError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ScriptError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/**
+Decode the bytes of a text file: UTF-8 (with or without a byte-order mark), or UTF-16 with a byte-order mark.
+
+# Examples
+
+```
+# use lilscript::script::decode_text;
+assert_eq!(decode_text(b"\xEF\xBB\xBFHello").unwrap(), "Hello");
+assert_eq!(decode_text(b"\xFF\xFEH\0i\0").unwrap(), "Hi");
+assert!(decode_text(b"\xC3\x28").is_err());
+```
+*/
+pub fn decode_text(bytes: &[u8]) -> Result<String, ScriptError> {
+    /// Decode UTF-16, given how to read each (two-byte) code unit.
+    fn utf16(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> Result<String, ScriptError> {
+        if !bytes.len().is_multiple_of(2) {
+            return Err(ScriptError::Encoding(String::from(
+                "UTF-16 text has an odd number of bytes",
+            )));
+        }
+
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| unit([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16(&units).map_err(|e| ScriptError::Encoding(e.to_string()))
+    }
+
+    match bytes {
+        [0xEF, 0xBB, 0xBF, rest @ ..] => {
+            String::from_utf8(rest.to_vec()).map_err(|e| ScriptError::Encoding(e.to_string()))
+        }
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+        _ => String::from_utf8(bytes.to_vec()).map_err(|e| ScriptError::Encoding(e.to_string())),
+    }
+}
+
+/**
+Write the contents to the given path atomically: they're written to a temporary file in the same
+directory, which is then renamed over the destination.
+
+# Arguments
+
+* `path` - the file to (over)write
+* `contents` - the new contents of the file
+*/
+pub fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let file_name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{:?} is not a file path", path),
+        )
+    })?;
+    let temp = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let write = || -> io::Result<()> {
+        let mut file = fs::File::create(&temp)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp, path)
+    };
+
+    write().inspect_err(|_| {
+        // don't leave the partial file behind
+        let _ = fs::remove_file(&temp);
+    })
+}
+
+impl FromStr for Script {
+    type Err = ScriptError;
+
+    /**
+    Parse a script, detecting its format (see `FileFormat::sniff`) from its contents.
+
+    # Examples
+
+    ```
+    # use lilscript::script::Script;
+    let tex = "\\renewcommand{\\SceneName}{Title}\n\\scriptAuthor{lilellia}\n\\scriptSeries{}\n\
+        \\scriptTags{[F4A]}\n\\scriptDate{}\n\\summary{Summary.}\n\\clearpage\n\\spoken{Hello.}";
+    let script: Script = tex.parse().unwrap();
+    assert_eq!(script.title, "Title");
+
+    assert!("name,age\nlilellia,7".parse::<Script>().is_err());
+    ```
+    */
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let format = FileFormat::sniff(s).ok_or(ScriptError::UnknownFormat)?;
+        Self::parse_as(s, format, &ParseOptions::default())
+    }
+}
+
+impl TryFrom<&Path> for Script {
+    type Error = ScriptError;
+
+    /// Read and parse the script at the given path; see `Script::load`.
+    fn try_from(value: &Path) -> Result<Self, Self::Error> {
+        Self::load(value)
+    }
+}
+
+impl Script {
+    /**
+    Load (read and parse) the script at the given path, with the default `ParseOptions`:
+    the counterpart of `Script::save`.
+
+    The format is determined from the extension where possible, and from the contents otherwise.
+    The file may be UTF-8 (with or without a byte-order mark), or UTF-16 with a byte-order mark.
+
+    # Return
+
+    * `Ok(Script)` if the script could be read and parsed;
+    * `Err(ScriptError)` describing which of those steps failed otherwise
+    */
+    pub fn load(path: &Path) -> Result<Self, ScriptError> {
+        Self::load_with(path, &ParseOptions::default())
+    }
+
+    /// Load the script at the given path, with the given `ParseOptions`; see `Script::load`.
+    pub fn load_with(path: &Path, options: &ParseOptions) -> Result<Self, ScriptError> {
+        let contents = decode_text(&fs::read(path)?)?;
+        let format = FileFormat::detect(path, &contents).ok_or(ScriptError::UnknownFormat)?;
+
+        Self::parse_as(&contents, format, options)
+    }
+
+    /**
+    Render the script in the given format and save it to the given path.
+
+    The file is written atomically: the script is written to a temporary file alongside it,
+    which then replaces it, so that an interrupted save never leaves a half-written file behind.
+
+    # Arguments
+
+    * `path` - where to save the script
+    * `format` - the format to save it in
+    * `options` - how to render it (see `render`)
+    */
+    pub fn save(
+        &self,
+        path: &Path,
+        format: FileFormat,
+        options: &RenderOptions,
+    ) -> Result<(), ScriptError> {
+        let contents = render(self, format, options).map_err(ScriptError::Render)?;
+        write_atomically(path, &contents)?;
+        Ok(())
+    }
+
+    /**
+    Parse the script from a string in the given format, by lowering its `Document` (see `Document::parse`).
+
+    # Examples
+
+    ```
+    # use lilscript::{script::{ParseOptions, Script, ScriptError}, FileFormat};
+    let result = Script::parse_as("<speak></speak>", FileFormat::Ssml, &ParseOptions::default());
+    assert!(matches!(result, Err(ScriptError::Unsupported(FileFormat::Ssml))));
+    ```
+    */
+    pub fn parse_as(
+        contents: &str,
+        format: FileFormat,
+        options: &ParseOptions,
+    ) -> Result<Self, ScriptError> {
+        Document::parse(contents, format, options).map(Script::from)
+    }
+}
+
+impl Display for Script {
+    /**
+    Write a readable plain-text rendering of the script: its metadata, and then its body with each line
+    labelled (by speaker, where known) in an aligned column.
+
+    If a width is given (e.g., `format!("{:80}", script)`), long lines are wrapped to fit within it.
+
+    # Examples
+
+    ```
+    # use lilscript::script::{ContainerKind, Script, TextContainer, TextSpan};
+    let mut script = Script::new("lilellia", "A Very Cool Script");
+    script.paragraphs.push(TextContainer::new(ContainerKind::StageDir).push(TextSpan::normal("A door opens.")));
+    script.paragraphs.push(
+        TextContainer::new(ContainerKind::Spoken)
+            .push(TextSpan::inline("softly"))
+            .push(TextSpan::normal("Oh! You're here."))
+            .spoken_by("Witch"),
+    );
+
+    let preview = script.to_string();
+    assert!(preview.starts_with("A Very Cool Script\nby lilellia\n"));
+    assert!(preview.ends_with("Stage direction  A door opens.\nWitch            (softly) Oh! You're here.\n"));
+    ```
+    */
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = f.width();
+
+        writeln!(f, "{}", self.title)?;
+        writeln!(f, "by {}", self.author)?;
+        writeln!(f, "{}", "=".repeat(self.title.chars().count().max(3)))?;
+        writeln!(f)?;
+
+        let tags = self
+            .tags
+            .iter()
+            .map(|tag| format!("[{}]", tag))
+            .collect::<Vec<String>>()
+            .join(" ");
+        let date = self
+            .date
+            .map(|date| date.format("%-d %B %Y").to_string())
+            .unwrap_or_else(|| String::from("—"));
+
+        let mut metadata: Vec<(&str, String)> = vec![
+            ("Series", self.series.to_string()),
+            ("Tags", tags),
+            ("Date", date),
+        ];
+        if let Some(target) = self.target {
+            metadata.push((
+                "Target",
+                format!("{} spoken words", target.to_formatted_string(&Locale::en)),
+            ));
+        }
+        metadata.push(("Words", self.wordcount().to_string()));
+        write_aligned(f, &metadata, width)?;
+
+        if !self.summary.is_empty() {
+            writeln!(f)?;
+            write_aligned(f, &[("Summary", self.summary.clone())], width)?;
+        }
+
+        if !self.characters.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "Characters")?;
+            let characters: Vec<(&str, String)> = self
+                .characters
+                .iter()
+                .map(|c| match c.details().as_str() {
+                    "" => (c.name.as_str(), c.description.clone()),
+                    details => (c.name.as_str(), format!("{} ({})", c.description, details)),
+                })
+                .collect();
+            write_aligned(f, &characters, width)?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "{}", "-".repeat(width.unwrap_or(40).min(40)))?;
+        writeln!(f)?;
+
+        // any concurrent cues come just before the line, noting that they run underneath it
+        let mut lines: Vec<(String, String)> = Vec::new();
+        for container in &self.paragraphs {
+            for cue in &container.overlaps {
+                lines.push((
+                    line_label(cue),
+                    format!("{} (over the following line)", cue),
+                ));
+            }
+            lines.push((line_label(container), container.to_string()));
+        }
+
+        let lines: Vec<(&str, String)> = lines
+            .iter()
+            .map(|(label, text)| (label.as_str(), text.clone()))
+            .collect();
+        write_aligned(f, &lines, width)
+    }
+}
+
+/// The label for a line of the script when previewing it: the speaker if known, or the kind of line.
+fn line_label(container: &TextContainer) -> String {
+    match &container.speaker {
+        Some(speaker) => speaker.clone(),
+        None => container.kind.to_string(),
+    }
+}
+
+/// Write `label  text` rows with the text in an aligned column, wrapping it to the width (if any).
+fn write_aligned(
+    f: &mut fmt::Formatter<'_>,
+    rows: &[(&str, String)],
+    width: Option<usize>,
+) -> fmt::Result {
+    let column = rows
+        .iter()
+        .map(|(label, _)| label.chars().count())
+        .max()
+        .unwrap_or(0)
+        + 2;
+
+    // keep a sensible amount of room for the text, however long the labels are
+    let text_width = width.map(|w| w.saturating_sub(column).max(20));
+
+    for (label, text) in rows {
+        let mut lines = wrap(text, text_width).into_iter();
+        writeln!(
+            f,
+            "{:column$}{}",
+            label,
+            lines.next().unwrap_or_default(),
+            column = column
+        )?;
+        for line in lines {
+            writeln!(f, "{:column$}{}", "", line, column = column)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Wrap the text into lines of at most the given width (where possible), breaking between words.
+fn wrap(text: &str, width: Option<usize>) -> Vec<String> {
+    let width = match width {
+        Some(width) => width,
+        None => return vec![text.to_owned()],
+    };
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    lines.push(line);
+
+    lines
+}
 
 #[cfg(test)]
 mod test {
-    // use super::*;
+    use super::*;
+    use crate::tex_handler::Tex;
+
+    fn script_with(containers: Vec<TextContainer>) -> Script {
+        let mut script = Script::new("lilellia", "A Very Cool Script");
+        script.paragraphs = containers;
+        script
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_whitespace() {
+        let a = script_with(vec![TextContainer::new(ContainerKind::Spoken)
+            .push(TextSpan::normal("Some   text"))
+            .push(TextSpan::inline("a cue"))]);
+        let b = script_with(vec![
+            TextContainer::new(ContainerKind::Spoken)
+                .push(TextSpan::normal("Some\ntext "))
+                .push(TextSpan::inline(" a  cue")),
+            TextContainer::new(ContainerKind::PlainText).push(TextSpan::normal("  ")),
+        ]);
+
+        assert_eq!(a.fingerprint(), b.fingerprint());
+    }
+
+    #[test]
+    fn test_fingerprint_is_the_same_in_every_format() {
+        let script = script_with(vec![
+            TextContainer::new(ContainerKind::StageDir).push(TextSpan::normal("A door opens.")),
+            TextContainer::new(ContainerKind::Spoken)
+                .push(TextSpan::inline("softly"))
+                .push(TextSpan::normal("Welcome home,"))
+                .push(TextSpan::phonetic("Siobhan", "shi-VAWN"))
+                .push(TextSpan::normal("and"))
+                .push(TextSpan::normal("bienvenue").with_lang("fr"))
+                .push(TextSpan::emphasis("really"))
+                .overlapping(TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal("rain")))
+                .with_commentary("The line the whole series was built around.")
+                .spoken_by("Witch"),
+            TextContainer::marker("take 2"),
+            TextContainer::new(ContainerKind::ListenerDialogue).push(TextSpan::normal("Hi.")),
+        ]);
+
+        for format in [
+            FileFormat::Tex,
+            FileFormat::Markdown,
+            FileFormat::Json,
+            FileFormat::Yaml,
+        ] {
+            let contents = render(&script, format, &RenderOptions::default()).unwrap();
+            let parsed = Script::parse_as(&contents, format, &ParseOptions::default()).unwrap();
+            assert_eq!(parsed.fingerprint(), script.fingerprint(), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_unicode_normalization() {
+        let a = script_with(vec![
+            TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Un caf\u{e9}"))
+        ]);
+        let b = script_with(vec![
+            TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Un cafe\u{301}"))
+        ]);
+
+        assert_eq!(a.fingerprint(), b.fingerprint());
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_metadata() {
+        let a = script_with(vec![
+            TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Some text"))
+        ]);
+        let mut b = script_with(vec![
+            TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Some text"))
+        ]);
+        b.title = String::from("A Re-uploaded Script");
+        b.tags = vec![String::from("F4A")];
+
+        assert_eq!(a.fingerprint(), b.fingerprint());
+    }
+
+    #[test]
+    fn test_wordcount_includes_overlaps() {
+        let container = TextContainer::new(ContainerKind::Spoken)
+            .push(TextSpan::normal("Listen to that."))
+            .overlapping(
+                TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal("heavy rain")),
+            );
+
+        assert_eq!(container.wordcount(), WordCount::new(3, 2));
+    }
+
+    #[test]
+    fn test_fingerprint_distinguishes_overlaps() {
+        let rain = TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal("rain"));
+        let line = TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Listen."));
+
+        let sequential = script_with(vec![rain.clone(), line.clone()]);
+        let concurrent = script_with(vec![line.overlapping(rain)]);
+
+        assert_ne!(sequential.fingerprint(), concurrent.fingerprint());
+    }
+
+    #[test]
+    fn test_fingerprint_differs_on_content() {
+        let a = script_with(vec![
+            TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Some text"))
+        ]);
+        let b = script_with(vec![
+            TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Other text"))
+        ]);
+        let c = script_with(vec![
+            TextContainer::new(ContainerKind::StageDir).push(TextSpan::normal("Some text"))
+        ]);
+
+        assert_ne!(a.fingerprint(), b.fingerprint());
+        assert_ne!(a.fingerprint(), c.fingerprint());
+    }
+
+    #[test]
+    fn test_excerpt_leaves_out_unspoken_text() {
+        let mut script = script_with(vec![
+            TextContainer::new(ContainerKind::Spoken)
+                .push(TextSpan::normal("Listen to that rain."))
+                .overlapping(TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal("rain"))),
+            TextContainer::marker("take 2"),
+            TextContainer::new(ContainerKind::Spoken)
+                .push(TextSpan::normal("Lovely."))
+                .spoken_by("Witch"),
+        ]);
+
+        let excerpt = script.excerpt(10);
+        assert_eq!(excerpt.text, "Listen to that rain. Lovely.");
+        assert_eq!(excerpt.to_string(), "Listen to that rain. Lovely.");
+
+        script.summary = "  A rainy evening. ".to_owned();
+        assert_eq!(
+            script.excerpt(2).to_string(),
+            "A rainy evening.\n\nListen to…"
+        );
+        assert_eq!(script.excerpt(0).to_string(), "A rainy evening.\n\n…");
+    }
+
+    #[test]
+    fn test_excerpt_spaces_parsed_spans() {
+        let line = Tex::from(r"\spoken{Listen to \ul{that} rain. \direct{softly} Come \ul{in}!}")
+            .parse_container(&ParseOptions::default())
+            .unwrap();
+        let script = script_with(vec![line]);
+
+        assert_eq!(script.excerpt(10).text, "Listen to that rain. Come in!");
+    }
+
+    #[test]
+    fn test_appearances_include_overlaps() {
+        let line = |speaker: &str| {
+            TextContainer::new(ContainerKind::Spoken)
+                .push(TextSpan::normal("Hello."))
+                .spoken_by(speaker)
+        };
+        let script = script_with(vec![
+            line("Alice"),
+            line("Alice").overlapping(line("Bob")),
+            line("Bob"),
+            line("Alice"),
+        ]);
+
+        let appearances = script.appearances();
+        let runs = |index: usize| -> Vec<(RangeInclusive<usize>, usize)> {
+            appearances[index]
+                .1
+                .iter()
+                .map(|a| (a.paragraphs.clone(), a.lines))
+                .collect()
+        };
+
+        assert_eq!(appearances[0].0, "Alice");
+        assert_eq!(runs(0), vec![(0..=1, 2), (3..=3, 1)]);
+        assert_eq!(appearances[1].0, "Bob");
+        assert_eq!(runs(1), vec![(1..=2, 2)]);
+    }
+
+    #[test]
+    fn test_series_entry_round_trip() {
+        for series in [
+            "Nightfall (Part 3)",
+            "Nightfall (Part XIV)",
+            "Nightfall (Prologue)",
+            "Nightfall (Finale)",
+        ] {
+            assert_eq!(SeriesEntry::from(series).to_string(), series);
+        }
+    }
+
+    #[test]
+    fn test_part_index_named_not_roman() {
+        // a named installment which happens to start with "Part" is still named
+        assert_eq!(
+            PartIndex::parse("Part the Second"),
+            PartIndex::Named("Part the Second".to_owned())
+        );
+        assert_eq!(
+            PartIndex::parse("Part IIII"),
+            PartIndex::Named("Part IIII".to_owned())
+        );
+        assert_eq!(PartIndex::Roman(4).number(), Some(4));
+    }
+
+    #[test]
+    fn test_display_wraps_to_width() {
+        let script = script_with(vec![TextContainer::new(ContainerKind::Spoken)
+            .push(TextSpan::normal(
+                "one two three four five six seven eight nine ten",
+            ))
+            .spoken_by("Witch")]);
+
+        // the label column is 7 wide, leaving 33 for the text
+        let preview = format!("{:40}", script);
+        let body: Vec<&str> = preview.lines().rev().take(2).collect();
+        assert_eq!(
+            body,
+            vec![
+                "       eight nine ten",
+                "Witch  one two three four five six seven"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_sniffs_the_format() {
+        let dir = std::env::temp_dir().join(format!("lilscript-from-path-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        // no useful extension, so the format is sniffed from the contents
+        let path = dir.join("script.txt");
+        fs::write(
+            &path,
+            "\\documentclass{article}\n\\renewcommand{\\SceneName}{Title}\n\\scriptAuthor{lilellia}\n\
+            \\scriptSeries{}\n\\scriptTags{}\n\\scriptDate{}\n\\summary{Summary.}\n\\clearpage\n\\spoken{Hello.}",
+        )
+        .unwrap();
+        let script = Script::try_from(path.as_path()).unwrap();
+        assert_eq!(script.paragraphs.len(), 1);
+
+        let missing = Script::load(&dir.join("missing.tex"));
+        assert!(matches!(missing, Err(ScriptError::Io(_))));
+
+        let path = dir.join("notes.txt");
+        fs::write(&path, "just some notes").unwrap();
+        assert!(matches!(
+            Script::load(&path),
+            Err(ScriptError::UnknownFormat)
+        ));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_and_save() {
+        let dir = std::env::temp_dir().join(format!("lilscript-load-save-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        // a byte-order mark shouldn't stop the format from being recognised
+        let path = dir.join("script.tex");
+        let tex = "\u{feff}\\renewcommand{\\SceneName}{Title}\n\\scriptAuthor{lilellia}\n\\scriptSeries{}\n\
+            \\scriptTags{}\n\\scriptDate{}\n\\summary{Summary.}\n\\clearpage\n\\spoken{Hello.}";
+        fs::write(&path, tex).unwrap();
+        let script = Script::load(&path).unwrap();
+        assert_eq!(script.title, "Title");
+
+        let out = dir.join("script.md");
+        script
+            .save(&out, FileFormat::Markdown, &RenderOptions::default())
+            .unwrap();
+        assert!(fs::read_to_string(&out).unwrap().ends_with("**Hello.**"));
+
+        // only the saved file is left behind
+        let files: Vec<_> = fs::read_dir(&dir).unwrap().collect();
+        assert_eq!(files.len(), 2);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_markdown_round_trip() {
+        use crate::md_handler::ToMarkdown;
+
+        let mut witch = Character::new("Witch", "a kind witch");
+        witch.set("pronouns", "she/her").unwrap();
+        witch.set("tags", "lead, comfort").unwrap();
+
+        let mut script = script_with(vec![
+            TextContainer::new(ContainerKind::StageDir)
+                .push(TextSpan::normal("A door"))
+                .push(TextSpan::emphasis("slams"))
+                .push(TextSpan::normal("open."))
+                .push(TextSpan::inline("loudly")),
+            TextContainer::new(ContainerKind::Spoken)
+                .push(TextSpan::inline("softly"))
+                .push(TextSpan::normal("Oh! You're here."))
+                .push(TextSpan::emphasis("Finally."))
+                .overlapping(TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal("rain")))
+                .with_commentary("The line the whole series was built around."),
+            TextContainer::new(ContainerKind::Spoken)
+                .push(TextSpan::normal("And you brought tea."))
+                .spoken_by("Witch"),
+            TextContainer::new(ContainerKind::PlainText).push(TextSpan::normal(SECTION_DIVIDER)),
+            TextContainer::new(ContainerKind::ListenerDialogue)
+                .push(TextSpan::normal("Hi, it's me.")),
+            TextContainer::marker("take-2"),
+            TextContainer::new(ContainerKind::PlainText)
+                .push(TextSpan::normal("Thanks for reading and/or listening!")),
+        ]);
+        script.characters.push(witch);
+
+        let parsed = Script::parse_as(
+            &script.to_markdown(),
+            FileFormat::Markdown,
+            &ParseOptions::default(),
+        )
+        .unwrap();
+
+        script.canonicalize();
+        assert_eq!(parsed.characters, script.characters);
+        assert_eq!(parsed.paragraphs, script.paragraphs);
+    }
+
+    #[test]
+    fn test_markdown_round_trip_is_canonical() {
+        use crate::md_handler::ToMarkdown;
+
+        let source = r"\renewcommand{\SceneName}{Rock \& Roll}
+\scriptAuthor{lilellia}
+\scriptSeries{Moonrise (Part IV)}
+\scriptTags{[F4A] [comfort]}
+\scriptDate{03 Mar 2024}
+\summary{A short script about rain.}
+\begin{document}
+\character{Witch}{a kind witch}
+\clearpage
+\stagedir{The door  creaks \ul{open}.}
+
+\spoken{Oh! You're here\textellipsis{} \direct{softly} I wasn't expecting you. \ul{Really}.}
+
+\overlap{\sfx{rain on the window}}{\spoken[Witch]{Listen to that rain.}}
+
+\listener{Hi, it's \ul{me}.}
+\end{document}";
+
+        let mut script =
+            Script::parse_as(source, FileFormat::Tex, &ParseOptions::default()).unwrap();
+        script.canonicalize();
+
+        let parsed = Script::parse_as(
+            &script.to_markdown(),
+            FileFormat::Markdown,
+            &ParseOptions::default(),
+        )
+        .unwrap();
+
+        // Markdown has no header, so only the characters and the body are kept
+        assert_eq!(parsed.characters, script.characters);
+        assert_eq!(parsed.paragraphs, script.paragraphs);
+    }
+
+    #[test]
+    fn test_json_and_yaml_round_trip() {
+        let mut witch = Character::new("Witch", "a kind witch");
+        witch.set("pronouns", "she/her").unwrap();
+
+        let mut script = script_with(vec![
+            TextContainer::new(ContainerKind::Spoken)
+                .push(TextSpan::phonetic("Siobhan", "shi-VAWN"))
+                .push(TextSpan::normal("Bonjour").with_lang("fr"))
+                .overlapping(
+                    TextContainer::new(ContainerKind::Sfx)
+                        .push(TextSpan::normal("rain"))
+                        .with_attribute("intensity", "soft"),
+                )
+                .with_commentary("Written on a rainy day.")
+                .spoken_by("Witch"),
+            TextContainer::marker("take-2"),
+        ]);
+        script.series = "Moonrise (Part IV)".into();
+        script.tags = vec!["F4A".into()];
+        script.date = NaiveDate::from_ymd_opt(2024, 3, 3);
+        script.target = Some(3500);
+        script.characters.push(witch);
+
+        for format in [FileFormat::Json, FileFormat::Yaml] {
+            let contents = render(&script, format, &RenderOptions::default()).unwrap();
+            let parsed = Script::parse_as(&contents, format, &ParseOptions::default()).unwrap();
+            assert_eq!(parsed, script);
+            assert_eq!(FileFormat::sniff(&contents), Some(format));
+        }
+    }
+
+    #[test]
+    fn test_markdown_output_is_sniffed_as_markdown() {
+        use crate::{format::OutputProfile, md_handler::MarkdownOptions};
+
+        let script = script_with(vec![
+            TextContainer::new(ContainerKind::StageDir).push(TextSpan::normal("A door opens.")),
+            TextContainer::new(ContainerKind::Spoken)
+                .push(TextSpan::inline("softly"))
+                .push(TextSpan::normal("Hello.")),
+            TextContainer::new(ContainerKind::ListenerDialogue).push(TextSpan::normal("Hi.")),
+        ]);
+
+        for profile in [
+            OutputProfile::Standard,
+            OutputProfile::Outline,
+            OutputProfile::Checklist,
+        ] {
+            for (characters, formatting_guide, number_lines) in
+                (0..8).map(|i: u8| (i & 1 != 0, i & 2 != 0, i & 4 != 0))
+            {
+                let mut markdown = MarkdownOptions {
+                    number_lines,
+                    ..Default::default()
+                };
+                markdown.style.characters = characters;
+                markdown.style.formatting_guide = formatting_guide;
+                let options = RenderOptions {
+                    profile,
+                    markdown,
+                    ..Default::default()
+                };
+
+                let contents = render(&script, FileFormat::Markdown, &options).unwrap();
+                assert_eq!(
+                    FileFormat::sniff(&contents),
+                    Some(FileFormat::Markdown),
+                    "{:?} {:?}",
+                    profile,
+                    options.markdown
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_render_strips_commentary_from_overlaps() {
+        use crate::format::{render, RenderOptions};
+
+        let mut cue = TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal("rain"));
+        cue.commentary.push("recorded in the garden".to_owned());
+        let mut line = TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Listen."));
+        line.overlaps.push(cue);
+        let script = script_with(vec![line]);
+
+        for format in [FileFormat::Markdown, FileFormat::Html, FileFormat::Ssml] {
+            let rendered = render(&script, format, &RenderOptions::default()).unwrap();
+            assert!(
+                !rendered.contains("recorded in the garden"),
+                "{:?} output kept the commentary",
+                format
+            );
+        }
+
+        let options = RenderOptions {
+            commentary: true,
+            ..Default::default()
+        };
+        let html = render(&script, FileFormat::Html, &options).unwrap();
+        assert!(html.contains(r#"<aside class="commentary">recorded in the garden</aside>"#));
+    }
+
+    #[test]
+    fn test_render_only_converts_quotes_where_asked() {
+        use crate::format::{render, RenderOptions};
+
+        let script =
+            script_with(vec![TextContainer::new(ContainerKind::Spoken)
+                .push(TextSpan::normal(r#"She said "hi"."#))]);
+        let options = RenderOptions {
+            quotes: Some(QuoteStyle::Curly),
+            ..Default::default()
+        };
+
+        for format in [FileFormat::Markdown, FileFormat::Html, FileFormat::Ssml] {
+            let rendered = render(&script, format, &options).unwrap();
+            assert!(
+                rendered.contains("“hi”"),
+                "{:?} output kept straight quotes",
+                format
+            );
+        }
+        for format in [
+            FileFormat::Json,
+            FileFormat::Yaml,
+            FileFormat::Srt,
+            FileFormat::Vtt,
+        ] {
+            let rendered = render(&script, format, &options).unwrap();
+            assert!(
+                !rendered.contains("“hi”"),
+                "{:?} output had its quotes converted",
+                format
+            );
+        }
+    }
 }
@@ -0,0 +1,166 @@
+use crate::script::{ContainerKind, Script, SpanKind, TextContainer, TextSpan};
+
+pub trait ToSsml {
+    /// Convert the object to SSML (Speech Synthesis Markup Language), for use with TTS engines.
+    fn to_ssml(&self) -> String;
+}
+
+impl ToSsml for TextSpan {
+    /// Convert the TextSpan to SSML.
+    ///
+    /// Inline directions are not read aloud, so they produce no output.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use lilscript::{script::TextSpan, ssml_handler::ToSsml};
+    /// let span = TextSpan::normal("Fish & chips");
+    /// assert_eq!(span.to_ssml(), "Fish &amp; chips");
+    /// ```
+    /// ```
+    /// # use lilscript::{script::TextSpan, ssml_handler::ToSsml};
+    /// let span = TextSpan::emphasis("really");
+    /// assert_eq!(span.to_ssml(), "<emphasis>really</emphasis>");
+    /// ```
+    /// ```
+    /// # use lilscript::{script::TextSpan, ssml_handler::ToSsml};
+    /// let span = TextSpan::inline("softly");
+    /// assert_eq!(span.to_ssml(), "");
+    /// ```
+    /// ```
+    /// # use lilscript::{script::TextSpan, ssml_handler::ToSsml};
+    /// let span = TextSpan::phonetic("Siobhan", "ʃɪˈvɔːn");
+    /// assert_eq!(span.to_ssml(), r#"<phoneme alphabet="ipa" ph="ʃɪˈvɔːn">Siobhan</phoneme>"#);
+    /// ```
+    /// ```
+    /// # use lilscript::{script::TextSpan, ssml_handler::ToSsml};
+    /// let span = TextSpan::normal("Bonjour").with_lang("fr");
+    /// assert_eq!(span.to_ssml(), r#"<lang xml:lang="fr">Bonjour</lang>"#);
+    /// ```
+    fn to_ssml(&self) -> String {
+        let s = escape_xml(&self.contents);
+        let ssml = match &self.kind {
+            SpanKind::Normal => s,
+            SpanKind::Emphasis => format!("<emphasis>{}</emphasis>", s),
+            SpanKind::InlineDirection => String::new(),
+            SpanKind::Phonetic(pronunciation) => {
+                format!(
+                    r#"<phoneme alphabet="ipa" ph="{}">{}</phoneme>"#,
+                    escape_xml_attribute(pronunciation),
+                    s
+                )
+            }
+        };
+
+        match &self.lang {
+            Some(lang) if !ssml.is_empty() => {
+                format!(
+                    r#"<lang xml:lang="{}">{}</lang>"#,
+                    escape_xml_attribute(lang),
+                    ssml
+                )
+            }
+            _ => ssml,
+        }
+    }
+}
+
+impl ToSsml for TextContainer {
+    /// Convert the TextContainer to SSML.
+    ///
+    /// Only spoken containers produce any output, as a single paragraph, except for markers,
+    /// which are kept as `<mark>`s so that the engine can report when it reaches them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use lilscript::{script::{ContainerKind, TextSpan, TextContainer}, ssml_handler::ToSsml};
+    /// let container = TextContainer::new(ContainerKind::Spoken)
+    ///     .push(TextSpan::inline("quietly"))
+    ///     .push(TextSpan::normal("Say"))
+    ///     .push(TextSpan::phonetic("Siobhan", "ʃɪˈvɔːn"))
+    ///     .push(TextSpan::normal("again?"));
+    /// let expected = r#"<p>Say <phoneme alphabet="ipa" ph="ʃɪˈvɔːn">Siobhan</phoneme> again?</p>"#;
+    /// assert_eq!(container.to_ssml(), expected);
+    /// ```
+    ///
+    /// ```
+    /// # use lilscript::{script::{ContainerKind, TextSpan, TextContainer}, ssml_handler::ToSsml};
+    /// let container = TextContainer::new(ContainerKind::StageDir)
+    ///     .push(TextSpan::normal("The door creaks."));
+    /// assert_eq!(container.to_ssml(), "");
+    /// ```
+    ///
+    /// ```
+    /// # use lilscript::{script::TextContainer, ssml_handler::ToSsml};
+    /// assert_eq!(TextContainer::marker("take 2").to_ssml(), r#"<mark name="take-2"/>"#);
+    /// ```
+    fn to_ssml(&self) -> String {
+        if let Some(id) = self.marker_id() {
+            return format!(r#"<mark name="{}"/>"#, escape_xml_attribute(&id));
+        }
+
+        if self.kind != ContainerKind::Spoken {
+            return String::new();
+        }
+
+        let text = self
+            .spans
+            .iter()
+            .map(|span| span.to_ssml())
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        if text.is_empty() {
+            String::new()
+        } else {
+            format!("<p>{}</p>", text)
+        }
+    }
+}
+
+impl ToSsml for Script {
+    fn to_ssml(&self) -> String {
+        let mut lines: Vec<String> = vec![
+            String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#),
+            String::from(
+                r#"<speak version="1.1" xmlns="http://www.w3.org/2001/10/synthesis" xml:lang="en">"#,
+            ),
+        ];
+
+        for container in &self.paragraphs {
+            let paragraph = container.to_ssml();
+            if !paragraph.is_empty() {
+                lines.push(format!("  {}", paragraph));
+            }
+        }
+
+        lines.push(String::from("</speak>"));
+        lines.join("\n")
+    }
+}
+
+/// Escape the characters which have special meaning in XML text.
+///
+/// # Examples
+/// ```
+/// # use lilscript::ssml_handler::escape_xml;
+/// assert_eq!(escape_xml(r#"<"this" & 'that'>"#), r#"&lt;"this" &amp; 'that'&gt;"#);
+/// ```
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Escape the characters which have special meaning in a (double-quoted) XML attribute.
+///
+/// # Examples
+/// ```
+/// # use lilscript::ssml_handler::escape_xml_attribute;
+/// assert_eq!(escape_xml_attribute(r#"<"this" & 'that'>"#), "&lt;&quot;this&quot; &amp; 'that'&gt;");
+/// ```
+pub fn escape_xml_attribute(text: &str) -> String {
+    escape_xml(text).replace('"', "&quot;")
+}
@@ -0,0 +1,552 @@
+use crate::{
+    md_handler::line_id,
+    script::{Appearance, ContainerKind, CountingRules, Script, WordCount},
+};
+use num_format::{Locale, ToFormattedString};
+use serde_json::{json, Map, Value};
+use std::{fmt, io, path::Path};
+
+/// The typical speaking rate of a narrated script, used to estimate how long it runs for.
+pub const WORDS_PER_MINUTE: f64 = 150.;
+
+/// The columns of the report written by `write_csv`.
+pub const CSV_HEADER: [&str; 11] = [
+    "file",
+    "title",
+    "series",
+    "part",
+    "spoken",
+    "unspoken",
+    "total",
+    "density",
+    "runtime_minutes",
+    "tag_count",
+    "target",
+];
+
+/// A summary of the statistics for a single script.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ScriptStats {
+    /// The word count of the whole script.
+    pub wordcount: WordCount,
+
+    /// The number of spoken words the script is aiming for, if any.
+    pub target: Option<usize>,
+
+    /// Where each speaker speaks within the script (see `Script::appearances`).
+    pub appearances: Vec<(String, Vec<Appearance>)>,
+
+    /// The word count of each speaker's lines (see `Script::wordcount_by_speaker`).
+    pub speakers: Vec<(String, WordCount)>,
+
+    /// The number of lines of each kind, in order of first appearance (not counting section dividers).
+    pub lines: Vec<(ContainerKind, usize)>,
+
+    /// The word count of each section (see `Script::wordcount_by_section`).
+    pub sections: Vec<WordCount>,
+
+    /// The speaking rate at which to estimate the runtime (falling back to `WORDS_PER_MINUTE`), which is
+    /// only displayed once it is set.
+    pub words_per_minute: Option<f64>,
+}
+
+impl From<&Script> for ScriptStats {
+    fn from(script: &Script) -> Self {
+        Self::counted(script, &CountingRules::default())
+    }
+}
+
+impl fmt::Display for ScriptStats {
+    /**
+    ```
+    # use lilscript::{script::{Appearance, ContainerKind, WordCount}, stats::ScriptStats};
+    let stats = ScriptStats { wordcount: WordCount::new(2100, 400), target: Some(3500), ..Default::default() };
+    let expected = "2,100 spoken + 400 unspoken -> 2,500 total (ρ = 84.00%)\nTarget: 2,100 / 3,500 spoken words (60.0%)";
+    assert_eq!(stats.to_string(), expected);
+
+    let mut stats = ScriptStats { wordcount: WordCount::new(2100, 400), ..Default::default() };
+    stats.appearances = vec![
+        ("Alice".into(), vec![Appearance { section: 1, paragraphs: 0..=6, lines: 4 }]),
+        ("Bob".into(), vec![
+            Appearance { section: 1, paragraphs: 7..=7, lines: 1 },
+            Appearance { section: 2, paragraphs: 12..=15, lines: 2 },
+        ]),
+    ];
+    let expected = "2,100 spoken + 400 unspoken -> 2,500 total (ρ = 84.00%)\nAppearances:\n  \
+        Alice: section 1, lines 001–007\n  \
+        Bob: section 1, line 008; section 2, lines 013–016";
+    assert_eq!(stats.to_string(), expected);
+
+    let mut stats = ScriptStats { wordcount: WordCount::new(2100, 400), ..Default::default() };
+    stats.speakers = vec![("Alice".into(), WordCount::new(1400, 0)), ("Bob".into(), WordCount::new(700, 0))];
+    let expected = "2,100 spoken + 400 unspoken -> 2,500 total (ρ = 84.00%)\nWords by speaker:\n  \
+        Alice: 1,400 spoken (66.7%)\n  \
+        Bob: 700 spoken (33.3%)";
+    assert_eq!(stats.to_string(), expected);
+
+    let mut stats = ScriptStats { wordcount: WordCount::new(2100, 400), words_per_minute: Some(140.), ..Default::default() };
+    stats.lines = vec![(ContainerKind::Spoken, 40), (ContainerKind::Sfx, 6)];
+    stats.sections = vec![WordCount::new(1500, 300), WordCount::new(600, 100)];
+    let expected = "2,100 spoken + 400 unspoken -> 2,500 total (ρ = 84.00%)\n\
+        Runtime: 15.0 minutes (at 140 words per minute)\n\
+        Lines: Spoken 40 · Sfx 6\nWords by section:\n  \
+        Section 1: 1,500 spoken + 300 unspoken -> 1,800 total (ρ = 83.33%)\n  \
+        Section 2: 600 spoken + 100 unspoken -> 700 total (ρ = 85.71%)";
+    assert_eq!(stats.to_string(), expected);
+    ```
+    */
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.wordcount)?;
+
+        if let (Some(target), Some(progress)) = (self.target, self.progress()) {
+            let locale = Locale::en;
+            write!(
+                f,
+                "\nTarget: {} / {} spoken words ({:.1}%)",
+                self.wordcount.spoken().to_formatted_string(&locale),
+                target.to_formatted_string(&locale),
+                100. * progress
+            )?;
+        }
+
+        if let Some(words_per_minute) = self.words_per_minute {
+            write!(
+                f,
+                "\nRuntime: {:.1} minutes (at {} words per minute)",
+                self.runtime_minutes(),
+                words_per_minute
+            )?;
+        }
+
+        if !self.lines.is_empty() {
+            let lines: Vec<String> = self
+                .lines
+                .iter()
+                .map(|(kind, n)| format!("{} {}", kind, n))
+                .collect();
+            write!(f, "\nLines: {}", lines.join(" · "))?;
+        }
+
+        // a script with no dividers is all the one section, which the totals already cover
+        if self.sections.len() > 1 {
+            write!(f, "\nWords by section:")?;
+            for (i, wordcount) in self.sections.iter().enumerate() {
+                write!(f, "\n  Section {}: {}", i + 1, wordcount)?;
+            }
+        }
+
+        if !self.appearances.is_empty() {
+            write!(f, "\nAppearances:")?;
+            for (speaker, appearances) in &self.appearances {
+                let appearances: Vec<String> = appearances.iter().map(|a| a.to_string()).collect();
+                write!(f, "\n  {}: {}", speaker, appearances.join("; "))?;
+            }
+        }
+
+        if !self.speakers.is_empty() {
+            let locale = Locale::en;
+            write!(f, "\nWords by speaker:")?;
+            for (speaker, wordcount) in &self.speakers {
+                let spoken = wordcount.spoken();
+                write!(
+                    f,
+                    "\n  {}: {} spoken",
+                    speaker,
+                    spoken.to_formatted_string(&locale)
+                )?;
+                if self.wordcount.spoken() > 0 {
+                    let share = spoken as f64 / self.wordcount.spoken() as f64;
+                    write!(f, " ({:.1}%)", 100. * share)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ScriptStats {
+    /// Summarise the statistics of the given script, counting its words according to the given rules.
+    pub fn counted(script: &Script, rules: &CountingRules) -> Self {
+        Self {
+            wordcount: script.wordcount_with(rules),
+            target: script.target,
+            appearances: script.appearances(),
+            speakers: script.wordcount_by_speaker(rules),
+            lines: line_counts(script),
+            sections: script.wordcount_by_section(rules),
+            words_per_minute: None,
+        }
+    }
+
+    /** The proportion of the target which has been reached by the spoken words.
+
+    # Return
+
+    * `Some(f64)` - the progress, where `1.0` means that the target has been met exactly;
+    * `None` if the script has no target
+
+    # Examples
+
+    ```
+    # use lilscript::{script::WordCount, stats::ScriptStats};
+    let stats = ScriptStats { wordcount: WordCount::new(1750, 0), target: Some(3500), ..Default::default() };
+    assert_eq!(stats.progress(), Some(0.5));
+    ```
+    */
+    pub fn progress(&self) -> Option<f64> {
+        self.target
+            .map(|target| self.wordcount.spoken() as f64 / target as f64)
+    }
+
+    /** An estimate of how long the script runs for when read aloud, in minutes, based on its spoken
+    words at `words_per_minute` (or `WORDS_PER_MINUTE`, if that isn't set).
+
+    # Examples
+
+    ```
+    # use lilscript::{script::WordCount, stats::ScriptStats};
+    let stats = ScriptStats { wordcount: WordCount::new(2250, 400), target: None, ..Default::default() };
+    assert_eq!(stats.runtime_minutes(), 15.);
+
+    let stats = ScriptStats { words_per_minute: Some(125.), ..stats };
+    assert_eq!(stats.runtime_minutes(), 18.);
+    ```
+    */
+    pub fn runtime_minutes(&self) -> f64 {
+        self.wordcount.spoken() as f64 / self.words_per_minute.unwrap_or(WORDS_PER_MINUTE)
+    }
+
+    /** Check whether the spoken word count is within the given tolerance of the target.
+
+    # Arguments
+
+    * `tolerance` - the allowed deviation from the target, as a percentage of it
+
+    # Return
+
+    * `Ok(())` if the script has no target, or is within the tolerance;
+    * `Err(_)` describing how far from the target it is otherwise
+
+    # Examples
+
+    ```
+    # use lilscript::{script::WordCount, stats::ScriptStats};
+    let stats = ScriptStats { wordcount: WordCount::new(3300, 0), target: Some(3500), ..Default::default() };
+    assert!(stats.check_target(10.).is_ok());
+    assert!(stats.check_target(5.).is_err());
+    ```
+    */
+    pub fn check_target(&self, tolerance: f64) -> Result<(), String> {
+        let (target, progress) = match (self.target, self.progress()) {
+            (Some(target), Some(progress)) => (target, progress),
+            _ => return Ok(()),
+        };
+
+        let deviation = 100. * (progress - 1.);
+        if deviation.abs() <= tolerance {
+            return Ok(());
+        }
+
+        let locale = Locale::en;
+        Err(format!(
+            "{} spoken words is {:.1}% {} the target of {} (tolerance: ±{}%)",
+            self.wordcount.spoken().to_formatted_string(&locale),
+            deviation.abs(),
+            if deviation < 0. { "under" } else { "over" },
+            target.to_formatted_string(&locale),
+            tolerance
+        ))
+    }
+}
+
+/**
+Count the lines of each kind within a script, in order of first appearance. Section dividers aren't lines of
+the script, so they aren't counted, and neither are any cues which overlap other lines.
+
+# Examples
+
+```
+# use lilscript::{script::{ContainerKind, Script, TextContainer, TextSpan}, stats::line_counts};
+let line = |kind: ContainerKind, text: &str| TextContainer::new(kind).push(TextSpan::normal(text));
+let mut script = Script::new("lilellia", "A Very Cool Script");
+script.paragraphs = vec![
+    line(ContainerKind::Sfx, "rain"),
+    line(ContainerKind::Spoken, "Hello."),
+    line(ContainerKind::PlainText, "--8<--"),
+    line(ContainerKind::Spoken, "Listen."),
+];
+
+assert_eq!(line_counts(&script), vec![(ContainerKind::Sfx, 1), (ContainerKind::Spoken, 2)]);
+```
+*/
+pub fn line_counts(script: &Script) -> Vec<(ContainerKind, usize)> {
+    let mut counts: Vec<(ContainerKind, usize)> = Vec::new();
+    for container in script.paragraphs.iter().filter(|c| !c.is_divider()) {
+        match counts.iter_mut().find(|(kind, _)| *kind == container.kind) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((container.kind.clone(), 1)),
+        }
+    }
+    counts
+}
+
+/**
+Write a report of the statistics of each script as CSV (with the columns of `CSV_HEADER`),
+one row per script, for analysing a whole archive in a spreadsheet.
+
+# Arguments
+
+* `rows` - the path of each script, along with the script and its statistics
+* `w` - where to write the report to
+
+# Examples
+
+```
+# use lilscript::{script::{ContainerKind, Script, TextContainer, TextSpan}, stats::{write_csv, ScriptStats}};
+# use std::path::Path;
+let mut script = Script::new("lilellia", "A Very Cool, Very Short Script");
+script.tags = vec!["f4a".into(), "comfort".into()];
+script.paragraphs.push(TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Hello there.")));
+let stats = ScriptStats::from(&script);
+
+let mut report: Vec<u8> = Vec::new();
+write_csv([(Path::new("short.tex"), &script, &stats)], &mut report).unwrap();
+
+let report = String::from_utf8(report).unwrap();
+let mut lines = report.lines();
+assert_eq!(lines.next(), Some("file,title,series,part,spoken,unspoken,total,density,runtime_minutes,tag_count,target"));
+assert_eq!(lines.next(), Some(r#"short.tex,"A Very Cool, Very Short Script",,,2,0,2,1.0000,0.01,2,"#));
+```
+*/
+pub fn write_csv<'a>(
+    rows: impl IntoIterator<Item = (&'a Path, &'a Script, &'a ScriptStats)>,
+    w: impl io::Write,
+) -> csv::Result<()> {
+    let mut writer = csv::Writer::from_writer(w);
+    writer.write_record(CSV_HEADER)?;
+
+    for (path, script, stats) in rows {
+        let wordcount = &stats.wordcount;
+        let density = wordcount.speech_density();
+
+        writer.write_record([
+            path.display().to_string(),
+            script.title.clone(),
+            script.series.title.clone().unwrap_or_default(),
+            script
+                .series
+                .part
+                .as_ref()
+                .map(|part| part.to_string())
+                .unwrap_or_default(),
+            wordcount.spoken().to_string(),
+            wordcount.unspoken().to_string(),
+            wordcount.total().to_string(),
+            // a script with no words has no density, which is better left blank than written as NaN
+            match density.is_nan() {
+                true => String::new(),
+                false => format!("{:.4}", density),
+            },
+            format!("{:.2}", stats.runtime_minutes()),
+            script.tags.len().to_string(),
+            stats
+                .target
+                .map(|target| target.to_string())
+                .unwrap_or_default(),
+        ])?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/**
+Write a report of the statistics of each script as a JSON array (one object per script), with each script's
+metadata, word counts (overall, per speaker, and per section), lines of each kind, estimated runtime, and sound
+effect cues, for feeding into other tools.
+
+# Examples
+
+```
+# use lilscript::{script::{ContainerKind, Script, TextContainer, TextSpan}, stats::{write_json, ScriptStats}};
+# use std::path::Path;
+let mut script = Script::new("lilellia", "A Very Cool Script");
+script.series = "Moonrise (Part 2)".into();
+script.paragraphs.push(
+    TextContainer::new(ContainerKind::Spoken)
+        .push(TextSpan::normal("Hello there."))
+        .spoken_by("Alice"),
+);
+let stats = ScriptStats::from(&script);
+
+let mut report: Vec<u8> = Vec::new();
+write_json([(Path::new("short.tex"), &script, &stats)], &mut report).unwrap();
+
+let report: serde_json::Value = serde_json::from_slice(&report).unwrap();
+assert_eq!(report[0]["series"], "Moonrise");
+assert_eq!(report[0]["part"], "Part 2");
+assert_eq!(report[0]["words"]["spoken"], 2);
+assert_eq!(report[0]["speakers"][0]["name"], "Alice");
+assert_eq!(report[0]["lines"]["spoken"], 1);
+```
+*/
+pub fn write_json<'a>(
+    rows: impl IntoIterator<Item = (&'a Path, &'a Script, &'a ScriptStats)>,
+    w: impl io::Write,
+) -> serde_json::Result<()> {
+    let words = |wordcount: &WordCount| {
+        let density = wordcount.speech_density();
+        json!({
+            "spoken": wordcount.spoken(),
+            "unspoken": wordcount.unspoken(),
+            "total": wordcount.total(),
+            // NaN isn't valid JSON, so a script with no words has a null density
+            "density": (!density.is_nan()).then_some(density),
+        })
+    };
+
+    let report: Vec<Value> = rows
+        .into_iter()
+        .map(|(path, script, stats)| {
+            let speakers: Vec<Value> = stats
+                .speakers
+                .iter()
+                .map(|(name, wordcount)| json!({ "name": name, "words": words(wordcount) }))
+                .collect();
+
+            // keyed as in .json exports of the script itself (see `data_handler`)
+            let lines: Map<String, Value> = stats
+                .lines
+                .iter()
+                .map(|(kind, n)| {
+                    (
+                        json!(kind).as_str().unwrap_or_default().to_owned(),
+                        json!(n),
+                    )
+                })
+                .collect();
+
+            let sfx: Vec<Value> = sfx_inventory(script)
+                .into_iter()
+                .map(|cue| {
+                    let lines: Vec<String> = cue.paragraphs.iter().map(|&i| line_id(i)).collect();
+                    json!({ "cue": cue.text, "lines": lines })
+                })
+                .collect();
+
+            json!({
+                "file": path.display().to_string(),
+                "title": script.title,
+                "author": script.author,
+                "series": script.series.title,
+                "part": script.series.part.as_ref().map(|part| part.to_string()),
+                "tags": script.tags,
+                "words": words(&stats.wordcount),
+                "target": stats.target,
+                "runtime_minutes": stats.runtime_minutes(),
+                "speakers": speakers,
+                "sections": stats.sections.iter().map(words).collect::<Vec<Value>>(),
+                "lines": lines,
+                "sfx": sfx,
+            })
+        })
+        .collect();
+
+    serde_json::to_writer_pretty(w, &report)
+}
+
+/// A distinct sound effect (or music) cue, and everywhere it occurs within a script.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SfxCue {
+    /// The cue, as it is first written.
+    pub text: String,
+
+    /// The index of the paragraph of each occurrence, in order.
+    pub paragraphs: Vec<usize>,
+}
+
+impl fmt::Display for SfxCue {
+    /**
+    ```
+    # use lilscript::stats::SfxCue;
+    let cue = SfxCue { text: "rain on the window".into(), paragraphs: vec![1, 13, 19] };
+    assert_eq!(cue.to_string(), "rain on the window ×3 (lines 002, 014, 020)");
+
+    let cue = SfxCue { text: "door closes".into(), paragraphs: vec![4] };
+    assert_eq!(cue.to_string(), "door closes ×1 (line 005)");
+    ```
+    */
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines: Vec<String> = self.paragraphs.iter().map(|&i| line_id(i)).collect();
+        write!(
+            f,
+            "{} ×{} ({} {})",
+            self.text,
+            self.paragraphs.len(),
+            if lines.len() == 1 { "line" } else { "lines" },
+            lines.join(", ")
+        )
+    }
+}
+
+/**
+List every distinct sound effect cue within a script (including those overlapping other lines), in order
+of first appearance, with where each of them occurs, so that the sounds can all be gathered before recording.
+Cues are the same if they differ only in case or whitespace.
+
+# Examples
+
+```
+# use lilscript::{script::{ContainerKind, Script, TextContainer, TextSpan}, stats::sfx_inventory};
+let sfx = |text: &str| TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal(text));
+let mut script = Script::new("lilellia", "A Very Cool Script");
+script.paragraphs = vec![
+    sfx("Rain on the window"),
+    TextContainer::new(ContainerKind::Spoken)
+        .push(TextSpan::normal("Hello."))
+        .overlapping(sfx("door closes")),
+    sfx("rain on  the window"),
+];
+
+let inventory = sfx_inventory(&script);
+assert_eq!(inventory.len(), 2);
+assert_eq!(inventory[0].text, "Rain on the window");
+assert_eq!(inventory[0].paragraphs, vec![0, 2]);
+assert_eq!(inventory[1].to_string(), "door closes ×1 (line 002)");
+```
+*/
+pub fn sfx_inventory(script: &Script) -> Vec<SfxCue> {
+    let mut inventory: Vec<(String, SfxCue)> = Vec::new();
+
+    for (index, paragraph) in script.paragraphs.iter().enumerate() {
+        for container in paragraph.with_overlaps() {
+            if container.kind != ContainerKind::Sfx {
+                continue;
+            }
+
+            let text = container.plain_text();
+            let key = text
+                .split_whitespace()
+                .collect::<Vec<&str>>()
+                .join(" ")
+                .to_lowercase();
+            if key.is_empty() {
+                continue;
+            }
+
+            match inventory.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, cue)) => cue.paragraphs.push(index),
+                None => inventory.push((
+                    key,
+                    SfxCue {
+                        text,
+                        paragraphs: vec![index],
+                    },
+                )),
+            }
+        }
+    }
+
+    inventory.into_iter().map(|(_, cue)| cue).collect()
+}
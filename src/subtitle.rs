@@ -0,0 +1,456 @@
+use crate::script::{ContainerKind, Script, TextContainer};
+use crate::stats::WORDS_PER_MINUTE;
+use regex::Regex;
+
+/// The shortest time (in seconds) that a cue is shown for, however few words it has.
+pub const MIN_CUE_SECONDS: f64 = 1.;
+
+/// How much text a single subtitle cue may show at once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SubtitleBudget {
+    /// The most characters on a single line of a cue.
+    pub max_chars_per_line: usize,
+
+    /// The most lines in a single cue.
+    pub max_lines_per_cue: usize,
+}
+
+impl Default for SubtitleBudget {
+    /// The common broadcast limits: two lines of at most 42 characters.
+    fn default() -> Self {
+        Self {
+            max_chars_per_line: 42,
+            max_lines_per_cue: 2,
+        }
+    }
+}
+
+/// The options for writing subtitles (.srt or .vtt), whose timings are estimated from the spoken words.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SubtitleOptions {
+    /// How much text each cue may show at once.
+    pub budget: SubtitleBudget,
+
+    /// The speaking rate from which each cue's timing is estimated.
+    pub words_per_minute: f64,
+}
+
+impl Default for SubtitleOptions {
+    fn default() -> Self {
+        Self {
+            budget: SubtitleBudget::default(),
+            words_per_minute: WORDS_PER_MINUTE,
+        }
+    }
+}
+
+/// A subtitle cue, with the (estimated) times in seconds at which it is shown and hidden.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimedCue {
+    /// When the cue is shown, in seconds from the start of the recording.
+    pub start: f64,
+
+    /// When the cue is hidden, in seconds from the start of the recording.
+    pub end: f64,
+
+    /// The lines of text shown.
+    pub lines: Vec<String>,
+}
+
+/**
+Lay out the spoken lines of a script as subtitle cues (see `split_cues`), one after another, each shown for
+as long as its words take to say at the given speaking rate (but at least `MIN_CUE_SECONDS`). Anything that
+isn't spoken takes no time; spoken lines overlapping one another start together.
+
+# Examples
+
+```
+# use lilscript::{script::{ContainerKind, Script, TextContainer, TextSpan}, subtitle::{timed_cues, SubtitleOptions}};
+let spoken = |text: &str| TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal(text));
+let mut script = Script::new("lilellia", "A Very Cool Script");
+script.paragraphs = vec![
+    spoken("Hello there, my dear."),
+    TextContainer::new(ContainerKind::StageDir).push(TextSpan::normal("she sits down")),
+    spoken("Hi."),
+];
+
+let cues = timed_cues(&script, &SubtitleOptions::default());
+assert_eq!(cues.len(), 2);
+assert_eq!((cues[0].start, cues[0].end), (0., 1.6));
+assert_eq!((cues[1].start, cues[1].end), (1.6, 2.6));
+assert_eq!(cues[1].lines, vec!["Hi."]);
+```
+*/
+pub fn timed_cues(script: &Script, options: &SubtitleOptions) -> Vec<TimedCue> {
+    let seconds_per_word = 60. / options.words_per_minute;
+
+    let mut timed = Vec::new();
+    let mut time = 0.;
+    for paragraph in &script.paragraphs {
+        let mut latest = time;
+        for container in paragraph.with_overlaps() {
+            if container.kind != ContainerKind::Spoken {
+                continue;
+            }
+
+            let mut start = time;
+            for lines in split_cues(container, &options.budget) {
+                let words = lines
+                    .iter()
+                    .map(|line| line.split_whitespace().count())
+                    .sum::<usize>();
+                let end = start + (words as f64 * seconds_per_word).max(MIN_CUE_SECONDS);
+                timed.push(TimedCue { start, end, lines });
+                start = end;
+            }
+            latest = f64::max(latest, start);
+        }
+        time = latest;
+    }
+
+    timed
+}
+
+/**
+Write the spoken lines of a script as SubRip (.srt) subtitles, timed as in `timed_cues`.
+
+# Examples
+
+```
+# use lilscript::{script::{ContainerKind, Script, TextContainer, TextSpan}, subtitle::{to_srt, SubtitleOptions}};
+let mut script = Script::new("lilellia", "A Very Cool Script");
+script.paragraphs = vec![TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Hi."))];
+
+assert_eq!(to_srt(&script, &SubtitleOptions::default()), "1\n00:00:00,000 --> 00:00:01,000\nHi.\n");
+```
+*/
+pub fn to_srt(script: &Script, options: &SubtitleOptions) -> String {
+    timed_cues(script, options)
+        .iter()
+        .enumerate()
+        .map(|(i, cue)| {
+            format!(
+                "{}\n{} --> {}\n{}\n",
+                i + 1,
+                timestamp(cue.start, ','),
+                timestamp(cue.end, ','),
+                cue.lines.join("\n")
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/**
+Write the spoken lines of a script as WebVTT (.vtt) subtitles, timed as in `timed_cues`.
+
+# Examples
+
+```
+# use lilscript::{script::{ContainerKind, Script, TextContainer, TextSpan}, subtitle::{to_vtt, SubtitleOptions}};
+let mut script = Script::new("lilellia", "A Very Cool Script");
+script.paragraphs = vec![TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Hi."))];
+
+assert_eq!(
+    to_vtt(&script, &SubtitleOptions::default()),
+    "WEBVTT\n\n00:00:00.000 --> 00:00:01.000\nHi.\n"
+);
+```
+*/
+pub fn to_vtt(script: &Script, options: &SubtitleOptions) -> String {
+    let cues = timed_cues(script, options)
+        .iter()
+        .map(|cue| {
+            format!(
+                "{} --> {}\n{}\n",
+                timestamp(cue.start, '.'),
+                timestamp(cue.end, '.'),
+                cue.lines.join("\n")
+            )
+        })
+        .collect::<Vec<String>>();
+
+    format!("WEBVTT\n\n{}", cues.join("\n"))
+}
+
+/// Format a time in seconds as `HH:MM:SS` followed by the separator and the milliseconds.
+fn timestamp(seconds: f64, separator: char) -> String {
+    let millis = (seconds * 1000.).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        separator,
+        millis % 1000
+    )
+}
+
+/**
+Split the spoken text of a container (leaving out tone cues) into subtitle cues which fit within the
+given budget, each cue being a list of lines.
+
+Lines are broken at the end of a sentence or clause (after `.`, `!`, `?`, `…`, `,`, `;`, `:`, or a dash)
+wherever the whole sentence or clause fits, and otherwise between words; a word is never split, even if
+it is longer than a line on its own. A cue is only started part-way through a sentence once the sentence
+has filled a whole cue.
+
+# Examples
+
+```
+# use lilscript::{script::{ContainerKind, TextContainer, TextSpan}, subtitle::{split_cues, SubtitleBudget}};
+let container = TextContainer::new(ContainerKind::Spoken)
+    .push(TextSpan::inline("softly"))
+    .push(TextSpan::normal("Oh! You're here already? I wasn't expecting you until later, but come in."));
+
+let budget = SubtitleBudget { max_chars_per_line: 24, max_lines_per_cue: 2 };
+assert_eq!(
+    split_cues(&container, &budget),
+    vec![
+        vec!["Oh! You're here already?", "I wasn't expecting you"],
+        vec!["until later,", "but come in."],
+    ]
+);
+```
+*/
+pub fn split_cues(container: &TextContainer, budget: &SubtitleBudget) -> Vec<Vec<String>> {
+    let text = container
+        .spans
+        .iter()
+        .filter(|span| span.is_spoken(ContainerKind::Spoken))
+        .map(|span| span.contents.as_str())
+        .collect::<Vec<&str>>()
+        .join(" ");
+
+    let mut cues = Cues::new(budget);
+    for sentence in sentences(&text) {
+        // a sentence which would overflow this cue, but fits in one of its own, starts a new cue
+        let mut trial = cues.clone();
+        trial.push_sentence(&sentence);
+        if trial.position() > cues.position() && Cues::new(budget).fits(&sentence) {
+            cues.finish_cue();
+        }
+
+        cues.push_sentence(&sentence);
+    }
+
+    cues.finish_cue();
+    cues.cues
+}
+
+/// The subtitle cues laid out so far, along with the cue and line being filled.
+#[derive(Clone)]
+struct Cues {
+    max_chars: usize,
+    max_lines: usize,
+
+    cues: Vec<Vec<String>>,
+    lines: Vec<String>,
+    line: String,
+}
+
+impl Cues {
+    fn new(budget: &SubtitleBudget) -> Self {
+        Self {
+            max_chars: budget.max_chars_per_line.max(1),
+            max_lines: budget.max_lines_per_cue.max(1),
+            cues: Vec::new(),
+            lines: Vec::new(),
+            line: String::new(),
+        }
+    }
+
+    /// The index of the cue which the current line will end up in.
+    fn position(&self) -> usize {
+        let overflowing = self.lines.len() == self.max_lines && !self.line.is_empty();
+        self.cues.len() + usize::from(overflowing)
+    }
+
+    /// Whether the sentence fits within the current cue.
+    fn fits(mut self, sentence: &str) -> bool {
+        let position = self.position();
+        self.push_sentence(sentence);
+        self.position() == position
+    }
+
+    /// Lay out a sentence, keeping each of its clauses on a single line wherever it fits on one.
+    fn push_sentence(&mut self, sentence: &str) {
+        for clause in clauses(sentence) {
+            let length = clause.chars().count();
+            if !self.line.is_empty()
+                && self.line.chars().count() + 1 + length > self.max_chars
+                && length <= self.max_chars
+            {
+                self.finish_line();
+            }
+
+            for word in clause.split_whitespace() {
+                self.push_word(word);
+            }
+        }
+    }
+
+    /// Add a word to the current line, starting a new line (or cue) if it doesn't fit.
+    fn push_word(&mut self, word: &str) {
+        if !self.line.is_empty()
+            && self.line.chars().count() + 1 + word.chars().count() > self.max_chars
+        {
+            self.finish_line();
+        }
+
+        if !self.line.is_empty() {
+            self.line.push(' ');
+        }
+        self.line.push_str(word);
+    }
+
+    /// Finish the current line (if it has anything on it), starting a new cue if this one is full.
+    fn finish_line(&mut self) {
+        if self.line.is_empty() {
+            return;
+        }
+
+        if self.lines.len() == self.max_lines {
+            self.cues.push(std::mem::take(&mut self.lines));
+        }
+        self.lines.push(std::mem::take(&mut self.line));
+    }
+
+    /// Finish the current cue (if it has anything in it).
+    fn finish_cue(&mut self) {
+        self.finish_line();
+        if !self.lines.is_empty() {
+            self.cues.push(std::mem::take(&mut self.lines));
+        }
+    }
+}
+
+/// Split the text into its sentences, each keeping its closing punctuation.
+fn sentences(text: &str) -> Vec<String> {
+    split_after(text, r#"[.!?…]+["'”’)\]]*\s+"#)
+}
+
+/// Split a sentence into its clauses, each keeping its closing punctuation.
+fn clauses(sentence: &str) -> Vec<String> {
+    split_after(sentence, r"(?:[,;:]|\s[—–-])\s+")
+}
+
+/// Split the text after each match of the given pattern, trimming each piece.
+fn split_after(text: &str, pattern: &str) -> Vec<String> {
+    let re = Regex::new(pattern).unwrap();
+
+    let mut pieces: Vec<String> = Vec::new();
+    let mut start = 0;
+    for m in re.find_iter(text) {
+        pieces.push(text[start..m.end()].trim().to_owned());
+        start = m.end();
+    }
+    pieces.push(text[start..].trim().to_owned());
+
+    pieces.retain(|piece| !piece.is_empty());
+    pieces
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::script::TextSpan;
+
+    fn spoken(text: &str) -> TextContainer {
+        TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal(text))
+    }
+
+    #[test]
+    fn test_sentence_starts_a_new_cue() {
+        let budget = SubtitleBudget {
+            max_chars_per_line: 20,
+            max_lines_per_cue: 2,
+        };
+        let cues = split_cues(
+            &spoken("Hi there, you. Shall we go for a walk by the river?"),
+            &budget,
+        );
+        assert_eq!(
+            cues,
+            vec![
+                vec!["Hi there, you."],
+                vec!["Shall we go for a", "walk by the river?"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_long_word_is_not_split() {
+        let budget = SubtitleBudget {
+            max_chars_per_line: 5,
+            max_lines_per_cue: 1,
+        };
+        let cues = split_cues(&spoken("Supercalifragilistic, yes."), &budget);
+        assert_eq!(cues, vec![vec!["Supercalifragilistic,"], vec!["yes."]]);
+    }
+
+    fn script(paragraphs: Vec<TextContainer>) -> Script {
+        let mut script = Script::new("lilellia", "A Very Cool Script");
+        script.paragraphs = paragraphs;
+        script
+    }
+
+    #[test]
+    fn test_srt_cues_follow_the_budget() {
+        let options = SubtitleOptions {
+            budget: SubtitleBudget {
+                max_chars_per_line: 20,
+                max_lines_per_cue: 2,
+            },
+            words_per_minute: 120.,
+        };
+        let script = script(vec![
+            spoken("Hi there, you. Shall we go for a walk by the river?"),
+            TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal("footsteps")),
+            spoken("Yes."),
+        ]);
+
+        assert_eq!(
+            to_srt(&script, &options),
+            "1\n00:00:00,000 --> 00:00:01,500\nHi there, you.\n\n\
+             2\n00:00:01,500 --> 00:00:06,000\nShall we go for a\nwalk by the river?\n\n\
+             3\n00:00:06,000 --> 00:00:07,000\nYes.\n"
+        );
+    }
+
+    #[test]
+    fn test_vtt_leaves_out_tone_cues_and_listener_lines() {
+        let script = script(vec![
+            TextContainer::new(ContainerKind::Spoken)
+                .push(TextSpan::inline("whispering"))
+                .push(TextSpan::normal("Over here.")),
+            TextContainer::new(ContainerKind::ListenerDialogue)
+                .push(TextSpan::normal("Where are you?")),
+        ]);
+
+        assert_eq!(
+            to_vtt(&script, &SubtitleOptions::default()),
+            "WEBVTT\n\n00:00:00.000 --> 00:00:01.000\nOver here.\n"
+        );
+    }
+
+    #[test]
+    fn test_overlapping_lines_start_together() {
+        let script = script(vec![
+            spoken("One two three four five.")
+                .overlapping(spoken("Six seven eight nine ten eleven twelve thirteen.")),
+            spoken("Done."),
+        ]);
+
+        let cues = timed_cues(&script, &SubtitleOptions::default());
+        assert_eq!(cues.len(), 3);
+        assert_eq!((cues[0].start, cues[0].end), (0., 3.2));
+        assert_eq!((cues[1].start, cues[1].end), (0., 2.));
+        assert_eq!(cues[2].start, 3.2);
+    }
+
+    #[test]
+    fn test_timestamps_roll_over() {
+        assert_eq!(timestamp(3723.4567, ','), "01:02:03,457");
+    }
+}
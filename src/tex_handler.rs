@@ -1,10 +1,19 @@
-// /// Convert from a string to a Script object.
-// pub use crate::tex_handler::parse::to_script as parse;
-
-use crate::script::{Character, ContainerKind, Script, SeriesEntry, TextContainer, TextSpan};
-use chrono::NaiveDate;
-use paris::warn;
-use regex::Regex;
+use crate::{
+    ast::{line_of, push_container, ContainerNode, Document, Positioned, Section, SourceRange},
+    emoji::emoji_for,
+    script::{
+        parse_date, Character, ContainerKind, ParseOptions, QuoteStyle, ReplaceScope, Script,
+        SeriesEntry, SpanKind, TextContainer, TextSpan,
+    },
+};
+use paris::error;
+use rayon::prelude::*;
+use regex::{Captures, Matches, Regex};
+use std::{borrow::Cow, collections::BTreeMap, fmt, sync::LazyLock};
+
+/// A custom command without arguments (e.g., `\sigh` or `\sigh{}`), as `Tex::substituted` replaces them.
+static CUSTOM_COMMAND: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\\([A-Za-z]+)(\{\})?").unwrap());
 
 /// A thin wrapper around a String, used to represent a .tex formatted string.
 /// Also includes a few convenience methods for parsing/exporting.
@@ -41,9 +50,11 @@ impl Tex {
 
     * `\ldots` and `\textellipsis` (or with {}) ⟶ `...` or `... `
     * TeX quotation marks are fixed: `"abc"`
-    * un-escape characters: `\$\&\%` ⟶ `$&%`
+    * un-escape characters: `\$\&\%\#\_\{\}` ⟶ `$&%#_{}`
+    * `\textasciitilde{}`, `\textasciicircum{}`, and `\textbackslash{}` ⟶ `~`, `^`, and `\`
     * `\kaosmile{}` ⟶ `^_^`
     * `\Tilde{}` ⟶ `∼`
+    * `\emoji{sparkles}` ⟶ `:sparkles:` (which can be turned into an emoji on export)
     * TeX href `\href{URL}{TEXT}` ⟶ Markdown style `[TEXT](URL)`
     * after those substitutions, extraneous spaces are removed
 
@@ -76,8 +87,7 @@ impl Tex {
             .replace(r"\textemdash", EM_DASH);
 
         // handle quotation marks: ``abc'' -> "abc" and \textquote{abc} -> "abc"
-        let re = Regex::new(r"``(.*?)''").unwrap();
-        let s = re.replace_all(&s, "\"$1\"");
+        let s = QuoteStyle::Straight.convert(&s);
 
         let re = Regex::new(r"\\textquote\{(.*?)\}").unwrap();
         let s = re.replace_all(&s, "\"$1\"");
@@ -86,8 +96,12 @@ impl Tex {
         let s = s.replace(r"\restoregeometry", "");
 
         // handle the special single-characters
-        let re = Regex::new(r"\\([%&$])").unwrap();
+        let re = Regex::new(r"\\([%&$#_{}])").unwrap();
         let s = re.replace_all(&s, "$1");
+        let re = Regex::new(r"\\textasciitilde(\{\})?").unwrap();
+        let s = re.replace_all(&s, "~");
+        let re = Regex::new(r"\\textasciicircum(\{\})?").unwrap();
+        let s = re.replace_all(&s, "^");
 
         // handle a few custom commands
         let re = Regex::new(r"\\kaosmile(\{\})?").unwrap();
@@ -96,6 +110,9 @@ impl Tex {
         let re = Regex::new(r"\\Tilde(\{\})?").unwrap();
         let s = re.replace_all(&s, "\u{223C}");
 
+        let re = Regex::new(r"\\emoji\{([a-z0-9_+-]+)\}").unwrap();
+        let s = re.replace_all(&s, ":$1:");
+
         // handle embedded link (convert to markdown format because...)
         let re = Regex::new(r"\\href\{(.*?)\}\{(.*?)\}").unwrap();
         let s = re.replace_all(&s, "[$2]($1)");
@@ -107,9 +124,47 @@ impl Tex {
         // remove trailing space
         let s = s.trim();
 
+        // only now can backslashes be restored, so that they aren't mistaken for the start of a command
+        let re = Regex::new(r"\\textbackslash(\{\})?").unwrap();
+        let s = re.replace_all(s, r"\");
+
         s.to_string()
     }
 
+    /** Replace custom TeX commands, which `Tex::unescaped` leaves as they are, with the given text.
+
+    # Arguments
+
+    * `s` - the (already unescaped) text
+    * `substitutions` - the text to replace each command with, keyed by its name (without the backslash)
+
+    Each command may be written either bare or followed by `{}`, but not with any arguments.
+
+    # Examples
+    ```
+    # use lilscript::tex_handler::Tex;
+    # use std::{borrow::Cow, collections::BTreeMap};
+    let substitutions = BTreeMap::from([("sigh".to_owned(), "*sighs*".to_owned())]);
+    let s = Tex::substituted(r"Well\sigh{} \sigh, fine. \sighing", &substitutions);
+    assert_eq!(s, r"Well*sighs* *sighs*, fine. \sighing");
+
+    // text without any commands is borrowed as it is
+    assert!(matches!(Tex::substituted("Hello.", &substitutions), Cow::Borrowed(_)));
+    ```
+    */
+    pub fn substituted<'a>(s: &'a str, substitutions: &BTreeMap<String, String>) -> Cow<'a, str> {
+        if substitutions.is_empty() || !s.contains('\\') {
+            return Cow::Borrowed(s);
+        }
+
+        CUSTOM_COMMAND.replace_all(s, |captures: &Captures| {
+            match substitutions.get(&captures[1]) {
+                Some(text) => text.clone(),
+                None => captures[0].to_owned(),
+            }
+        })
+    }
+
     /** The same as Tex::prettified, but done in-place.
 
     # Examples:
@@ -126,327 +181,2344 @@ impl Tex {
     }
 }
 
-impl TryFrom<&Tex> for TextContainer {
-    type Error = String;
+/// A token of .tex source; see `tokenize`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Token<'a> {
+    /// A command, without its backslash: either a name (`\spoken` ⟶ `spoken`) or a single symbol (`\%` ⟶ `%`)
+    Command(&'a str),
 
-    fn try_from(value: &Tex) -> Result<Self, Self::Error> {
-        let text = Tex::unescaped(&value.text);
-        let re = Regex::new(r"^\\(.*?)\{(.*)\}$").unwrap();
-        let captures = re
-            .captures(&text)
-            .ok_or(format!("Invalid tex line: {}", &text))?;
-        let command = captures.get(1).unwrap().as_str();
-        let remainder = captures.get(2).unwrap().as_str();
-
-        let kind = match command {
-            "spoken" => ContainerKind::Spoken,
-            "stagedir" => ContainerKind::StageDir,
-            "listener" => ContainerKind::ListenerDialogue,
-            "sfx" => ContainerKind::Sfx,
-            _ => {
-                warn!("Could not identify container kind for command: {}", command);
-                ContainerKind::PlainText
-            }
-        };
+    /// The contents of an optional argument directly following a command (`[Alice]` ⟶ `Alice`)
+    Optional(&'a str),
+
+    /// The contents of a balanced `{...}` group, without its outer braces (any groups nested within are kept as they are)
+    Group(&'a str),
 
-        // remainder will have one of the two forms:
-        // form 1: "This is some text."
-        // form 2: "This is some text \direct{a direction} and more text."
-        // We need to split out these inline directions (or anything else) that occur in the middle.
-        let re = Regex::new(r"\\.+?\{.*?\}").unwrap();
+    /// A run of anything else, including any unbalanced braces
+    Text(&'a str),
+}
+
+impl Token<'_> {
+    /// Whether this is text which is nothing but whitespace.
+    fn is_blank(&self) -> bool {
+        matches!(self, Token::Text(text) if text.trim().is_empty())
+    }
+}
+
+/** Split .tex source into its tokens, each positioned by its byte range within the source (including
+any backslash, brackets, or braces). Comments (from an unescaped `%` to the end of its line) are skipped.
+
+A brace which isn't balanced is kept as text, so that a mistake only affects the command it's part of.
+
+# Examples
 
-        let mut spans: Vec<TextSpan> = Vec::new();
-        for s in regex_partition(re, &remainder) {
-            if s.is_empty() {
-                // ignore empty spans
+```
+# use lilscript::tex_handler::{tokenize, Token};
+let source = "\\spoken[Alice]{Hi, \\direct{with {nested} braces}.} % a comment\n100\\% sure";
+let tokens: Vec<Token> = tokenize(source).into_iter().map(|token| token.value).collect();
+assert_eq!(
+    tokens,
+    vec![
+        Token::Command("spoken"),
+        Token::Optional("Alice"),
+        Token::Group(r"Hi, \direct{with {nested} braces}."),
+        Token::Text(" "),
+        Token::Text("\n100"),
+        Token::Command("%"),
+        Token::Text(" sure"),
+    ]
+);
+```
+*/
+pub fn tokenize(source: &str) -> Vec<Positioned<Token<'_>>> {
+    let bytes = source.as_bytes();
+    let mut tokens: Vec<Positioned<Token>> = Vec::new();
+    let mut text_start: Option<usize> = None;
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        let token = match bytes[i] {
+            b'\\' => {
+                let name_len = source[i + 1..]
+                    .bytes()
+                    .take_while(u8::is_ascii_alphabetic)
+                    .count();
+                let name_len = match (name_len, source[i + 1..].chars().next()) {
+                    (0, Some(symbol)) => symbol.len_utf8(),
+                    (len, _) => len,
+                };
+                let name = &source[i + 1..i + 1 + name_len];
+                i += 1 + name_len;
+                Some(Token::Command(name))
+            }
+            b'[' if matches!(tokens.last(), Some(t) if matches!(t.value, Token::Command(_)))
+                && text_start.is_none() =>
+            {
+                match source[i..].find(']') {
+                    Some(end) => {
+                        i += end + 1;
+                        Some(Token::Optional(&source[start + 1..i - 1]))
+                    }
+                    None => {
+                        i += 1;
+                        None
+                    }
+                }
+            }
+            b'{' => match group_end(source, i) {
+                Some(end) => {
+                    i = end + 1;
+                    Some(Token::Group(&source[start + 1..end]))
+                }
+                None => {
+                    i += 1;
+                    None
+                }
+            },
+            b'%' => {
+                // the comment isn't a token, but it does end any text before it
+                if let Some(text) = text_start.take() {
+                    tokens.push(Positioned {
+                        value: Token::Text(&source[text..start]),
+                        range: text..start,
+                    });
+                }
+                i = source[i..].find('\n').map_or(bytes.len(), |end| i + end);
                 continue;
             }
+            _ => {
+                i += source[i..].chars().next().map_or(1, char::len_utf8);
+                None
+            }
+        };
 
-            let t = Tex::from(s);
-            if let Ok(span) = TextSpan::try_from(&t) {
-                spans.push(span);
-            } else {
-                return Err(format!(
-                    "[TextContainer::try_from<&Tex>] Could not parse span {}",
-                    t.text
-                ));
+        match token {
+            Some(token) => {
+                if let Some(text) = text_start.take() {
+                    tokens.push(Positioned {
+                        value: Token::Text(&source[text..start]),
+                        range: text..start,
+                    });
+                }
+                tokens.push(Positioned {
+                    value: token,
+                    range: start..i,
+                });
+            }
+            None => {
+                text_start.get_or_insert(start);
             }
         }
-
-        let container = Self { kind, spans };
-        Ok(container)
     }
-}
 
-impl TryFrom<&Tex> for TextSpan {
-    type Error = String;
+    if let Some(text) = text_start {
+        tokens.push(Positioned {
+            value: Token::Text(&source[text..]),
+            range: text..source.len(),
+        });
+    }
 
-    fn try_from(value: &Tex) -> Result<Self, Self::Error> {
-        let text = Tex::unescaped(&value.text);
-        let re = Regex::new(r"\\(.+)\{(.*)\}").unwrap();
+    tokens
+}
 
-        match re.captures(&text) {
-            None => {
-                // this is just a block of text!
-                let value = Tex::unescaped(value.text.trim());
-                Ok(TextSpan::normal(&value))
-            }
-            Some(cap) => {
-                // this is a command
-                let command = cap.get(1).unwrap().as_str();
-                let arg = cap.get(2).unwrap().as_str().trim();
-                let arg = Tex::unescaped(arg);
-
-                match command {
-                    "direct" => Ok(TextSpan::inline(&arg)),
-                    "ul" | "emph" => Ok(TextSpan::emphasis(&arg)),
-                    _ => {
-                        let err = format!("unparsable TeX command: {:?}", command);
-                        Err(err)
-                    },
+/// The index of the `}` which closes the `{` at the given index, skipping over escaped braces and comments.
+fn group_end(source: &str, open: usize) -> Option<usize> {
+    let bytes = source.as_bytes();
+    let mut depth = 0;
+    let mut i = open;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 1,
+            b'%' => i = source[i..].find('\n').map_or(bytes.len(), |end| i + end),
+            b'{' => depth += 1,
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
                 }
             }
+            _ => (),
         }
+        i += 1;
     }
+
+    None
 }
 
-impl TryFrom<&Tex> for Script {
-    type Error = String;
+/** Replace each comment in the .tex source with spaces, keeping every other byte where it is (so that
+positions within the result are also positions within the source).
 
-    /// Attempt to create a Script from the give .tex file.
-    fn try_from(value: &Tex) -> Result<Self, Self::Error> {
-        // try to process the header information
-        let title = search_tex(r"renewcommand\{\\SceneName\}", &value.text)
-            .ok_or("Could not parse title")?;
-        let author = search_tex("scriptAuthor", &value.text).ok_or("Could not parse author")?;
+# Examples
 
-        let series = search_tex("scriptSeries", &value.text).ok_or("Could not find series")?;
-        let series = SeriesEntry::from(series);
+```
+# use lilscript::tex_handler::blank_comments;
+let source = "\\spoken{Hi.} % TODO: reword\n\\sfx{100\\% rain}";
+assert_eq!(blank_comments(source), "\\spoken{Hi.}               \n\\sfx{100\\% rain}");
+```
+*/
+pub fn blank_comments(source: &str) -> String {
+    let mut blanked = String::with_capacity(source.len());
+    let mut last = 0;
 
-        let tags = search_tex("scriptTags", &value.text).ok_or("Could not find tags")?;
-        let tags: Vec<String> = Regex::new(r"\[(.*?)\]")
-            .unwrap()
-            .captures_iter(&tags)
-            .map(|c| c.get(1).unwrap().as_str().to_owned())
-            .collect();
+    let mut i = 0;
+    let bytes = source.as_bytes();
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'%' => {
+                let end = source[i..].find('\n').map_or(bytes.len(), |end| i + end);
+                blanked.push_str(&source[last..i]);
+                blanked.extend(std::iter::repeat_n(' ', end - i));
+                last = end;
+                i = end;
+            }
+            _ => i += 1,
+        }
+    }
+    blanked.push_str(&source[last.min(source.len())..]);
 
-        let date = search_tex("scriptDate", &value.text).ok_or("Could not find date")?;
-        let date = NaiveDate::parse_from_str(&date, "%d %b %Y").ok();
+    blanked
+}
 
-        let summary = search_tex("summary", &value.text).ok_or("Could not find summary")?;
+/// A command along with its arguments, as read from a list of tokens by `read_command`.
+struct Invocation<'a> {
+    /// The name of the command (without its backslash).
+    name: &'a str,
 
-        // Handle the character processing
-        let re = Regex::new(r"\\character\{(?P<name>.*?)\}\s*\{(?P<desc>.*?)\}").unwrap();
-        let characters: Vec<Character> = re
-            .captures_iter(&value.text)
-            .map(|capture| {
-                let name = Tex::unescaped(&capture["name"]);
-                let description = Tex::unescaped(&capture["desc"]);
-                Character::new(&name, &description)
-            })
-            .collect();
+    /// The contents of its optional argument, if it has one.
+    optional: Option<&'a str>,
 
-        // Find the start of the actual script part. It'll be after \clearpage
-        let index = match Regex::new(r"\\clearpage").unwrap().find(&value.text) {
-            None => 0,
-            Some(m) => m.end(),
-        };
-        let text = &value.text[index..].replace(r"\end{document}", "");
+    /// The contents of each of its `{...}` arguments, positioned within the source (without the braces).
+    args: Vec<Positioned<&'a str>>,
 
-        let mut paragraphs: Vec<TextContainer> = Vec::new();
-        for line in text.split("\n").filter(|line| !line.is_empty()) {
-            let tex = Tex::from(line);
-            let container = TextContainer::try_from(&tex).map_err(|err| {
-                format!(
-                    "[Script::try_from<&Tex>] Could not parse line: \"{}\" — via: {}",
-                    line, err
-                )
-            });
+    /// The whole of the command and its arguments within the source.
+    range: SourceRange,
+}
 
-            match container {
-                Ok(c) => paragraphs.push(c),
-                Err(e) => warn!("{e}. Skipping container.", e=e)
-            }            
-        }
+/// Read the command at the given index of the tokens, along with its arguments: an optional argument
+/// directly after it, and then each `{...}` group following on (which may be separated by whitespace, if
+/// `spaced`). Returns the command and the index of the token after it.
+fn read_command<'a>(
+    tokens: &[Positioned<Token<'a>>],
+    index: usize,
+    spaced: bool,
+) -> (Invocation<'a>, usize) {
+    let Token::Command(name) = tokens[index].value else {
+        unreachable!("read_command must start at a command");
+    };
+    let mut invocation = Invocation {
+        name,
+        optional: None,
+        args: Vec::new(),
+        range: tokens[index].range.clone(),
+    };
+
+    let mut i = index + 1;
+    if let Some(Token::Optional(optional)) = tokens.get(i).map(|t| t.value) {
+        invocation.optional = Some(optional);
+        invocation.range.end = tokens[i].range.end;
+        i += 1;
+    }
 
-        let script = Script {
-            author: author.to_owned(),
-            title: title.to_owned(),
-            series,
-            tags,
-            date,
-            characters,
-            summary: summary.to_owned(),
-            paragraphs,
+    loop {
+        // arguments may be spread out over several lines, e.g. \overlap{...}\n{...}
+        let next = match tokens.get(i) {
+            Some(token) if spaced && token.value.is_blank() => i + 1,
+            _ => i,
+        };
+        let Some(Positioned {
+            value: Token::Group(contents),
+            range,
+        }) = tokens.get(next)
+        else {
+            break;
         };
 
-        Ok(script)
+        invocation.args.push(Positioned {
+            value: contents,
+            range: range.start + 1..range.end - 1,
+        });
+        invocation.range.end = range.end;
+        i = next + 1;
     }
+
+    (invocation, i)
 }
 
-/** Partition the given string according to the given pattern.
-Like Regex::split, except we preserve the delimiters.
+/// Parse the source as a single command with its arguments, and nothing else besides whitespace.
+fn sole_command(source: &str) -> Option<Invocation<'_>> {
+    let tokens = tokenize(source);
+    let first = tokens.iter().position(|t| !t.value.is_blank())?;
+    if !matches!(tokens[first].value, Token::Command(_)) {
+        return None;
+    }
 
-# Arguments
+    let (invocation, next) = read_command(&tokens, first, true);
+    tokens[next..]
+        .iter()
+        .all(|t| t.value.is_blank())
+        .then_some(invocation)
+}
 
-* `delimit_re` - a `Regex` object that finds the partitioning blocks
-* `to_partition` - a string silce that we will be partitioning
+/// Split .tex source into the ranges of each of its top-level commands (with their arguments), skipping
+/// whitespace. Any other text is kept together up to the next command, so that it can be reported.
+fn top_level_commands(source: &str) -> Vec<SourceRange> {
+    let tokens = tokenize(source);
+    let mut ranges: Vec<SourceRange> = Vec::new();
+    let mut stray: Option<SourceRange> = None;
 
-# Returns
+    let mut i = 0;
+    while i < tokens.len() {
+        match tokens[i].value {
+            Token::Command(_) => {
+                ranges.extend(stray.take());
+                let (invocation, next) = read_command(&tokens, i, true);
+                ranges.push(invocation.range);
+                i = next;
+            }
+            token if token.is_blank() => i += 1,
+            _ => {
+                let range = tokens[i].range.clone();
+                stray = Some(match stray {
+                    Some(stray) => stray.start..range.end,
+                    // text running straight on from a command (e.g., an unbalanced brace) is part of it
+                    None => match ranges.last() {
+                        Some(last) if last.end == range.start => {
+                            ranges.pop().unwrap().start..range.end
+                        }
+                        _ => range,
+                    },
+                });
+                i += 1;
+            }
+        }
+    }
+    ranges.extend(stray);
+
+    ranges
+}
 
-* `Vec<&str>` - the individual components of the partition (lifetime matches with `to_partition`)
+/// The commands which only stand for text, and so are read as part of the normal text around them
+/// (see `Tex::unescaped`).
+const TEXT_COMMANDS: [&str; 13] = [
+    "ldots",
+    "textellipsis",
+    "beat",
+    "textemdash",
+    "textquote",
+    "restoregeometry",
+    "kaosmile",
+    "Tilde",
+    "emoji",
+    "href",
+    "textasciitilde",
+    "textasciicircum",
+    "textbackslash",
+];
+
+/// The commands which make a span of their own within a container's contents.
+const SPAN_COMMANDS: [&str; 5] = ["direct", "ul", "emph", "phonetic", "lang"];
+
+/** Read .tex text as plain text: any bare `{...}` groups are flattened into the text around them, as are
+the arguments of any span commands nested within it, and then it's unescaped (see `Tex::unescaped`).
 
 # Examples
 
 ```
-# use regex::Regex;
-# use lilscript::tex_handler::regex_partition;
-let s = "ABCCQBCPCCC";
-let re = Regex::new("C+").unwrap();
-let v = regex_partition(re, &s);
-assert_eq!(v, vec!["AB", "CC", "QB", "C", "P", "CCC"]);
-```
-
-If we instead used `Regex::split`:
-```
-# use regex::Regex;
-# let s = "ABCCQBCPCCC";
-# let re = Regex::new("C+").unwrap();
-let w: Vec<&str> = re.split(&s).collect();
-assert_eq!(w, vec!["AB", "QB", "P", ""]);  // we've lost the delimiters!
+# use lilscript::tex_handler::plain_text;
+assert_eq!(plain_text(r"a cue with {nested} braces\textellipsis{} and \ul{more}"), "a cue with nested braces... and more");
 ```
 */
-pub fn regex_partition<'h>(delimit_re: Regex, to_partition: &'h str) -> Vec<&'h str> {
-    let mut results: Vec<&'h str> = Vec::new();
+pub fn plain_text(source: &str) -> String {
+    fn flatten(source: &str, out: &mut String) {
+        let tokens = tokenize(source);
+        let mut i = 0;
+        while i < tokens.len() {
+            match tokens[i].value {
+                Token::Group(contents) => {
+                    flatten(contents, out);
+                    i += 1;
+                }
+                Token::Command(name) if SPAN_COMMANDS.contains(&name) => {
+                    let (invocation, next) = read_command(&tokens, i, false);
+                    for arg in &invocation.args {
+                        out.push(' ');
+                        flatten(arg.value, out);
+                        out.push(' ');
+                    }
+                    i = next;
+                }
+                Token::Command(_) => {
+                    // anything else (e.g., \textellipsis{}) is left for unescaping
+                    let (invocation, next) = read_command(&tokens, i, false);
+                    out.push_str(&source[invocation.range]);
+                    i = next;
+                }
+                Token::Optional(text) | Token::Text(text) => {
+                    out.push_str(text);
+                    i += 1;
+                }
+            }
+        }
+    }
 
-    let mut i = 0;
-    delimit_re.find_iter(&to_partition).for_each(|m| {
-        let before = &to_partition[i..m.start()];
-        let block = m.as_str();
+    let mut flattened = String::new();
+    flatten(source, &mut flattened);
+    Tex::unescaped(&flattened)
+}
 
-        results.push(before);
-        results.push(block);
+/// Parse the contents of a container into its spans, each positioned within the contents: the text between
+/// any span commands (`\direct{...}`, etc.) is normal text.
+fn parse_spans(contents: &str) -> Result<Vec<Positioned<TextSpan>>, String> {
+    let tokens = tokenize(contents);
+    let mut spans: Vec<Positioned<TextSpan>> = Vec::new();
+
+    // the normal text which hasn't yet been made into a span
+    let mut pending: Option<SourceRange> = None;
+    let flush = |pending: &mut Option<SourceRange>, spans: &mut Vec<Positioned<TextSpan>>| {
+        if let Some(range) = pending.take() {
+            let text = plain_text(&contents[range.clone()]);
+            if !text.is_empty() {
+                spans.push(Positioned {
+                    value: TextSpan::normal(&text),
+                    range,
+                });
+            }
+        }
+    };
 
-        i = m.end();
-    });
+    let mut i = 0;
+    while i < tokens.len() {
+        let (range, next) = match tokens[i].value {
+            Token::Command(name) if SPAN_COMMANDS.contains(&name) => {
+                flush(&mut pending, &mut spans);
+
+                let (invocation, next) = read_command(&tokens, i, false);
+                let args: Vec<String> = invocation
+                    .args
+                    .iter()
+                    .map(|arg| plain_text(arg.value))
+                    .collect();
+                let span = match (name, args.as_slice()) {
+                    ("direct", [arg]) => TextSpan::inline(arg),
+                    ("ul" | "emph", [arg]) => TextSpan::emphasis(arg),
+                    ("phonetic", [word, pronunciation]) => TextSpan::phonetic(word, pronunciation),
+                    ("lang", [lang, arg]) => TextSpan::normal(arg).with_lang(lang),
+                    _ => return Err(format!("unparsable TeX command: {:?}", name)),
+                };
+
+                spans.push(Positioned {
+                    value: span,
+                    range: invocation.range,
+                });
+                i = next;
+                continue;
+            }
+            Token::Command(name) => {
+                let (invocation, next) = read_command(&tokens, i, false);
+                // a custom command without arguments (e.g., `\sigh{}`) is kept as text, for `Tex::substituted`
+                let is_text = TEXT_COMMANDS.contains(&name)
+                    || !name.starts_with(|c: char| c.is_ascii_alphabetic())
+                    || invocation.args.iter().all(|arg| arg.value.is_empty());
+                if !is_text {
+                    return Err(format!("unparsable TeX command: {:?}", name));
+                }
+                (invocation.range, next)
+            }
+            _ => (tokens[i].range.clone(), i + 1),
+        };
 
-    // get anything after the last delimiter
-    let tail = &to_partition[i..];
-    if !tail.is_empty() {
-        results.push(tail);
+        pending = Some(match pending {
+            Some(pending) => pending.start..range.end,
+            None => range,
+        });
+        i = next;
     }
+    flush(&mut pending, &mut spans);
 
-    results
+    Ok(spans)
 }
 
-/** Search a string of .tex formatted text for the value corresponding to a particular function.
+impl TryFrom<&Tex> for TextContainer {
+    type Error = String;
 
-# Arguments
+    fn try_from(value: &Tex) -> Result<Self, Self::Error> {
+        value.parse_container(&ParseOptions::default())
+    }
+}
 
-- `command_key` - a string slice of the command that we're searching for
-- `string` - a string slice of the text to search
+impl Tex {
+    /** Parse a single line of the script's body into a TextContainer.
 
-# Return
+    # Arguments
 
-- `Some(value)` if the command was found
-- `None` otherwise
+    * `options` - controls whether questionable input is warned about or rejected
 
-# Examples
-```
-# use lilscript::tex_handler::search_tex;
-let s = r"This is some text with a \randomCommand{6} and another \differentCommand{-3px}.";
+    # Examples
 
-let value = search_tex("randomCommand", s).unwrap();
-assert_eq!(value, "6");
+    ```
+    # use lilscript::{script::ParseOptions, tex_handler::Tex};
+    let tex = Tex::from(r"\whisper{Hello.}");
+    assert!(tex.parse_container(&ParseOptions::default()).is_ok());
+    assert!(tex.parse_container(&ParseOptions { strict: true, ..Default::default() }).is_err());
+    ```
+    */
+    pub fn parse_container(&self, options: &ParseOptions) -> Result<TextContainer, String> {
+        // \command[optional speaker, key=value, ...]{contents}
+        let invocation = sole_command(&self.text)
+            .ok_or_else(|| format!("Invalid tex line: {}", Tex::unescaped(&self.text)))?;
+
+        // \overlap{<cues>}{<container>} marks cues which run concurrently with a container
+        if invocation.name == "overlap" {
+            return parse_overlap(&invocation, options);
+        }
 
-let value = search_tex("differentCommand", s).unwrap();
-assert_eq!(value, "-3px");
-```
+        let [contents] = invocation.args.as_slice() else {
+            return Err(format!("Invalid tex line: {}", Tex::unescaped(&self.text)));
+        };
+        let (speaker, attributes) = parse_optional_argument(
+            &Tex::unescaped(invocation.optional.unwrap_or_default()),
+            options,
+        )?;
+
+        let kind = match invocation.name {
+            "spoken" => ContainerKind::Spoken,
+            "stagedir" => ContainerKind::StageDir,
+            "listener" => ContainerKind::ListenerDialogue,
+            "sfx" => ContainerKind::Sfx,
+            "plaintext" => ContainerKind::PlainText,
+            "marker" => ContainerKind::Marker,
+            command => {
+                options.warn_or_fail(format!(
+                    "Could not identify container kind for command: {}",
+                    command
+                ))?;
+                ContainerKind::PlainText
+            }
+        };
+
+        // the contents are normal text, with any span commands (e.g., \direct{a direction}) split out
+        let spans = parse_spans(contents.value).map_err(|e| {
+            format!(
+                "[TextContainer::try_from<&Tex>] Could not parse span: {}",
+                e
+            )
+        })?;
+
+        let mut container =
+            TextContainer::with_spans(kind, spans.into_iter().map(|span| span.value).collect());
+        container.speaker = speaker;
+        container.attributes = attributes;
+        Ok(container)
+    }
+}
+
+/** Parse the optional argument of a container command: any `key=value` entries (separated by commas)
+are attributes of the container, and whatever else there is names its speaker.
+
+# Examples
 
 ```
-# use lilscript::tex_handler::search_tex;
-# let s = r"This is some text with a \randomCommand{6} and another \differentCommand{-3px}.";
-let value = search_tex("nonexistentCommand", s);
-assert!(value.is_none());
+# use lilscript::{script::ParseOptions, tex_handler::Tex};
+let tex = Tex::from(r"\sfx[category=rain, intensity=soft]{rain on a window}");
+let cue = tex.parse_container(&ParseOptions::default()).unwrap();
+assert_eq!(cue.speaker, None);
+assert_eq!(cue.attributes["category"], "rain");
+assert_eq!(cue.attributes["intensity"], "soft");
+
+let tex = Tex::from(r"\spoken[Witch, mood=wry]{Hello.}");
+let line = tex.parse_container(&ParseOptions::default()).unwrap();
+assert_eq!(line.speaker.as_deref(), Some("Witch"));
+assert_eq!(line.attributes["mood"], "wry");
 ```
 */
-pub fn search_tex<'a>(command_key: &str, string: &'a str) -> Option<&'a str> {
-    let pattern = format!(r"\\{x}\{{(?P<value>.*?)\}}", x = command_key);
-    let re = Regex::new(&pattern);
+fn parse_optional_argument(
+    argument: &str,
+    options: &ParseOptions,
+) -> Result<(Option<String>, BTreeMap<String, String>), String> {
+    let key_pattern = Regex::new(r"^[A-Za-z][\w-]*$").unwrap();
+    let mut attributes: BTreeMap<String, String> = BTreeMap::new();
+
+    // a speaker may contain commas itself, so only split when there are any attributes to split out
+    if !argument.contains('=') {
+        let speaker = argument.trim();
+        return Ok((
+            (!speaker.is_empty()).then(|| speaker.to_owned()),
+            attributes,
+        ));
+    }
 
-    if re.is_err() {
-        // the regex failed to be initialised
-        return None;
+    let mut speaker: Option<String> = None;
+    for entry in argument.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        match entry.split_once('=') {
+            Some((key, value)) if key_pattern.is_match(key.trim()) => {
+                attributes.insert(key.trim().to_owned(), value.trim().to_owned());
+            }
+            Some(_) => options.warn_or_fail(format!("Invalid attribute name in \"{}\"", entry))?,
+            None if speaker.is_none() => speaker = Some(entry.to_owned()),
+            None => options.warn_or_fail(format!(
+                "Expected key=value for \"{}\", as the speaker is already {:?}",
+                entry,
+                speaker.as_deref().unwrap_or_default()
+            ))?,
+        }
     }
 
-    let re = re.unwrap();
-    match re.captures(string) {
-        Some(captures) => Some(captures.name("value").unwrap().as_str()),
-        None => None,
+    Ok((speaker, attributes))
+}
+
+/// Parse an `\overlap{<cues>}{<container>}` command into a compound container.
+fn parse_overlap(invocation: &Invocation, options: &ParseOptions) -> Result<TextContainer, String> {
+    let [cues, main] = invocation.args.as_slice() else {
+        return Err(format!(
+            "Invalid overlap: expected two arguments, but found {}",
+            invocation.args.len()
+        ));
+    };
+
+    let mut container = Tex::from(main.value).parse_container(options)?;
+    for cue in top_level_commands(cues.value) {
+        container
+            .overlaps
+            .push(Tex::from(&cues.value[cue]).parse_container(options)?);
     }
+
+    Ok(container)
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
+/// The byte offset of `part` within `whole`, of which it must be a subslice.
+fn offset_in(whole: &str, part: &str) -> usize {
+    part.as_ptr() as usize - whole.as_ptr() as usize
+}
 
-    #[test]
-    fn test_text_span_parse_normal() {
-        let tex = Tex::from("This is some text");
-        let span = TextSpan::try_from(&tex).unwrap();
+/// Position a parsed container within the source it was parsed from, which starts at the byte offset `start`.
+fn position_container(container: TextContainer, source: &str, start: usize) -> ContainerNode {
+    let trimmed = source.trim();
+    let start = start + offset_in(source, trimmed);
+    let range = start..start + trimmed.len();
+
+    let TextContainer {
+        kind,
+        spans,
+        overlaps,
+        speaker,
+        attributes,
+        commentary: _,
+    } = container;
+
+    // \overlap{<cues>}{<container>}: position the cues and the container within their own arguments
+    let (contents, cues) = match sole_command(trimmed) {
+        Some(invocation) if invocation.name == "overlap" && invocation.args.len() == 2 => {
+            let cues = invocation.args[0].value;
+            let cues = top_level_commands(cues)
+                .into_iter()
+                .map(|range| &cues[range])
+                .collect();
+            (invocation.args[1].value, cues)
+        }
+        _ => (trimmed, Vec::new()),
+    };
+    let overlaps = overlaps
+        .into_iter()
+        .enumerate()
+        .map(|(i, cue)| {
+            let source = cues.get(i).copied().unwrap_or(trimmed);
+            position_container(cue, source, start + offset_in(trimmed, source))
+        })
+        .collect();
+
+    // \command[optional speaker]{contents}
+    let contents = match sole_command(contents) {
+        Some(invocation) if invocation.args.len() == 1 => invocation.args[0].value,
+        _ => contents,
+    };
+
+    ContainerNode {
+        kind,
+        speaker,
+        attributes,
+        spans: position_spans(spans, contents, start + offset_in(trimmed, contents)),
+        overlaps,
+        commentary: Vec::new(),
+        range,
+    }
+}
 
-        let expected = TextSpan::normal("This is some text");
-        assert_eq!(span, expected);
+/// Position the parsed spans of a container within the contents it was parsed from, which start at the byte offset `start`.
+///
+/// The spans are split in the same way as in `Tex::parse_container`; should that not line up with the
+/// spans which were parsed, each span is positioned at the whole of the contents instead.
+fn position_spans(spans: Vec<TextSpan>, contents: &str, start: usize) -> Vec<Positioned<TextSpan>> {
+    let mut pieces: Vec<SourceRange> = parse_spans(contents)
+        .map(|spans| spans.into_iter().map(|span| span.range).collect())
+        .unwrap_or_default();
+
+    if pieces.len() != spans.len() {
+        pieces = vec![0..contents.len(); spans.len()];
     }
 
-    #[test]
-    fn test_text_span_parse_inline() {
-        let tex = Tex::from("\\direct{an inline!}");
-        let span = TextSpan::try_from(&tex).unwrap();
+    spans
+        .into_iter()
+        .zip(pieces)
+        .map(|(value, piece)| Positioned {
+            value,
+            range: start + piece.start..start + piece.end,
+        })
+        .collect()
+}
 
-        let expected = TextSpan::inline("an inline!");
-        assert_eq!(span, expected);
-    }
+/** Split off the balanced `{...}` groups at the start of the given string.
 
-    #[test]
-    fn test_text_span_parse_emphasis() {
-        let tex = Tex::from("\\ul{EMPHASIS}");
-        let span = TextSpan::try_from(&tex).unwrap();
+# Arguments
 
-        let expected = TextSpan::emphasis("EMPHASIS");
-        assert_eq!(span, expected);
-    }
+* `s` - a string slice which (after any whitespace) starts with the groups
 
-    #[test]
-    fn test_text_container_parse_one_span() {
-        let tex = Tex::from("\\spoken{This is some text.}");
-        let container = TextContainer::try_from(&tex).unwrap();
+# Returns
 
-        let spans = vec![TextSpan::normal("This is some text.")];
-        let expected = TextContainer {
-            kind: ContainerKind::Spoken,
-            spans,
-        };
+* `(Vec<&str>, &str)` - the contents of each group (without the outer braces), and the remainder of the string
 
-        assert_eq!(container, expected);
-    }
+# Examples
 
-    #[test]
-    fn test_text_container_parse_multiple_spans() {
-        let tex = Tex::from(
-            "\\spoken{This is some text. \\direct{an inline direction} And some more dialogue.}",
-        );
-        let container = TextContainer::try_from(&tex).unwrap();
+```
+# use lilscript::tex_handler::brace_groups;
+let (groups, rest) = brace_groups(r"{\sfx{rain}} {\spoken{Hello.}} trailing");
+assert_eq!(groups, vec![r"\sfx{rain}", r"\spoken{Hello.}"]);
+assert_eq!(rest, " trailing");
 
-        let spans = vec![
-            TextSpan::normal("This is some text."),
+let (groups, _) = brace_groups(r"{Curly \{braces\}} {x}");
+assert_eq!(groups, vec![r"Curly \{braces\}", "x"]);
+```
+*/
+pub fn brace_groups(s: &str) -> (Vec<&str>, &str) {
+    let mut groups: Vec<&str> = Vec::new();
+    let mut rest = s;
+
+    while rest.trim_start().starts_with('{') {
+        let group = rest.trim_start();
+
+        // escaped braces (`\{`) don't count towards the nesting
+        match group_end(group, 0) {
+            Some(end) => {
+                groups.push(&group[1..end]);
+                rest = &group[end + 1..];
+            }
+            // unbalanced: leave it for the caller to deal with
+            None => break,
+        }
+    }
+
+    (groups, rest)
+}
+
+impl TryFrom<&Tex> for TextSpan {
+    type Error = String;
+
+    fn try_from(value: &Tex) -> Result<Self, Self::Error> {
+        match parse_spans(&value.text)?.as_slice() {
+            // just a block of text, or a single command
+            [span] => Ok(span.value.clone()),
+            [] => Ok(TextSpan::normal("")),
+            _ => Err(format!("expected a single span: {:?}", value.text)),
+        }
+    }
+}
+
+impl TryFrom<&Tex> for Script {
+    type Error = String;
+
+    /// Attempt to create a Script from the give .tex file.
+    fn try_from(value: &Tex) -> Result<Self, Self::Error> {
+        value.parse_script(&ParseOptions::default())
+    }
+}
+
+impl Tex {
+    /** Parse the whole .tex file into a Script.
+
+    # Arguments
+
+    * `options` - controls whether questionable input is warned about or rejected
+
+    # Return
+
+    * `Ok(Script)` if the header could be found and (in strict mode) the whole file is conformant;
+    * `Err(_)` otherwise
+    */
+    pub fn parse_script(&self, options: &ParseOptions) -> Result<Script, String> {
+        self.parse_document(options).map(Script::from)
+    }
+
+    /** Parse a .tex file which may hold several scripts (e.g., a whole series) into each of them;
+    see `Tex::split`.
+
+    # Return
+
+    * `Ok(Vec<Script>)` - every script in the file, in order (just the one, for most files)
+    * `Err(String)` - if any of the scripts couldn't be parsed
+    */
+    pub fn parse_scripts(&self, options: &ParseOptions) -> Result<Vec<Script>, String> {
+        let sources = self.split();
+        let count = sources.len();
+
+        sources
+            .iter()
+            .enumerate()
+            .map(|(i, source)| match count {
+                1 => source.parse_script(options),
+                _ => source
+                    .parse_script(options)
+                    .map_err(|e| format!("script {} of {}: {}", i + 1, count, e)),
+            })
+            .collect()
+    }
+
+    /** Split a .tex file holding several scripts into a separate source for each, so that each can
+    be parsed (or checked, etc.) on its own.
+
+    A new script starts at each repeated metadata block (i.e., each `\renewcommand{\SceneName}{...}`
+    line after the first), and at each `\part{Title}` line within a body, which starts a script with the
+    same metadata as the one before except for its title. Each source keeps the shared preamble.
+
+    # Examples
+
+    ```
+    # use lilscript::{script::ParseOptions, tex_handler::Tex};
+    let source = r"\documentclass{article}
+    \renewcommand{\SceneName}{First Night}
+    \scriptAuthor{lilellia}
+    \scriptSeries{Moonrise (Part 1)}
+    \scriptTags{}
+    \scriptDate{}
+    \summary{}
+    \begin{document}
+    \clearpage
+    \spoken{Hello.}
+    \part{Second Night}
+    \spoken{Hello again.}
+    \renewcommand{\SceneName}{Last Night}
+    \scriptAuthor{lilellia}
+    \scriptSeries{Moonrise (Part 3)}
+    \scriptTags{}
+    \scriptDate{}
+    \summary{}
+    \clearpage
+    \spoken{Goodbye.}
+    \end{document}";
+
+    let tex = Tex::from(source);
+    assert_eq!(tex.split().len(), 3);
+
+    let scripts = tex.parse_scripts(&ParseOptions::default()).unwrap();
+    let titles: Vec<&str> = scripts.iter().map(|s| s.title.as_str()).collect();
+    assert_eq!(titles, vec!["First Night", "Second Night", "Last Night"]);
+    assert_eq!(scripts[1].series, scripts[0].series);
+    assert_eq!(scripts[1].paragraphs.len(), 1);
+    assert_eq!(scripts[2].series.to_string(), "Moonrise (Part 3)");
+    ```
+    */
+    pub fn split(&self) -> Vec<Tex> {
+        let text = &self.text;
+        let header = Regex::new(r"(?m)^[ \t]*\\renewcommand\{\\SceneName\}").unwrap();
+        let part = Regex::new(r"(?m)^[ \t]*\\part\*?\{(.*)\}[ \t]*$").unwrap();
+
+        // a commented-out header or \part doesn't start a script
+        let blanked = blank_comments(text);
+        let headers: Vec<usize> = header.find_iter(&blanked).map(|m| m.start()).collect();
+        let Some(&first) = headers.first() else {
+            return vec![Tex::from(text.as_str())];
+        };
+        let preamble = &text[..first];
+
+        // every point at which a script starts, along with the title given by a \part (if any)
+        let mut starts: Vec<(usize, Option<&str>)> = headers.iter().map(|&h| (h, None)).collect();
+        starts.extend(
+            part.captures_iter(&blanked)
+                .filter(|caps| caps.get(0).unwrap().start() > first)
+                .map(|caps| {
+                    let line = caps.get(0).unwrap();
+                    (line.start(), Some(caps.get(1).unwrap().as_str()))
+                }),
+        );
+        starts.sort_by_key(|&(start, _)| start);
+        if starts.len() == 1 {
+            return vec![Tex::from(text.as_str())];
+        }
+
+        let mut sources: Vec<Tex> = Vec::with_capacity(starts.len());
+
+        // the metadata block of the latest script, up to and including its \clearpage
+        let mut metadata = "";
+        for (i, &(start, title)) in starts.iter().enumerate() {
+            let end = starts.get(i + 1).map_or(text.len(), |&(next, _)| next);
+            let chunk = &text[start..end];
+
+            let source = match title {
+                None => {
+                    metadata = chunk
+                        .find(r"\clearpage")
+                        .map_or(chunk, |c| &chunk[..c + r"\clearpage".len()]);
+                    format!("{}{}", preamble, chunk)
+                }
+                Some(title) => {
+                    // everything after the \part line is the body
+                    let body = chunk.split_once('\n').map_or("", |(_, body)| body);
+                    let metadata = search_tex(r"renewcommand\{\\SceneName\}", metadata).map_or(
+                        metadata.to_owned(),
+                        |old| {
+                            metadata.replacen(
+                                &format!(r"\renewcommand{{\SceneName}}{{{}}}", old),
+                                &format!(r"\renewcommand{{\SceneName}}{{{}}}", title),
+                                1,
+                            )
+                        },
+                    );
+                    format!("{}{}\n{}", preamble, metadata, body)
+                }
+            };
+
+            sources.push(Tex { text: source });
+        }
+
+        sources
+    }
+
+    /** Parse the .tex file into a `Document`: the script, along with where in the source each part of it is.
+
+    # Arguments
+
+    * `options` - controls whether questionable input is warned about or rejected
+
+    # Return
+
+    * `Ok(Document)` - the parsed document, whose ranges are byte offsets into `self.text`
+    * `Err(String)` - if the header is incomplete, or (in strict mode) anything couldn't be parsed
+    */
+    pub fn parse_document(&self, options: &ParseOptions) -> Result<Document, String> {
+        // comments are blanked out, rather than removed, so that positions still line up with the source
+        let value = Tex::from(blank_comments(&self.text));
+
+        // every field of the header (but the target) must be present, even if it's left empty, so that a
+        // malformed header (e.g., a misspelt command or an unclosed brace) isn't mistaken for an empty field
+        let required = |pattern: &str, command: &str| {
+            search_tex(pattern, &value.text).ok_or_else(|| {
+                format!(
+                    "Malformed header: could not find a complete {}{{...}}",
+                    command
+                )
+            })
+        };
+
+        // try to process the header information
+        let title = required(r"renewcommand\{\\SceneName\}", r"\renewcommand{\SceneName}")
+            .map(plain_text)?;
+        let author = required("scriptAuthor", r"\scriptAuthor").map(plain_text)?;
+
+        let series = required("scriptSeries", r"\scriptSeries")?;
+        let series = match SeriesEntry::parse(&plain_text(series)) {
+            Ok(series) => series,
+            Err(e) => {
+                options.warn_or_fail(e)?;
+                SeriesEntry::default()
+            }
+        };
+
+        let tags = required("scriptTags", r"\scriptTags")?;
+        let re = Regex::new(r"\[(.*?)\]").unwrap();
+        let untagged = re.replace_all(tags, "");
+        if !untagged.trim().is_empty() {
+            options.warn_or_fail(format!(
+                "Could not parse tags \"{}\": expected each tag in brackets, e.g. \"[F4A] [comfort]\"",
+                tags.trim()
+            ))?;
+        }
+        let tags: Vec<String> = re
+            .captures_iter(tags)
+            .map(|c| c.get(1).unwrap().as_str().to_owned())
+            .collect();
+
+        let date = required("scriptDate", r"\scriptDate")?;
+        let date = match date.trim() {
+            "" | "—" | "\\textemdash" => None,
+            date => match parse_date(date, options.date_order) {
+                Ok(date) => Some(date),
+                Err(e) => {
+                    options.warn_or_fail(e)?;
+                    None
+                }
+            },
+        };
+
+        let summary = required("summary", r"\summary").map(plain_text)?;
+
+        // the target word count is optional
+        let target =
+            match search_tex("scriptTarget", &value.text).map(|t| t.trim().replace(',', "")) {
+                None => None,
+                Some(target) => match target.parse::<usize>() {
+                    Ok(target) => Some(target),
+                    Err(_) => {
+                        options.warn_or_fail(format!(
+                            "Could not parse target word count \"{}\": expected a number",
+                            target
+                        ))?;
+                        None
+                    }
+                },
+            };
+
+        // Handle the character processing: \character[fields]{name}{description}
+        let tokens = tokenize(&value.text);
+        let mut characters: Vec<Character> = Vec::new();
+        for (i, token) in tokens.iter().enumerate() {
+            if token.value != Token::Command("character") {
+                continue;
+            }
+            let (invocation, _) = read_command(&tokens, i, true);
+            let [name, description, ..] = invocation.args.as_slice() else {
+                options.warn_or_fail(format!(
+                    "Malformed character on line {}: expected \\character[fields]{{name}}{{description}}",
+                    line_of(&value.text, token.range.start)
+                ))?;
+                continue;
+            };
+
+            let name = plain_text(name.value);
+            let mut character = Character::new(&name, &plain_text(description.value));
+
+            // the optional fields, e.g. \character[pronouns=she/her; age=30s]{...}{...}
+            let fields = invocation.optional.unwrap_or_default();
+            for field in fields.split(';').filter(|field| !field.trim().is_empty()) {
+                let result = match field.split_once('=') {
+                    Some((key, value)) => character.set(key, &Tex::unescaped(value)),
+                    None => Err(format!(
+                        "Invalid character field \"{}\": expected key=value",
+                        field.trim()
+                    )),
+                };
+                if let Err(e) = result {
+                    options.warn_or_fail(format!("{} (for character {})", e, name))?;
+                }
+            }
+
+            characters.push(character);
+        }
+
+        // Find the start of the actual script part. It'll be after \clearpage
+        let index = match Regex::new(r"\\clearpage").unwrap().find(&value.text) {
+            None => 0,
+            Some(m) => m.end(),
+        };
+        let header_start = value
+            .text
+            .find(r"\renewcommand{\SceneName}")
+            .unwrap_or(0)
+            .min(index);
+
+        // each command of the body (which may run over several lines) is a line of the script,
+        // positioned within the source so that the containers can be positioned
+        let mut lines: Vec<(SourceRange, String)> = Vec::new();
+        for range in top_level_commands(&value.text[index..]) {
+            let range = index + range.start..index + range.end;
+            let line = &value.text[range.clone()];
+            if line != r"\end{document}" {
+                lines.push((range, line.to_owned()));
+            }
+        }
+
+        // each line is parsed independently, so they can be parsed in parallel (in order)
+        let containers: Vec<Result<BodyLine, Positioned<String>>> = lines
+            .par_iter()
+            .map(|(range, line)| {
+                if let Some(text) = parse_commentary(line) {
+                    return Ok(BodyLine::Commentary(Positioned {
+                        value: text,
+                        range: range.clone(),
+                    }));
+                }
+
+                let container =
+                    Tex::from(line.as_str())
+                        .parse_container(options)
+                        .map_err(|err| Positioned {
+                            value: format!(
+                                "[Script::try_from<&Tex>] Could not parse line: \"{}\" — via: {}",
+                                line, err
+                            ),
+                            range: range.clone(),
+                        })?;
+                Ok(BodyLine::Container(position_container(
+                    container,
+                    &value.text[range.clone()],
+                    range.start,
+                )))
+            })
+            .collect();
+
+        let mut sections: Vec<Section> = Vec::new();
+        let mut skipped: Vec<Positioned<String>> = Vec::new();
+        for container in containers {
+            let container = match container {
+                Ok(BodyLine::Container(c)) => c,
+                Ok(BodyLine::Commentary(text)) => {
+                    // commentary belongs to the container before it
+                    match sections.last_mut() {
+                        Some(section) if !section.containers.is_empty() => {
+                            section.range.end = text.range.end;
+                            section.containers.last_mut().unwrap().commentary.push(text);
+                        }
+                        _ => options.warn_or_fail(format!(
+                            "Commentary \"{}\" comes before any line of the script, so has nothing to annotate",
+                            text.value
+                        ))?,
+                    }
+                    continue;
+                }
+                Err(e) if options.strict => return Err(e.value),
+                Err(e) => {
+                    options.warn(&format!("{}. Skipping container.", e.value));
+                    skipped.push(e);
+                    continue;
+                }
+            };
+
+            push_container(&mut sections, container);
+        }
+
+        let mut metadata = Script {
+            author,
+            title,
+            series,
+            tags,
+            date,
+            characters,
+            summary,
+            target,
+            paragraphs: Vec::new(),
+        };
+
+        if options.normalize_tags {
+            metadata.normalize_tags(&options.tag_aliases);
+        }
+
+        Ok(Document {
+            metadata,
+            header: header_start..index,
+            sections,
+            skipped,
+            range: 0..value.text.len(),
+        })
+    }
+}
+
+/// A single line of the body of a .tex script: either a container, or commentary on the container before it.
+enum BodyLine {
+    Container(ContainerNode),
+    Commentary(Positioned<String>),
+}
+
+/** Parse a `\commentary{...}` line of the body of a .tex script, returning its text.
+
+# Examples
+
+```
+# use lilscript::tex_handler::parse_commentary;
+assert_eq!(parse_commentary(r"\commentary{Written after a long walk.}"), Some("Written after a long walk.".to_owned()));
+assert_eq!(parse_commentary(r"\spoken{Hello.}"), None);
+```
+*/
+pub fn parse_commentary(line: &str) -> Option<String> {
+    match sole_command(line)? {
+        Invocation {
+            name: "commentary",
+            optional: None,
+            args,
+            ..
+        } if args.len() == 1 => Some(plain_text(args[0].value)),
+        _ => None,
+    }
+}
+
+/** Find the lines of a leniently-parsed document which would have been rejected in strict mode
+(e.g., an unknown command, read as plain text), along with why.
+
+# Arguments
+
+* `text` - the source which the document was parsed from
+* `document` - the parsed document
+* `options` - the options it was parsed with
+
+# Examples
+
+```
+# use lilscript::{script::ParseOptions, tex_handler::{lenient_lines, Tex}};
+let text = "\\renewcommand{\\SceneName}{Title}\n\\scriptAuthor{lilellia}\n\\scriptSeries{}\n\\scriptTags{}\n\\scriptDate{}\n\\summary{}\n\\clearpage\n\\spoken{Hi.}\n\\whisper{Psst.}";
+let options = ParseOptions { quiet: true, ..Default::default() };
+let document = Tex::from(text).parse_document(&options).unwrap();
+
+let problems = lenient_lines(text, &document, &options);
+assert_eq!(problems.len(), 1);
+assert_eq!(&text[problems[0].range.clone()], "\\whisper{Psst.}");
+```
+*/
+pub fn lenient_lines(
+    text: &str,
+    document: &Document,
+    options: &ParseOptions,
+) -> Vec<Positioned<String>> {
+    let strict = ParseOptions {
+        strict: true,
+        ..options.clone()
+    };
+
+    document
+        .containers()
+        .filter_map(|node| {
+            let line = &text[node.range.clone()];
+            match Tex::from(line).parse_container(&strict) {
+                Ok(_) => None,
+                Err(value) => Some(Positioned {
+                    value,
+                    range: node.range.clone(),
+                }),
+            }
+        })
+        .collect()
+}
+
+/** Partition the given string according to the given pattern.
+Like Regex::split, except we preserve the delimiters.
+
+# Arguments
+
+* `delimit_re` - a `Regex` object that finds the partitioning blocks
+* `to_partition` - a string silce that we will be partitioning
+
+# Returns
+
+* `Partition` - an iterator over the non-empty components of the partition, as `(is_delimiter, component)` pairs
+  (lifetime matches with `to_partition`)
+
+# Examples
+
+```
+# use regex::Regex;
+# use lilscript::tex_handler::regex_partition;
+let s = "ABCCQBCPCCC";
+let re = Regex::new("C+").unwrap();
+let v: Vec<(bool, &str)> = regex_partition(&re, s).collect();
+assert_eq!(
+    v,
+    vec![(false, "AB"), (true, "CC"), (false, "QB"), (true, "C"), (false, "P"), (true, "CCC")]
+);
+```
+
+If we instead used `Regex::split`:
+```
+# use regex::Regex;
+# let s = "ABCCQBCPCCC";
+# let re = Regex::new("C+").unwrap();
+let w: Vec<&str> = re.split(&s).collect();
+assert_eq!(w, vec!["AB", "QB", "P", ""]);  // we've lost the delimiters!
+```
+*/
+pub fn regex_partition<'r, 'h>(delimit_re: &'r Regex, to_partition: &'h str) -> Partition<'r, 'h> {
+    Partition {
+        matches: delimit_re.find_iter(to_partition),
+        haystack: to_partition,
+        position: 0,
+        delimiter: None,
+    }
+}
+
+/// An iterator over the components of a string partitioned by a regex; see `regex_partition`.
+pub struct Partition<'r, 'h> {
+    /// The delimiters still to be found.
+    matches: Matches<'r, 'h>,
+
+    /// The string being partitioned.
+    haystack: &'h str,
+
+    /// The position just after the last delimiter found.
+    position: usize,
+
+    /// A delimiter which has been found, but not yet yielded (as the text before it came first).
+    delimiter: Option<&'h str>,
+}
+
+impl<'h> Iterator for Partition<'_, 'h> {
+    type Item = (bool, &'h str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(delimiter) = self.delimiter.take() {
+                return Some((true, delimiter));
+            }
+
+            match self.matches.next() {
+                Some(m) => {
+                    let before = &self.haystack[self.position..m.start()];
+                    self.position = m.end();
+                    self.delimiter = Some(m.as_str());
+
+                    if !before.is_empty() {
+                        return Some((false, before));
+                    }
+                }
+                None => {
+                    // get anything after the last delimiter
+                    let tail = &self.haystack[self.position..];
+                    self.position = self.haystack.len();
+                    return (!tail.is_empty()).then_some((false, tail));
+                }
+            }
+        }
+    }
+}
+
+/** Search a string of .tex formatted text for the value corresponding to a particular function.
+
+# Arguments
+
+- `command_key` - a string slice of the command that we're searching for
+- `string` - a string slice of the text to search
+
+# Return
+
+- `Some(value)` if the command was found
+- `None` otherwise
+
+# Examples
+```
+# use lilscript::tex_handler::search_tex;
+let s = r"This is some text with a \randomCommand{6} and another \differentCommand{-3px}.";
+
+let value = search_tex("randomCommand", s).unwrap();
+assert_eq!(value, "6");
+
+let value = search_tex("differentCommand", s).unwrap();
+assert_eq!(value, "-3px");
+```
+
+```
+# use lilscript::tex_handler::search_tex;
+# let s = r"This is some text with a \randomCommand{6} and another \differentCommand{-3px}.";
+let value = search_tex("nonexistentCommand", s);
+assert!(value.is_none());
+```
+*/
+pub fn search_tex<'a>(command_key: &str, string: &'a str) -> Option<&'a str> {
+    let pattern = format!(r"\\{x}\{{", x = command_key);
+    let m = Regex::new(&pattern).ok()?.find(string)?;
+
+    // the value runs to the matching brace, so it may contain braces of its own
+    let (groups, _) = brace_groups(&string[m.end() - 1..]);
+    groups.first().copied()
+}
+
+/** Format the given tags as the value of a `\scriptTags{...}` command.
+
+# Examples
+
+```
+# use lilscript::tex_handler::tags_to_tex;
+let tags = vec!["f4a".to_owned(), "comfort".to_owned()];
+assert_eq!(tags_to_tex(&tags), "[f4a] [comfort]");
+```
+*/
+pub fn tags_to_tex(tags: &[String]) -> String {
+    tags.iter()
+        .map(|tag| format!("[{}]", tag))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/** Replace the tags of the given .tex source with the given tags, leaving everything else untouched.
+
+# Return
+
+* `Some(String)` - the updated source, if it has a `\scriptTags{...}` command;
+* `None` otherwise
+
+# Examples
+
+```
+# use lilscript::tex_handler::replace_tags;
+let s = "\\scriptAuthor{lilellia}\n\\scriptTags{[F4A] [Comfort]}\n";
+let tags = vec!["f4a".to_owned(), "comfort".to_owned()];
+assert_eq!(replace_tags(s, &tags).unwrap(), "\\scriptAuthor{lilellia}\n\\scriptTags{[f4a] [comfort]}\n");
+
+// the whole argument is replaced, even if it has braces nested within it
+let s = "\\scriptTags{[F4A] {[Comfort]} [{Rain}]}\n\\scriptDate{}";
+assert_eq!(replace_tags(s, &tags).unwrap(), "\\scriptTags{[f4a] [comfort]}\n\\scriptDate{}");
+assert_eq!(replace_tags("\\scriptAuthor{lilellia}", &tags), None);
+```
+*/
+pub fn replace_tags(text: &str, tags: &[String]) -> Option<String> {
+    let tokens = tokenize(text);
+    let index = tokens
+        .iter()
+        .position(|token| token.value == Token::Command("scriptTags"))?;
+    let (invocation, _) = read_command(&tokens, index, false);
+    // the argument is positioned without its braces, so the closing brace is just after it
+    let end = invocation.args.first()?.range.end + 1;
+
+    let replacement = format!(r"\scriptTags{{{}}}", tags_to_tex(tags));
+    Some(format!(
+        "{}{}{}",
+        &text[..invocation.range.start],
+        replacement,
+        &text[end..]
+    ))
+}
+
+/// The preamble written at the top of exported .tex files, defining the commands used by the script.
+pub const DEFAULT_PREAMBLE: &str = r"\documentclass{article}
+\usepackage{soul}
+
+\newcommand{\SceneName}{}
+\newcommand{\scriptAuthor}[1]{\def\theScriptAuthor{#1}}
+\newcommand{\scriptSeries}[1]{\def\theScriptSeries{#1}}
+\newcommand{\scriptTags}[1]{\def\theScriptTags{#1}}
+\newcommand{\scriptDate}[1]{\def\theScriptDate{#1}}
+\newcommand{\scriptTarget}[1]{\def\theScriptTarget{#1}}
+\newcommand{\summary}[1]{\def\theSummary{#1}}
+\newcommand{\character}[3][]{\par\noindent\textbf{#2} --- #3}
+
+\newcommand{\spoken}[2][]{\par\textbf{#2}}
+\newcommand{\listener}[2][]{\par\textit{#2}}
+\newcommand{\stagedir}[2][]{\par[\textit{#2}]}
+\newcommand{\sfx}[2][]{\par[sfx: \textit{#2}]}
+\newcommand{\plaintext}[2][]{\par #2}
+\newcommand{\marker}[2][]{}
+\newcommand{\commentary}[1]{}
+\newcommand{\overlap}[2]{#2 #1}
+\newcommand{\direct}[1]{\textit{(#1)}}
+\newcommand{\phonetic}[2]{#1}
+\newcommand{\lang}[2]{#2}
+\newcommand{\emoji}[1]{:#1:}
+";
+
+/// The header written after the preamble of exported .tex files, holding the script's metadata and characters.
+/// This is the structure which the parser expects, so it shouldn't be changed lightly.
+pub const DEFAULT_HEADER: &str = r"\renewcommand{\SceneName}{{{title}}}
+\scriptAuthor{{{author}}}
+\scriptSeries{{{series}}}
+\scriptTags{{{tags}}}
+\scriptDate{{{date}}}
+\scriptTarget{{{target}}}
+\summary{{{summary}}}
+\begin{document}
+{{characters}}\clearpage
+";
+
+/// Options controlling how scripts are exported to .tex.
+#[derive(Debug, Clone)]
+pub struct TexOptions {
+    /// The template for the preamble written at the top of the file (see `fill_template`).
+    pub preamble: String,
+
+    /// The template for the header which follows the preamble, up to the first line of the script
+    /// (see `write_header`).
+    pub header: String,
+
+    /// The commands emitted for each kind of container and span.
+    pub commands: TexCommands,
+}
+
+impl Default for TexOptions {
+    fn default() -> Self {
+        Self {
+            preamble: DEFAULT_PREAMBLE.to_owned(),
+            header: DEFAULT_HEADER.to_owned(),
+            commands: TexCommands::default(),
+        }
+    }
+}
+
+/// The name (without the leading `\`) of the command emitted for each kind of container and span.
+///
+/// The defaults are the commands which the parser reads; anything else can still be emitted to
+/// match an existing template, but will only be read back if the parser already knows it (e.g., `emph`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TexCommands {
+    pub spoken: String,
+    pub stagedir: String,
+    pub listener: String,
+    pub sfx: String,
+    pub plaintext: String,
+    pub marker: String,
+    pub emphasis: String,
+    pub direction: String,
+    pub phonetic: String,
+    pub lang: String,
+}
+
+impl Default for TexCommands {
+    fn default() -> Self {
+        Self {
+            spoken: "spoken".to_owned(),
+            stagedir: "stagedir".to_owned(),
+            listener: "listener".to_owned(),
+            sfx: "sfx".to_owned(),
+            plaintext: "plaintext".to_owned(),
+            marker: "marker".to_owned(),
+            emphasis: "ul".to_owned(),
+            direction: "direct".to_owned(),
+            phonetic: "phonetic".to_owned(),
+            lang: "lang".to_owned(),
+        }
+    }
+}
+
+impl TexCommands {
+    /** Set the command emitted for the given kind of container or span.
+
+    # Arguments
+
+    * `kind` - one of `spoken`, `stagedir`, `listener`, `sfx`, `plaintext`, `marker`, `emphasis`, `direction`,
+      `phonetic`, or `lang`
+    * `command` - the name of the command, with or without the leading `\`
+
+    # Examples
+
+    ```
+    # use lilscript::tex_handler::TexCommands;
+    let mut commands = TexCommands::default();
+    commands.set("emphasis", r"\emph").unwrap();
+    assert_eq!(commands.emphasis, "emph");
+
+    assert!(commands.set("whisper", "textit").is_err());
+    assert!(commands.set("spoken", "two words").is_err());
+    ```
+    */
+    pub fn set(&mut self, kind: &str, command: &str) -> Result<(), String> {
+        let command = command.trim().trim_start_matches('\\');
+        if command.is_empty() || !command.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(format!("Invalid TeX command name: {:?}", command));
+        }
+
+        let field = match kind.trim() {
+            "spoken" => &mut self.spoken,
+            "stagedir" => &mut self.stagedir,
+            "listener" => &mut self.listener,
+            "sfx" => &mut self.sfx,
+            "plaintext" => &mut self.plaintext,
+            "marker" => &mut self.marker,
+            "emphasis" => &mut self.emphasis,
+            "direction" => &mut self.direction,
+            "phonetic" => &mut self.phonetic,
+            "lang" => &mut self.lang,
+            _ => return Err(format!("Unknown kind of container or span: {:?}", kind)),
+        };
+        *field = command.to_owned();
+
+        Ok(())
+    }
+
+    /// The command emitted for the given kind of container.
+    pub fn container(&self, kind: &ContainerKind) -> &str {
+        match kind {
+            ContainerKind::Spoken => &self.spoken,
+            ContainerKind::StageDir => &self.stagedir,
+            ContainerKind::Sfx => &self.sfx,
+            ContainerKind::ListenerDialogue => &self.listener,
+            ContainerKind::PlainText => &self.plaintext,
+            ContainerKind::Marker => &self.marker,
+        }
+    }
+
+    /// The commands emitted for every kind of container.
+    pub fn containers(&self) -> [&str; 6] {
+        [
+            &self.spoken,
+            &self.stagedir,
+            &self.listener,
+            &self.sfx,
+            &self.plaintext,
+            &self.marker,
+        ]
+    }
+}
+
+/// A line of a template holding the `{{target}}` placeholder, dropped when the script has no target.
+static TARGET_LINE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^.*\{\{\s*target\s*\}\}.*(\n|$)").unwrap());
+
+/// A `{{placeholder}}` within a template.
+static PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{\{\s*([a-z]+)\s*\}\}").unwrap());
+
+/** Fill in the metadata placeholders of a preamble or header template with the script's metadata.
+
+The placeholders are `{{title}}`, `{{author}}`, `{{series}}`, `{{tags}}`, `{{date}}`, `{{target}}`,
+and `{{summary}}`, whose values are escaped for TeX, and `{{characters}}`, which is one
+`\character[fields]{name}{description}` line per character (the `[fields]`, such as
+`[pronouns=she/her; age=30s]`, are only written if the character has any).
+Any other `{{...}}` is left as it is (see `unknown_placeholders`).
+
+The target word count is optional, so if the script has none, any line holding `{{target}}` is left out.
+
+# Examples
+
+```
+# use lilscript::{script::Script, tex_handler::fill_template};
+let script = Script::new("lilellia", "Fish & Chips");
+let template = "\\documentclass{article}\n\\title{{{title}}}\n\\author{{{author}}}\n% {{target}} words\n";
+assert_eq!(
+    fill_template(template, &script),
+    "\\documentclass{article}\n\\title{Fish \\& Chips}\n\\author{lilellia}\n"
+);
+```
+*/
+pub fn fill_template(template: &str, script: &Script) -> String {
+    let mut template = template.to_owned();
+    if script.target.is_none() {
+        template = TARGET_LINE.replace_all(&template, "").into_owned();
+    }
+
+    PLACEHOLDER
+        .replace_all(&template, |caps: &Captures| match &caps[1] {
+            "title" => escape(&script.title),
+            "author" => escape(&script.author),
+            "series" => escape(&script.series.to_string()),
+            // tags are read as written, so they are written as they are too
+            "tags" => tags_to_tex(&script.tags),
+            "date" => script
+                .date
+                .map(|d| d.format("%d %b %Y").to_string())
+                .unwrap_or_default(),
+            "target" => script.target.map(|t| t.to_string()).unwrap_or_default(),
+            "summary" => escape(&script.summary),
+            "characters" => script
+                .characters
+                .iter()
+                .map(|c| {
+                    let fields: Vec<String> = c
+                        .fields()
+                        .into_iter()
+                        .map(|(key, value)| format!("{}={}", key, escape(&value)))
+                        .collect();
+                    let fields = match fields.is_empty() {
+                        true => String::new(),
+                        false => format!("[{}]", fields.join("; ")),
+                    };
+
+                    format!(
+                        "\\character{}{{{}}}{{{}}}\n",
+                        fields,
+                        escape(&c.name),
+                        escape(&c.description)
+                    )
+                })
+                .collect(),
+            _ => caps[0].to_owned(),
+        })
+        .into_owned()
+}
+
+/// The placeholders understood by `fill_template`.
+const PLACEHOLDERS: [&str; 8] = [
+    "title",
+    "author",
+    "series",
+    "tags",
+    "date",
+    "target",
+    "summary",
+    "characters",
+];
+
+/** Describe each `{{...}}` in a template which `fill_template` doesn't understand (and so leaves as it is),
+so that a mistake can be warned about (see `ParseOptions::warn_or_fail`) when the template is read.
+
+# Examples
+
+```
+# use lilscript::tex_handler::unknown_placeholders;
+let template = "\\title{{{title}}}\n\\author{{{autor}}}\n\\date{{{ date }}}";
+assert_eq!(unknown_placeholders(template), vec!["{{autor}}".to_owned()]);
+```
+*/
+pub fn unknown_placeholders(template: &str) -> Vec<String> {
+    PLACEHOLDER
+        .captures_iter(template)
+        .filter(|caps| !PLACEHOLDERS.contains(&&caps[1]))
+        .map(|caps| caps[0].to_owned())
+        .collect()
+}
+
+pub trait ToTex {
+    /// Write the object, in a .tex format, into the given buffer, using the given options.
+    fn write_tex_with(&self, options: &TexOptions, w: &mut impl fmt::Write) -> fmt::Result;
+
+    /// Write the object, in a .tex format, into the given buffer, using the default options.
+    fn write_tex(&self, w: &mut impl fmt::Write) -> fmt::Result {
+        self.write_tex_with(&TexOptions::default(), w)
+    }
+
+    /// Convert the object to a .tex format, using the default options.
+    fn to_tex(&self) -> String {
+        self.to_tex_with(&TexOptions::default())
+    }
+
+    /// Convert the object to a .tex format, using the given options.
+    fn to_tex_with(&self, options: &TexOptions) -> String {
+        let mut buf = String::new();
+        self.write_tex_with(options, &mut buf).unwrap_or_else(|_| {
+            error!("<red>Failed writing TeX to buffer</>");
+        });
+        buf
+    }
+}
+
+/** Escape text for use in a .tex file; the reverse of `Tex::unescaped`.
+
+# Examples
+
+```
+# use lilscript::tex_handler::{escape, Tex};
+let s = r#"She said "50% off" & left"#;
+assert_eq!(escape(s), r"She said ``50\% off'' \& left");
+assert_eq!(Tex::unescaped(&escape(s)), s);
+
+let s = r"Curly {braces} and \back ~ ^";
+assert_eq!(escape(s), r"Curly \{braces\} and \textbackslash{}back \textasciitilde{} \textasciicircum{}");
+assert_eq!(Tex::unescaped(&escape(s)), s);
+```
+*/
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '%' | '&' | '$' | '#' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\\' => escaped.push_str(r"\textbackslash{}"),
+            '~' => escaped.push_str(r"\textasciitilde{}"),
+            '^' => escaped.push_str(r"\textasciicircum{}"),
+            _ => escaped.push(c),
+        }
+    }
+
+    QuoteStyle::Tex.convert(&escaped)
+}
+
+/// A `:shortcode:` within the body of a script.
+static SHORTCODE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r":([a-z0-9_+-]+):").unwrap());
+
+/// Escape text within the body of a script, turning any known `:shortcode:`s back into `\emoji{...}` commands.
+/// Those are the only commands written: any other backslash is literal text (a custom command which
+/// `Tex::unescaped` left in the script included), and so is escaped as `\textbackslash{}`.
+fn escape_body(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for (is_shortcode, s) in regex_partition(&SHORTCODE, text) {
+        let shortcode = s.trim_matches(':');
+        if is_shortcode && emoji_for(shortcode).is_some() {
+            escaped.push_str(&format!(r"\emoji{{{}}}", shortcode));
+        } else {
+            escaped.push_str(&escape(s));
+        }
+    }
+    escaped
+}
+
+impl ToTex for TextSpan {
+    /**
+    ```
+    # use lilscript::{script::TextSpan, tex_handler::ToTex};
+    assert_eq!(TextSpan::normal("Hello & welcome").to_tex(), r"Hello \& welcome");
+    assert_eq!(TextSpan::emphasis("really").to_tex(), r"\ul{really}");
+    assert_eq!(TextSpan::inline("softly").to_tex(), r"\direct{softly}");
+    assert_eq!(TextSpan::phonetic("Siobhan", "shi-VAWN").to_tex(), r"\phonetic{Siobhan}{shi-VAWN}");
+    assert_eq!(TextSpan::normal("Vite!").with_lang("fr").to_tex(), r"\lang{fr}{Vite!}");
+    ```
+    */
+    fn write_tex_with(&self, options: &TexOptions, w: &mut impl fmt::Write) -> fmt::Result {
+        let contents = escape_body(&self.contents);
+        let commands = &options.commands;
+
+        match (&self.kind, &self.lang) {
+            (SpanKind::Normal, Some(lang)) => {
+                write!(w, r"\{}{{{}}}{{{}}}", commands.lang, lang, contents)
+            }
+            (SpanKind::Normal, None) => w.write_str(&contents),
+            (SpanKind::Emphasis, _) => write!(w, r"\{}{{{}}}", commands.emphasis, contents),
+            (SpanKind::InlineDirection, _) => {
+                write!(w, r"\{}{{{}}}", commands.direction, contents)
+            }
+            (SpanKind::Phonetic(pronunciation), _) => write!(
+                w,
+                r"\{}{{{}}}{{{}}}",
+                commands.phonetic,
+                contents,
+                escape(pronunciation)
+            ),
+        }
+    }
+}
+
+impl ToTex for TextContainer {
+    /**
+    ```
+    # use lilscript::{script::{ContainerKind, TextContainer, TextSpan}, tex_handler::ToTex};
+    let container = TextContainer::new(ContainerKind::Spoken)
+        .push(TextSpan::inline("softly"))
+        .push(TextSpan::normal("Listen to that rain."))
+        .spoken_by("Witch")
+        .overlapping(TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal("rain")));
+
+    assert_eq!(
+        container.to_tex(),
+        r"\overlap{\sfx{rain}}{\spoken[Witch]{\direct{softly} Listen to that rain.}}"
+    );
+    ```
+    */
+    fn write_tex_with(&self, options: &TexOptions, w: &mut impl fmt::Write) -> fmt::Result {
+        if !self.overlaps.is_empty() {
+            w.write_str(r"\overlap{")?;
+            for (i, cue) in self.overlaps.iter().enumerate() {
+                if i > 0 {
+                    w.write_char(' ')?;
+                }
+                cue.write_tex_with(options, w)?;
+            }
+            w.write_str("}{")?;
+        }
+
+        write!(w, r"\{}", options.commands.container(&self.kind))?;
+        let mut optional: Vec<String> = self.speaker.iter().map(|s| escape(s)).collect();
+        optional.extend(
+            self.attributes
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, escape(value))),
+        );
+        if !optional.is_empty() {
+            write!(w, "[{}]", optional.join(", "))?;
+        }
+
+        w.write_char('{')?;
+        for (i, span) in self.spans.iter().enumerate() {
+            // keep punctuation attached to whatever comes before it, e.g. \ul{really}.
+            let punctuation = span.kind == SpanKind::Normal
+                && span
+                    .contents
+                    .starts_with(['.', ',', ';', ':', '!', '?', ')']);
+            if i > 0 && !punctuation {
+                w.write_char(' ')?;
+            }
+            span.write_tex_with(options, w)?;
+        }
+        w.write_char('}')?;
+
+        if !self.overlaps.is_empty() {
+            w.write_char('}')?;
+        }
+
+        for text in &self.commentary {
+            write!(w, "\n\\commentary{{{}}}", escape_body(text))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ToTex for Script {
+    /// Write the script in its canonical form (see `Script::canonicalize`), so that parsing the output
+    /// again gives back exactly that form.
+    /// The preamble is filled in from the template of the given options (see `fill_template`).
+    fn write_tex_with(&self, options: &TexOptions, w: &mut impl fmt::Write) -> fmt::Result {
+        let mut script = self.clone();
+        script.canonicalize();
+
+        w.write_str(&fill_template(&options.preamble, &script))?;
+        write_document(&script, options, w)
+    }
+}
+
+/** Write the header of the script, which follows the preamble: its metadata commands, the start of the
+document, and its characters, filled in from the header template of the given options (see `fill_template`).
+
+# Examples
+
+```
+# use lilscript::{script::{Character, Script}, tex_handler::{write_header, TexOptions}};
+let mut script = Script::new("lilellia", "A Very Cool Script");
+script.target = Some(3500);
+script.characters.push(Character::new("Speaker", "a kind witch"));
+
+let mut tex = String::new();
+write_header(&script, &TexOptions::default(), &mut tex).unwrap();
+assert_eq!(tex, r"\renewcommand{\SceneName}{A Very Cool Script}
+\scriptAuthor{lilellia}
+\scriptSeries{}
+\scriptTags{}
+\scriptDate{}
+\scriptTarget{3500}
+\summary{}
+\begin{document}
+\character{Speaker}{a kind witch}
+\clearpage
+");
+```
+*/
+pub fn write_header(script: &Script, options: &TexOptions, w: &mut impl fmt::Write) -> fmt::Result {
+    w.write_str(&fill_template(&options.header, script))
+}
+
+/** Write everything of the script which follows the preamble: its header, then the document itself.
+
+# Examples
+
+```
+# use lilscript::{script::{ContainerKind, Script, SeriesEntry, TextContainer, TextSpan}, tex_handler::{write_document, TexOptions}};
+let mut script = Script::new("lilellia", "A Very Cool Script");
+script.series = SeriesEntry::new("Moonrise", 2);
+script.tags = vec!["F4A".to_owned(), "comfort".to_owned()];
+script.paragraphs.push(TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Hello.")));
+
+let mut tex = String::new();
+write_document(&script, &TexOptions::default(), &mut tex).unwrap();
+assert_eq!(tex, r"\renewcommand{\SceneName}{A Very Cool Script}
+\scriptAuthor{lilellia}
+\scriptSeries{Moonrise (Part 2)}
+\scriptTags{[F4A] [comfort]}
+\scriptDate{}
+\summary{}
+\begin{document}
+\clearpage
+\spoken{Hello.}
+\end{document}
+");
+```
+*/
+pub fn write_document(
+    script: &Script,
+    options: &TexOptions,
+    w: &mut impl fmt::Write,
+) -> fmt::Result {
+    write_header(script, options, w)?;
+
+    for (i, container) in script.paragraphs.iter().enumerate() {
+        if i > 0 {
+            w.write_char('\n')?;
+        }
+        container.write_tex_with(options, w)?;
+        w.write_char('\n')?;
+    }
+
+    writeln!(w, r"\end{{document}}")
+}
+
+/// The commands of the header which `write_header` writes (by default), and so survive being rewritten,
+/// along with the only argument which each is written with (if it is checked).
+const HEADER_COMMANDS: [(&str, Option<&str>); 10] = [
+    ("renewcommand", Some(r"\SceneName")),
+    ("scriptAuthor", None),
+    ("scriptSeries", None),
+    ("scriptTags", None),
+    ("scriptDate", None),
+    ("scriptTarget", None),
+    ("summary", None),
+    ("begin", Some("document")),
+    ("character", None),
+    ("clearpage", None),
+];
+
+/** Describe anything in the given .tex source (after its preamble) which would be lost on rewriting it from the
+parsed script: `%` comments, commands in the header which aren't part of a script's metadata, and anything
+after `\end{document}`. The preamble itself is kept as it is, so isn't checked.
+
+# Examples
+
+```
+# use lilscript::tex_handler::unkept;
+let s = "\\documentclass{article} % kept\n\\renewcommand{\\SceneName}{Title}\n\\usepackage{xcolor}\n\
+    \\clearpage\n\\spoken{100\\% sure.} % a note\n\\end{document}\nP.S.";
+assert_eq!(
+    unkept(s),
+    vec![
+        "the comment on line 5".to_owned(),
+        "the \\usepackage command on line 3".to_owned(),
+        "the text after \\end{document} on line 7".to_owned(),
+    ]
+);
+
+// only the \renewcommand and \begin which the header is written with are kept
+let s = "\\renewcommand{\\SceneName}{Title}\n\\renewcommand{\\foo}{bar}\n\\begin{itemize}\n\\begin{document}\n\\clearpage";
+assert_eq!(
+    unkept(s),
+    vec![
+        "the \\renewcommand command on line 2".to_owned(),
+        "the \\begin command on line 3".to_owned(),
+    ]
+);
+```
+*/
+pub fn unkept(source: &str) -> Vec<String> {
+    let start = preamble(source).map_or(0, str::len);
+    let mut lost: Vec<String> = Vec::new();
+
+    // comments are blanked out with spaces, so any difference is a comment
+    let blanked = blank_comments(&source[start..]);
+    let mut previous = None;
+    for (offset, _) in source[start..]
+        .char_indices()
+        .zip(blanked.chars())
+        .filter(|((_, a), b)| a != b)
+        .map(|(a, _)| a)
+    {
+        let line = line_of(source, start + offset);
+        if previous != Some(line) {
+            lost.push(format!("the comment on line {}", line));
+            previous = Some(line);
+        }
+    }
+
+    // the header runs up to the start of the body
+    let header_end = blanked
+        .find(r"\clearpage")
+        .or_else(|| blanked.find(r"\begin{document}"))
+        .unwrap_or(blanked.len());
+    let tokens = tokenize(&blanked[..header_end]);
+    for (i, token) in tokens.iter().enumerate() {
+        let Token::Command(name) = token.value else {
+            continue;
+        };
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            continue;
+        }
+
+        // e.g., only \renewcommand{\SceneName} is written, so any other \renewcommand is lost
+        let argument = tokens[i + 1..]
+            .iter()
+            .find(|token| !token.value.is_blank())
+            .and_then(|token| match token.value {
+                Token::Group(argument) => Some(argument.trim()),
+                _ => None,
+            });
+        let kept = HEADER_COMMANDS.iter().any(|(command, only)| {
+            *command == name && only.is_none_or(|only| argument == Some(only))
+        });
+        if !kept {
+            lost.push(format!(
+                "the \\{} command on line {}",
+                name,
+                line_of(source, start + token.range.start)
+            ));
+        }
+    }
+
+    if let Some(end) = blanked.find(r"\end{document}") {
+        let after = end + r"\end{document}".len();
+        if let Some(offset) = blanked[after..].find(|c: char| !c.is_whitespace()) {
+            lost.push(format!(
+                "the text after \\end{{document}} on line {}",
+                line_of(source, start + after + offset)
+            ));
+        }
+    }
+
+    lost
+}
+
+/** Return the preamble of the given .tex source: everything before its header (i.e., before `\renewcommand{\SceneName}`).
+
+# Examples
+
+```
+# use lilscript::tex_handler::preamble;
+let s = "\\documentclass{article}\n\\renewcommand{\\SceneName}{A Very Cool Script}\n";
+assert_eq!(preamble(s), Some("\\documentclass{article}\n"));
+assert_eq!(preamble("\\begin{document}"), None);
+```
+*/
+pub fn preamble(text: &str) -> Option<&str> {
+    text.find(r"\renewcommand{\SceneName}")
+        .map(|start| &text[..start])
+}
+
+/// A brace group (or optional `[...]` argument) of a command: which command, and which of its arguments.
+#[derive(Clone, Copy)]
+struct Argument<'a> {
+    command: &'a str,
+    index: usize,
+}
+
+/// The commands which the parser reads, and so which hold the text of the script in the source.
+static PARSED_COMMANDS: LazyLock<TexCommands> = LazyLock::new(TexCommands::default);
+
+/// Return whether the command holds a line of the script.
+fn is_container_command(command: &str) -> bool {
+    PARSED_COMMANDS.containers().contains(&command)
+}
+
+/// Return whether text directly within the given (nested) arguments is within the scope of a find-and-replace.
+fn in_replace_scope(arguments: &[Argument], scope: ReplaceScope) -> bool {
+    let Some(position) = arguments
+        .iter()
+        .rposition(|a| is_container_command(a.command) || a.command == "character")
+    else {
+        // anything outside the body (the header, the preamble) is left alone
+        return false;
+    };
+    let (container, inner) = (arguments[position], &arguments[position + 1..]);
+
+    // these arguments hold names, codes, or hints rather than text of the script
+    let not_text = inner.iter().any(|a| {
+        matches!(
+            (a.command, a.index),
+            ("lang", 0) | ("emoji", _) | ("href", 0) | ("phonetic", 1)
+        )
+    });
+    if not_text {
+        return false;
+    }
+
+    let commands = &*PARSED_COMMANDS;
+    let direction = [commands.stagedir.as_str(), commands.sfx.as_str()]
+        .contains(&container.command)
+        || inner.iter().any(|a| a.command == commands.direction);
+
+    match scope {
+        ReplaceScope::Spoken => container.command == commands.spoken && !direction,
+        ReplaceScope::Directions => direction,
+        ReplaceScope::All => true,
+    }
+}
+
+/** Replace every match of the pattern within the text of the given .tex source, limited to the given scope.
+
+This is the source-level counterpart of `Script::replace`: command names, the header, and arguments which
+aren't text (such as the language of `\lang{fr}{...}`) are never touched, and everything else in the
+file is left exactly as it was. Since each run of text between commands is searched separately,
+a match cannot span a command.
+
+# Arguments
+
+* `text` - the .tex source
+* `pattern` - the regular expression to search for
+* `replacement` - the text to replace each match with, which may refer to capture groups (e.g., `$1`)
+* `scope` - which text to search
+
+# Return
+
+* `(String, usize)` - the updated source, and the number of replacements made
+
+# Examples
+
+```
+# use lilscript::{script::ReplaceScope, tex_handler::replace_text};
+# use regex::Regex;
+let s = r"\character{Mira}{a witch}
+\spoken[Mira]{\direct{to Mira} Mira\textellipsis{} come here.}";
+let pattern = Regex::new("Mira").unwrap();
+
+let (replaced, count) = replace_text(s, &pattern, "Nell", ReplaceScope::Spoken);
+assert_eq!(count, 1);
+assert_eq!(replaced, r"\character{Mira}{a witch}
+\spoken[Mira]{\direct{to Mira} Nell\textellipsis{} come here.}");
+
+let (replaced, count) = replace_text(s, &pattern, "Nell", ReplaceScope::All);
+assert_eq!(count, 4);
+assert!(!replaced.contains("Mira"));
+```
+*/
+pub fn replace_text(
+    text: &str,
+    pattern: &Regex,
+    replacement: &str,
+    scope: ReplaceScope,
+) -> (String, usize) {
+    let mut replaced = String::with_capacity(text.len());
+    let mut count = 0;
+
+    // the arguments which the current position is nested within
+    let mut arguments: Vec<Argument> = Vec::new();
+
+    // the argument which an immediately following `{` or `[` would open
+    let mut pending: Option<Argument> = None;
+
+    let mut replace_run = |run: &str, arguments: &[Argument], replaced: &mut String| {
+        let matches = if in_replace_scope(arguments, scope) {
+            pattern.find_iter(run).count()
+        } else {
+            0
+        };
+
+        if matches > 0 {
+            replaced.push_str(&pattern.replace_all(run, replacement));
+            count += matches;
+        } else {
+            replaced.push_str(run);
+        }
+    };
+
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let len = match c {
+            '\\' => {
+                let name = rest[1..]
+                    .find(|c: char| !c.is_ascii_alphabetic())
+                    .unwrap_or(rest.len() - 1);
+
+                if name == 0 {
+                    // a control symbol such as \% or \\, which is just text
+                    pending = None;
+                    1 + rest[1..].chars().next().map_or(0, char::len_utf8)
+                } else {
+                    pending = Some(Argument {
+                        command: &rest[1..=name],
+                        index: 0,
+                    });
+                    1 + name
+                }
+            }
+            '{' => {
+                arguments.push(pending.take().unwrap_or(Argument {
+                    command: "",
+                    index: 0,
+                }));
+                1
+            }
+            '}' => {
+                // a command may be followed by further arguments
+                pending = arguments
+                    .pop()
+                    .filter(|a| !a.command.is_empty())
+                    .map(|a| Argument {
+                        index: a.index + 1,
+                        ..a
+                    });
+                1
+            }
+            '%' => rest.find('\n').unwrap_or(rest.len()),
+            '[' if pending.is_some() => {
+                // an optional argument: only the speaker of a line (e.g., \spoken[Mira]{...}) is text,
+                // and it is only within the scope of a replacement across everything
+                let Some(close) = rest.find(']') else {
+                    replaced.push_str(rest);
+                    break;
+                };
+
+                let speaker = pending.is_some_and(|a| is_container_command(a.command));
+                if speaker && scope == ReplaceScope::All {
+                    // any key=value attributes alongside the speaker are left alone
+                    replaced.push('[');
+                    arguments.extend(pending);
+                    for (i, entry) in rest[1..close].split(',').enumerate() {
+                        if i > 0 {
+                            replaced.push(',');
+                        }
+                        match entry.contains('=') {
+                            true => replaced.push_str(entry),
+                            false => replace_run(entry, &arguments, &mut replaced),
+                        }
+                    }
+                    arguments.pop();
+                    replaced.push(']');
+                } else {
+                    replaced.push_str(&rest[..=close]);
+                }
+
+                rest = &rest[close + 1..];
+                continue;
+            }
+            _ => {
+                let run = rest.find(['\\', '{', '}', '%']).unwrap_or(rest.len());
+                if !rest[..run].trim().is_empty() {
+                    pending = None;
+                }
+
+                replace_run(&rest[..run], &arguments, &mut replaced);
+                rest = &rest[run..];
+                continue;
+            }
+        };
+
+        replaced.push_str(&rest[..len]);
+        rest = &rest[len..];
+    }
+
+    (replaced, count)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{md_handler::Markdown, script::DateOrder};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_text_span_parse_normal() {
+        let tex = Tex::from("This is some text");
+        let span = TextSpan::try_from(&tex).unwrap();
+
+        let expected = TextSpan::normal("This is some text");
+        assert_eq!(span, expected);
+    }
+
+    #[test]
+    fn test_text_span_parse_inline() {
+        let tex = Tex::from("\\direct{an inline!}");
+        let span = TextSpan::try_from(&tex).unwrap();
+
+        let expected = TextSpan::inline("an inline!");
+        assert_eq!(span, expected);
+    }
+
+    #[test]
+    fn test_text_span_parse_emphasis() {
+        let tex = Tex::from("\\ul{EMPHASIS}");
+        let span = TextSpan::try_from(&tex).unwrap();
+
+        let expected = TextSpan::emphasis("EMPHASIS");
+        assert_eq!(span, expected);
+    }
+
+    #[test]
+    fn test_text_span_parse_phonetic() {
+        let tex = Tex::from("\\phonetic{Nguyen}{nwin}");
+        let span = TextSpan::try_from(&tex).unwrap();
+
+        let expected = TextSpan::phonetic("Nguyen", "nwin");
+        assert_eq!(span, expected);
+    }
+
+    #[test]
+    fn test_text_span_parse_lang() {
+        let tex = Tex::from("\\lang{fr}{C'est la vie.}");
+        let span = TextSpan::try_from(&tex).unwrap();
+
+        let expected = TextSpan::normal("C'est la vie.").with_lang("fr");
+        assert_eq!(span, expected);
+    }
+
+    #[test]
+    fn test_text_container_parse_one_span() {
+        let tex = Tex::from("\\spoken{This is some text.}");
+        let container = TextContainer::try_from(&tex).unwrap();
+
+        let spans = vec![TextSpan::normal("This is some text.")];
+        let expected = TextContainer::with_spans(ContainerKind::Spoken, spans);
+
+        assert_eq!(container, expected);
+    }
+
+    #[test]
+    fn test_text_container_parse_multiple_spans() {
+        let tex = Tex::from(
+            "\\spoken{This is some text. \\direct{an inline direction} And some more dialogue.}",
+        );
+        let container = TextContainer::try_from(&tex).unwrap();
+
+        let spans = vec![
+            TextSpan::normal("This is some text."),
             TextSpan::inline("an inline direction"),
             TextSpan::normal("And some more dialogue."),
         ];
-        let expected = TextContainer {
-            kind: ContainerKind::Spoken,
-            spans,
-        };
+        let expected = TextContainer::with_spans(ContainerKind::Spoken, spans);
 
         assert_eq!(container, expected);
     }
@@ -460,19 +2532,182 @@ mod test {
             TextSpan::inline("slowly, quietly"),
             TextSpan::normal("some text?"),
         ];
-        let expected = TextContainer {
-            kind: ContainerKind::ListenerDialogue,
-            spans,
-        };
+        let expected = TextContainer::with_spans(ContainerKind::ListenerDialogue, spans);
+
+        assert_eq!(container, expected);
+    }
+
+    #[test]
+    fn test_text_container_parse_phonetic() {
+        let tex =
+            Tex::from("\\spoken{My name is \\phonetic{Siobhan}{shi-VAWN}, nice to meet you.}");
+        let container = TextContainer::try_from(&tex).unwrap();
+
+        let spans = vec![
+            TextSpan::normal("My name is"),
+            TextSpan::phonetic("Siobhan", "shi-VAWN"),
+            TextSpan::normal(", nice to meet you."),
+        ];
+        let expected = TextContainer::with_spans(ContainerKind::Spoken, spans);
+
+        assert_eq!(container, expected);
+    }
+
+    #[test]
+    fn test_text_container_parse_speaker() {
+        let tex = Tex::from("\\spoken[Alice]{Hello there.}");
+        let container = TextContainer::try_from(&tex).unwrap();
 
+        let expected = TextContainer::new(ContainerKind::Spoken)
+            .push(TextSpan::normal("Hello there."))
+            .spoken_by("Alice");
         assert_eq!(container, expected);
     }
 
+    #[test]
+    fn test_text_container_parse_overlap() {
+        let tex =
+            Tex::from("\\overlap{\\sfx{rain} \\sfx{distant thunder}}{\\spoken{Listen to that.}}");
+        let container = TextContainer::try_from(&tex).unwrap();
+
+        let expected = TextContainer::new(ContainerKind::Spoken)
+            .push(TextSpan::normal("Listen to that."))
+            .overlapping(TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal("rain")))
+            .overlapping(
+                TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal("distant thunder")),
+            );
+
+        assert_eq!(container, expected);
+    }
+
+    #[test]
+    fn test_text_container_parse_overlap_invalid() {
+        let tex = Tex::from("\\overlap{\\sfx{rain}}");
+        assert!(TextContainer::try_from(&tex).is_err());
+    }
+
+    #[test]
+    fn test_text_container_parse_strict() {
+        let tex = Tex::from("\\overlap{\\thunder{rumbling}}{\\spoken{Hello.}}");
+        assert!(tex.parse_container(&ParseOptions::default()).is_ok());
+        assert!(tex
+            .parse_container(&ParseOptions {
+                strict: true,
+                ..Default::default()
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn test_script_parse_strict_series() {
+        let tex = Tex::from(
+            "\\renewcommand{\\SceneName}{Title}\n\\scriptAuthor{lilellia}\n\\scriptSeries{Moonrise, part two}\n\
+            \\scriptTags{[F4A]}\n\\scriptDate{03 Mar 2024}\n\\summary{Summary.}\n\\clearpage\n\\spoken{Hello.}",
+        );
+
+        let script = tex.parse_script(&ParseOptions::default()).unwrap();
+        assert_eq!(script.series, SeriesEntry::default());
+        assert_eq!(script.paragraphs.len(), 1);
+
+        assert!(tex
+            .parse_script(&ParseOptions {
+                strict: true,
+                ..Default::default()
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn test_script_parse_normalize_tags() {
+        let tex = Tex::from(
+            "\\renewcommand{\\SceneName}{Title}\n\\scriptAuthor{lilellia}\n\\scriptSeries{}\n\
+            \\scriptTags{[F4A] [ Comfort] [f4a] [SFW]}\n\\scriptDate{03 Mar 2024}\n\\summary{Summary.}\n\\clearpage",
+        );
+
+        let script = tex.parse_script(&ParseOptions::default()).unwrap();
+        assert_eq!(script.tags, vec!["F4A", " Comfort", "f4a", "SFW"]);
+
+        let options = ParseOptions {
+            normalize_tags: true,
+            tag_aliases: [("sfw".to_owned(), "safe for work".to_owned())].into(),
+            ..Default::default()
+        };
+        let script = tex.parse_script(&options).unwrap();
+        assert_eq!(script.tags, vec!["f4a", "comfort", "safe for work"]);
+    }
+
+    #[test]
+    fn test_script_parse_dates() {
+        let tex_with_date = |date: &str| {
+            Tex::from(format!(
+                "\\renewcommand{{\\SceneName}}{{Title}}\n\\scriptAuthor{{lilellia}}\n\\scriptSeries{{}}\n\
+                \\scriptTags{{}}\n\\scriptDate{{{}}}\n\\summary{{Summary.}}\n\\clearpage",
+                date
+            ))
+        };
+        let month_first = ParseOptions {
+            date_order: DateOrder::MonthFirst,
+            ..Default::default()
+        };
+
+        let script = tex_with_date("03/04/2024")
+            .parse_script(&month_first)
+            .unwrap();
+        assert_eq!(script.date, NaiveDate::from_ymd_opt(2024, 3, 4));
+
+        let script = tex_with_date("\\today").parse_script(&month_first).unwrap();
+        assert!(script.date.is_some());
+
+        let tex = tex_with_date("sometime in spring");
+        assert_eq!(
+            tex.parse_script(&ParseOptions::default()).unwrap().date,
+            None
+        );
+
+        let strict = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let err = tex.parse_script(&strict).unwrap_err();
+        assert!(err.contains("2024-03-03"));
+    }
+
+    #[test]
+    fn test_script_parse_target() {
+        let tex = Tex::from(
+            "\\renewcommand{\\SceneName}{Title}\n\\scriptAuthor{lilellia}\n\\scriptSeries{}\n\
+            \\scriptTags{}\n\\scriptDate{}\n\\scriptTarget{3,500}\n\\summary{Summary.}\n\\clearpage",
+        );
+        let script = tex.parse_script(&ParseOptions::default()).unwrap();
+        assert_eq!(script.target, Some(3500));
+
+        let tex = Tex::from(tex.text.replace("3,500", "lots"));
+        let script = tex.parse_script(&ParseOptions::default()).unwrap();
+        assert_eq!(script.target, None);
+    }
+
+    #[test]
+    fn test_script_parse_keeps_paragraph_order() {
+        let body: Vec<String> = (0..64)
+            .map(|i| format!("\\spoken{{Line {}.}}", i))
+            .collect();
+        let tex = Tex::from(format!(
+            "\\renewcommand{{\\SceneName}}{{Title}}\n\\scriptAuthor{{lilellia}}\n\\scriptSeries{{}}\n\
+            \\scriptTags{{}}\n\\scriptDate{{}}\n\\summary{{Summary.}}\n\\clearpage\n{}",
+            body.join("\n")
+        ));
+
+        let script = tex.parse_script(&ParseOptions::default()).unwrap();
+        let lines: Vec<String> = script.paragraphs.iter().map(|c| c.plain_text()).collect();
+        let expected: Vec<String> = (0..64).map(|i| format!("Line {}.", i)).collect();
+        assert_eq!(lines, expected);
+    }
+
     #[test]
     fn test_regex_partition() {
         let s = "ABCCQBCPCCCS";
         let re = Regex::new("C+").unwrap();
-        let v = regex_partition(re, &s);
+        let v: Vec<&str> = regex_partition(&re, s).map(|(_, part)| part).collect();
         assert_eq!(v, vec!["AB", "CC", "QB", "C", "P", "CCC", "S"]);
     }
 
@@ -480,21 +2715,29 @@ mod test {
     fn test_regex_partition_trailing_delim() {
         let s = "ABCCQBCPCCC";
         let re = Regex::new("C+").unwrap();
-        let v = regex_partition(re, &s);
+        let v: Vec<&str> = regex_partition(&re, s).map(|(_, part)| part).collect();
         assert_eq!(v, vec!["AB", "CC", "QB", "C", "P", "CCC"]);
     }
 
+    #[test]
+    fn test_regex_partition_leading_delim() {
+        let s = "CCABC";
+        let re = Regex::new("C+").unwrap();
+        let v: Vec<(bool, &str)> = regex_partition(&re, s).collect();
+        assert_eq!(v, vec![(true, "CC"), (false, "AB"), (true, "C")]);
+    }
+
     #[test]
     fn test_search_tex_success() {
         let contents = r"blah blah \randomCommand{7} and more blah.";
-        let value = search_tex("randomCommand", &contents).unwrap();
+        let value = search_tex("randomCommand", contents).unwrap();
         assert_eq!(value, "7");
     }
 
     #[test]
     fn test_search_tex_fail() {
         let contents = r"blah blah \randomCommand{7} and more blah.";
-        let value = search_tex("differentCommand", &contents);
+        let value = search_tex("differentCommand", contents);
         assert!(value.is_none());
     }
 
@@ -528,6 +2771,13 @@ mod test {
         assert_eq!(Tex::unescaped(s), expected);
     }
 
+    #[test]
+    fn test_unescaped_emoji() {
+        let s = r"Sweet dreams \emoji{sparkles}\emoji{crescent_moon}";
+        let expected = "Sweet dreams :sparkles::crescent_moon:";
+        assert_eq!(Tex::unescaped(s), expected);
+    }
+
     #[test]
     fn test_unescaped_href() {
         let s = r"This is some text with a \href{https://google.com}{link} in it.";
@@ -586,146 +2836,610 @@ mod test {
             "This is some text with a [link](https://google.com) in it."
         );
     }
-}
 
-// /// Handle the re-exporting of a script into .tex format
-// mod export {
-//     use crate::script::{Script, TextBlock};
-
-//     /**
-//     Format a value between braces.
-
-//     # Arguments
-
-//     * `value` - a string slice containing the value to put between braces
-
-//     # Return
-
-//     * `&str` - a string slice containing the given value between braces
-
-//     # Examples
-
-//     ```ignore
-//     # use lilscript::tex_handler::export::embrace;
-//     let values: Vec<&str> = vec!["a", "6", "this", "word", ""];
-//     let actual = values.into_iter().map(embrace).collect::<Vec<String>>();
-//     let expected = vec!["{a}", "{6}", "{this}", "{word}", "{}"];
-
-//     assert_eq!(actual, expected);
-//     ```
-//     */
-//     fn embrace(value: &str) -> String {
-//         const OPEN_BRACE: char = '{';
-//         const CLOSE_BRACE: char = '}';
-//         format!("{}{}{}", OPEN_BRACE, value, CLOSE_BRACE)
-//     }
-
-//     /**
-//     Return a string representation of the TextBlock in .tex format.
-
-//     # Return
-
-//     * `String` - the .tex representation of the block
-
-//     # Example
-
-//     ```ignore
-//     # use lilscript::tex_handler::export::block_to_tex;
-//     # use lilscript::script::TextBlock;
-//     let s = "The characters do something.".to_string();
-//     let block = TextBlock::StageDir(s);
-//     assert_eq!(block_to_tex(&block), r"\stagedir{The characters do something.}");
-//     ```
-//     */
-//     pub fn block_to_tex(block: &TextBlock) -> String {
-//         match block {
-//             TextBlock::Spoken(dialogue, None) => format!("\\spoken{}", embrace(dialogue)),
-//             TextBlock::Spoken(dialogue, Some(speaker)) => {
-//                 format!("\\spoken[{}]{}", speaker, embrace(dialogue))
-//             }
-//             TextBlock::InlineDirection(direction) => format!("\\direct{}", embrace(direction)),
-//             TextBlock::SFX(sfx) => format!("\\sfx{}", embrace(sfx)),
-//             TextBlock::StageDir(direction) => format!("\\stagedir{}", embrace(direction)),
-//             TextBlock::ListenerDialogue(dialogue) => format!("\\listener{}", embrace(dialogue)),
-//             TextBlock::Emphasis(em) => format!("\\ul{}", embrace(em)),
-//             TextBlock::Separator => String::from("\n\n")
-//         }
-//     }
-
-//     /// Render the given script in .tex format.
-//     pub fn script_to_tex(script: &Script) -> String {
-//         // TODO: output preamble
-//         // TODO: output script header info
-//         script
-//             .paragraphs
-//             .iter()
-//             .map(|line| {
-//                 line.iter()
-//                     .map(block_to_tex)
-//                     .collect::<Vec<String>>()
-//                     .join(" ")
-//             })
-//             .collect::<Vec<String>>()
-//             .join("\n\n")
-//     }
-
-//     #[cfg(test)]
-//     mod test {
-//         use super::*;
-
-//         #[test]
-//         fn test_embrace() {
-//             let values: Vec<&str> = vec!["a", "6", "this", "word", ""];
-//             let actual = values.into_iter().map(embrace).collect::<Vec<String>>();
-//             let expected = vec!["{a}", "{6}", "{this}", "{word}", "{}"];
-
-//             assert_eq!(actual, expected);
-//         }
-
-//         #[test]
-//         fn test_textblock_to_tex_stagedir() {
-//             let s = "The characters do something.".to_owned();
-//             let block = TextBlock::StageDir(s);
-//             let output = block_to_tex(&block);
-
-//             assert_eq!(output, r"\stagedir{The characters do something.}");
-//         }
-
-//         #[test]
-//         fn test_textblock_to_tex_spoken_with_speaker() {
-//             let s = "I'm going to say something.".to_owned();
-//             let a = Some("lilellia".to_owned());
-//             let block = TextBlock::Spoken(s, a);
-//             let output = block_to_tex(&block);
-
-//             assert_eq!(output, r"\spoken[lilellia]{I'm going to say something.}");
-//         }
-
-//         #[test]
-//         fn test_textblock_to_tex_spoken_without_speaker() {
-//             let s = "I'm going to say something.".to_owned();
-//             let block = TextBlock::Spoken(s, None);
-//             let output = block_to_tex(&block);
-
-//             assert_eq!(output, r"\spoken{I'm going to say something.}");
-//         }
-
-//         #[test]
-//         fn test_textblock_to_tex_sfx() {
-//             let s = "a sound!".to_owned();
-//             let block = TextBlock::SFX(s);
-//             let output = block_to_tex(&block);
-
-//             assert_eq!(output, r"\sfx{a sound!}");
-//         }
-
-//         #[test]
-//         fn test_textblock_to_tex_listener() {
-//             let s = "Some secret words".to_owned();
-//             let block = TextBlock::ListenerDialogue(s);
-//             let output = block_to_tex(&block);
-
-//             assert_eq!(output, r"\listener{Some secret words}");
-//         }
-//     }
-// }
+    #[test]
+    fn test_round_trip_is_canonical() {
+        let source = r"\documentclass{article}
+\renewcommand{\SceneName}{Rock \& Roll}
+\scriptAuthor{lilellia}
+\scriptSeries{Moonrise (Part IV)}
+\scriptTags{[F4A] [comfort]}
+\scriptDate{03 Mar 2024}
+\scriptTarget{3,500}
+\summary{A ``short'' script about rain: 100\% cosy.}
+\begin{document}
+\character{Speaker}{a kind witch}
+\clearpage
+\stagedir{The door creaks open.}
+
+\spoken{Oh! You're here\textellipsis{} \direct{softly} I wasn't expecting you. \ul{Really}.}
+
+\overlap{\sfx{rain on the window}}{\spoken[Witch]{Listen to that rain.}}
+
+\listener{\emoji{wave} Hi, \phonetic{Siobhan}{shi-VAWN}.}
+
+\plaintext{--8<--}
+
+\spoken{Come in ``quickly'' before you catch a cold. \lang{fr}{Vite, vite!}}
+\end{document}";
+
+        let mut script = Script::try_from(&Tex::from(source)).unwrap();
+        script.canonicalize();
+
+        let reparsed = Script::try_from(&Tex::from(script.to_tex())).unwrap();
+        assert_eq!(reparsed, script);
+        assert_eq!(reparsed.paragraphs.len(), 6);
+    }
+
+    #[test]
+    fn test_round_trip_escapes_literal_backslashes() {
+        for text in [
+            r"C:\Users\lilellia",
+            r"\Users then \direct and \emoji{wave}",
+            r"\sigh{} :wave:",
+        ] {
+            let mut script = Script::new("lilellia", "Title");
+            script.paragraphs =
+                vec![TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal(text))];
+            script.canonicalize();
+
+            let tex = script.to_tex();
+            assert!(
+                !tex.contains(r"\Users"),
+                "{:?} was written as a command",
+                text
+            );
+            assert_eq!(
+                Script::try_from(&Tex::from(tex)).unwrap(),
+                script,
+                "{:?} did not round-trip",
+                text
+            );
+        }
+
+        // known shortcodes are the only text written as commands
+        assert_eq!(
+            TextSpan::normal(r"C:\Users :wave:").to_tex(),
+            r"C:\textbackslash{}Users \emoji{wave}"
+        );
+    }
+
+    #[test]
+    fn test_round_trip_escapes_special_characters() {
+        let texts = [
+            r"Curly {braces} and \back",
+            r"100% & $5 for #1_fan",
+            "a ~tilde~ and a ^_^ face",
+            r"a \spoken{command} that isn't one",
+            r"{unbalanced and \",
+            r#"She said "hi" and 'bye'"#,
+            "}{",
+        ];
+
+        for text in texts {
+            let mut script = Script::new("lilellia", text);
+            script.summary = text.to_owned();
+            script.characters = vec![Character::new(text, text)];
+            script.paragraphs = vec![
+                TextContainer::new(ContainerKind::Spoken)
+                    .push(TextSpan::normal(text))
+                    .push(TextSpan::emphasis(text))
+                    .push(TextSpan::inline(text)),
+                TextContainer::new(ContainerKind::StageDir).push(TextSpan::normal(text)),
+            ];
+            script.canonicalize();
+
+            let reparsed = Script::try_from(&Tex::from(script.to_tex()))
+                .unwrap_or_else(|e| panic!("{:?} could not be read back: {}", text, e));
+            assert_eq!(reparsed, script, "{:?} did not round-trip", text);
+        }
+    }
+
+    #[test]
+    fn test_custom_commands_are_substituted() {
+        use crate::md_handler::{to_markdown_with, MarkdownOptions, MarkdownStyle};
+
+        let source = r"\renewcommand{\SceneName}{Title}
+\scriptAuthor{lilellia}
+\scriptSeries{}
+\scriptTags{}
+\scriptDate{}
+\summary{}
+\begin{document}
+\clearpage
+\spoken{Well\sigh{} fine. \direct{\sigh} Come in.}
+\stagedir{She \sigh.}
+\end{document}";
+        let script = Script::try_from(&Tex::from(source)).unwrap();
+        assert_eq!(script.paragraphs.len(), 2);
+
+        let options = MarkdownOptions {
+            style: MarkdownStyle::from_toml("[substitutions]\nsigh = \"*sighs*\"").unwrap(),
+            ..Default::default()
+        };
+        let markdown = to_markdown_with(&script, &options);
+        assert!(markdown.contains("**Well*sighs* fine.** *(*sighs*)* **Come in.**"));
+        assert!(markdown.contains("She *sighs*."));
+
+        // only the commands the emitter produces are written as commands, so a custom command left in the
+        // text is written back out as literal text, which reads back as the same script
+        let tex = script.to_tex();
+        assert!(tex.contains(r"\spoken{Well\textbackslash{}sigh\{\} fine."));
+        assert_eq!(Script::try_from(&Tex::from(tex)).unwrap(), script);
+    }
+
+    #[test]
+    fn test_emoji_round_trip_through_shortcodes() {
+        use crate::{
+            emoji::emojify,
+            md_handler::{Markdown, ToMarkdown},
+        };
+
+        let options = ParseOptions {
+            shortcodes: true,
+            ..Default::default()
+        };
+        let source = "# Good night ✨\n\n**Sleep well ✨ \u{1f4a4}**";
+        let script = Markdown::from(source).parse_script(&options).unwrap();
+        assert_eq!(script.title, "Good night :sparkles:");
+        assert_eq!(
+            script.paragraphs[0].spans[0].contents,
+            "Sleep well :sparkles: :zzz:"
+        );
+
+        // the shortcodes are written to .tex as \emoji commands, and read back as shortcodes
+        let tex = script.to_tex();
+        assert!(tex.contains(r"\spoken{Sleep well \emoji{sparkles} \emoji{zzz}}"));
+        let mut reparsed = Script::try_from(&Tex::from(tex)).unwrap();
+        assert_eq!(reparsed, script);
+
+        emojify(&mut reparsed);
+        assert!(reparsed.to_markdown().contains("**Sleep well ✨ 💤**"));
+
+        // without the option, the emoji are kept as they are
+        let script = Markdown::from(source)
+            .parse_script(&ParseOptions::default())
+            .unwrap();
+        assert_eq!(script.paragraphs[0].spans[0].contents, "Sleep well ✨ 💤");
+    }
+
+    #[test]
+    fn test_container_parse_nested_braces() {
+        let tex = Tex::from(r"\spoken{Oh! \direct{a cue with {nested} braces} Hello {there}.}");
+        let container = TextContainer::try_from(&tex).unwrap();
+
+        assert_eq!(
+            container.spans,
+            vec![
+                TextSpan::normal("Oh!"),
+                TextSpan::inline("a cue with nested braces"),
+                TextSpan::normal("Hello there."),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_script_parse_multiline_commands_and_comments() {
+        let source = r"\renewcommand{\SceneName}{Title} % the working title
+\scriptAuthor{lilellia}
+\scriptSeries{}
+\scriptTags{[F4A]}
+\scriptDate{}
+\summary{A summary with {braces}.}
+\begin{document}
+\character{Speaker}
+    {a kind witch}
+\clearpage
+% \spoken{A line which was cut.}
+\spoken{Oh! You're here.
+    \direct{softly} I wasn't expecting you.} % 100\% a keeper
+\overlap{\sfx{rain}}
+    {\spoken{Listen to that.}}
+\stagedir{She smiles.}\sfx{Thunder.}
+\end{document}";
+
+        let document = Tex::from(source)
+            .parse_document(&ParseOptions {
+                strict: true,
+                ..Default::default()
+            })
+            .unwrap();
+        let script = Script::from(document.clone());
+
+        assert_eq!(script.title, "Title");
+        assert_eq!(script.summary, "A summary with braces.");
+        assert_eq!(script.characters[0].description, "a kind witch");
+        assert_eq!(script.paragraphs.len(), 4);
+        assert_eq!(
+            script.paragraphs[0].spans,
+            vec![
+                TextSpan::normal("Oh! You're here."),
+                TextSpan::inline("softly"),
+                TextSpan::normal("I wasn't expecting you."),
+            ]
+        );
+        assert_eq!(script.paragraphs[1].overlaps.len(), 1);
+
+        // each container is positioned at the whole of its (multi-line) command
+        let ranges: Vec<&str> = document
+            .containers()
+            .map(|node| &source[node.range.clone()])
+            .collect();
+        assert!(ranges[0].starts_with(r"\spoken{Oh!") && ranges[0].ends_with("you.}"));
+        assert_eq!(ranges[3], r"\sfx{Thunder.}");
+    }
+
+    #[test]
+    fn test_markdown_to_tex() {
+        let md = "# First Night\n\n## Characters\n\n- **Speaker** ∼ a kind witch\n\n\
+            > *[The door creaks open.]*\n\n\
+            *(softly)* **Oh! You're here & dry.**\n\n\
+            > *[sfx: rain (over the following line)]*\n\n\
+            **Listen to that rain.**";
+        let script = Script::try_from(&Markdown::from(md)).unwrap();
+
+        let tex = script.to_tex();
+        assert!(tex.contains(r"\renewcommand{\SceneName}{First Night}"));
+        assert!(tex.contains(r"\character{Speaker}{a kind witch}"));
+        assert!(tex.contains(r"\spoken{\direct{softly} Oh! You're here \& dry.}"));
+        assert!(tex.contains(r"\overlap{\sfx{rain}}{\spoken{Listen to that rain.}}"));
+
+        let reparsed = Script::try_from(&Tex::from(tex)).unwrap();
+        assert_eq!(reparsed.paragraphs, script.paragraphs);
+        assert_eq!(reparsed.characters, script.characters);
+    }
+
+    #[test]
+    fn test_round_trip_canonicalizes() {
+        let mut script = Script::new("lilellia", " A  Very Cool\tScript ");
+        script.series = SeriesEntry {
+            title: Some("Moonrise".to_owned()),
+            part: None,
+        };
+        script.tags = vec!["f4a".to_owned(), " ".to_owned()];
+        script.paragraphs = vec![
+            TextContainer::new(ContainerKind::Spoken)
+                .push(TextSpan::normal("Hello, "))
+                .push(TextSpan::normal("cafe\u{301} \"owner\"."))
+                .push(TextSpan::emphasis("#1_fan").with_lang("en"))
+                .push(TextSpan::inline(" "))
+                .spoken_by(""),
+            TextContainer::new(ContainerKind::StageDir),
+            TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal(":sparkles: 10:30:00")),
+        ];
+
+        let reparsed = Script::try_from(&Tex::from(script.to_tex())).unwrap();
+        script.canonicalize();
+        assert_eq!(reparsed, script);
+    }
+
+    #[test]
+    fn test_round_trip_with_preamble_template() {
+        let mut script = Script::new("lilellia", "Rock & Roll");
+        script.date = NaiveDate::from_ymd_opt(2024, 3, 3);
+        script.paragraphs =
+            vec![TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Hello."))];
+
+        let options = TexOptions {
+            preamble: "\\documentclass{article}\n\\title{{{title}}}\n\\date{{{ date }}}\n\\usepackage{{{unknown}}}\n"
+                .to_owned(),
+            ..Default::default()
+        };
+        let tex = script.to_tex_with(&options);
+
+        assert_eq!(
+            preamble(&tex),
+            Some("\\documentclass{article}\n\\title{Rock \\& Roll}\n\\date{03 Mar 2024}\n\\usepackage{{{unknown}}}\n")
+        );
+        assert_eq!(Script::try_from(&Tex::from(tex)).unwrap(), script);
+    }
+
+    #[test]
+    fn test_header_leaves_out_missing_target() {
+        let mut script = Script::new("lilellia", "Rock & Roll");
+        script.series = SeriesEntry::new("Moonrise", 2);
+        script.tags = vec!["F4A".to_owned()];
+        script.date = NaiveDate::from_ymd_opt(2024, 3, 3);
+        script.summary = "100% cosy.".to_owned();
+
+        let mut tex = String::new();
+        write_header(&script, &TexOptions::default(), &mut tex).unwrap();
+        assert_eq!(
+            tex,
+            r"\renewcommand{\SceneName}{Rock \& Roll}
+\scriptAuthor{lilellia}
+\scriptSeries{Moonrise (Part 2)}
+\scriptTags{[F4A]}
+\scriptDate{03 Mar 2024}
+\summary{100\% cosy.}
+\begin{document}
+\clearpage
+"
+        );
+    }
+
+    #[test]
+    fn test_round_trip_with_header_template() {
+        let mut script = Script::new("lilellia", "A Very Cool Script");
+        script.target = Some(3500);
+        script.characters = vec![
+            Character::new("Speaker", "a kind witch"),
+            Character::new("Listener", "a weary traveller"),
+        ];
+        script.paragraphs =
+            vec![TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Hello."))];
+
+        let options = TexOptions {
+            header: r"\renewcommand{\SceneName}{{{title}}}
+\scriptAuthor{{{author}}}
+\scriptSeries{{{series}}}
+\scriptTags{{{tags}}}
+\scriptDate{{{date}}}
+\summary{{{summary}}}
+\scriptTarget{{{ target }}}
+\begin{document}
+\section*{Characters}
+{{characters}}\clearpage
+"
+            .to_owned(),
+            ..Default::default()
+        };
+        let tex = script.to_tex_with(&options);
+
+        assert!(tex.contains(
+            "\\scriptTarget{3500}\n\\begin{document}\n\\section*{Characters}\n\\character{Speaker}{a kind witch}\n"
+        ));
+        assert_eq!(Script::try_from(&Tex::from(tex)).unwrap(), script);
+    }
+
+    #[test]
+    fn test_marker_round_trip() {
+        let mut script = Script::new("lilellia", "A Very Cool Script");
+        script.paragraphs = vec![
+            TextContainer::marker("take 1"),
+            TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Hello.")),
+        ];
+
+        let tex = script.to_tex();
+        assert!(tex.contains("\\clearpage\n\\marker{take 1}\n\n\\spoken{Hello.}\n"));
+
+        let reparsed = Script::try_from(&Tex::from(tex)).unwrap();
+        assert_eq!(reparsed, script);
+        assert_eq!(reparsed.wordcount().total(), 1);
+    }
+
+    #[test]
+    fn test_character_fields_round_trip() {
+        let mut witch = Character::new("Speaker", "a kind witch");
+        witch.voice = Some("warm, low & unhurried".to_owned());
+        witch.age = Some("30s".to_owned());
+        witch.pronouns = Some("she/her".to_owned());
+        witch.tags = vec!["lead".to_owned(), "witch".to_owned()];
+
+        let mut script = Script::new("lilellia", "A Very Cool Script");
+        script.characters = vec![witch, Character::new("Listener", "a weary traveller")];
+
+        let tex = script.to_tex();
+        assert!(tex.contains(
+            "\\character[voice=warm, low \\& unhurried; age=30s; pronouns=she/her; tags=lead, witch]{Speaker}{a kind witch}\n\\character{Listener}{a weary traveller}\n"
+        ));
+
+        let reparsed = Script::try_from(&Tex::from(tex)).unwrap();
+        assert_eq!(reparsed, script);
+    }
+
+    #[test]
+    fn test_unknown_character_field() {
+        let mut script = Script::new("lilellia", "A Very Cool Script");
+        script.characters = vec![Character::new("Speaker", "a kind witch")];
+        let source = script
+            .to_tex()
+            .replace(r"\character{", r"\character[height=tall]{");
+
+        let lenient = Tex::from(source.as_str())
+            .parse_script(&ParseOptions::default())
+            .unwrap();
+        assert_eq!(
+            lenient.characters,
+            vec![Character::new("Speaker", "a kind witch")]
+        );
+
+        let strict = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        assert!(Tex::from(source.as_str()).parse_script(&strict).is_err());
+    }
+
+    #[test]
+    fn test_malformed_header() {
+        let mut script = Script::new("lilellia", "A Very Cool Script");
+        script.date = NaiveDate::from_ymd_opt(2024, 3, 3);
+        script.characters = vec![Character::new("Speaker", "a kind witch")];
+        let source = script.to_tex();
+
+        let strict = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let parse = |source: &str, options: &ParseOptions| Tex::from(source).parse_script(options);
+
+        let reparsed = parse(&source, &strict).unwrap();
+        assert_eq!(reparsed.date, script.date);
+        assert_eq!(reparsed.characters, script.characters);
+
+        // a missing (or unclosed) field is always an error, naming the command
+        let missing = source.replace(r"\scriptAuthor{lilellia}", r"\scriptAuthr{lilellia}");
+        let e = parse(&missing, &ParseOptions::default()).unwrap_err();
+        assert!(e.contains(r"\scriptAuthor{...}"));
+        let unclosed = source.replace(r"\summary{", r"\summary{{");
+        assert!(parse(&unclosed, &ParseOptions::default()).is_err());
+
+        // anything merely questionable falls back to empty, unless parsing strictly
+        let date = source.replace("03 Mar 2024", "3rd March 2024");
+        assert_eq!(parse(&date, &strict).unwrap().date, script.date);
+        for malformed in [
+            source.replace("03 Mar 2024", "sometime in March"),
+            source.replace(r"\scriptTags{}", r"\scriptTags{F4A, comfort}"),
+            source.replace(r"{Speaker}{a kind witch}", r"{Speaker}"),
+        ] {
+            assert_ne!(malformed, source);
+            assert!(parse(&malformed, &ParseOptions::default()).is_ok());
+            assert!(parse(&malformed, &strict).is_err());
+        }
+    }
+
+    #[test]
+    fn test_split_single_script_is_unchanged() {
+        let mut script = Script::new("lilellia", "A Very Cool Script");
+        script.paragraphs =
+            vec![TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Hello."))];
+        let tex = Tex::from(script.to_tex());
+
+        let sources = tex.split();
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].text, tex.text);
+        assert_eq!(
+            tex.parse_scripts(&ParseOptions::default()).unwrap(),
+            vec![script]
+        );
+    }
+
+    #[test]
+    fn test_attributes_round_trip() {
+        let mut script = Script::new("lilellia", "A Very Cool Script");
+        script.paragraphs = vec![TextContainer::new(ContainerKind::Spoken)
+            .push(TextSpan::normal("Listen."))
+            .spoken_by("Witch")
+            .with_attribute("mood", "wry")
+            .overlapping(
+                TextContainer::new(ContainerKind::Sfx)
+                    .push(TextSpan::normal("rain"))
+                    .with_attribute("intensity", "soft")
+                    .with_attribute("category", "weather"),
+            )];
+
+        let tex = script.to_tex();
+        assert!(tex.contains(
+            r"\overlap{\sfx[category=weather, intensity=soft]{rain}}{\spoken[Witch, mood=wry]{Listen.}}"
+        ));
+
+        let reparsed = Script::try_from(&Tex::from(tex)).unwrap();
+        assert_eq!(reparsed, script);
+    }
+
+    #[test]
+    fn test_commentary_round_trip() {
+        let mut script = Script::new("lilellia", "A Very Cool Script");
+        script.paragraphs = vec![
+            TextContainer::new(ContainerKind::Spoken)
+                .push(TextSpan::normal("Welcome home."))
+                .with_commentary("The line the whole series was built around.")
+                .with_commentary("Recorded in one take."),
+            TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal("door closes")),
+        ];
+
+        let tex = script.to_tex();
+        assert!(tex.contains(
+            "\\spoken{Welcome home.}\n\\commentary{The line the whole series was built around.}\n"
+        ));
+
+        let reparsed = Script::try_from(&Tex::from(tex)).unwrap();
+        assert_eq!(reparsed, script);
+        assert_eq!(reparsed.wordcount().total(), 4);
+    }
+
+    #[test]
+    fn test_commentary_without_a_line() {
+        let tex = "\\renewcommand{\\SceneName}{Title}\n\\scriptAuthor{lilellia}\n\\scriptSeries{}\n\\scriptTags{}\n\\scriptDate{}\n\\summary{}\n\\clearpage\n\\commentary{Too early.}\n\\spoken{Hi.}";
+        let strict = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        assert!(Tex::from(tex).parse_document(&strict).is_err());
+
+        let lenient = ParseOptions {
+            quiet: true,
+            ..Default::default()
+        };
+        let document = Tex::from(tex).parse_document(&lenient).unwrap();
+        let script = Script::from(document);
+        assert_eq!(script.paragraphs.len(), 1);
+        assert!(script.paragraphs[0].commentary.is_empty());
+    }
+
+    #[test]
+    fn test_replace_leaves_attributes_alone() {
+        let s = r"\sfx[category=rain]{rain} \spoken[Rain, mood=rain]{Rain!}";
+        let pattern = Regex::new("(?i)rain").unwrap();
+
+        let (replaced, count) = replace_text(s, &pattern, "Snow", ReplaceScope::All);
+        assert_eq!(count, 3);
+        assert_eq!(
+            replaced,
+            r"\sfx[category=rain]{Snow} \spoken[Snow, mood=rain]{Snow!}"
+        );
+    }
+
+    #[test]
+    fn test_custom_commands() {
+        let mut script = Script::new("lilellia", "A Very Cool Script");
+        script.paragraphs = vec![
+            TextContainer::new(ContainerKind::Spoken)
+                .push(TextSpan::inline("softly"))
+                .push(TextSpan::normal("Listen."))
+                .push(TextSpan::emphasis("Really"))
+                .push(TextSpan::normal(".")),
+            TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal("rain")),
+        ];
+
+        let mut options = TexOptions::default();
+        options.commands.set("emphasis", "emph").unwrap();
+        options.commands.set("direction", "tone").unwrap();
+        options.commands.set("sfx", "sound").unwrap();
+
+        let tex = script.to_tex_with(&options);
+        assert!(tex.contains("\\spoken{\\tone{softly} Listen. \\emph{Really}.}\n\n\\sound{rain}\n"));
+
+        // \emph is understood by the parser, so only the emphasis survives a round trip
+        let mut options = TexOptions::default();
+        options.commands.set("emphasis", "emph").unwrap();
+        let reparsed = Script::try_from(&Tex::from(script.to_tex_with(&options))).unwrap();
+        assert_eq!(reparsed, script);
+    }
+
+    #[test]
+    fn test_replace_text_matches_script_replace() {
+        let source = r"\renewcommand{\SceneName}{Mira's Kettle}
+\scriptAuthor{lilellia}
+\scriptSeries{Mira (Part 1)}
+\scriptTags{[Mira]}
+\scriptDate{}
+\summary{Mira makes tea.}
+\begin{document}
+\character{Mira}{a witch called Mira}
+\clearpage
+\stagedir{Mira opens the door.}
+\spoken[Mira]{\direct{to Mira} Mira\textellipsis{} \lang{fr}{Mira}, come in.}
+\overlap{\sfx{Mira's kettle}}{\listener{Hi, Mira.}}
+\plaintext{Mira's note}
+\marker{Mira}
+\end{document}";
+        let pattern = Regex::new("Mira").unwrap();
+
+        for scope in [
+            ReplaceScope::Spoken,
+            ReplaceScope::Directions,
+            ReplaceScope::All,
+        ] {
+            let (replaced, count) = replace_text(source, &pattern, "Nell", scope);
+            let from_source = Script::try_from(&Tex::from(replaced)).unwrap();
+
+            let mut script = Script::try_from(&Tex::from(source)).unwrap();
+            assert_eq!(script.replace(&pattern, "Nell", scope), count);
+            assert_eq!(script.paragraphs, from_source.paragraphs);
+            assert_eq!(script.characters[0].name, from_source.characters[0].name);
+            assert_eq!(script.title, from_source.title);
+        }
+    }
+}
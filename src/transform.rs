@@ -0,0 +1,395 @@
+use crate::script::{normalize_tag, ContainerKind, Script, SpanKind, TextSpan};
+use regex::{Captures, Regex};
+use std::collections::BTreeMap;
+
+/// The built-in synonyms for the wording of tone cues, applied by `--normalize-cues`.
+pub const CUE_SYNONYMS: [(&str, &str); 8] = [
+    ("softly", "quietly"),
+    ("giggling", "giggle"),
+    ("giggles", "giggle"),
+    ("laughing", "laugh"),
+    ("laughs", "laugh"),
+    ("sighing", "sigh"),
+    ("sighs", "sigh"),
+    ("whispering", "whisper"),
+];
+
+/// The built-in transforms which can be applied to a script in place.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transform {
+    /// Write the text of sound effects in upper case, e.g. "RAIN ON THE WINDOW"
+    UppercaseSfx,
+
+    /// Write tone cues (inline directions) in sentence case, e.g. "Softly, to herself"
+    SentenceCaseCues,
+
+    /// Turn words written in ALL CAPS within spoken lines into emphasised spans
+    CapsEmphasis,
+}
+
+impl Transform {
+    /// Every built-in transform, in the order in which `--normalize` applies them.
+    pub const ALL: [Self; 3] = [
+        Self::UppercaseSfx,
+        Self::SentenceCaseCues,
+        Self::CapsEmphasis,
+    ];
+
+    /// Apply the transform to the given script.
+    pub fn apply(&self, script: &mut Script) {
+        match self {
+            Self::UppercaseSfx => uppercase_sfx(script),
+            Self::SentenceCaseCues => sentence_case_cues(script),
+            Self::CapsEmphasis => caps_emphasis(script),
+        }
+    }
+}
+
+/** Write the text of every sound effect in upper case.
+
+# Examples
+
+```
+# use lilscript::{script::{ContainerKind, Script, TextContainer, TextSpan}, transform::uppercase_sfx};
+let mut script = Script::new("lilellia", "A Very Cool Script");
+script.paragraphs.push(TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal("rain on the window")));
+
+uppercase_sfx(&mut script);
+assert_eq!(script.paragraphs[0].plain_text(), "RAIN ON THE WINDOW");
+```
+*/
+pub fn uppercase_sfx(script: &mut Script) {
+    script.map_containers(|container| {
+        if container.kind == ContainerKind::Sfx {
+            for span in container.spans.iter_mut() {
+                span.contents = span.contents.to_uppercase();
+            }
+        }
+    });
+}
+
+/** Write every tone cue (i.e., inline direction) in sentence case.
+
+Note that this also lowercases any proper nouns after the first word.
+
+# Examples
+
+```
+# use lilscript::{script::{ContainerKind, Script, TextContainer, TextSpan}, transform::sentence_case_cues};
+let mut script = Script::new("lilellia", "A Very Cool Script");
+script.paragraphs.push(
+    TextContainer::new(ContainerKind::Spoken)
+        .push(TextSpan::inline("SOFTLY, To Herself"))
+        .push(TextSpan::normal("Hello.")),
+);
+
+sentence_case_cues(&mut script);
+assert_eq!(script.paragraphs[0].to_string(), "(Softly, to herself) Hello.");
+```
+*/
+pub fn sentence_case_cues(script: &mut Script) {
+    script.map_spans(|span| {
+        if span.kind == SpanKind::InlineDirection {
+            span.contents = sentence_case(&span.contents);
+        }
+    });
+}
+
+/// Convert the given text to sentence case: the first letter in upper case, and the rest in lower case.
+fn sentence_case(text: &str) -> String {
+    let lower = text.to_lowercase();
+    match lower.char_indices().find(|(_, c)| c.is_alphabetic()) {
+        Some((i, c)) => format!(
+            "{}{}{}",
+            &lower[..i],
+            c.to_uppercase(),
+            &lower[i + c.len_utf8()..]
+        ),
+        None => lower,
+    }
+}
+
+/** Turn runs of words written in ALL CAPS within spoken lines into emphasised spans (in lower case).
+
+Only words of at least two letters count, so "I" and "A" are left alone. Any punctuation directly after a run
+stays attached to it, rather than becoming a span of its own. Note that acronyms (e.g., "ASMR") are
+indistinguishable from emphasis, and will be converted too.
+
+# Examples
+
+```
+# use lilscript::{script::{ContainerKind, Script, TextContainer, TextSpan}, transform::caps_emphasis};
+let mut script = Script::new("lilellia", "A Very Cool Script");
+script.paragraphs.push(
+    TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("I am VERY VERY tired.")),
+);
+
+caps_emphasis(&mut script);
+assert_eq!(
+    script.paragraphs[0].spans,
+    vec![
+        TextSpan::normal("I am"),
+        TextSpan::emphasis("very very"),
+        TextSpan::normal("tired."),
+    ]
+);
+```
+
+```
+# use lilscript::{render, script::{ContainerKind, Script, TextContainer, TextSpan}, transform::caps_emphasis};
+# use lilscript::{FileFormat, RenderOptions};
+let mut script = Script::new("lilellia", "A Very Cool Script");
+script.paragraphs.push(
+    TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("I am VERY tired, HONESTLY!")),
+);
+
+caps_emphasis(&mut script);
+assert_eq!(
+    script.paragraphs[0].spans,
+    vec![
+        TextSpan::normal("I am"),
+        TextSpan::emphasis("very"),
+        TextSpan::normal("tired,"),
+        TextSpan::emphasis("honestly!"),
+    ]
+);
+
+// nothing about the emphasis is ambiguous, even in strict mode
+let options = RenderOptions { strict: true, ..Default::default() };
+assert!(render(&script, FileFormat::Markdown, &options).is_ok());
+```
+*/
+pub fn caps_emphasis(script: &mut Script) {
+    let re = Regex::new(r"\b\p{Lu}{2,}(?:\s+\p{Lu}{2,})*\b[\p{P}--\p{Ps}]*").unwrap();
+
+    script.map_containers(|container| {
+        if container.kind != ContainerKind::Spoken {
+            return;
+        }
+
+        let mut spans: Vec<TextSpan> = Vec::with_capacity(container.spans.len());
+        for span in container.spans.drain(..) {
+            if span.kind != SpanKind::Normal || !re.is_match(&span.contents) {
+                spans.push(span);
+                continue;
+            }
+
+            let mut position = 0;
+            let mut push = |kind: SpanKind, contents: &str| {
+                let contents = contents.trim();
+                if !contents.is_empty() {
+                    spans.push(TextSpan {
+                        kind,
+                        contents: contents.to_owned(),
+                        lang: span.lang.clone(),
+                    });
+                }
+            };
+
+            for m in re.find_iter(&span.contents) {
+                push(SpanKind::Normal, &span.contents[position..m.start()]);
+                push(SpanKind::Emphasis, &m.as_str().to_lowercase());
+                position = m.end();
+            }
+            push(SpanKind::Normal, &span.contents[position..]);
+        }
+
+        container.spans = spans;
+    });
+}
+
+/** Reword tone cues (i.e., inline directions) consistently, replacing each word or phrase in the given
+map of synonyms with the wording it maps to (e.g., "softly" ⟶ "quietly"), so that the cues of a series
+written over months read the same throughout.
+
+Synonyms are matched as whole words, ignoring case, and a replacement is capitalised if the text it
+replaces was. When synonyms overlap, the longest is replaced.
+
+# Examples
+
+```
+# use lilscript::{script::{ContainerKind, Script, TextContainer, TextSpan}, transform::normalize_cues};
+# use std::collections::BTreeMap;
+let mut script = Script::new("lilellia", "A Very Cool Script");
+script.paragraphs.push(
+    TextContainer::new(ContainerKind::Spoken)
+        .push(TextSpan::inline("Giggling softly"))
+        .push(TextSpan::normal("You're so softly spoken.")),
+);
+
+let synonyms = BTreeMap::from([
+    ("softly".to_owned(), "quietly".to_owned()),
+    ("giggling".to_owned(), "giggle".to_owned()),
+]);
+normalize_cues(&mut script, &synonyms);
+assert_eq!(script.paragraphs[0].to_string(), "(Giggle quietly) You're so softly spoken.");
+```
+*/
+pub fn normalize_cues(script: &mut Script, synonyms: &BTreeMap<String, String>) {
+    let synonyms: BTreeMap<String, &str> = synonyms
+        .iter()
+        .filter(|(from, _)| !from.trim().is_empty())
+        .map(|(from, to)| (from.trim().to_lowercase(), to.trim()))
+        .collect();
+    if synonyms.is_empty() {
+        return;
+    }
+
+    let mut words: Vec<&str> = synonyms.keys().map(String::as_str).collect();
+    words.sort_by_key(|word| std::cmp::Reverse(word.len()));
+    let words: Vec<String> = words.into_iter().map(regex::escape).collect();
+    let re = Regex::new(&format!(r"(?i)\b(?:{})\b", words.join("|"))).unwrap();
+
+    script.map_spans(|span| {
+        if span.kind != SpanKind::InlineDirection {
+            return;
+        }
+
+        let replaced = re.replace_all(&span.contents, |caps: &Captures| {
+            let matched = &caps[0];
+            let replacement = synonyms[&matched.to_lowercase()];
+            match matched.chars().next() {
+                Some(c) if c.is_uppercase() => sentence_case_first(replacement),
+                _ => replacement.to_owned(),
+            }
+        });
+        span.contents = replaced.into_owned();
+    });
+}
+
+/// Capitalise the first letter of the given text, leaving the rest as it is.
+fn sentence_case_first(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(c) => c.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/** Move each tone cue (i.e., inline direction) from before the sentence it modifies to just after it,
+for communities which prefer trailing parentheticals. A cue which has no sentence after it stays where it is.
+
+Sentences end at `.`, `!`, `?`, or `…` (along with any closing quotes or brackets) followed by whitespace,
+or at the end of the line.
+
+# Examples
+
+```
+# use lilscript::{script::{ContainerKind, Script, TextContainer, TextSpan}, transform::trailing_directions};
+let mut script = Script::new("lilellia", "A Very Cool Script");
+script.paragraphs.push(
+    TextContainer::new(ContainerKind::Spoken)
+        .push(TextSpan::inline("softly"))
+        .push(TextSpan::normal("Oh! You're"))
+        .push(TextSpan::emphasis("here"))
+        .push(TextSpan::normal("already?"))
+        .push(TextSpan::inline("giggles"))
+        .push(TextSpan::normal("Come in, then")),
+);
+
+trailing_directions(&mut script);
+assert_eq!(
+    script.paragraphs[0].to_string(),
+    "Oh! (softly) You're /here/ already? Come in, then (giggles)"
+);
+```
+*/
+pub fn trailing_directions(script: &mut Script) {
+    let boundary = Regex::new(r#"[.!?…]+["'”’)\]]*(?:\s+|$)"#).unwrap();
+
+    script.map_containers(|container| {
+        let mut spans: Vec<TextSpan> = Vec::with_capacity(container.spans.len());
+
+        // the cues waiting for the end of the sentence which they modify
+        let mut pending: Vec<TextSpan> = Vec::new();
+
+        for span in container.spans.drain(..) {
+            if span.kind == SpanKind::InlineDirection {
+                pending.push(span);
+                continue;
+            }
+
+            let end = match boundary.find(&span.contents) {
+                Some(m) if !pending.is_empty() => m.end(),
+                _ => {
+                    spans.push(span);
+                    continue;
+                }
+            };
+
+            let (head, tail) = span.contents.split_at(end);
+            let (head, tail) = (head.trim_end().to_owned(), tail.trim_start().to_owned());
+            spans.push(TextSpan {
+                contents: head,
+                ..span.clone()
+            });
+            spans.append(&mut pending);
+            if !tail.is_empty() {
+                spans.push(TextSpan {
+                    contents: tail,
+                    ..span
+                });
+            }
+        }
+
+        spans.append(&mut pending);
+        container.spans = spans;
+    });
+}
+
+/** Remove everything from the script's metadata which could give its twist away, leaving the body intact:
+its summary, and any of the given spoiler tags (compared after normalization; see `normalize_tag`).
+
+# Examples
+
+```
+# use lilscript::{script::{ContainerKind, Script, TextContainer, TextSpan}, transform::strip_spoilers};
+let mut script = Script::new("lilellia", "A Very Cool Script");
+script.summary = "The witch was the cat all along.".to_owned();
+script.tags = vec!["F4A".into(), "Secret Identity".into(), "comfort".into()];
+script.paragraphs.push(TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Meow.")));
+
+strip_spoilers(&mut script, &["secret identity".to_owned()]);
+assert_eq!(script.summary, "");
+assert_eq!(script.tags, vec!["F4A", "comfort"]);
+assert_eq!(script.paragraphs.len(), 1);
+```
+*/
+pub fn strip_spoilers(script: &mut Script, spoiler_tags: &[String]) {
+    let spoiler_tags: Vec<String> = spoiler_tags.iter().map(|tag| normalize_tag(tag)).collect();
+
+    script.summary.clear();
+    script
+        .tags
+        .retain(|tag| !spoiler_tags.contains(&normalize_tag(tag)));
+}
+
+/// The stage direction which replaces each listener line in `hide_listener_lines`.
+pub const LISTENER_PLACEHOLDER: &str = "listener responds";
+
+/** Replace the text of every line of listener dialogue with a neutral stage direction (`LISTENER_PLACEHOLDER`),
+for platforms and performers who prefer not to show scripted listener lines at all. Any cues overlapping
+the line are kept.
+
+# Examples
+
+```
+# use lilscript::{md_handler::ToMarkdown, script::{ContainerKind, Script, TextContainer, TextSpan}, transform::hide_listener_lines};
+let mut script = Script::new("lilellia", "A Very Cool Script");
+script.paragraphs.push(
+    TextContainer::new(ContainerKind::ListenerDialogue).push(TextSpan::normal("Is it raining?")),
+);
+
+hide_listener_lines(&mut script);
+assert_eq!(script.paragraphs[0].to_markdown(), "> *[listener responds]*");
+```
+*/
+pub fn hide_listener_lines(script: &mut Script) {
+    script.map_containers(|container| {
+        if container.kind == ContainerKind::ListenerDialogue {
+            container.kind = ContainerKind::StageDir;
+            container.spans = vec![TextSpan::normal(LISTENER_PLACEHOLDER)];
+            container.speaker = None;
+        }
+    });
+}
@@ -0,0 +1,490 @@
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    DefaultTerminal, Frame,
+};
+
+use crate::{
+    ast::{line_of, Document},
+    format::FileFormat,
+    script::{
+        decode_text, ContainerKind, ParseOptions, Script, ScriptError, TextContainer, WordCount,
+    },
+    stats::ScriptStats,
+    tex_handler::lenient_lines,
+};
+
+/// How often to check whether the file has changed, when no key has been pressed.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A row of the inspector: either a line of the script, or a problem with the source.
+#[derive(Clone, Debug, PartialEq)]
+enum Row {
+    Line {
+        line: usize,
+        container: TextContainer,
+    },
+    Problem {
+        line: usize,
+        message: String,
+    },
+}
+
+impl Row {
+    /// The (1-based) line of the source which the row is from.
+    fn line(&self) -> usize {
+        match self {
+            Self::Line { line, .. } | Self::Problem { line, .. } => *line,
+        }
+    }
+}
+
+/// The state of the inspector: the script as it was last parsed, and where in it the cursor is.
+pub struct Inspector {
+    path: PathBuf,
+    options: ParseOptions,
+    modified: Option<SystemTime>,
+    script: Option<Script>,
+    error: Option<String>,
+    rows: Vec<Row>,
+    state: ListState,
+}
+
+impl Inspector {
+    /// Create an inspector for the script at the given path, and parse it for the first time.
+    pub fn new(path: &Path, options: &ParseOptions) -> Self {
+        let mut inspector = Self {
+            path: path.to_owned(),
+            options: ParseOptions {
+                quiet: true,
+                strict: false,
+                ..options.clone()
+            },
+            modified: None,
+            script: None,
+            error: None,
+            rows: Vec::new(),
+            state: ListState::default(),
+        };
+        inspector.reload();
+        inspector
+    }
+
+    /// Re-read and re-parse the file, keeping the cursor where it was as far as possible.
+    fn reload(&mut self) {
+        self.modified = modified(&self.path);
+
+        let contents = match fs::read(&self.path)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| decode_text(&bytes).map_err(|e| e.to_string()))
+        {
+            Ok(contents) => contents,
+            Err(e) => return self.fail(e),
+        };
+
+        let Some(format) = FileFormat::detect(&self.path, &contents) else {
+            return self.fail(ScriptError::UnknownFormat.to_string());
+        };
+        match Document::parse(&contents, format, &self.options) {
+            Ok(document) => {
+                self.rows = rows(&contents, format, &document, &self.options);
+                self.script = Some(Script::from(document));
+                self.error = None;
+            }
+            Err(e) => return self.fail(e.to_string()),
+        }
+
+        let selected = self.state.selected().unwrap_or(0);
+        self.state
+            .select((!self.rows.is_empty()).then(|| selected.min(self.rows.len() - 1)));
+    }
+
+    /// Record that the file couldn't be read or parsed at all.
+    fn fail(&mut self, error: String) {
+        self.script = None;
+        self.rows.clear();
+        self.error = Some(error);
+        self.state.select(None);
+    }
+
+    /// Reload the file if it has changed since it was last read.
+    fn reload_if_changed(&mut self) {
+        if modified(&self.path) != self.modified {
+            self.reload();
+        }
+    }
+
+    /// Move the cursor to the next (or, going backwards, the previous) problem, wrapping around.
+    fn jump_to_problem(&mut self, forwards: bool) {
+        let n = self.rows.len();
+        let current = self.state.selected().unwrap_or(0);
+
+        let found = (1..=n)
+            .map(|step| {
+                if forwards {
+                    (current + step) % n
+                } else {
+                    (current + n - step % n) % n
+                }
+            })
+            .find(|&i| matches!(self.rows[i], Row::Problem { .. }));
+
+        if let Some(i) = found {
+            self.state.select(Some(i));
+        }
+    }
+
+    /// The number of problems found in the file.
+    fn problems(&self) -> usize {
+        self.rows
+            .iter()
+            .filter(|row| matches!(row, Row::Problem { .. }))
+            .count()
+    }
+
+    /// Draw the inspector: a title bar, the lines of the script, the statistics, and the keys.
+    fn draw(&mut self, frame: &mut Frame) {
+        let [title, body, help] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [lines, stats] =
+            Layout::horizontal([Constraint::Min(0), Constraint::Length(36)]).areas(body);
+
+        let heading = match &self.script {
+            Some(script) => format!("{} — by {}", script.title, script.author),
+            None => String::from("(could not be parsed)"),
+        };
+        frame.render_widget(
+            Paragraph::new(Line::from(vec![
+                Span::styled(heading, Style::new().add_modifier(Modifier::BOLD)),
+                Span::raw(format!("  {}", self.path.display())),
+            ])),
+            title,
+        );
+
+        if let Some(error) = &self.error {
+            frame.render_widget(
+                Paragraph::new(error.as_str())
+                    .style(Style::new().fg(Color::Red))
+                    .block(Block::new().borders(Borders::ALL).title("Error")),
+                body,
+            );
+        } else {
+            let items: Vec<ListItem> = self.rows.iter().map(list_item).collect();
+            let list = List::new(items)
+                .block(Block::new().borders(Borders::ALL).title("Script"))
+                .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+            frame.render_stateful_widget(list, lines, &mut self.state);
+
+            frame.render_widget(
+                Paragraph::new(self.stats())
+                    .block(Block::new().borders(Borders::ALL).title("Stats")),
+                stats,
+            );
+        }
+
+        frame.render_widget(
+            Paragraph::new(
+                "↑/↓ move · g/G top/bottom · n/N next/previous problem · r reload · q quit",
+            )
+            .style(Style::new().fg(Color::DarkGray)),
+            help,
+        );
+    }
+
+    /// The statistics panel: the word counts of the script and of the selected line, and the problems.
+    fn stats(&self) -> Vec<Line<'static>> {
+        let script = match &self.script {
+            Some(script) => script,
+            None => return Vec::new(),
+        };
+
+        let mut lines: Vec<Line> = ScriptStats::from(script)
+            .to_string()
+            .replace(" -> ", "\n-> ")
+            .lines()
+            .map(|line| Line::from(line.to_owned()))
+            .collect();
+
+        if let Some(Row::Line { container, .. }) =
+            self.state.selected().and_then(|i| self.rows.get(i))
+        {
+            let wordcount = container.wordcount();
+            lines.push(Line::from(""));
+            lines.push(Line::from(format!(
+                "This line: {} / {} words",
+                wordcount.spoken(),
+                wordcount.total()
+            )));
+        }
+
+        let problems = self.problems();
+        lines.push(Line::from(""));
+        lines.push(Line::styled(
+            format!("{} problems", problems),
+            match problems {
+                0 => Style::new().fg(Color::Green),
+                _ => Style::new().fg(Color::Red),
+            },
+        ));
+        lines
+    }
+}
+
+/// When the file at the path was last modified, if that can be determined.
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Build the rows of the inspector: each line of the document, along with anything which would be warned about
+/// (lines which were skipped, and lines which only parsed because the parser was lenient), in source order.
+fn rows(
+    contents: &str,
+    format: FileFormat,
+    document: &Document,
+    options: &ParseOptions,
+) -> Vec<Row> {
+    let mut rows: Vec<Row> = document
+        .containers()
+        .map(|node| Row::Line {
+            line: line_of(contents, node.range.start),
+            container: TextContainer::from(node),
+        })
+        .collect();
+
+    // only .tex is parsed leniently, line by line; the other formats report their problems as skipped
+    let problems = match format {
+        FileFormat::Tex => lenient_lines(contents, document, options),
+        _ => Vec::new(),
+    };
+    for problem in problems.iter().chain(&document.skipped) {
+        rows.push(Row::Problem {
+            line: line_of(contents, problem.range.start),
+            message: problem.value.clone(),
+        });
+    }
+
+    // stable, so each problem stays after the line it's about
+    rows.sort_by_key(Row::line);
+    rows
+}
+
+/// The colour which each kind of container is shown in.
+fn colour(kind: &ContainerKind) -> Color {
+    match kind {
+        ContainerKind::Spoken => Color::White,
+        ContainerKind::StageDir => Color::Yellow,
+        ContainerKind::Sfx => Color::Cyan,
+        ContainerKind::ListenerDialogue => Color::Magenta,
+        ContainerKind::PlainText => Color::Gray,
+        ContainerKind::Marker => Color::Green,
+    }
+}
+
+/// Render a row of the inspector as an item of the list.
+fn list_item(row: &Row) -> ListItem<'static> {
+    match row {
+        Row::Line { line, container } => {
+            let label = match &container.speaker {
+                Some(speaker) => format!("{} ({})", container.kind, speaker),
+                None => container.kind.to_string(),
+            };
+            let words = match container.wordcount() {
+                wordcount if wordcount == WordCount::zero() => String::new(),
+                wordcount => format!("  [{}]", wordcount.total()),
+            };
+
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{:>5} ", line), Style::new().fg(Color::DarkGray)),
+                Span::styled(
+                    format!("{}: ", label),
+                    Style::new()
+                        .fg(colour(&container.kind))
+                        .add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(
+                    container.to_string(),
+                    Style::new().fg(colour(&container.kind)),
+                ),
+                Span::styled(words, Style::new().fg(Color::DarkGray)),
+            ]))
+        }
+        Row::Problem { line, message } => ListItem::new(Line::from(vec![
+            Span::styled(format!("{:>5} ", line), Style::new().fg(Color::DarkGray)),
+            Span::styled(format!("⚠ {}", message), Style::new().fg(Color::Red)),
+        ])),
+    }
+}
+
+/// Run the inspector over the .tex file at the given path until the user quits.
+pub fn run(path: &Path, options: &ParseOptions) -> Result<(), Box<dyn Error>> {
+    let mut inspector = Inspector::new(path, options);
+
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut inspector);
+    ratatui::restore();
+
+    result
+}
+
+/// Draw the inspector and respond to keys (and changes to the file) until the user quits.
+fn event_loop(
+    terminal: &mut DefaultTerminal,
+    inspector: &mut Inspector,
+) -> Result<(), Box<dyn Error>> {
+    loop {
+        terminal.draw(|frame| inspector.draw(frame))?;
+
+        if !event::poll(POLL_INTERVAL)? {
+            inspector.reload_if_changed();
+            continue;
+        }
+
+        let key = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Down | KeyCode::Char('j') => inspector.state.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => inspector.state.select_previous(),
+            KeyCode::PageDown => inspector.state.scroll_down_by(20),
+            KeyCode::PageUp => inspector.state.scroll_up_by(20),
+            KeyCode::Home | KeyCode::Char('g') => inspector.state.select_first(),
+            KeyCode::End | KeyCode::Char('G') => inspector.state.select_last(),
+            KeyCode::Char('n') => inspector.jump_to_problem(true),
+            KeyCode::Char('N') => inspector.jump_to_problem(false),
+            KeyCode::Char('r') => inspector.reload(),
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SOURCE: &str = r"\renewcommand{\SceneName}{A Very Cool Script}
+\scriptAuthor{lilellia}
+\scriptSeries{}
+\scriptTags{}
+\scriptDate{}
+\summary{}
+\begin{document}
+\clearpage
+\spoken{Hello.}
+\whisper{Psst.}
+\spoken{Still here?}
+\spoken{unbalanced
+\sfx{rain}
+\end{document}";
+
+    fn inspector() -> Inspector {
+        let path = std::env::temp_dir().join(format!("lilscript-tui-{}.tex", std::process::id()));
+        fs::write(&path, SOURCE).unwrap();
+        let inspector = Inspector::new(&path, &ParseOptions::default());
+        fs::remove_file(&path).unwrap();
+        inspector
+    }
+
+    #[test]
+    fn test_rows_interleave_problems() {
+        let inspector = inspector();
+        let lines: Vec<(usize, bool)> = inspector
+            .rows
+            .iter()
+            .map(|row| (row.line(), matches!(row, Row::Problem { .. })))
+            .collect();
+
+        assert_eq!(
+            lines,
+            vec![
+                (9, false),
+                (10, false),
+                (10, true),
+                (11, false),
+                (12, true),
+                (13, false)
+            ]
+        );
+        assert_eq!(inspector.problems(), 2);
+    }
+
+    #[test]
+    fn test_jump_to_problem_wraps_around() {
+        let mut inspector = inspector();
+        inspector.state.select(Some(0));
+
+        inspector.jump_to_problem(true);
+        assert_eq!(inspector.state.selected(), Some(2));
+        inspector.jump_to_problem(true);
+        assert_eq!(inspector.state.selected(), Some(4));
+        inspector.jump_to_problem(true);
+        assert_eq!(inspector.state.selected(), Some(2));
+
+        inspector.jump_to_problem(false);
+        assert_eq!(inspector.state.selected(), Some(4));
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut inspector = inspector();
+        inspector.jump_to_problem(true);
+
+        let mut terminal =
+            ratatui::Terminal::new(ratatui::backend::TestBackend::new(100, 12)).unwrap();
+        terminal.draw(|frame| inspector.draw(frame)).unwrap();
+
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("A Very Cool Script — by lilellia"));
+        assert!(screen.contains("Spoken: Hello."));
+        assert!(screen.contains("2 problems"));
+    }
+
+    #[test]
+    fn test_reads_every_input_format() {
+        let path = std::env::temp_dir().join(format!("lilscript-tui-{}.md", std::process::id()));
+        fs::write(
+            &path,
+            "# A Very Cool Script\n\n> *[A door opens.]*\n\n**Hello.**\n",
+        )
+        .unwrap();
+        let inspector = Inspector::new(&path, &ParseOptions::default());
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(inspector.error, None);
+        assert_eq!(inspector.script.unwrap().title, "A Very Cool Script");
+        let lines: Vec<(usize, ContainerKind)> = inspector
+            .rows
+            .iter()
+            .map(|row| match row {
+                Row::Line { line, container } => (*line, container.kind.clone()),
+                Row::Problem { message, .. } => panic!("unexpected problem: {}", message),
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            vec![(3, ContainerKind::StageDir), (5, ContainerKind::Spoken)]
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use crate::script::{Script, TextContainer, TextSpan};
+
+/// Where a container sits within the script being visited.
+#[derive(Clone, Copy, Debug)]
+pub struct ContainerContext<'a> {
+    /// The index of the paragraph (within `Script::paragraphs`) which the container belongs to.
+    pub index: usize,
+
+    /// For an overlapping cue, the line which it runs concurrently with; `None` otherwise.
+    pub overlapping: Option<&'a TextContainer>,
+}
+
+/**
+A visitor over the document tree of a script: its metadata, each of its containers (including any
+overlapping cues), and each of their spans.
+
+Every method has a default implementation, so a visitor only needs to implement those it cares about.
+The defaults of `visit_script` and `visit_container` carry on walking down the tree (via `walk_script` and
+`walk_container`), so a visitor which overrides one of them should call the corresponding `walk_*`
+function if it still wants to visit what's inside.
+
+Cues which overlap a line are visited just before that line, in the same order as `TextContainer::with_overlaps`.
+
+# Examples
+
+```
+# use lilscript::{script::{ContainerKind, Script, SpanKind, TextContainer, TextSpan}, visitor::{ContainerContext, ScriptVisitor}};
+/// Count the inline directions in a script.
+struct CountDirections(usize);
+
+impl ScriptVisitor for CountDirections {
+    fn visit_span(&mut self, span: &TextSpan, _container: &TextContainer, _context: ContainerContext) {
+        if span.kind == SpanKind::InlineDirection {
+            self.0 += 1;
+        }
+    }
+}
+
+let mut script = Script::new("lilellia", "A Very Cool Script");
+script.paragraphs.push(
+    TextContainer::new(ContainerKind::Spoken)
+        .push(TextSpan::inline("softly"))
+        .push(TextSpan::normal("Hello."))
+        .push(TextSpan::inline("laughs")),
+);
+
+let mut counter = CountDirections(0);
+counter.visit_script(&script);
+assert_eq!(counter.0, 2);
+```
+*/
+pub trait ScriptVisitor {
+    /// Visit the whole script. By default, this visits its metadata and then walks its body.
+    fn visit_script(&mut self, script: &Script) {
+        walk_script(self, script);
+    }
+
+    /// Visit the script's metadata (title, author, series, tags, characters, etc.).
+    fn visit_metadata(&mut self, _script: &Script) {}
+
+    /// Visit a container. By default, this walks its spans.
+    fn visit_container(&mut self, container: &TextContainer, context: ContainerContext) {
+        walk_container(self, container, context);
+    }
+
+    /// Visit a span, along with the container it belongs to.
+    fn visit_span(
+        &mut self,
+        _span: &TextSpan,
+        _container: &TextContainer,
+        _context: ContainerContext,
+    ) {
+    }
+}
+
+/// Visit the metadata of the script, and then each of its containers (with any overlapping cues
+/// just before the line they overlap).
+pub fn walk_script<V: ScriptVisitor + ?Sized>(visitor: &mut V, script: &Script) {
+    visitor.visit_metadata(script);
+
+    for (index, container) in script.paragraphs.iter().enumerate() {
+        walk_overlaps(visitor, container, index);
+        visitor.visit_container(
+            container,
+            ContainerContext {
+                index,
+                overlapping: None,
+            },
+        );
+    }
+}
+
+/// Visit the cues which overlap the given container (and, recursively, any which overlap them).
+fn walk_overlaps<V: ScriptVisitor + ?Sized>(
+    visitor: &mut V,
+    container: &TextContainer,
+    index: usize,
+) {
+    for cue in &container.overlaps {
+        walk_overlaps(visitor, cue, index);
+        visitor.visit_container(
+            cue,
+            ContainerContext {
+                index,
+                overlapping: Some(container),
+            },
+        );
+    }
+}
+
+/// Visit each of the spans of the container.
+pub fn walk_container<V: ScriptVisitor + ?Sized>(
+    visitor: &mut V,
+    container: &TextContainer,
+    context: ContainerContext,
+) {
+    for span in &container.spans {
+        visitor.visit_span(span, container, context);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::script::ContainerKind;
+
+    /// Record the order in which containers are visited, along with their context.
+    struct Order(Vec<(String, usize, Option<String>)>);
+
+    impl ScriptVisitor for Order {
+        fn visit_container(&mut self, container: &TextContainer, context: ContainerContext) {
+            self.0.push((
+                container.plain_text(),
+                context.index,
+                context.overlapping.map(|c| c.plain_text()),
+            ));
+        }
+    }
+
+    #[test]
+    fn test_walk_visits_overlaps_first() {
+        let mut script = Script::new("lilellia", "A Very Cool Script");
+        script
+            .paragraphs
+            .push(TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Hello.")));
+
+        let mut line = TextContainer::new(ContainerKind::Spoken).push(TextSpan::normal("Shh."));
+        line.overlaps
+            .push(TextContainer::new(ContainerKind::Sfx).push(TextSpan::normal("rain")));
+        script.paragraphs.push(line);
+
+        let mut order = Order(Vec::new());
+        order.visit_script(&script);
+
+        assert_eq!(
+            order.0,
+            vec![
+                ("Hello.".to_owned(), 0, None),
+                ("rain".to_owned(), 1, Some("Shh.".to_owned())),
+                ("Shh.".to_owned(), 1, None),
+            ]
+        );
+    }
+}